@@ -0,0 +1,65 @@
+// Measures which lines of the stage2 compiler run while it compiles its own
+// source and every `.bp` file under `test/`, and writes an lcov tracefile.
+//
+//   bun scripts/coverage_stage2.ts [output.lcov]
+
+import { readdir } from "node:fs/promises";
+
+import { Compiler, Target, compile, formatLcov } from "../src/index";
+import type { CoverageReport } from "../src/index";
+import { AST_COMPILER_ENTRY_PATH, readAstCompilerModules } from "../test/helpers";
+
+const outputPath = process.argv[2] ?? "stage2.lcov";
+const modules = await readAstCompilerModules();
+const entry = modules.find((module) => module.path === AST_COMPILER_ENTRY_PATH);
+if (!entry) {
+  throw new Error("ast compiler entry module not found");
+}
+const compilerOptions = {
+  entryPath: AST_COMPILER_ENTRY_PATH,
+  modules: modules.filter((module) => module !== entry),
+};
+const covered = await compile(entry.source, Target.Wasm, { ...compilerOptions, strict: true, coverage: true });
+
+// Each compile clears the instance's memory, counters included, so they are
+// added up after every input.
+let instance: WebAssembly.Instance | null = null;
+let report: CoverageReport | null = null;
+const module = await WebAssembly.compile(covered.wasm);
+const compiler = await Compiler.create(async () => {
+  instance = await WebAssembly.instantiate(module, {});
+  const exports = instance.exports as Record<string, unknown>;
+  return {
+    memory: exports.memory as WebAssembly.Memory,
+    loadModuleFromSource: exports.loadModuleFromSource as (pathPtr: number, contentPtr: number) => number,
+    compileFromPath: exports.compileFromPath as (pathPtr: number) => number,
+    setCompileFlags: exports.setCompileFlags as (flags: number) => number,
+  };
+});
+const inputs: (() => Promise<unknown>)[] = [() => compiler.compile(entry.source, Target.Wasm, compilerOptions)];
+const testDirectory = new URL("../test/", import.meta.url).pathname;
+const testFiles = await readdir(testDirectory, { recursive: true });
+for (const path of testFiles.filter((file) => file.endsWith(".bp")).sort()) {
+  const source = await Bun.file(`${testDirectory}${path}`).text();
+  inputs.push(() => compiler.compile(source));
+}
+let failed = 0;
+for (const input of inputs) {
+  try {
+    await input();
+  } catch {
+    failed += 1;
+  }
+  if (instance) {
+    report = covered.readCoverage(instance, report);
+  }
+}
+if (!report) {
+  throw new Error("stage2 compiler was not instantiated with coverage");
+}
+
+await Bun.write(outputPath, formatLcov(report));
+const lines = report.lines.filter((line) => line.path.startsWith("/compiler/"));
+const hit = lines.filter((line) => line.count > 0).length;
+console.log(`${inputs.length} inputs (${failed} rejected), ${hit} of ${lines.length} compiler lines ran`);
+console.log(`wrote ${outputPath}`);
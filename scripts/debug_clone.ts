@@ -1,7 +1,17 @@
 import { fileURLToPath } from "node:url";
 import { readdir } from "node:fs/promises";
 
-import { describeCompilationFailure } from "../src/index";
+import {
+  PRELUDE_MODULE_PATH,
+  astExpressionTableBase,
+  astProgramBase,
+  describeCompilationFailure,
+  readImplicitModule,
+  readStage2Layout,
+  selectImplicitModules,
+} from "../src/index";
+import type { Stage2Layout } from "../src/index";
+import { readCallNames } from "../src/frontend";
 
 const encoder = new TextEncoder();
 const decoder = new TextDecoder();
@@ -9,8 +19,8 @@ const decoder = new TextDecoder();
 const MODULE_STATE_BASE = 1_048_576;
 const MODULE_STORAGE_TOP_OFFSET = 4;
 const MODULE_COUNT_OFFSET = 0;
-const MODULE_TABLE_OFFSET = 8;
-const MODULE_ENTRY_SIZE = 20;
+const MODULE_TABLE_OFFSET = 20;
+const MODULE_ENTRY_SIZE = 24;
 const MODULE_PATH_PTR = 1_024;
 const MODULE_CONTENT_PTR = 4_096;
 const SCRATCH_FAILURE_PATH_PTR_OFFSET = 4_048;
@@ -24,93 +34,14 @@ const CLONE_DEBUG_CALLER_FUNC_OFFSET = 5_064;
 const CLONE_DEBUG_LOCATION_OFFSET = 5_068;
 
 const WORD_SIZE = 4;
-const SCRATCH_INSTR_CAPACITY = 131_072;
-const SCRATCH_FN_BASE_OFFSET = 921_600;
-const AST_MAX_FUNCTIONS = 1_024;
-const AST_FUNCTION_ENTRY_SIZE = 68;
-const AST_NAMES_CAPACITY = 131_072;
-const AST_CONSTANTS_CAPACITY = 1_024;
-const AST_CONSTANT_ENTRY_SIZE = 28;
-const AST_ARRAY_TYPES_CAPACITY = 256;
-const AST_ARRAY_TYPE_ENTRY_SIZE = 12;
-const AST_TUPLE_TYPES_CAPACITY = 256;
-const AST_TUPLE_TYPE_ENTRY_SIZE = 12;
-const AST_STRUCT_TYPES_CAPACITY = 256;
-const AST_STRUCT_TYPE_ENTRY_SIZE = 20;
-const AST_FUNCTION_TYPES_CAPACITY = 256;
-const AST_FUNCTION_TYPE_ENTRY_SIZE = 16;
-const AST_EXPR_ENTRY_SIZE = 20;
-const AST_CONSTANTS_SECTION_SIZE =
-  WORD_SIZE + AST_CONSTANTS_CAPACITY * AST_CONSTANT_ENTRY_SIZE;
-const AST_CONSTANTS_SECTION_WORDS = AST_CONSTANTS_SECTION_SIZE >> 2;
-const AST_CALL_DATA_CAPACITY = 131_072 - AST_CONSTANTS_SECTION_WORDS;
-const AST_ARRAY_TYPES_SECTION_SIZE =
-  WORD_SIZE + AST_ARRAY_TYPES_CAPACITY * AST_ARRAY_TYPE_ENTRY_SIZE;
-const AST_TUPLE_TYPES_SECTION_SIZE =
-  WORD_SIZE + AST_TUPLE_TYPES_CAPACITY * AST_TUPLE_TYPE_ENTRY_SIZE;
-const AST_STRUCT_TYPES_SECTION_SIZE =
-  WORD_SIZE + AST_STRUCT_TYPES_CAPACITY * AST_STRUCT_TYPE_ENTRY_SIZE;
-const AST_ARRAY_HEAP_INDEX_SECTION_SIZE = AST_ARRAY_TYPES_CAPACITY * WORD_SIZE;
-const AST_TUPLE_HEAP_INDEX_SECTION_SIZE = AST_TUPLE_TYPES_CAPACITY * WORD_SIZE;
-const AST_STRUCT_HEAP_INDEX_SECTION_SIZE = AST_STRUCT_TYPES_CAPACITY * WORD_SIZE;
-const AST_FUNCTION_TYPES_SECTION_SIZE =
-  WORD_SIZE + AST_FUNCTION_TYPES_CAPACITY * AST_FUNCTION_TYPE_ENTRY_SIZE;
 
 const COMPILER_DIR_URL = new URL("../compiler/", import.meta.url);
 const STD_MEMORY_URL = new URL("../stdlib/memory.bp", import.meta.url);
+const STD_PRELUDE_URL = new URL("../stdlib/prelude.bp", import.meta.url);
 const COMPILER_ENTRY_PATH = "/compiler/ast_compiler.bp";
 
-function astOutputReserve(inputLen: number): number {
-  const optionA = inputLen + SCRATCH_INSTR_CAPACITY;
-  const optionB = SCRATCH_FN_BASE_OFFSET + 16_384;
-  return optionA > optionB ? optionA : optionB;
-}
-
-function astBase(outPtr: number, inputLen: number): number {
-  return outPtr + astOutputReserve(inputLen);
-}
-
-function astConstantsCountPtr(astBasePtr: number): number {
-  const functionsSection = WORD_SIZE + AST_MAX_FUNCTIONS * AST_FUNCTION_ENTRY_SIZE;
-  const namesSection = WORD_SIZE + AST_NAMES_CAPACITY;
-  const callDataSection = WORD_SIZE + AST_CALL_DATA_CAPACITY * WORD_SIZE;
-  return astBasePtr + functionsSection + namesSection + callDataSection;
-}
-
-function astArrayTypesCountPtr(astBasePtr: number): number {
-  return astConstantsCountPtr(astBasePtr) + AST_CONSTANTS_SECTION_SIZE;
-}
-
-function astTupleTypesCountPtr(astBasePtr: number): number {
-  return astArrayTypesCountPtr(astBasePtr) + AST_ARRAY_TYPES_SECTION_SIZE;
-}
-
-function astArrayHeapIndicesBase(astBasePtr: number): number {
-  return astTupleTypesCountPtr(astBasePtr) + AST_TUPLE_TYPES_SECTION_SIZE;
-}
-
-function astTupleHeapIndicesBase(astBasePtr: number): number {
-  return astArrayHeapIndicesBase(astBasePtr) + AST_ARRAY_HEAP_INDEX_SECTION_SIZE;
-}
-
-function astStructTypesCountPtr(astBasePtr: number): number {
-  return astTupleHeapIndicesBase(astBasePtr) + AST_TUPLE_HEAP_INDEX_SECTION_SIZE;
-}
-
-function astStructHeapIndicesBase(astBasePtr: number): number {
-  return astStructTypesCountPtr(astBasePtr) + AST_STRUCT_TYPES_SECTION_SIZE;
-}
-
-function astFunctionTypesCountPtr(astBasePtr: number): number {
-  return astStructHeapIndicesBase(astBasePtr) + AST_STRUCT_HEAP_INDEX_SECTION_SIZE;
-}
-
-function astExtraBase(astBasePtr: number): number {
-  return astFunctionTypesCountPtr(astBasePtr) + AST_FUNCTION_TYPES_SECTION_SIZE;
-}
-
-function astExprEntryPtr(astBasePtr: number, index: number): number {
-  return astExtraBase(astBasePtr) + WORD_SIZE + index * AST_EXPR_ENTRY_SIZE;
+function astExprEntryPtr(astBasePtr: number, index: number, layout: Stage2Layout): number {
+  return astExpressionTableBase(astBasePtr, layout) + WORD_SIZE + index * layout.astExprEntrySize;
 }
 
 function readBytes(memory: WebAssembly.Memory, ptr: number, length: number): string {
@@ -164,13 +95,17 @@ async function main() {
   if (!memory) {
     throw new Error("stage2 missing memory");
   }
+  const layout = readStage2Layout(instance.exports);
   const loadModuleFromSource = instance.exports.loadModuleFromSource as
     | ((pathPtr: number, contentPtr: number) => number | bigint)
     | undefined;
   const compileFromPath = instance.exports.compileFromPath as
     | ((pathPtr: number) => number | bigint)
     | undefined;
-  if (!loadModuleFromSource || !compileFromPath) {
+  const parseFromPath = instance.exports.parseFromPath as
+    | ((pathPtr: number) => number | bigint)
+    | undefined;
+  if (!loadModuleFromSource || !compileFromPath || !parseFromPath) {
     throw new Error("stage2 missing exports");
   }
 
@@ -200,11 +135,26 @@ async function main() {
   }
   load(entry.path, entrySource);
 
+  const parseResult = parseFromPath(MODULE_PATH_PTR);
+  const parsed = typeof parseResult === "bigint" ? Number(parseResult) : (parseResult | 0);
+  if (parsed <= 0) {
+    throw new Error(`parse failure => ${parsed}`);
+  }
+  const prelude = await readImplicitModule({
+    path: PRELUDE_MODULE_PATH,
+    source: await Bun.file(STD_PRELUDE_URL).text(),
+  });
+  const calls = readCallNames(memory, readModuleStorageTop(memory), entryLength, layout);
+  for (const moduleInfo of selectImplicitModules([prelude], calls)) {
+    load(moduleInfo.path, moduleInfo.source);
+  }
+  writeModuleString(memory, MODULE_PATH_PTR, entry.path);
+
   const compileResult = compileFromPath(MODULE_PATH_PTR);
   const status = typeof compileResult === "bigint" ? Number(compileResult) : (compileResult | 0);
   const outPtr = readModuleStorageTop(memory);
   if (status <= 0) {
-    const failure = describeCompilationFailure(memory, outPtr, status);
+    const failure = describeCompilationFailure(memory, outPtr, status, entryLength, layout);
     console.log("compile status", status);
     console.log("failure detail", failure.detail);
     const view = new DataView(memory.buffer);
@@ -224,9 +174,9 @@ async function main() {
       local: view.getInt32(5_044, true),
     };
     console.log("const eval", constEval);
-    const astBasePtr = astBase(outPtr, entryLength);
+    const astBasePtr = astProgramBase(outPtr, entryLength, layout);
     if (constEval.expr >= 0) {
-      const exprPtr = astExprEntryPtr(astBasePtr, constEval.expr);
+      const exprPtr = astExprEntryPtr(astBasePtr, constEval.expr, layout);
       const expr = {
         kind: view.getInt32(exprPtr, true),
         data0: view.getInt32(exprPtr + WORD_SIZE, true),
@@ -247,7 +197,7 @@ async function main() {
       if (index < 0 || index >= funcCount) {
         return "<invalid>";
       }
-      const entryPtr = astBasePtr + WORD_SIZE + index * AST_FUNCTION_ENTRY_SIZE;
+      const entryPtr = astBasePtr + WORD_SIZE + index * layout.astFunctionEntrySize;
       const namePtr = view.getInt32(entryPtr, true);
       const nameLen = view.getInt32(entryPtr + 4, true);
       const moduleIndex = view.getInt32(entryPtr + 52, true);
@@ -269,7 +219,7 @@ async function main() {
     const heavyConstFns: Array<string> = [];
     const FUNCTION_FLAG_HAS_CONST_PARAMS = 2;
     for (let i = 0; i < funcCount; i += 1) {
-      const entryPtr = astBasePtr + WORD_SIZE + i * AST_FUNCTION_ENTRY_SIZE;
+      const entryPtr = astBasePtr + WORD_SIZE + i * layout.astFunctionEntrySize;
       const flags = view.getInt32(entryPtr + 32, true);
       if ((flags & FUNCTION_FLAG_HAS_CONST_PARAMS) !== 0) {
         const namePtr = view.getInt32(entryPtr, true);
@@ -293,7 +243,7 @@ async function main() {
       console.log("heavy const fns", heavyConstFns.slice(0, 10));
     }
     for (let i = 0; i < Math.min(funcCount, 8); i += 1) {
-      const entryPtr = astBasePtr + WORD_SIZE + i * AST_FUNCTION_ENTRY_SIZE;
+      const entryPtr = astBasePtr + WORD_SIZE + i * layout.astFunctionEntrySize;
       const namePtr = view.getInt32(entryPtr, true);
       const nameLen = view.getInt32(entryPtr + 4, true);
       const moduleIndex = view.getInt32(entryPtr + 52, true);
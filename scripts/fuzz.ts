@@ -0,0 +1,39 @@
+// Compiles random mutations of the example programs and records every input
+// that crashes the stage2 compiler as a replay case.
+//
+//   bun scripts/fuzz.ts [iterations] [seed] [crash-dir]
+//
+// Replay a saved crash with `bootstrapc replay <case-dir>`.
+
+import { readdir } from "node:fs/promises";
+
+import { fuzz } from "../src/fuzz";
+import { Target } from "../src/index";
+import { replayCaseFromFailure, writeReplayCase } from "../src/replay";
+
+const iterations = Number(process.argv[2] ?? 1_000);
+const seed = Number(process.argv[3] ?? Date.now() % 0x7fffffff);
+const crashDirectory = process.argv[4] ?? "fuzz-crashes";
+
+const seeds: string[] = [];
+for (const directory of ["../examples/", "../test/snapshots/"]) {
+  const url = new URL(directory, import.meta.url);
+  for (const file of (await readdir(url)).filter((name) => name.endsWith(".bp")).sort()) {
+    seeds.push(await Bun.file(new URL(file, url)).text());
+  }
+}
+
+console.log(`fuzzing ${iterations} inputs with seed ${seed}`);
+const report = await fuzz(seeds, {
+  iterations,
+  seed,
+  onCrash: async ({ source, error }) => {
+    const replayCase = replayCaseFromFailure("fuzz.bp", source, Target.Wasm, {}, error);
+    const caseDirectory = await writeReplayCase(crashDirectory, replayCase);
+    console.log(`crash: ${error.message}\n  saved to ${caseDirectory}`);
+  },
+});
+console.log(
+  `${report.accepted} accepted, ${report.rejected} rejected, ${report.crashes.length} crashed`,
+);
+process.exitCode = report.crashes.length > 0 ? 1 : 0;
@@ -0,0 +1,39 @@
+// Compiles examples/mandelbrot.bp, renders it, and writes the image as a
+// binary PPM.  Prints how long compiling and rendering took, so the example
+// doubles as a benchmark.
+//
+//   bun scripts/mandelbrot.ts [output.ppm] [width] [height] [iterations]
+
+import { compile } from "../src/index";
+
+const outputPath = process.argv[2] ?? "mandelbrot.ppm";
+const [width, height, iterations] = [640, 480, 256].map((fallback, index) =>
+  Number(process.argv[3 + index] ?? fallback),
+) as [number, number, number];
+
+const source = await Bun.file(new URL("../examples/mandelbrot.bp", import.meta.url)).text();
+const compileStart = performance.now();
+const compilation = await compile(source);
+const compileTime = performance.now() - compileStart;
+
+const { instance } = await WebAssembly.instantiate(compilation.wasm, {});
+const exports = instance.exports as Record<string, unknown>;
+const render = exports.render as (width: number, height: number, iterations: number) => number;
+const memory = exports.memory as WebAssembly.Memory;
+
+const renderStart = performance.now();
+const pixels = render(width, height, iterations);
+const renderTime = performance.now() - renderStart;
+if (pixels === 0) {
+  throw new Error(`a ${width}x${height} image does not fit in the module's heap`);
+}
+
+const header = new TextEncoder().encode(`P6\n${width} ${height}\n255\n`);
+const image = new Uint8Array(header.length + width * height * 3);
+image.set(header);
+image.set(new Uint8Array(memory.buffer, pixels, width * height * 3), header.length);
+await Bun.write(outputPath, image);
+console.log(
+  `wrote ${width}x${height} image to ${outputPath} ` +
+    `(compile ${compileTime.toFixed(1)} ms, render ${renderTime.toFixed(1)} ms)`,
+);
@@ -0,0 +1,48 @@
+// Counts the instructions each stage2 compiler function executes while the
+// compiler compiles its own source, and prints the busiest ones.
+//
+//   bun scripts/profile_stage2.ts [limit]
+
+import { Compiler } from "../src/index";
+import {
+  AST_COMPILER_ENTRY_PATH,
+  instrumentWasmWithFuel,
+  readAstCompilerModules,
+  readFunctionFuel,
+} from "../test/helpers";
+
+const limit = Number(process.argv[2] ?? 30);
+const wasm = new Uint8Array(await Bun.file(new URL("../compiler.wasm", import.meta.url)).arrayBuffer());
+const module = await WebAssembly.compile(instrumentWasmWithFuel(wasm, { perFunction: true }));
+
+let instance: WebAssembly.Instance | null = null;
+const compiler = await Compiler.create(async () => {
+  instance = await WebAssembly.instantiate(module, {});
+  const exports = instance.exports as Record<string, unknown>;
+  return {
+    memory: exports.memory as WebAssembly.Memory,
+    loadModuleFromSource: exports.loadModuleFromSource as (pathPtr: number, contentPtr: number) => number,
+    compileFromPath: exports.compileFromPath as (pathPtr: number) => number,
+    setCompileFlags: exports.setCompileFlags as (flags: number) => number,
+  };
+});
+
+const modules = await readAstCompilerModules();
+const entry = modules.find((module) => module.path === AST_COMPILER_ENTRY_PATH);
+if (!entry) {
+  throw new Error("ast compiler entry module not found");
+}
+await compiler.compile(entry.source, undefined, {
+  entryPath: AST_COMPILER_ENTRY_PATH,
+  modules: modules.filter((module) => module !== entry),
+});
+if (!instance) {
+  throw new Error("stage2 compiler was not instantiated");
+}
+
+const counts = [...readFunctionFuel(instance)].sort((a, b) => b[1] - a[1]);
+const total = counts.reduce((sum, [, count]) => sum + count, 0);
+console.log(`total ${total} instructions`);
+for (const [name, count] of counts.slice(0, limit)) {
+  console.log(`${((count / total) * 100).toFixed(2).padStart(6)}% ${String(count).padStart(11)} ${name}`);
+}
@@ -0,0 +1,162 @@
+import { CompileError, Compiler, Target, instantiateStage2 } from "./index";
+import type { CompilerModuleSource } from "./index";
+import { SECTION_NAMES } from "./wasm_reader";
+
+/** The options the compiler is always built with, as by a bare `bootstrapc`. */
+const BOOTSTRAP_COMPILE_OPTIONS = { strict: true, noStd: true } as const;
+
+export interface SectionDiff {
+  /** The standard section name, or `custom:<name>` for a custom section. */
+  readonly name: string;
+  /** Section payload sizes, or `null` where a module has no such section. */
+  readonly expectedSize: number | null;
+  readonly actualSize: number | null;
+}
+
+export interface WasmDiff {
+  readonly expectedSize: number;
+  readonly actualSize: number;
+  /** Offset of the first byte that differs, counting a length change as one. */
+  readonly firstDifference: number;
+  /** Bytes that differ within the shorter module's length. */
+  readonly differingBytes: number;
+  /** Sections whose contents differ, in the order they first appear. */
+  readonly sections: ReadonlyArray<SectionDiff>;
+}
+
+export interface BootstrapReport {
+  /** `ast_compiler.bp` compiled by the checked-in `compiler.wasm`. */
+  readonly stage2: Uint8Array;
+  /** `ast_compiler.bp` compiled by `stage2`. */
+  readonly stage3: Uint8Array;
+  /** Where the stages differ, or `null` at a fixed point. */
+  readonly diff: WasmDiff | null;
+}
+
+interface Section {
+  readonly name: string;
+  readonly bytes: Uint8Array;
+}
+
+function readSections(wasm: Uint8Array): Section[] {
+  const sections: Section[] = [];
+  let offset = 8;
+  const readLeb = (): number => {
+    let result = 0;
+    let shift = 0;
+    let byte: number;
+    do {
+      if (offset >= wasm.length) {
+        throw new CompileError("wasm module is truncated");
+      }
+      byte = wasm[offset++]!;
+      result += (byte & 0x7f) * 2 ** shift;
+      shift += 7;
+    } while (byte & 0x80);
+    return result;
+  };
+  while (offset < wasm.length) {
+    const id = wasm[offset++]!;
+    const size = readLeb();
+    const bytes = wasm.subarray(offset, offset + size);
+    let name = SECTION_NAMES[id] ?? `section#${id}`;
+    if (id === 0) {
+      const start = offset;
+      const nameLength = readLeb();
+      name = `custom:${new TextDecoder().decode(wasm.subarray(offset, offset + nameLength))}`;
+      offset = start;
+    }
+    sections.push({ name, bytes });
+    offset += size;
+  }
+  return sections;
+}
+
+function sameBytes(left: Uint8Array, right: Uint8Array): boolean {
+  return left.length === right.length && left.every((byte, index) => byte === right[index]);
+}
+
+/** Compares two modules byte for byte and section by section. */
+export function diffWasm(expected: Uint8Array, actual: Uint8Array): WasmDiff | null {
+  const shared = Math.min(expected.length, actual.length);
+  let firstDifference = -1;
+  let differingBytes = 0;
+  for (let index = 0; index < shared; index += 1) {
+    if (expected[index] !== actual[index]) {
+      differingBytes += 1;
+      if (firstDifference < 0) {
+        firstDifference = index;
+      }
+    }
+  }
+  if (firstDifference < 0) {
+    if (expected.length === actual.length) {
+      return null;
+    }
+    firstDifference = shared;
+  }
+
+  // Sections are matched by name and occurrence, so a repeated custom section
+  // pairs with its counterpart rather than with the first of its name.
+  const keyed = (sections: Section[]): Map<string, Section> => {
+    const seen = new Map<string, number>();
+    const result = new Map<string, Section>();
+    for (const section of sections) {
+      const count = seen.get(section.name) ?? 0;
+      seen.set(section.name, count + 1);
+      result.set(`${section.name}#${count}`, section);
+    }
+    return result;
+  };
+  const expectedSections = keyed(readSections(expected));
+  const actualSections = keyed(readSections(actual));
+  const sections: SectionDiff[] = [];
+  for (const key of new Set([...expectedSections.keys(), ...actualSections.keys()])) {
+    const left = expectedSections.get(key);
+    const right = actualSections.get(key);
+    if (left && right && sameBytes(left.bytes, right.bytes)) {
+      continue;
+    }
+    sections.push({
+      name: (left ?? right)!.name,
+      expectedSize: left ? left.bytes.length : null,
+      actualSize: right ? right.bytes.length : null,
+    });
+  }
+  return {
+    expectedSize: expected.length,
+    actualSize: actual.length,
+    firstDifference,
+    differingBytes,
+    sections,
+  };
+}
+
+/**
+ * Checks that the compiler is a fixed point of itself: `entry` compiled by
+ * the checked-in `compiler.wasm` gives stage2, and compiled again by stage2
+ * gives stage3, which must be the same bytes.
+ */
+export async function verifyBootstrap(
+  entry: CompilerModuleSource,
+  modules: ReadonlyArray<CompilerModuleSource>,
+): Promise<BootstrapReport> {
+  const options = { ...BOOTSTRAP_COMPILE_OPTIONS, entryPath: entry.path, modules: [...modules] };
+  const checkedIn = await Compiler.create();
+  const stage2 = (await checkedIn.compile(entry.source, Target.Wasm, options)).intoWasm();
+  const fresh = await Compiler.create(() => instantiateStage2(stage2));
+  const stage3 = (await fresh.compile(entry.source, Target.Wasm, options)).intoWasm();
+  return { stage2, stage3, diff: diffWasm(stage2, stage3) };
+}
+
+export function formatWasmDiff(diff: WasmDiff, expectedName: string, actualName: string): string {
+  const lines = [
+    `${expectedName} is ${diff.expectedSize} bytes and ${actualName} is ${diff.actualSize} bytes`,
+    `first difference at offset 0x${diff.firstDifference.toString(16)}, ${diff.differingBytes} differing bytes in the shared length`,
+  ];
+  const size = (value: number | null): string => (value === null ? "missing" : `${value} bytes`);
+  for (const section of diff.sections) {
+    lines.push(`  ${section.name}: ${size(section.expectedSize)} -> ${size(section.actualSize)}`);
+  }
+  return lines.join("\n");
+}
@@ -0,0 +1,66 @@
+import { WasmReader, encodeUnsignedLeb } from "./wasm_reader";
+
+/** The custom section `bootstrapc` stamps its output modules with. */
+export const BUILD_META_SECTION_NAME = "bootstrap.meta";
+
+/** The compiler's release, kept equal to `version` in `package.json`. */
+export const COMPILER_VERSION = "0.1.0";
+
+/** Where a module came from, as recorded in its `bootstrap.meta` section. */
+export interface BuildMeta {
+  /** {@link COMPILER_VERSION} of the compiler that built the module. */
+  readonly version: string;
+  /** Hex SHA-256 of the stage2 `compiler.wasm` that ran. */
+  readonly compilerHash: string;
+  /** Hex SHA-256 of the entry source and the modules it imports. */
+  readonly sourceHash: string;
+}
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+/**
+ * Appends a `bootstrap.meta` custom section holding `meta` as JSON.  Custom
+ * sections go last, so no offset in the module moves.
+ */
+export function appendBuildMeta(wasm: Uint8Array, meta: BuildMeta): Uint8Array {
+  const name = encoder.encode(BUILD_META_SECTION_NAME);
+  const value = encoder.encode(
+    JSON.stringify({ version: meta.version, compilerHash: meta.compilerHash, sourceHash: meta.sourceHash }),
+  );
+  const payload = [...encodeUnsignedLeb(name.length), ...name, ...value];
+  const section = [0, ...encodeUnsignedLeb(payload.length), ...payload];
+  const result = new Uint8Array(wasm.length + section.length);
+  result.set(wasm);
+  result.set(section, wasm.length);
+  return result;
+}
+
+/** The module's `bootstrap.meta` section, or `null` when it has none. */
+export function readBuildMeta(wasm: Uint8Array): BuildMeta | null {
+  const reader = new WasmReader(wasm, 8);
+  while (reader.offset < wasm.length) {
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    const end = reader.offset + size;
+    if (id === 0) {
+      const nameLength = reader.readLeb();
+      const name = decoder.decode(wasm.subarray(reader.offset, reader.offset + nameLength));
+      if (name === BUILD_META_SECTION_NAME) {
+        const fields = JSON.parse(decoder.decode(wasm.subarray(reader.offset + nameLength, end))) as BuildMeta;
+        return { version: fields.version, compilerHash: fields.compilerHash, sourceHash: fields.sourceHash };
+      }
+    }
+    reader.offset = end;
+  }
+  return null;
+}
+
+/** Renders `meta` one field per line, as `bootstrapc inspect` prints it. */
+export function formatBuildMeta(meta: BuildMeta): string {
+  return [
+    `version: ${meta.version}`,
+    `compiler: sha256:${meta.compilerHash}`,
+    `source: sha256:${meta.sourceHash}`,
+  ].join("\n");
+}
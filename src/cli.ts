@@ -1,41 +1,209 @@
 #!/usr/bin/env bun
 import { fileURLToPath } from "node:url";
-import { dirname, extname } from "node:path";
-import { mkdir, readdir } from "node:fs/promises";
+import { basename, dirname, extname, join, relative, resolve } from "node:path";
+import { watch as watchDirectory } from "node:fs";
+import { mkdir, readdir, stat } from "node:fs/promises";
+import { createHash } from "node:crypto";
 
 import process from "node:process";
+import { createInterface } from "node:readline";
 
 import {
   Target,
+  COMPILER_VERSION,
+  appendBuildMeta,
+  appendSourceMappingUrl,
   compile,
-  parseTarget,
+  parseTargetSpec,
+  toTargetSpec,
+  DEFAULT_ENTRY_MODULE_PATH,
   DEFAULT_TARGET,
   CompileError,
   Compilation,
+  formatCompileError,
+  formatCompileWarning,
+  formatLcov,
+  formatSizeReport,
+  formatRuntimePanic,
+  formatStackTrace,
+  availableLocales,
+  hasLocale,
+  formatWasmInspection,
+  inspectWasm,
+  link,
+  LIBRARY_ARCHIVE_EXTENSION,
+  libraryInterfaceSource,
+  readLibraryInterface,
+  writeLibraryArchive,
+  readSizeReport,
+  WASM_FEATURE_NAMES,
+  runTests,
+  CompileOptions,
+  CompilerLibrary,
   CompilerModuleSource,
+  TargetSpec,
 } from "./index";
+import type { BuildMeta, CompileLogger, WasmFeature, WasmFeatures, WasmInspection } from "./index";
+import { diffWasm, formatWasmDiff, verifyBootstrap } from "./bootstrap";
+import { FileSystemSourceProvider } from "./fs_source_provider";
+import { ReplSession } from "./repl";
+import { readReplayCase, replay, replayCaseFromFailure, writeReplayCase } from "./replay";
+import { loadImports, loadLibraries } from "./sources";
 
 const COMPILER_OUTPUT_PATH = new URL("../compiler.wasm", import.meta.url);
 const COMPILER_DIR_URL = new URL("../compiler/", import.meta.url);
 const COMPILER_ENTRY_PATH = "/compiler/ast_compiler.bp";
 
 function printUsage(program: string) {
-  console.error(`Usage: ${program} <input.bp> [options]`);
+  console.error(`Usage: ${program} <input.bp|-> [options]`);
+  console.error(`       ${program} <input.bp>... (--out-dir <dir> | -o <output.wasm>) [options]`);
+  console.error(`       ${program} --watch [<input.bp>... [options]]`);
+  console.error(`       ${program} bootstrap [--verify]`);
+  console.error(`       ${program} repl`);
+  console.error(`       ${program} replay <case-dir>`);
+  console.error(`       ${program} inspect <module.wasm>`);
+  console.error(`       ${program} link <module.wasm>... [-o <output.wasm>]`);
+  console.error(`       ${program} test <input.bp|-> [filter]`);
+  console.error("Arguments:");
+  console.error("    -                    Read the source from stdin; `use` paths resolve from the working directory");
   console.error("Options:");
-  console.error("    -o <path>            Write output to file (.wasm)");
+  console.error("    -o <path>            Write output to file (.wasm, or .bpl for a precompiled library),");
+  console.error("                         or to stdout when the path is -; several inputs are linked into it");
+  console.error("    --out-dir <dir>      Build each input on its own into <dir>/<name>.wasm");
   console.error("    --emit wasm          Write wasm binary to stdout (default when no -o)");
+  console.error("    --emit size-report   Print section sizes and the biggest functions instead");
   console.error("    --run                Execute the compiled module with Bun");
   console.error("    --target <wasm|wgsl> Select the compilation target (default: wasm)");
+  console.error("                         wgsl:<compute|vertex|fragment> also picks the shader stage");
+  console.error("    --checked-division   Report division by zero and overflow with a source location");
+  console.error("    --strict             Require discard(...) around ignored call results");
+  console.error("    --integer-conditions Accept integer if/while conditions as != 0, with a warning");
+  console.error("    --no-std             Leave out /stdlib/core.bp, the library callable without a `use`");
+  console.error("    --define <NAME[=value]> Define a name for #[cfg(...)] and cfg!(...) to test");
+  console.error("    --optimize           Fold constants and constant branches, reuse repeated expressions");
+  console.error("                         and drop a memory no code uses");
+  console.error("    --deny-warnings      Fail the build when the compiler reports any warning");
+  console.error("    --const-table        Emit a bootstrap.consts section listing scalar constants");
+  console.error("    --source-map         Write <output>.map mapping code offsets to source lines (needs -o)");
+  console.error("    --layout-globals     Export __data_end/__heap_base and report them with --run");
+  console.error("    --memory-canaries    Bracket static data with canary words checked after --run");
+  console.error("    --guard-stores       Trap on stores into the low guard region or over a canary");
+  console.error("    --instrument asan    Check loads and stores against /stdlib/alloc.bp allocations");
+  console.error("    --instrument profile Count calls and instructions per function and report them with --run");
+  console.error("    --instrument coverage Count executed blocks and write <input>.lcov after --run");
+  console.error("    --validate-output    Compile the output with the host engine and report invalid code");
+  console.error("    --disable-feature <name> Avoid a post-MVP wasm feature, failing if the program needs it");
+  console.error("                         (sign-ext, bulk-memory, nontrapping-float-to-int, multi-value,");
+  console.error("                         reference-types, simd, threads)");
+  console.error("    --memory-pages <n>   Size the module's memory in 64 KiB pages (default: 256)");
+  console.error("    --shared-memory      Mark the module's memory as shared for threaded hosts");
+  console.error("    --max-nesting-depth <n> Reject expressions nested deeper than n (default: 256)");
+  console.error("    --locale <name>      Print diagnostics in this locale (en, es; default: en)");
+  console.error("    -v, --verbose        Print each compiler phase with its result and time");
+  console.error("    -vv                  Also print the stage2 memory addresses and lengths each phase uses");
+  console.error("    --record <dir>       Save the inputs of a failed compile as a case for replay");
+  console.error("    --watch              Rebuild whenever the input changes (-o or --run required)");
 }
 
-async function runWithBun(wasm: Uint8Array) {
+// Colors diagnostics for terminals, honoring the NO_COLOR convention.
+function useColor(): boolean {
+  return Boolean(process.stderr.isTTY) && !process.env.NO_COLOR;
+}
+
+// Prints compiler progress to stderr: phases at verbosity 1 (-v), and the
+// stage2 memory behind them at 2 (-vv).
+function verboseLogger(verbosity: number): CompileLogger {
+  return (level, message) => {
+    if (level <= verbosity) {
+      console.error(`${level === 1 ? "verbose" : "trace"}: ${message}`);
+    }
+  };
+}
+
+const PROFILE_REPORT_ROWS = 20;
+
+// Lists the functions that ran, by their own instruction counts.  Self time
+// splits the run's wall time in proportion to those counts, so it is only an
+// estimate.
+function printProfileReport(compilation: Compilation, instance: WebAssembly.Instance, elapsed: number) {
+  const profiles = compilation.readProfile(instance);
+  if (!profiles) {
+    return;
+  }
+  const ran = profiles
+    .filter((profile) => profile.calls > 0)
+    .sort((a, b) => b.selfInstructions - a.selfInstructions);
+  const total = ran.reduce((sum, profile) => sum + profile.selfInstructions, 0);
+  console.error(
+    `profile: ${ran.length} functions ran ${total} instructions in ${elapsed.toFixed(1)} ms`,
+  );
+  console.error(
+    `${"self %".padStart(7)}${"self ms".padStart(10)}${"calls".padStart(10)}${"instructions".padStart(14)}  function`,
+  );
+  for (const profile of ran.slice(0, PROFILE_REPORT_ROWS)) {
+    const share = total > 0 ? profile.selfInstructions / total : 0;
+    const name = profile.name ?? `wasm-function[${profile.functionIndex}]`;
+    const unwound = profile.calls - profile.returns;
+    console.error(
+      `${(share * 100).toFixed(1).padStart(6)}%${(share * elapsed).toFixed(2).padStart(10)}` +
+        `${String(profile.calls).padStart(10)}${String(profile.selfInstructions).padStart(14)}  ${name}` +
+        (unwound > 0 ? ` (${unwound} unwound by the trap)` : ""),
+    );
+  }
+  if (ran.length > PROFILE_REPORT_ROWS) {
+    console.error(`... ${ran.length - PROFILE_REPORT_ROWS} more`);
+  }
+}
+
+// Writes an lcov tracefile next to the input, named after it.
+async function writeCoverageReport(compilation: Compilation, instance: WebAssembly.Instance, inputPath: string) {
+  const report = compilation.readCoverage(instance);
+  if (!report) {
+    return;
+  }
+  const reportPath = `${inputPath.replace(/\.bp$/, "")}.lcov`;
+  await Bun.write(reportPath, formatLcov(report, { entryPath: inputPath }));
+  const hit = report.lines.filter((line) => line.count > 0).length;
+  console.error(`coverage: ${hit} of ${report.lines.length} lines ran; wrote ${reportPath}`);
+}
+
+async function runWithBun(compilation: Compilation, wasm: Uint8Array, inputPath: string) {
   const { instance } = await WebAssembly.instantiate(wasm, {});
   const main = (instance.exports as Record<string, unknown>).main;
   if (typeof main !== "function") {
     throw new CompileError("wasm module does not export 'main'");
   }
 
-  const result = (main as () => unknown)();
+  let result: unknown;
+  const start = performance.now();
+  try {
+    result = (main as () => unknown)();
+  } catch (error) {
+    printProfileReport(compilation, instance, performance.now() - start);
+    await writeCoverageReport(compilation, instance, inputPath);
+    const panic = compilation.describePanic(instance);
+    const frames = compilation.symbolicate(error);
+    if (!panic && frames.length === 0) {
+      throw error;
+    }
+    const message = panic
+      ? formatRuntimePanic(panic, { entryPath: inputPath })
+      : `wasm trap: ${error instanceof Error ? error.message : String(error)}`;
+    const trace = formatStackTrace(frames, { entryPath: inputPath });
+    throw new CompileError(trace ? `${message}\n${trace}` : message);
+  }
+  printProfileReport(compilation, instance, performance.now() - start);
+  await writeCoverageReport(compilation, instance, inputPath);
+  const canaryPanic = compilation.checkCanaries(instance);
+  if (canaryPanic) {
+    throw new CompileError(formatRuntimePanic(canaryPanic, { entryPath: inputPath }));
+  }
+  const layout = compilation.describeLayout(instance);
+  if (layout) {
+    console.error(
+      `layout: memory=${layout.memoryBytes} __data_end=${layout.dataEnd} __heap_base=${layout.heapBase}`,
+    );
+  }
   if (typeof result === "bigint") {
     console.log(result.toString());
   } else if (result !== undefined) {
@@ -43,22 +211,375 @@ async function runWithBun(wasm: Uint8Array) {
   }
 }
 
-async function buildStage2Wasm() {
+async function readCompilerEntry(): Promise<{
+  entry: CompilerModuleSource;
+  extraModules: CompilerModuleSource[];
+}> {
   const modules = await readCompilerModules();
   const entry = modules.find((module) => module.path === COMPILER_ENTRY_PATH);
   if (!entry) {
     throw new CompileError("stage1 compiler entry module not found");
   }
-  const extraModules = modules.filter((module) => module.path !== COMPILER_ENTRY_PATH);
+  return { entry, extraModules: modules.filter((module) => module.path !== COMPILER_ENTRY_PATH) };
+}
+
+async function buildStage2Wasm() {
+  const { entry, extraModules } = await readCompilerEntry();
   const compilation = await compile(entry.source, Target.Wasm, {
     entryPath: COMPILER_ENTRY_PATH,
     modules: extraModules,
+    strict: true,
+    noStd: true,
   });
+  for (const warning of compilation.warnings) {
+    console.error(formatCompileWarning(warning, { color: useColor() }));
+  }
   const wasm = compilation.intoWasm();
   await Bun.write(COMPILER_OUTPUT_PATH, wasm);
   console.log(`wrote stage2 wasm to ${fileURLToPath(COMPILER_OUTPUT_PATH)}`);
 }
 
+// Net count of `{` over `}`, so an item spanning several lines is read whole.
+function braceDepth(text: string): number {
+  let depth = 0;
+  for (const char of text.replace(/\/\/.*$/gm, "").replace(/"(\\.|[^"\\])*"/g, "")) {
+    if (char === "{") {
+      depth += 1;
+    } else if (char === "}") {
+      depth -= 1;
+    }
+  }
+  return depth;
+}
+
+async function runRepl() {
+  const session = new ReplSession({ color: useColor() });
+  const terminal = Boolean(process.stdin.isTTY);
+  const lines = createInterface({ input: process.stdin, output: process.stdout, terminal });
+  const prompt = (continuation: boolean): void => {
+    if (terminal) {
+      lines.setPrompt(continuation ? "...> " : ">> ");
+      lines.prompt();
+    }
+  };
+  if (terminal) {
+    console.log("bootstrap repl: enter items or expressions, :reset to clear items, :quit to exit");
+  }
+
+  let pending = "";
+  prompt(false);
+  for await (const line of lines) {
+    if (!pending) {
+      const command = line.trim();
+      if (command === ":quit" || command === ":q") {
+        break;
+      }
+      if (command === ":reset") {
+        session.reset();
+        prompt(false);
+        continue;
+      }
+    }
+    pending = pending ? `${pending}\n${line}` : line;
+    if (braceDepth(pending) > 0) {
+      prompt(true);
+      continue;
+    }
+    const outcome = await session.submit(pending);
+    pending = "";
+    if (outcome.kind === "error") {
+      console.error(outcome.message);
+    } else {
+      for (const warning of outcome.warnings) {
+        console.error(warning);
+      }
+      if (outcome.kind === "value") {
+        console.log(`${outcome.value}: ${outcome.type}`);
+      }
+    }
+    prompt(false);
+  }
+  lines.close();
+}
+
+async function replayRecordedCase(caseDirectory: string) {
+  const replayCase = await readReplayCase(caseDirectory);
+  const outcome = await replay(replayCase);
+  if (outcome.error) {
+    console.error(
+      formatCompileError(outcome.error, { entryPath: replayCase.inputPath, color: useColor() }),
+    );
+  }
+  if (outcome.status === "reproduced") {
+    console.error("replay: failed with the recorded error");
+  } else if (outcome.status === "changed") {
+    const recorded = replayCase.failure.record?.message ?? replayCase.failure.message;
+    console.error(`replay: failed with a different error than the recorded one: ${recorded}`);
+  } else {
+    console.error("replay: compiled successfully");
+  }
+  if (outcome.status !== "fixed") {
+    process.exit(1);
+  }
+}
+
+async function verifyStage2Wasm() {
+  const { entry, extraModules } = await readCompilerEntry();
+  const report = await verifyBootstrap(entry, extraModules);
+  if (report.diff) {
+    console.error("bootstrap: stage3 differs from stage2");
+    console.error(formatWasmDiff(report.diff, "stage2", "stage3"));
+    process.exit(1);
+  }
+  console.log(`bootstrap: stage2 and stage3 are identical (${report.stage2.length} bytes)`);
+  const checkedIn = new Uint8Array(await Bun.file(COMPILER_OUTPUT_PATH).arrayBuffer());
+  const stale = diffWasm(checkedIn, report.stage2);
+  if (stale) {
+    console.error("bootstrap: compiler.wasm differs from stage2; run bootstrapc to rebuild it");
+    console.error(formatWasmDiff(stale, "compiler.wasm", "stage2"));
+    process.exit(1);
+  }
+}
+
+// The stamp `build` puts in every module it writes.  The source hash covers
+// the imports and libraries too, in the order `readImports` found them.
+async function buildMetaFor(source: string, imports: ImportedSources): Promise<BuildMeta> {
+  const compiler = new Uint8Array(await Bun.file(COMPILER_OUTPUT_PATH).arrayBuffer());
+  const sourceHash = createHash("sha256");
+  sourceHash.update(JSON.stringify([source, imports.modules.map((module) => [module.path, module.source])]));
+  for (const library of imports.libraries) {
+    sourceHash.update(library.path).update(library.archive);
+  }
+  return {
+    version: COMPILER_VERSION,
+    compilerHash: createHash("sha256").update(compiler).digest("hex"),
+    sourceHash: sourceHash.digest("hex"),
+  };
+}
+
+async function inspectModule(modulePath: string) {
+  let wasm: Uint8Array;
+  try {
+    wasm = new Uint8Array(await Bun.file(modulePath).arrayBuffer());
+  } catch (error) {
+    console.error(`error: failed to read '${modulePath}': ${error}`);
+    process.exit(1);
+  }
+  let inspection: WasmInspection;
+  try {
+    inspection = inspectWasm(wasm);
+  } catch (error) {
+    console.error(`error: '${modulePath}' is not a valid wasm module: ${error instanceof Error ? error.message : error}`);
+    process.exit(1);
+  }
+  console.log(formatWasmInspection(inspection));
+}
+
+async function linkModules(modulePaths: ReadonlyArray<string>, outputPath: string | null) {
+  const modules: Uint8Array[] = [];
+  for (const modulePath of modulePaths) {
+    try {
+      modules.push(new Uint8Array(await Bun.file(modulePath).arrayBuffer()));
+    } catch (error) {
+      console.error(`error: failed to read '${modulePath}': ${error}`);
+      process.exit(1);
+    }
+  }
+  let linked: Uint8Array;
+  try {
+    linked = link(modules, { labels: modulePaths });
+  } catch (error) {
+    console.error(error instanceof CompileError ? error.message : error);
+    process.exit(1);
+  }
+  try {
+    if (outputPath === null || outputPath === STDOUT_OUTPUT) {
+      await Bun.write(Bun.stdout, linked);
+    } else {
+      await ensureParentDirectory(outputPath);
+      await Bun.write(outputPath, linked);
+    }
+  } catch (error) {
+    const target = outputPath === null || outputPath === STDOUT_OUTPUT ? "stdout" : `'${outputPath}'`;
+    console.error(`error: failed to write ${target}: ${error}`);
+    process.exit(1);
+  }
+}
+
+async function rebuildStage2Wasm(): Promise<boolean> {
+  try {
+    await buildStage2Wasm();
+    return true;
+  } catch (error) {
+    console.error(error instanceof CompileError ? error.message : error);
+    return false;
+  }
+}
+
+// Runs `rebuild` once, then again whenever a watched path changes.  A path
+// may be a file or a directory of `.bp` modules.  Parent directories are
+// watched rather than files, because editors often save by replacing the
+// file, which ends a watch on the file itself.
+async function watchAndRebuild(
+  paths: ReadonlyArray<string>,
+  rebuild: () => Promise<boolean>,
+): Promise<never> {
+  let building = false;
+  let pending = false;
+  let timer: ReturnType<typeof setTimeout> | undefined;
+  const runRebuild = async (): Promise<void> => {
+    if (building) {
+      pending = true;
+      return;
+    }
+    building = true;
+    do {
+      pending = false;
+      const ok = await rebuild();
+      console.error(`watch: ${ok ? "build succeeded" : "build failed"}, waiting for changes`);
+    } while (pending);
+    building = false;
+  };
+  // Saving often fires several events; wait for them to settle.
+  const schedule = (): void => {
+    clearTimeout(timer);
+    timer = setTimeout(() => void runRebuild(), 50);
+  };
+
+  for (const path of paths) {
+    const isDirectory = await stat(path).then(
+      (stats) => stats.isDirectory(),
+      () => false,
+    );
+    const directory = isDirectory ? path : dirname(path);
+    const name = basename(path);
+    watchDirectory(directory, (_event, filename) => {
+      if (filename === null || (isDirectory ? filename.endsWith(".bp") : filename === name)) {
+        schedule();
+      }
+    });
+  }
+  await runRebuild();
+  return new Promise<never>(() => {});
+}
+
+// Input path that reads the source from stdin.
+const STDIN_INPUT = "-";
+// Output path that writes the module to stdout.
+const STDOUT_OUTPUT = "-";
+
+// Names the input in diagnostics.
+function displayPath(inputPath: string): string {
+  return inputPath === STDIN_INPUT ? "<stdin>" : inputPath;
+}
+
+// Reads the entry source, printing an error and returning null when that
+// fails.  Empty stdin is reported apart from a failed read, since it usually
+// means the producer upstream of the pipe wrote nothing.
+async function readSource(inputPath: string): Promise<string | null> {
+  if (inputPath !== STDIN_INPUT) {
+    try {
+      return await Bun.file(inputPath).text();
+    } catch (error) {
+      console.error(`error: failed to read '${inputPath}': ${error}`);
+      return null;
+    }
+  }
+  let source: string;
+  try {
+    source = await Bun.stdin.text();
+  } catch (error) {
+    console.error(`error: failed to read stdin: ${error}`);
+    return null;
+  }
+  if (source.length === 0) {
+    console.error("error: no input on stdin");
+    return null;
+  }
+  return source;
+}
+
+// The input is the entry module, so `use "./lib.bp"` in it names `lib.bp`
+// beside the input file.
+interface ImportedSources {
+  readonly modules: CompilerModuleSource[];
+  readonly libraries: CompilerLibrary[];
+}
+
+async function readImports(inputPath: string, source: string): Promise<ImportedSources> {
+  const provider = new FileSystemSourceProvider(dirname(inputPath));
+  const entry = { path: DEFAULT_ENTRY_MODULE_PATH, source };
+  const modules = await loadImports(provider, entry);
+  return { modules, libraries: await loadLibraries(provider, entry, modules) };
+}
+
+// When several inputs are linked, an input that `use`s another one compiles
+// against that input's interface, as for a `.bpl` library, and linking then
+// resolves the placeholders to the other input's functions.
+function useSiblingInterfaces(
+  inputPath: string,
+  imports: ImportedSources,
+  siblings: ReadonlyArray<string>,
+): { readonly imports: ImportedSources; readonly placeholders: string[] } {
+  const root = dirname(resolve(inputPath));
+  const siblingFiles = new Set(siblings.map((path) => resolve(path)));
+  const placeholders: string[] = [];
+  const modules = imports.modules.map((module) => {
+    if (!siblingFiles.has(resolve(root, `.${module.path}`))) {
+      return module;
+    }
+    const functions = readLibraryInterface(module.source);
+    placeholders.push(...functions.map((entry) => entry.name));
+    return { path: module.path, source: libraryInterfaceSource({ functions, wasm: new Uint8Array() }) };
+  });
+  return { imports: { modules, libraries: imports.libraries }, placeholders };
+}
+
+// Prints results in the style of `cargo test` and exits nonzero when a test
+// fails or the program does not compile.
+async function runTestCommand(inputPath: string, filter: string | undefined) {
+  const source = await readSource(inputPath);
+  if (source === null) {
+    process.exit(1);
+  }
+  const entryPath = displayPath(inputPath);
+  let compilation: Compilation;
+  try {
+    compilation = await compile(source, DEFAULT_TARGET, await readImports(inputPath, source));
+  } catch (error) {
+    if (error instanceof CompileError) {
+      console.error(formatCompileError(error, { entryPath, color: useColor() }));
+    } else {
+      console.error(error);
+    }
+    process.exit(1);
+  }
+  for (const warning of compilation.warnings) {
+    console.error(formatCompileWarning(warning, { entryPath, color: useColor() }));
+  }
+
+  const results = await runTests(compilation, { filter, format: { entryPath } });
+  const failures = results.filter((result) => !result.passed);
+  const filteredOut = compilation.tests.length - results.length;
+  console.log(`\nrunning ${results.length} test${results.length === 1 ? "" : "s"}`);
+  for (const result of results) {
+    console.log(`test ${result.name} ... ${result.passed ? "ok" : "FAILED"}`);
+  }
+  if (failures.length > 0) {
+    console.log("\nfailures:\n");
+    for (const failure of failures) {
+      console.log(`---- ${failure.name} ----\n${failure.error}\n`);
+    }
+  }
+  console.log(
+    `\ntest result: ${failures.length > 0 ? "FAILED" : "ok"}. ${results.length - failures.length} passed; ` +
+      `${failures.length} failed; ${filteredOut} filtered out\n`,
+  );
+  if (failures.length > 0) {
+    process.exit(1);
+  }
+}
+
 async function readCompilerModules(): Promise<CompilerModuleSource[]> {
   const directoryPath = fileURLToPath(COMPILER_DIR_URL);
   const entries = await readdir(directoryPath, { withFileTypes: true });
@@ -86,6 +607,15 @@ async function ensureParentDirectory(path: string) {
 async function main() {
   const args = Bun.argv.slice(2);
   const program = Bun.argv[1] ?? "bootstrap";
+  let watch = false;
+  if (args[0] === "--watch") {
+    watch = true;
+    args.shift();
+  }
+
+  if (args.length === 0 && watch) {
+    await watchAndRebuild([fileURLToPath(COMPILER_DIR_URL)], rebuildStage2Wasm);
+  }
 
   if (args.length === 0) {
     try {
@@ -101,16 +631,111 @@ async function main() {
     }
   }
 
+  if (args[0] === "bootstrap") {
+    const verify = args[1] === "--verify";
+    if (args.length > (verify ? 2 : 1)) {
+      printUsage(program);
+      process.exit(1);
+    }
+    try {
+      await (verify ? verifyStage2Wasm() : buildStage2Wasm());
+      return;
+    } catch (error) {
+      console.error(error instanceof CompileError ? error.message : error);
+      process.exit(1);
+    }
+  }
+
+  if (args[0] === "repl") {
+    await runRepl();
+    return;
+  }
+
+  if (args[0] === "test") {
+    const inputPath = args[1];
+    if (typeof inputPath !== "string" || inputPath.length === 0 || args.length > 3) {
+      printUsage(program);
+      process.exit(1);
+    }
+    await runTestCommand(inputPath, args[2]);
+    return;
+  }
+
+  if (args[0] === "inspect") {
+    const modulePath = args[1];
+    if (typeof modulePath !== "string" || modulePath.length === 0 || args.length > 2) {
+      printUsage(program);
+      process.exit(1);
+    }
+    await inspectModule(modulePath);
+    return;
+  }
+
+  if (args[0] === "link") {
+    const modulePaths: string[] = [];
+    let outputPath: string | null = null;
+    for (let index = 1; index < args.length; index += 1) {
+      if (args[index] === "-o") {
+        outputPath = args[index + 1] ?? "";
+        index += 1;
+      } else {
+        modulePaths.push(args[index]!);
+      }
+    }
+    if (modulePaths.length === 0 || modulePaths.some((path) => path.length === 0) || outputPath === "") {
+      printUsage(program);
+      process.exit(1);
+    }
+    await linkModules(modulePaths, outputPath);
+    return;
+  }
+
+  if (args[0] === "replay") {
+    const caseDirectory = args[1];
+    if (typeof caseDirectory !== "string" || caseDirectory.length === 0 || args.length > 2) {
+      printUsage(program);
+      process.exit(1);
+    }
+    await replayRecordedCase(caseDirectory);
+    return;
+  }
+
   const inputPath = args.shift();
   if (typeof inputPath !== "string" || inputPath.length === 0) {
     printUsage(program);
     process.exit(1);
   }
+  const inputPaths = [inputPath];
 
   let outputPath: string | null = null;
+  let outDir: string | null = null;
   let emitFlag: boolean | null = null;
+  let sizeReport = false;
   let run = false;
-  let target: Target = DEFAULT_TARGET;
+  let target: TargetSpec = toTargetSpec(DEFAULT_TARGET);
+  let checkedDivision = false;
+  let strict = false;
+  let integerConditions = false;
+  let noStd = false;
+  const defines: Record<string, string> = {};
+  let optimize = false;
+  let denyWarnings = false;
+  let constantTable = false;
+  let layoutGlobals = false;
+  let memoryCanaries = false;
+  let guardStores = false;
+  let addressSanitizer = false;
+  let profile = false;
+  let coverage = false;
+  let validateOutput = false;
+  let features: WasmFeatures | undefined;
+  let memoryPages: number | undefined;
+  let sharedMemory = false;
+  let maxNestingDepth: number | undefined;
+  let recordDirectory: string | null = null;
+  let sourceMap = false;
+  let verbosity = 0;
+  let locale: string | undefined;
 
   while (args.length > 0) {
     const arg = args.shift();
@@ -125,6 +750,13 @@ async function main() {
         process.exit(1);
       }
       outputPath = next;
+    } else if (arg === "--out-dir") {
+      const next = args.shift();
+      if (typeof next !== "string" || next.length === 0) {
+        console.error("error: expected directory after --out-dir");
+        process.exit(1);
+      }
+      outDir = next;
     } else if (arg === "--emit") {
       const next = args.shift();
       if (typeof next !== "string" || next.length === 0) {
@@ -133,6 +765,8 @@ async function main() {
       }
       if (next === "wasm") {
         emitFlag = true;
+      } else if (next === "size-report") {
+        sizeReport = true;
       } else if (next === "wat") {
         console.error("error: WAT output is no longer supported");
         process.exit(1);
@@ -142,6 +776,105 @@ async function main() {
       }
     } else if (arg === "--run") {
       run = true;
+    } else if (arg === "--checked-division") {
+      checkedDivision = true;
+    } else if (arg === "--strict") {
+      strict = true;
+    } else if (arg === "--integer-conditions") {
+      integerConditions = true;
+    } else if (arg === "--no-std") {
+      noStd = true;
+    } else if (arg === "--verbose" || arg === "-v") {
+      verbosity += 1;
+    } else if (arg === "-vv") {
+      verbosity += 2;
+    } else if (arg === "--define") {
+      const next = args.shift();
+      const match = typeof next === "string" ? /^([A-Za-z_][A-Za-z0-9_]*)(?:=(.*))?$/.exec(next) : null;
+      if (!match) {
+        console.error("error: expected NAME or NAME=value after --define");
+        process.exit(1);
+      }
+      defines[match[1]!] = match[2] ?? "";
+    } else if (arg === "--optimize") {
+      optimize = true;
+    } else if (arg === "--deny-warnings") {
+      denyWarnings = true;
+    } else if (arg === "--const-table") {
+      constantTable = true;
+    } else if (arg === "--source-map") {
+      sourceMap = true;
+    } else if (arg === "--layout-globals") {
+      layoutGlobals = true;
+    } else if (arg === "--memory-canaries") {
+      memoryCanaries = true;
+    } else if (arg === "--guard-stores") {
+      guardStores = true;
+    } else if (arg === "--instrument") {
+      const next = args.shift();
+      if (typeof next !== "string" || next.length === 0) {
+        console.error("error: expected instrumentation after --instrument");
+        process.exit(1);
+      }
+      if (next === "asan") {
+        addressSanitizer = true;
+      } else if (next === "profile") {
+        profile = true;
+      } else if (next === "coverage") {
+        coverage = true;
+      } else {
+        console.error(`error: unsupported instrumentation '${next}'`);
+        process.exit(1);
+      }
+    } else if (arg === "--validate-output") {
+      validateOutput = true;
+    } else if (arg === "--disable-feature") {
+      const next = args.shift();
+      const feature = (Object.keys(WASM_FEATURE_NAMES) as WasmFeature[]).find(
+        (key) => WASM_FEATURE_NAMES[key] === next,
+      );
+      if (!feature) {
+        const names = Object.values(WASM_FEATURE_NAMES).join(", ");
+        console.error(`error: expected a wasm feature after --disable-feature (${names})`);
+        process.exit(1);
+      }
+      features = { ...features, [feature]: false };
+    } else if (arg === "--shared-memory") {
+      sharedMemory = true;
+    } else if (arg === "--memory-pages") {
+      const next = args.shift();
+      if (typeof next !== "string" || !/^[0-9]+$/.test(next)) {
+        console.error("error: expected a page count after --memory-pages");
+        process.exit(1);
+      }
+      memoryPages = Number(next);
+    } else if (arg === "--max-nesting-depth") {
+      const next = args.shift();
+      if (typeof next !== "string" || !/^[0-9]+$/.test(next)) {
+        console.error("error: expected a depth after --max-nesting-depth");
+        process.exit(1);
+      }
+      maxNestingDepth = Number(next);
+    } else if (arg === "--locale") {
+      const next = args.shift();
+      if (typeof next !== "string" || next.length === 0) {
+        console.error("error: expected locale after --locale");
+        process.exit(1);
+      }
+      if (!hasLocale(next)) {
+        console.error(`error: unknown locale '${next}' (available: ${availableLocales().join(", ")})`);
+        process.exit(1);
+      }
+      locale = next;
+    } else if (arg === "--record") {
+      const next = args.shift();
+      if (typeof next !== "string" || next.length === 0) {
+        console.error("error: expected directory after --record");
+        process.exit(1);
+      }
+      recordDirectory = next;
+    } else if (arg === "--watch") {
+      watch = true;
     } else if (arg === "--target") {
       const next = args.shift();
       if (typeof next !== "string" || next.length === 0) {
@@ -149,7 +882,7 @@ async function main() {
         process.exit(1);
       }
       try {
-        target = parseTarget(next);
+        target = parseTargetSpec(next);
       } catch (error) {
         if (error instanceof CompileError) {
           console.error(error.message);
@@ -158,6 +891,8 @@ async function main() {
         }
         process.exit(1);
       }
+    } else if (arg === STDIN_INPUT || !arg.startsWith("-")) {
+      inputPaths.push(arg);
     } else {
       console.error(`error: unexpected argument '${arg}'`);
       printUsage(program);
@@ -165,99 +900,363 @@ async function main() {
     }
   }
 
-  if (run && target !== Target.Wasm) {
-    console.error(`error: target '${target}' cannot be executed with --run`);
+  const linking = inputPaths.length > 1 && outDir === null;
+  if (inputPaths.length > 1 && inputPaths.includes(STDIN_INPUT)) {
+    console.error("error: stdin can only be read when it is the only input");
+    process.exit(1);
+  }
+  if (outDir !== null && outputPath !== null) {
+    console.error("error: --out-dir and -o cannot be combined");
+    process.exit(1);
+  }
+  if (outDir !== null && inputPaths.includes(STDIN_INPUT)) {
+    console.error("error: --out-dir needs input files, since stdin has no name to write under");
+    process.exit(1);
+  }
+  if (outDir !== null) {
+    const outputs = new Map<string, string>();
+    for (const path of inputPaths) {
+      const output = outputPathInDirectory(outDir, path);
+      const other = outputs.get(output);
+      if (other !== undefined) {
+        console.error(`error: inputs '${other}' and '${path}' would both be written to '${output}'`);
+        process.exit(1);
+      }
+      outputs.set(output, path);
+    }
+  }
+  if (inputPaths.length > 1 && outDir === null && outputPath === null) {
+    console.error("error: several inputs need --out-dir, or -o to link them into one module");
+    process.exit(1);
+  }
+  if (linking && (run || sourceMap)) {
+    console.error("error: --run and --source-map need a single input or --out-dir");
+    process.exit(1);
+  }
+  if (linking && outputPath !== STDOUT_OUTPUT && ![".wasm", ""].includes(extname(outputPath!).toLowerCase())) {
+    console.error("error: linked inputs can only be written to '.wasm' files");
+    process.exit(1);
+  }
+
+  if (watch && (!outputPath || outputPath === STDOUT_OUTPUT) && outDir === null && !run) {
+    console.error("error: --watch needs -o with a file, --out-dir, or --run, since the output is rebuilt repeatedly");
+    process.exit(1);
+  }
+  if (watch && inputPaths.includes(STDIN_INPUT)) {
+    console.error("error: --watch needs an input file, since stdin cannot be watched");
     process.exit(1);
   }
 
-  if (target !== Target.Wasm && !outputPath && (emitFlag ?? true)) {
-    console.error(`error: target '${target}' cannot be emitted to stdout as WebAssembly`);
+  if (outputPath === STDOUT_OUTPUT && (sizeReport || run)) {
+    console.error("error: -o - cannot be combined with --emit size-report or --run, which also print to stdout");
     process.exit(1);
   }
 
-  let source: string;
-  try {
-    source = await Bun.file(inputPath).text();
-  } catch (error) {
-    console.error(`error: failed to read '${inputPath}': ${error}`);
+  if (sourceMap && (!outputPath || outputPath === STDOUT_OUTPUT || target.kind !== Target.Wasm)) {
+    console.error("error: --source-map needs -o with a file and the wasm target");
+    process.exit(1);
+  }
+
+  if (run && target.kind !== Target.Wasm) {
+    console.error(`error: target '${target.kind}' cannot be executed with --run`);
     process.exit(1);
   }
 
+  if (sizeReport && target.kind !== Target.Wasm) {
+    console.error(`error: target '${target.kind}' has no size report`);
+    process.exit(1);
+  }
+
+  if (target.kind !== Target.Wasm && (!outputPath || outputPath === STDOUT_OUTPUT) && (emitFlag ?? true)) {
+    console.error(`error: target '${target.kind}' cannot be emitted to stdout as WebAssembly`);
+    process.exit(1);
+  }
+
+  const settings: BuildSettings = {
+    target,
+    compileOptions: {
+      checkedDivision,
+      strict,
+      integerConditions,
+      noStd,
+      defines,
+      optimize,
+      constantTable,
+      layoutGlobals,
+      memoryCanaries,
+      guardStores,
+      addressSanitizer,
+      profile,
+      coverage,
+      validateOutput,
+      features,
+      memoryPages,
+      sharedMemory,
+      maxNestingDepth,
+      log: verbosity > 0 ? verboseLogger(verbosity) : undefined,
+    },
+    outputPath,
+    emitFlag,
+    sizeReport,
+    run,
+    denyWarnings,
+    recordDirectory,
+    sourceMap,
+    locale,
+  };
+  const rebuild = (): Promise<boolean> =>
+    inputPaths.length === 1 && outDir === null ? build(inputPath, settings) : buildAll(inputPaths, settings, outDir);
+  if (watch) {
+    await watchAndRebuild(inputPaths, rebuild);
+  } else if (!(await rebuild())) {
+    process.exit(1);
+  }
+}
+
+interface BuildSettings {
+  readonly target: TargetSpec;
+  readonly compileOptions: CompileOptions;
+  readonly outputPath: string | null;
+  readonly emitFlag: boolean | null;
+  readonly sizeReport: boolean;
+  readonly run: boolean;
+  readonly denyWarnings: boolean;
+  readonly recordDirectory: string | null;
+  readonly sourceMap: boolean;
+  readonly locale: string | undefined;
+}
+
+interface CompiledInput {
+  readonly source: string;
+  readonly compilation: Compilation;
+  /** The module, stamped with its `bootstrap.meta` section. */
+  readonly wasmBytes: Uint8Array;
+  /** Functions that stand in for another input's, when inputs are linked. */
+  readonly placeholders: ReadonlyArray<string>;
+}
+
+// Compiles `inputPath`, printing diagnostics.  Returns null when it failed.
+// `siblings` are the inputs it will be linked with.
+async function compileInput(
+  inputPath: string,
+  settings: BuildSettings,
+  siblings: ReadonlyArray<string> = [],
+): Promise<CompiledInput | null> {
+  const source = await readSource(inputPath);
+  if (source === null) {
+    return null;
+  }
+  const entryPath = displayPath(inputPath);
+
+  const { target, denyWarnings, recordDirectory, locale } = settings;
+  const { imports, placeholders } = useSiblingInterfaces(
+    inputPath,
+    await readImports(inputPath, source),
+    siblings,
+  );
+  const parentLog = settings.compileOptions.log;
+  const log: CompileLogger | undefined =
+    parentLog && ((level, message) => parentLog(level, `${entryPath}: ${message}`));
+  log?.(
+    1,
+    `read ${source.length} characters, ${imports.modules.length} imported modules, ` +
+      `${imports.libraries.length} libraries`,
+  );
+  const compileOptions = { ...settings.compileOptions, ...imports, log };
   let compilation: Compilation;
   try {
-    compilation = await compile(source, target);
+    compilation = await compile(source, target, compileOptions);
   } catch (error) {
     if (error instanceof CompileError) {
-      console.error(error.message);
+      console.error(
+        formatCompileError(error, { entryPath, color: useColor(), locale }),
+      );
+      if (recordDirectory) {
+        const replayCase = replayCaseFromFailure(inputPath, source, target, compileOptions, error);
+        const caseDirectory = await writeReplayCase(recordDirectory, replayCase);
+        console.error(`recorded failure to ${caseDirectory}`);
+      }
     } else {
       console.error(error);
     }
-    process.exit(1);
+    return null;
+  }
+
+  for (const warning of compilation.warnings) {
+    console.error(formatCompileWarning(warning, { entryPath, color: useColor(), locale }));
+  }
+  if (denyWarnings && compilation.warnings.length > 0) {
+    const count = compilation.warnings.length;
+    console.error(`error: ${count} warning${count === 1 ? "" : "s"} denied by --deny-warnings`);
+    return null;
   }
 
   let wasmBytes: Uint8Array;
   try {
     wasmBytes = compilation.toWasm();
+    if (target.kind === Target.Wasm) {
+      wasmBytes = appendBuildMeta(wasmBytes, await buildMetaFor(source, imports));
+    }
   } catch (error) {
     if (error instanceof CompileError) {
       console.error(error.message);
     } else {
       console.error(error);
     }
-    process.exit(1);
+    return null;
   }
+  return { source, compilation, wasmBytes, placeholders };
+}
+
+// Compiles `inputPath` and writes or runs the result, printing diagnostics.
+// Returns false when any step failed.
+async function build(inputPath: string, settings: BuildSettings): Promise<boolean> {
+  const compiled = await compileInput(inputPath, settings);
+  if (compiled === null) {
+    return false;
+  }
+  const { source, compilation } = compiled;
+  let { wasmBytes } = compiled;
+  const { target, outputPath, emitFlag, sizeReport, run, sourceMap } = settings;
 
-  if (outputPath) {
+  if (outputPath && outputPath !== STDOUT_OUTPUT) {
     const resolved = outputPath;
     const ext = extname(resolved).toLowerCase();
-    if (ext === ".wasm" && target !== Target.Wasm) {
-      console.error(`error: target '${target}' cannot be written to '.wasm' files`);
-      process.exit(1);
+    if (ext === ".wasm" && target.kind !== Target.Wasm) {
+      console.error(`error: target '${target.kind}' cannot be written to '.wasm' files`);
+      return false;
     }
     if (ext === ".wat") {
       console.error("error: WAT output is no longer supported");
-      process.exit(1);
+      return false;
     }
-    if (ext === ".wgsl" && target !== Target.Wgsl) {
-      console.error(`error: target '${target}' cannot be written to '.wgsl' files`);
-      process.exit(1);
+    if (ext === ".wgsl" && target.kind !== Target.Wgsl) {
+      console.error(`error: target '${target.kind}' cannot be written to '.wgsl' files`);
+      return false;
+    }
+    if (ext === LIBRARY_ARCHIVE_EXTENSION && target.kind !== Target.Wasm) {
+      console.error(`error: target '${target.kind}' cannot be written to '${LIBRARY_ARCHIVE_EXTENSION}' files`);
+      return false;
     }
-    if (ext && ext !== ".wasm" && ext !== ".wgsl" && ext !== "") {
+    if (ext && ext !== ".wasm" && ext !== ".wgsl" && ext !== LIBRARY_ARCHIVE_EXTENSION && ext !== "") {
       console.error(`error: unsupported output extension '${ext}'`);
-      process.exit(1);
+      return false;
     }
 
     try {
       await ensureParentDirectory(resolved);
+      if (sourceMap) {
+        const mapPath = `${resolved}.map`;
+        const map = compilation.sourceMap({
+          file: basename(resolved),
+          entryPath: relative(dirname(resolved), inputPath),
+        });
+        await Bun.write(mapPath, `${JSON.stringify(map)}\n`);
+        wasmBytes = appendSourceMappingUrl(wasmBytes, basename(mapPath));
+      }
+      if (ext === LIBRARY_ARCHIVE_EXTENSION) {
+        wasmBytes = writeLibraryArchive({ functions: readLibraryInterface(source), wasm: wasmBytes });
+      }
       await Bun.write(resolved, wasmBytes);
+      settings.compileOptions.log?.(1, `wrote ${wasmBytes.length} bytes to ${resolved}`);
     } catch (error) {
       console.error(`error: failed to write '${resolved}': ${error}`);
-      process.exit(1);
+      return false;
     }
   } else {
-    const emitToStdout = emitFlag ?? true;
+    const emitToStdout = emitFlag ?? !sizeReport;
     if (emitToStdout) {
       try {
         await Bun.write(Bun.stdout, wasmBytes);
       } catch (error) {
         console.error(`error: failed to write wasm to stdout: ${error}`);
-        process.exit(1);
+        return false;
       }
     }
   }
 
+  if (sizeReport) {
+    console.log(formatSizeReport(readSizeReport(wasmBytes)));
+  }
+
   if (run) {
     try {
-      await runWithBun(wasmBytes);
+      await runWithBun(compilation, wasmBytes, inputPath);
     } catch (error) {
       if (error instanceof CompileError) {
         console.error(error.message);
       } else {
         console.error(error);
       }
-      process.exit(1);
+      return false;
+    }
+  }
+  return true;
+}
+
+// Where `--out-dir` puts the module built from `inputPath`.
+function outputPathInDirectory(outDir: string, inputPath: string): string {
+  return join(outDir, `${basename(inputPath, extname(inputPath))}.wasm`);
+}
+
+// Builds several inputs at once and reports how many failed, after every
+// input's own diagnostics.  With `outDir` each input is built on its own;
+// otherwise the modules are linked into `settings.outputPath`.
+async function buildAll(
+  inputPaths: ReadonlyArray<string>,
+  settings: BuildSettings,
+  outDir: string | null,
+): Promise<boolean> {
+  const reportFailures = (succeeded: ReadonlyArray<boolean>): boolean => {
+    const failed = inputPaths.filter((_, index) => !succeeded[index]);
+    if (failed.length > 0 && inputPaths.length > 1) {
+      console.error(`error: ${failed.length} of ${inputPaths.length} inputs failed: ${failed.join(", ")}`);
+    }
+    return failed.length === 0;
+  };
+  if (outDir !== null) {
+    const succeeded = await Promise.all(
+      inputPaths.map((inputPath) =>
+        build(inputPath, { ...settings, outputPath: outputPathInDirectory(outDir, inputPath) }),
+      ),
+    );
+    return reportFailures(succeeded);
+  }
+
+  const compiled = await Promise.all(
+    inputPaths.map((inputPath) => compileInput(inputPath, settings, inputPaths)),
+  );
+  if (!reportFailures(compiled.map((input) => input !== null))) {
+    return false;
+  }
+  let linked: Uint8Array;
+  try {
+    linked = link(
+      compiled.map((input) => input!.wasmBytes),
+      { labels: inputPaths, placeholders: compiled.map((input) => input!.placeholders) },
+    );
+  } catch (error) {
+    console.error(error instanceof CompileError ? error.message : error);
+    return false;
+  }
+  settings.compileOptions.log?.(1, `linked ${inputPaths.length} inputs into ${linked.length} bytes`);
+  const outputPath = settings.outputPath!;
+  try {
+    if (outputPath === STDOUT_OUTPUT) {
+      await Bun.write(Bun.stdout, linked);
+    } else {
+      await ensureParentDirectory(outputPath);
+      await Bun.write(outputPath, linked);
     }
+  } catch (error) {
+    const target = outputPath === STDOUT_OUTPUT ? "stdout" : `'${outputPath}'`;
+    console.error(`error: failed to write ${target}: ${error}`);
+    return false;
+  }
+  if (settings.sizeReport) {
+    console.log(formatSizeReport(readSizeReport(linked)));
   }
+  return true;
 }
 
 await main();
@@ -0,0 +1,129 @@
+import { CompileError } from "./index";
+import type { CompilerModuleSource } from "./index";
+import { parseProgram, walkProgram } from "./frontend";
+import type { CallNames, Program } from "./frontend";
+
+/** The path the core library compiles under. */
+export const CORE_MODULE_PATH = "/stdlib/core.bp";
+/** The path the prelude compiles under, with or without the core library. */
+export const PRELUDE_MODULE_PATH = "/stdlib/prelude.bp";
+
+/** A top-level function of an implicit module. */
+export interface ImplicitItem {
+  readonly name: string;
+  /** The function with the comment and attribute lines directly above it. */
+  readonly text: string;
+  /** The free functions it calls. */
+  readonly calls: ReadonlySet<string>;
+}
+
+/** An implicit module, such as the core library, split into its functions. */
+export interface ImplicitModule extends CompilerModuleSource {
+  readonly items: ReadonlyArray<ImplicitItem>;
+}
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+const implicitModules = new Map<string, Promise<ImplicitModule>>();
+
+/**
+ * Parses `module` and splits it into its functions.  Each runs from the
+ * comment and attribute lines above it to those of the next one.  The result
+ * is kept for the next compile with the same module.
+ */
+export function readImplicitModule(module: CompilerModuleSource): Promise<ImplicitModule> {
+  const key = `${module.path}\0${module.source}`;
+  let parsed = implicitModules.get(key);
+  if (!parsed) {
+    parsed = splitImplicitModule(module);
+    implicitModules.set(key, parsed);
+  }
+  return parsed;
+}
+
+async function splitImplicitModule(module: CompilerModuleSource): Promise<ImplicitModule> {
+  let program: Program;
+  try {
+    program = await parseProgram(module.source);
+  } catch (error) {
+    if (error instanceof CompileError && error.record) {
+      const at = error.span ? `:${error.span.line}:${error.span.column}` : "";
+      throw new CompileError(`${module.path}${at}: ${error.record.message}`);
+    }
+    throw error;
+  }
+  // Methods, intrinsics, and calls through parameters have callees below -1.
+  const calls = new Map<string, Set<string>>();
+  walkProgram(program, {
+    enterFunction: (item) => {
+      calls.set(item.name, new Set());
+    },
+    enterExpression: (expression, item) => {
+      if (expression.kind === "call" && expression.callee >= -1) {
+        calls.get(item.name)!.add(expression.name);
+      }
+    },
+  });
+  // Offsets are in bytes, as the parser counts them.
+  const bytes = encoder.encode(module.source);
+  const lineStart = (offset: number): number => {
+    let start = offset;
+    while (start > 0 && bytes[start - 1] !== 0x0a) {
+      start -= 1;
+    }
+    return start;
+  };
+  const starts = program.functions.map((item) => {
+    let start = lineStart(item.offset);
+    while (start > 0) {
+      const above = lineStart(start - 1);
+      const line = decoder.decode(bytes.subarray(above, start - 1)).trimStart();
+      if (!line.startsWith("//") && !line.startsWith("#[")) {
+        break;
+      }
+      start = above;
+    }
+    return start;
+  });
+  const items = program.functions.map(
+    (item, index): ImplicitItem => ({
+      name: item.name,
+      text: decoder.decode(bytes.subarray(starts[index]!, starts[index + 1] ?? bytes.length)).trimEnd(),
+      calls: calls.get(item.name)!,
+    }),
+  );
+  return { ...module, items };
+}
+
+/**
+ * The parts of the implicit modules that `program` needs: the functions it
+ * calls but does not declare, and the functions those call in turn, which may
+ * sit in another of the modules.  `program` comes from parsing the entry
+ * module with everything but the implicit modules loaded.  Modules that none
+ * of it comes from are left out, so a program that needs nothing compiles
+ * exactly as without them.
+ */
+export function selectImplicitModules(
+  modules: ReadonlyArray<ImplicitModule>,
+  program: CallNames,
+): CompilerModuleSource[] {
+  const items = new Map(modules.flatMap((module) => module.items.map((item) => [item.name, item] as const)));
+  const pending = [...program.called];
+  const needed = new Set<string>();
+  for (let name = pending.pop(); name !== undefined; name = pending.pop()) {
+    const item = items.get(name);
+    if (!item || program.declared.has(name) || needed.has(name)) {
+      continue;
+    }
+    needed.add(name);
+    pending.push(...item.calls);
+  }
+  return modules.flatMap((module) => {
+    const selected = module.items.filter((item) => needed.has(item.name));
+    if (selected.length === 0) {
+      return [];
+    }
+    return [{ path: module.path, source: selected.map((item) => `${item.text}\n`).join("\n") }];
+  });
+}
@@ -0,0 +1,148 @@
+import type { CodeLocation, SourceSpan } from "./index";
+import { counterIncrement, instrumentWithCounters, readCounterTable, readModuleShape } from "./instrument";
+import type { InstrumentedWasm } from "./instrument";
+import { readFunctionBodies, readFunctionNames } from "./wasm_reader";
+
+/** Name of the exported global holding the coverage table's address. */
+export const COVERAGE_TABLE_EXPORT = "__coverage_table";
+// One `u32` execution count per block.
+const COVERAGE_COUNTER_SIZE = 4;
+
+/** A straight-line run of code with its own counter. */
+export interface CoverageBlock {
+  readonly functionIndex: number;
+  /** Offset of the block's first instruction. */
+  readonly start: number;
+  /** Offset just past the block's last instruction. */
+  readonly end: number;
+}
+
+export interface CoveredWasm extends InstrumentedWasm {
+  /** The counted blocks in table order, at offsets in the original module. */
+  readonly blocks: ReadonlyArray<CoverageBlock>;
+}
+
+export interface FunctionCoverage {
+  readonly functionIndex: number;
+  readonly name: string | null;
+  /** Where the function is declared, or `null` for runtime helpers. */
+  readonly span: SourceSpan | null;
+  /** How many times the function was entered. */
+  readonly calls: number;
+}
+
+export interface LineCoverage {
+  readonly path: string;
+  readonly line: number;
+  /** Executions of the most often run block with code from this line. */
+  readonly count: number;
+}
+
+export interface CoverageReport {
+  /** Executions of each block, in table order. */
+  readonly blocks: ReadonlyArray<number>;
+  /** Functions with at least one block, in index order. */
+  readonly functions: ReadonlyArray<FunctionCoverage>;
+  /** Every line with code, by path and then line. */
+  readonly lines: ReadonlyArray<LineCoverage>;
+}
+
+/**
+ * Gives every straight-line run of code in a module its own execution
+ * counter, in a table exported as `__coverage_table`; see
+ * {@link instrumentWithCounters}.  Runs without a counted instruction, such
+ * as a lone `end`, get no counter.
+ */
+export function instrumentWasmWithCoverage(wasm: Uint8Array): CoveredWasm {
+  const importedFunctions = readModuleShape(wasm).importedFunctions;
+  const blocks: CoverageBlock[] = [];
+  const instrumented = instrumentWithCounters(wasm, "coverage", COVERAGE_TABLE_EXPORT, (tableBase) => ({
+    enter: () => [],
+    run: (body, start, end, length) => {
+      if (length === 0) {
+        return [];
+      }
+      blocks.push({ functionIndex: importedFunctions + body, start, end });
+      return counterIncrement(tableBase + (blocks.length - 1) * COVERAGE_COUNTER_SIZE, false, 1);
+    },
+    exit: () => [],
+    tableSize: () => blocks.length * COVERAGE_COUNTER_SIZE,
+  }));
+  return { ...instrumented, blocks };
+}
+
+/**
+ * Reads the block counters of an instance of a module instrumented with
+ * {@link instrumentWasmWithCoverage} and attributes them to functions and
+ * source lines.  `blocks` and `locations` use the instrumented module's
+ * offsets.  Counts add to those of `base`, a report on an earlier instance
+ * of the same module.  Returns `null` when the module has no coverage table.
+ */
+export function readCoverageReport(
+  instance: WebAssembly.Instance,
+  wasm: Uint8Array,
+  blocks: ReadonlyArray<CoverageBlock>,
+  locations: ReadonlyArray<CodeLocation>,
+  base: CoverageReport | null = null,
+): CoverageReport | null {
+  const view = readCounterTable(instance, COVERAGE_TABLE_EXPORT);
+  if (!view) {
+    return null;
+  }
+  const counts = blocks.map(
+    (_, index) => view.getUint32(index * COVERAGE_COUNTER_SIZE, true) + (base?.blocks[index] ?? 0),
+  );
+  const names = readFunctionNames(wasm);
+  const bodies = readFunctionBodies(wasm);
+  // The first location at or after `offset`.
+  const firstLocation = (offset: number): number => {
+    let low = 0;
+    let high = locations.length;
+    while (low < high) {
+      const middle = (low + high) >> 1;
+      if (locations[middle]!.offset < offset) {
+        low = middle + 1;
+      } else {
+        high = middle;
+      }
+    }
+    return low;
+  };
+
+  const functions: FunctionCoverage[] = [];
+  const lines = new Map<string, Map<number, number>>();
+  blocks.forEach((block, index) => {
+    const count = counts[index]!;
+    const body = bodies.get(block.functionIndex) ?? block.start;
+    if (functions.at(-1)?.functionIndex !== block.functionIndex) {
+      // A function's first block runs once per call.
+      const location = locations[firstLocation(body)];
+      const span = location?.offset === body ? location.span : null;
+      const name = names.get(block.functionIndex) ?? null;
+      functions.push({ functionIndex: block.functionIndex, name, span, calls: count });
+    }
+    // The block's code comes from the location in effect at its start, as
+    // long as that is inside the same body, and any that start inside it.
+    let next = firstLocation(block.start);
+    if (next > 0 && locations[next]?.offset !== block.start && locations[next - 1]!.offset > body) {
+      next -= 1;
+    }
+    for (; next < locations.length && locations[next]!.offset < block.end; next += 1) {
+      const span = locations[next]!.span;
+      if (span) {
+        const file = lines.get(span.path) ?? new Map<number, number>();
+        lines.set(span.path, file);
+        file.set(span.line, Math.max(file.get(span.line) ?? 0, count));
+      }
+    }
+  });
+
+  const lineCoverage: LineCoverage[] = [];
+  for (const path of [...lines.keys()].sort()) {
+    const file = lines.get(path)!;
+    for (const line of [...file.keys()].sort((a, b) => a - b)) {
+      lineCoverage.push({ path, line, count: file.get(line)! });
+    }
+  }
+  return { blocks: counts, functions, lines: lineCoverage };
+}
@@ -0,0 +1,150 @@
+import { CompileError, CompileErrorKind, Target, compile, formatRuntimePanic } from "./index";
+import type { Compilation, CompileOptions } from "./index";
+
+/** Name of the function an expression is compiled into. */
+export const EXPRESSION_FUNCTION_NAME = "evaluate";
+
+/**
+ * Scalar types an expression may take and return.  Bootstrap has no inference
+ * for function results, so an expression without a declared result type is
+ * tried with each of these in order and the compiler rejects the wrong ones.
+ */
+export const EXPRESSION_TYPES = ["i32", "i64", "bool", "u32", "u64", "i8", "i16", "u8", "u16"] as const;
+
+export type ExpressionType = (typeof EXPRESSION_TYPES)[number];
+
+export type ExpressionValue = number | bigint | boolean;
+
+export interface ExpressionParameter {
+  readonly name: string;
+  readonly type: ExpressionType;
+}
+
+export interface CompileExpressionOptions extends CompileOptions {
+  /** The result type.  When omitted, the first type the expression compiles as. */
+  readonly returns?: ExpressionType;
+  /** Items placed before the generated function, such as helpers or `use`s. */
+  readonly prelude?: string;
+}
+
+export interface CompiledExpression {
+  readonly compilation: Compilation;
+  readonly parameters: ReadonlyArray<ExpressionParameter>;
+  readonly returns: ExpressionType;
+  /**
+   * Instantiates the module and returns the expression as a host function.
+   * Calls share one instance, so memory written by one call is seen by the
+   * next.  A panic or trap is thrown as an `Error` with the rendered panic.
+   */
+  evaluator(): Promise<(...args: ReadonlyArray<ExpressionValue>) => ExpressionValue>;
+}
+
+const IDENTIFIER_PATTERN = /^[A-Za-z_][A-Za-z0-9_]*$/;
+
+/** Whether `error` rejected a function result type rather than the code. */
+export function isReturnTypeMismatch(error: unknown): boolean {
+  return (
+    error instanceof CompileError &&
+    error.kind === CompileErrorKind.Semantic &&
+    error.record?.message === "return expression type does not match function return type"
+  );
+}
+
+/**
+ * Compiles `expression` as the body of a function named
+ * {@link EXPRESSION_FUNCTION_NAME} that takes `parameters` in order, so a host
+ * can use Bootstrap as an embedded expression language.  The expression may
+ * be a block body, with `let` statements before the final value.
+ */
+export async function compileExpression(
+  expression: string,
+  parameters: ReadonlyArray<ExpressionParameter> = [],
+  options: CompileExpressionOptions = {},
+): Promise<CompiledExpression> {
+  const { returns, prelude = "", ...compileOptions } = options;
+  for (const parameter of parameters) {
+    if (!IDENTIFIER_PATTERN.test(parameter.name)) {
+      throw new CompileError(`expression parameter name '${parameter.name}' is not an identifier`);
+    }
+    if (!EXPRESSION_TYPES.includes(parameter.type)) {
+      throw new CompileError(`expression parameter '${parameter.name}' has unsupported type '${parameter.type}'`);
+    }
+  }
+  const signature = parameters.map((parameter) => `${parameter.name}: ${parameter.type}`).join(", ");
+  const candidates = returns ? [returns] : EXPRESSION_TYPES;
+  let firstError: unknown = null;
+  for (const type of candidates) {
+    const source = `${prelude ? `${prelude}\n\n` : ""}fn ${EXPRESSION_FUNCTION_NAME}(${signature}) -> ${type} {\n${expression}\n}\n`;
+    try {
+      const compilation = await compile(source, Target.Wasm, compileOptions);
+      return compiledExpression(compilation, parameters, type);
+    } catch (error) {
+      firstError ??= error;
+      if (!isReturnTypeMismatch(error)) {
+        throw firstError;
+      }
+    }
+  }
+  throw firstError;
+}
+
+function compiledExpression(
+  compilation: Compilation,
+  parameters: ReadonlyArray<ExpressionParameter>,
+  returns: ExpressionType,
+): CompiledExpression {
+  return {
+    compilation,
+    parameters,
+    returns,
+    evaluator: async () => {
+      const { instance } = await WebAssembly.instantiate(compilation.wasm, {});
+      const exported = instance.exports[EXPRESSION_FUNCTION_NAME] as (...args: unknown[]) => unknown;
+      return (...args) => {
+        if (args.length !== parameters.length) {
+          throw new Error(`expression takes ${parameters.length} arguments but got ${args.length}`);
+        }
+        const wasmArgs = args.map((arg, index) => toWasmValue(arg, parameters[index].type));
+        let result: unknown;
+        try {
+          result = exported(...wasmArgs);
+        } catch (error) {
+          const panic = compilation.describePanic(instance);
+          if (panic) {
+            throw new Error(formatRuntimePanic(panic));
+          }
+          throw error;
+        }
+        return fromWasmValue(result, returns);
+      };
+    },
+  };
+}
+
+function toWasmValue(value: ExpressionValue, type: ExpressionType): number | bigint {
+  if (type === "i64" || type === "u64") {
+    return BigInt(value);
+  }
+  if (typeof value === "boolean") {
+    return value ? 1 : 0;
+  }
+  return Number(value);
+}
+
+// Wasm hands back i32 and i64 results as signed, so unsigned types are
+// reinterpreted.  Narrow types are returned as they are, since emitted 8- and
+// 16-bit arithmetic keeps its full i32 result.
+function fromWasmValue(value: unknown, type: ExpressionType): ExpressionValue {
+  switch (type) {
+    case "bool":
+      return value !== 0;
+    case "u32":
+      return Number(value) >>> 0;
+    case "u64":
+      return BigInt.asUintN(64, value as bigint);
+    case "i64":
+      return value as bigint;
+    default:
+      return Number(value);
+  }
+}
@@ -0,0 +1,254 @@
+import type { Compilation } from "./index";
+import { emitErrorAt } from "./validate";
+import { WasmReader } from "./wasm_reader";
+
+/**
+ * The post-MVP wasm proposals emitted code may use.  A missing field means
+ * the feature is enabled, so `{}` targets a current engine and
+ * {@link MVP_WASM_FEATURES} an old one.
+ */
+export interface WasmFeatures {
+  /** `i32.extend8_s` and the other sign extension operators. */
+  readonly signExt?: boolean;
+  /** `memory.copy`, `memory.fill`, passive data, and the table operators. */
+  readonly bulkMemory?: boolean;
+  /** The saturating `trunc_sat` float to integer conversions. */
+  readonly nontrappingFloatToInt?: boolean;
+  /** Functions and blocks with more than one result or with parameters. */
+  readonly multiValue?: boolean;
+  /**
+   * Reference value types and operators.  Arrays, tuples, and structs are
+   * garbage collected references, so programs that use them need this too.
+   */
+  readonly referenceTypes?: boolean;
+  /** The `v128` value type and the 128-bit SIMD operators `/stdlib/simd.bp` uses. */
+  readonly simd?: boolean;
+  /** Shared memories and the atomic operators `/stdlib/atomics.bp` uses. */
+  readonly threads?: boolean;
+}
+
+export type WasmFeature = keyof WasmFeatures;
+
+export const MVP_WASM_FEATURES: Required<WasmFeatures> = {
+  signExt: false,
+  bulkMemory: false,
+  nontrappingFloatToInt: false,
+  multiValue: false,
+  referenceTypes: false,
+  simd: false,
+  threads: false,
+};
+
+/** Each feature's bit in the mask `setWasmFeatures` takes. */
+const FEATURE_BITS: Readonly<Record<WasmFeature, number>> = {
+  signExt: 1,
+  bulkMemory: 2,
+  nontrappingFloatToInt: 4,
+  multiValue: 8,
+  referenceTypes: 16,
+  simd: 32,
+  threads: 64,
+};
+
+/** Command line and message names, as used by other wasm tools. */
+export const WASM_FEATURE_NAMES: Readonly<Record<WasmFeature, string>> = {
+  signExt: "sign-ext",
+  bulkMemory: "bulk-memory",
+  nontrappingFloatToInt: "nontrapping-float-to-int",
+  multiValue: "multi-value",
+  referenceTypes: "reference-types",
+  simd: "simd",
+  threads: "threads",
+};
+
+export function wasmFeatureEnabled(features: WasmFeatures, feature: WasmFeature): boolean {
+  return features[feature] ?? true;
+}
+
+export function wasmFeatureMask(features: WasmFeatures): number {
+  let mask = 0;
+  for (const feature of Object.keys(FEATURE_BITS) as WasmFeature[]) {
+    if (wasmFeatureEnabled(features, feature)) {
+      mask |= FEATURE_BITS[feature];
+    }
+  }
+  return mask;
+}
+
+const NUMERIC_VALUE_TYPES: ReadonlySet<number> = new Set([0x7f, 0x7e, 0x7d, 0x7c]);
+const V128_VALUE_TYPE = 0x7b;
+
+// The feature an instruction needs, or null for MVP instructions.
+function instructionFeature(opcode: number, sub: number): WasmFeature | null {
+  if (opcode >= 0xc0 && opcode <= 0xc4) {
+    return "signExt";
+  }
+  if (opcode === 0xfc) {
+    if (sub <= 7) {
+      return "nontrappingFloatToInt";
+    }
+    return sub <= 14 ? "bulkMemory" : "referenceTypes";
+  }
+  if (opcode === 0xfd) {
+    return "simd";
+  }
+  if (opcode === 0xfe) {
+    return "threads";
+  }
+  if (opcode === 0x1c || opcode === 0x25 || opcode === 0x26 || opcode === 0xfb || (opcode >= 0xd0 && opcode <= 0xd6)) {
+    return "referenceTypes";
+  }
+  return null;
+}
+
+function blockTypeFeature(byte: number): WasmFeature | null {
+  if (byte === 0x40 || NUMERIC_VALUE_TYPES.has(byte)) {
+    return null;
+  }
+  if (byte === V128_VALUE_TYPE) {
+    return "simd";
+  }
+  // Type indices are non-negative, so they start below 0x40 or continue.
+  return byte < 0x40 || (byte & 0x80) !== 0 ? "multiValue" : "referenceTypes";
+}
+
+interface FeatureUse {
+  readonly feature: WasmFeature;
+  /** Where the use is in the module. */
+  readonly offset: number;
+}
+
+function readValueTypeFeature(reader: WasmReader): WasmFeature | null {
+  const byte = reader.bytes[reader.offset]!;
+  reader.skipValueType();
+  if (byte === V128_VALUE_TYPE) {
+    return "simd";
+  }
+  return NUMERIC_VALUE_TYPES.has(byte) ? null : "referenceTypes";
+}
+
+function findTypeUse(reader: WasmReader, end: number, enabled: (feature: WasmFeature) => boolean): FeatureUse | null {
+  for (let count = reader.readLeb(); count > 0 && reader.offset < end; count -= 1) {
+    const offset = reader.offset;
+    const form = reader.readByte();
+    if (form !== 0x60) {
+      // Recursion groups, subtypes, structs, and arrays are GC types.
+      return enabled("referenceTypes") ? null : { feature: "referenceTypes", offset };
+    }
+    const params = reader.readLeb();
+    for (let index = 0; index < params; index += 1) {
+      const feature = readValueTypeFeature(reader);
+      if (feature && !enabled(feature)) {
+        return { feature, offset };
+      }
+    }
+    const results = reader.readLeb();
+    if (results > 1 && !enabled("multiValue")) {
+      return { feature: "multiValue", offset };
+    }
+    for (let index = 0; index < results; index += 1) {
+      const feature = readValueTypeFeature(reader);
+      if (feature && !enabled(feature)) {
+        return { feature, offset };
+      }
+    }
+  }
+  return null;
+}
+
+function findCodeUse(reader: WasmReader, enabled: (feature: WasmFeature) => boolean): FeatureUse | null {
+  for (let count = reader.readLeb(); count > 0; count -= 1) {
+    const bodySize = reader.readLeb();
+    const bodyEnd = reader.offset + bodySize;
+    for (let runs = reader.readLeb(); runs > 0; runs -= 1) {
+      reader.skipLeb();
+      const offset = reader.offset;
+      const feature = readValueTypeFeature(reader);
+      if (feature && !enabled(feature)) {
+        return { feature, offset };
+      }
+    }
+    while (reader.offset < bodyEnd) {
+      const offset = reader.offset;
+      const opcode = reader.readByte();
+      if (opcode === 0x02 || opcode === 0x03 || opcode === 0x04) {
+        const feature = blockTypeFeature(reader.bytes[reader.offset]!);
+        if (feature && !enabled(feature)) {
+          return { feature, offset };
+        }
+      }
+      const feature = instructionFeature(opcode, reader.skipImmediates(opcode));
+      if (feature && !enabled(feature)) {
+        return { feature, offset };
+      }
+    }
+  }
+  return null;
+}
+
+/** Finds the first part of a module that needs a feature `features` disables. */
+export function findDisabledFeatureUse(wasm: Uint8Array, features: WasmFeatures): FeatureUse | null {
+  const enabled = (feature: WasmFeature): boolean => wasmFeatureEnabled(features, feature);
+  const reader = new WasmReader(wasm, 8);
+  while (reader.offset < wasm.length) {
+    const start = reader.offset;
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    const end = reader.offset + size;
+    let use: FeatureUse | null = null;
+    if (id === 1) {
+      use = findTypeUse(reader, end, enabled);
+    } else if (id === 5 && !enabled("threads")) {
+      for (let count = reader.readLeb(); count > 0 && !use; count -= 1) {
+        const offset = reader.offset;
+        const flags = reader.readByte();
+        if (flags & 0x02) {
+          use = { feature: "threads", offset };
+        }
+        reader.skipLeb();
+        if (flags & 0x01) {
+          reader.skipLeb();
+        }
+      }
+    } else if (id === 6) {
+      for (let count = reader.readLeb(); count > 0 && !use; count -= 1) {
+        const offset = reader.offset;
+        const feature = readValueTypeFeature(reader);
+        if (feature && !enabled(feature)) {
+          use = { feature, offset };
+        }
+        reader.readByte();
+        for (let opcode = reader.readByte(); opcode !== 0x0b; opcode = reader.readByte()) {
+          reader.skipImmediates(opcode);
+        }
+      }
+    } else if (id === 10) {
+      use = findCodeUse(reader, enabled);
+    } else if (id === 12 && !enabled("bulkMemory")) {
+      use = { feature: "bulkMemory", offset: start };
+    }
+    if (use) {
+      return use;
+    }
+    reader.offset = end;
+  }
+  return null;
+}
+
+/**
+ * Rejects a compilation whose module needs a feature `features` disables.
+ * The error names the feature and, for code, the function and the source of
+ * the instruction.
+ */
+export function checkWasmFeatures(compilation: Compilation, features: WasmFeatures): void {
+  const wasm = compilation.wasm;
+  const use = findDisabledFeatureUse(wasm, features);
+  if (!use) {
+    return;
+  }
+  throw emitErrorAt(
+    compilation,
+    { offset: use.offset },
+    (where) => `emitted wasm needs the disabled ${WASM_FEATURE_NAMES[use.feature]} feature${where}`,
+  );
+}
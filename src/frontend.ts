@@ -0,0 +1,799 @@
+// Typed views of the trees the stage2 compiler builds, for tools that analyze
+// `.bp` programs without compiling them.  `parseProgram` returns the AST as the
+// parser left it, and `lowerToHir` returns it after semantic validation, which
+// resolves calls, assigns types, and adds const specializations.  Both decode
+// the compiler's own tables, so they agree with it by construction.
+
+import { CompileError, DEFAULT_STAGE2_LAYOUT, astExpressionTableBase, astProgramBase } from "./index";
+import type { Stage2Layout } from "./index";
+import { Stage2Phases } from "./stage2";
+
+/** Names of the stage2 builtin type ids, indexed by id. */
+export const BUILTIN_TYPES = ["i32", "bool", "i8", "i16", "i64", "u8", "u16", "u32", "u64", "type", "v128"] as const;
+
+export type BinaryOperator =
+  | "+"
+  | "-"
+  | "*"
+  | "/"
+  | "%"
+  | "=="
+  | "!="
+  | "<"
+  | ">"
+  | "<="
+  | ">="
+  | "||"
+  | "&&"
+  | "|"
+  | "&"
+  | "<<"
+  | ">>";
+
+interface NodeBase {
+  /** The node's index in the expression table, or -1 for a synthesized node. */
+  readonly index: number;
+  /** The stage2 type id, or -1 before one is assigned. */
+  readonly type: number;
+  /** Byte offset into the module the node came from, when it records one. */
+  readonly offset: number | null;
+}
+
+export interface LiteralExpression extends NodeBase {
+  readonly kind: "literal";
+  readonly value: number;
+}
+
+export interface CallExpression extends NodeBase {
+  readonly kind: "call";
+  readonly name: string;
+  readonly args: ReadonlyArray<Expression>;
+  /** The callee's index in the function table, or -1 while unresolved. */
+  readonly callee: number;
+}
+
+export interface BinaryExpression extends NodeBase {
+  readonly kind: "binary";
+  readonly operator: BinaryOperator;
+  readonly left: Expression;
+  readonly right: Expression;
+}
+
+export interface NotExpression extends NodeBase {
+  readonly kind: "not";
+  readonly value: Expression;
+}
+
+export interface ParamExpression extends NodeBase {
+  readonly kind: "param";
+  readonly param: number;
+}
+
+export interface LocalExpression extends NodeBase {
+  readonly kind: "local";
+  readonly local: number;
+}
+
+export interface LetExpression extends NodeBase {
+  readonly kind: "let";
+  readonly local: number;
+  readonly init: Expression;
+  /** The rest of the block, in which the local is in scope. */
+  readonly body: Expression;
+}
+
+export interface AssignExpression extends NodeBase {
+  readonly kind: "assign";
+  readonly local: number;
+  readonly value: Expression;
+}
+
+export interface SequenceExpression extends NodeBase {
+  readonly kind: "sequence";
+  readonly first: Expression;
+  readonly then: Expression;
+  /** Set for `value;`, whose result is dropped. */
+  readonly discard: boolean;
+}
+
+export interface IfExpression extends NodeBase {
+  readonly kind: "if";
+  readonly condition: Expression;
+  readonly then: Expression;
+  readonly else: Expression | null;
+}
+
+export interface LoopExpression extends NodeBase {
+  readonly kind: "loop";
+  readonly body: Expression;
+}
+
+export interface BreakExpression extends NodeBase {
+  readonly kind: "break";
+  readonly value: Expression | null;
+  /** How many loops lie between the `break` and its target. */
+  readonly depth: number;
+}
+
+export interface ContinueExpression extends NodeBase {
+  readonly kind: "continue";
+  readonly depth: number;
+}
+
+export interface ReturnExpression extends NodeBase {
+  readonly kind: "return";
+  /** `null` for a bare `return`. */
+  readonly value: Expression | null;
+}
+
+export interface LoadExpression extends NodeBase {
+  readonly kind: "load";
+  /** Bytes read: 1, 2, or 4. */
+  readonly width: number;
+  readonly pointer: Expression;
+}
+
+export interface StoreExpression extends NodeBase {
+  readonly kind: "store";
+  readonly width: number;
+  readonly pointer: Expression;
+  readonly value: Expression;
+}
+
+export interface ArrayExpression extends NodeBase {
+  readonly kind: "array";
+  readonly elements: ReadonlyArray<Expression>;
+}
+
+export interface ArrayRepeatExpression extends NodeBase {
+  readonly kind: "array-repeat";
+  readonly element: Expression;
+  /** The length when it is a literal, and `null` when `lengthExpression` gives it. */
+  readonly length: number | null;
+  readonly lengthExpression: Expression | null;
+}
+
+export interface ArrayGetExpression extends NodeBase {
+  readonly kind: "array-get";
+  readonly array: Expression;
+  readonly element: Expression;
+}
+
+export interface ArraySetExpression extends NodeBase {
+  readonly kind: "array-set";
+  readonly array: Expression;
+  readonly element: Expression;
+  readonly value: Expression;
+}
+
+export interface ArrayLenExpression extends NodeBase {
+  readonly kind: "array-len";
+  readonly array: Expression;
+}
+
+export interface TupleExpression extends NodeBase {
+  readonly kind: "tuple";
+  readonly elements: ReadonlyArray<Expression>;
+}
+
+export interface TupleGetExpression extends NodeBase {
+  readonly kind: "tuple-get";
+  readonly tuple: Expression;
+  readonly field: number;
+}
+
+export interface TupleSetExpression extends NodeBase {
+  readonly kind: "tuple-set";
+  readonly tuple: Expression;
+  readonly field: number;
+  readonly value: Expression;
+}
+
+/** A struct field label: a name, or a `[expression]` that evaluates to one. */
+export type FieldLabel = string | Expression;
+
+export interface StructField {
+  readonly label: FieldLabel;
+  readonly value: Expression;
+}
+
+export interface StructExpression extends NodeBase {
+  readonly kind: "struct";
+  readonly structType: Expression;
+  readonly fields: ReadonlyArray<StructField>;
+}
+
+export interface FieldExpression extends NodeBase {
+  readonly kind: "field";
+  readonly struct: Expression;
+  readonly label: FieldLabel;
+}
+
+export interface CastExpression extends NodeBase {
+  readonly kind: "cast";
+  readonly value: Expression;
+  readonly targetType: number;
+}
+
+export interface ConstRefExpression extends NodeBase {
+  readonly kind: "const-ref";
+  readonly name: string;
+}
+
+export interface InlineWasmExpression extends NodeBase {
+  readonly kind: "inline-wasm";
+  readonly bytes: Uint8Array;
+}
+
+export interface AnonymousFunctionExpression extends NodeBase {
+  readonly kind: "anonymous-function";
+  readonly paramCount: number;
+  readonly body: ReadonlyArray<Expression>;
+}
+
+export interface PanicExpression extends NodeBase {
+  readonly kind: "panic";
+  readonly code: number;
+  readonly message: string;
+}
+
+export interface StackallocExpression extends NodeBase {
+  readonly kind: "stackalloc";
+  readonly size: Expression;
+}
+
+export interface AddressOfExpression extends NodeBase {
+  readonly kind: "address-of";
+  readonly local: number;
+}
+
+export type Expression =
+  | LiteralExpression
+  | CallExpression
+  | BinaryExpression
+  | NotExpression
+  | ParamExpression
+  | LocalExpression
+  | LetExpression
+  | AssignExpression
+  | SequenceExpression
+  | IfExpression
+  | LoopExpression
+  | BreakExpression
+  | ContinueExpression
+  | ReturnExpression
+  | LoadExpression
+  | StoreExpression
+  | ArrayExpression
+  | ArrayRepeatExpression
+  | ArrayGetExpression
+  | ArraySetExpression
+  | ArrayLenExpression
+  | TupleExpression
+  | TupleGetExpression
+  | TupleSetExpression
+  | StructExpression
+  | FieldExpression
+  | CastExpression
+  | ConstRefExpression
+  | InlineWasmExpression
+  | AnonymousFunctionExpression
+  | PanicExpression
+  | StackallocExpression
+  | AddressOfExpression;
+
+export type ExpressionKind = Expression["kind"];
+
+export interface Parameter {
+  /** `null` for a specialization, which does not keep parameter names. */
+  readonly name: string | null;
+  readonly type: number;
+  readonly isConst: boolean;
+}
+
+export interface FunctionItem {
+  readonly name: string;
+  readonly params: ReadonlyArray<Parameter>;
+  readonly returnType: number;
+  readonly isConst: boolean;
+  /** Byte offset of the name in the function's module. */
+  readonly offset: number;
+  readonly body: Expression;
+}
+
+export interface Program {
+  readonly functions: ReadonlyArray<FunctionItem>;
+}
+
+const WORD_SIZE = 4;
+
+const FUNCTION_FLAG_CONST = 1;
+const FUNCTION_FLAG_HAS_CONST_PARAMS = 2;
+const FUNCTION_FLAG_PARAM_NAME_STARTS = 16;
+
+const BODY_KIND_LITERAL = 0;
+const BODY_KIND_CALL = 1;
+
+const EXPRESSION_KIND_CALL = 1;
+// Callees below this are methods, intrinsics, and calls through parameters.
+const CALLEE_UNRESOLVED = -1;
+
+/** Binary operators by their stage2 expression kind. */
+export const BINARY_OPERATORS: ReadonlyMap<number, BinaryOperator> = new Map([
+  [2, "+"],
+  [3, "-"],
+  [4, "*"],
+  [5, "/"],
+  [46, "%"],
+  [14, "=="],
+  [15, "!="],
+  [16, "<"],
+  [17, ">"],
+  [18, "<="],
+  [19, ">="],
+  [20, "||"],
+  [21, "&&"],
+  [25, "|"],
+  [26, "&"],
+  [27, "<<"],
+  [28, ">>"],
+]);
+
+const STRUCT_LABEL_KIND_IDENTIFIER = 0;
+const STRUCT_LITERAL_FIELD_WORDS = 5;
+
+const decoder = new TextDecoder();
+
+class TreeReader {
+  readonly #view: DataView;
+  readonly #memory: WebAssembly.Memory;
+  readonly #table: number;
+  readonly #count: number;
+  readonly #layout: Stage2Layout;
+
+  constructor(memory: WebAssembly.Memory, table: number, layout: Stage2Layout) {
+    this.#memory = memory;
+    this.#view = new DataView(memory.buffer);
+    this.#table = table;
+    this.#layout = layout;
+    this.#count = this.word(table);
+  }
+
+  word(ptr: number): number {
+    return this.#view.getInt32(ptr, true);
+  }
+
+  text(ptr: number, length: number): string {
+    return length > 0 ? decoder.decode(new Uint8Array(this.#memory.buffer, ptr, length)) : "";
+  }
+
+  // Call data keeps byte strings one byte per word.
+  bytes(ptr: number, length: number): Uint8Array {
+    return Uint8Array.from({ length: Math.max(length, 0) }, (_, byte) => this.word(ptr + byte * WORD_SIZE));
+  }
+
+  list(ptr: number, count: number): Expression[] {
+    return Array.from({ length: Math.max(count, 0) }, (_, item) =>
+      this.expression(this.word(ptr + item * WORD_SIZE)),
+    );
+  }
+
+  optional(index: number): Expression | null {
+    return index < 0 ? null : this.expression(index);
+  }
+
+  call(metadata: number, base: NodeBase): CallExpression {
+    return {
+      ...base,
+      kind: "call",
+      name: this.text(this.word(metadata), this.word(metadata + WORD_SIZE)),
+      args: this.list(metadata + 4 * WORD_SIZE, this.word(metadata + 2 * WORD_SIZE)),
+      callee: this.word(metadata + 3 * WORD_SIZE),
+    };
+  }
+
+  // A label is its kind, then a name's address and length or an expression.
+  label(ptr: number): FieldLabel {
+    const data = this.word(ptr + WORD_SIZE);
+    if (this.word(ptr) === STRUCT_LABEL_KIND_IDENTIFIER) {
+      return this.text(data, this.word(ptr + 2 * WORD_SIZE));
+    }
+    return this.expression(data);
+  }
+
+  expression(index: number): Expression {
+    if (index < 0 || index >= this.#count) {
+      throw new CompileError(`AST expression ${index} is out of range`);
+    }
+    const entry = this.#table + WORD_SIZE + index * this.#layout.astExprEntrySize;
+    const kind = this.word(entry);
+    const [data0, data1, data2] = [1, 2, 3].map((field) => this.word(entry + field * WORD_SIZE)) as [
+      number,
+      number,
+      number,
+    ];
+    const extra = this.word(entry + 4 * WORD_SIZE);
+    const types = this.#table + WORD_SIZE + this.#layout.astExprCapacity * this.#layout.astExprEntrySize;
+    const type = this.word(types + index * WORD_SIZE);
+    const at = (offset: number): NodeBase => ({ index, type, offset: offset >= 0 ? offset : null });
+    const base = at(-1);
+    const operator = BINARY_OPERATORS.get(kind);
+    if (operator) {
+      return {
+        ...at(data2),
+        kind: "binary",
+        operator,
+        left: this.expression(data0),
+        right: this.expression(data1),
+      };
+    }
+    switch (kind) {
+      case 0:
+        return { ...base, kind: "literal", value: data0 };
+      case 1:
+        return this.call(data0, at(data2));
+      case 6:
+        return { ...base, kind: "param", param: data0 };
+      case 7:
+        return {
+          ...base,
+          kind: "if",
+          condition: this.expression(data0),
+          then: this.expression(data1),
+          else: this.optional(data2),
+        };
+      case 8:
+        return { ...base, kind: "local", local: data0 };
+      case 9:
+        return { ...base, kind: "let", local: data0, init: this.expression(data1), body: this.expression(data2) };
+      case 10:
+        return { ...base, kind: "assign", local: data0, value: this.expression(data1) };
+      case 11:
+        return {
+          ...base,
+          kind: "sequence",
+          first: this.expression(data0),
+          then: this.expression(data1),
+          discard: (data2 & 1) !== 0,
+        };
+      case 12:
+        return { ...base, kind: "loop", body: this.expression(data0) };
+      case 13:
+        return { ...at(data2), kind: "break", value: this.optional(data1), depth: extra };
+      case 22:
+        return { ...at(data2), kind: "not", value: this.expression(data0) };
+      case 23:
+        return { ...at(data2), kind: "return", value: data1 !== 0 ? null : this.optional(data0) };
+      case 24:
+        return { ...at(data2), kind: "continue", depth: extra };
+      case 29:
+      case 30:
+      case 31:
+        return { ...base, kind: "load", width: 1 << (kind - 29), pointer: this.expression(data0) };
+      case 32:
+      case 33:
+      case 34:
+        return {
+          ...base,
+          kind: "store",
+          width: 1 << (kind - 32),
+          pointer: this.expression(data0),
+          value: this.expression(data1),
+        };
+      case 35:
+        return {
+          ...at(extra),
+          kind: "array-repeat",
+          element: this.expression(data0),
+          length: data1 >= 0 ? data1 : null,
+          lengthExpression: data2 > 0 ? this.optional(this.word(data2)) : null,
+        };
+      case 36:
+        return {
+          ...at(data2),
+          kind: "array-get",
+          array: this.expression(data0),
+          element: this.expression(data1),
+        };
+      case 37:
+        return { ...at(extra), kind: "array", elements: this.list(data0, data1) };
+      case 38:
+        return { ...base, kind: "array-len", array: this.expression(data0) };
+      case 39:
+        return { ...base, kind: "cast", value: this.expression(data0), targetType: data1 };
+      case 40:
+        return { ...base, kind: "tuple", elements: this.list(data0, data1) };
+      case 41:
+        return { ...at(data2), kind: "tuple-get", tuple: this.expression(data0), field: data1 };
+      case 42:
+        return {
+          ...base,
+          kind: "inline-wasm",
+          bytes: this.bytes(data0, data1),
+        };
+      case 43:
+        return { ...at(data0), kind: "const-ref", name: this.text(data2 + data0, data1) };
+      case 44:
+        return {
+          ...base,
+          kind: "array-set",
+          array: this.expression(data0),
+          element: this.expression(data1),
+          value: this.expression(data2),
+        };
+      case 45:
+        return {
+          ...base,
+          kind: "tuple-set",
+          tuple: this.expression(data0),
+          field: data1,
+          value: this.expression(data2),
+        };
+      case 47:
+        return {
+          ...at(extra),
+          kind: "struct",
+          structType: this.expression(data0),
+          fields: Array.from({ length: data2 }, (_, field) => {
+            const fieldPtr = data1 + (1 + field * STRUCT_LITERAL_FIELD_WORDS) * WORD_SIZE;
+            return {
+              label: this.label(fieldPtr),
+              value: this.expression(this.word(fieldPtr + 4 * WORD_SIZE)),
+            };
+          }),
+        };
+      case 48:
+        return {
+          ...at(data2),
+          kind: "field",
+          struct: this.expression(data0),
+          label: this.label(data1),
+        };
+      case 49:
+        return {
+          ...at(extra),
+          kind: "anonymous-function",
+          paramCount: data0 > 0 ? this.word(data0) : 0,
+          body: this.list(data1, data2),
+        };
+      case 50:
+        return {
+          ...at(data2),
+          kind: "panic",
+          code: this.word(data0),
+          message: decoder.decode(this.bytes(this.word(data0 + 2 * WORD_SIZE), this.word(data0 + WORD_SIZE))),
+        };
+      case 51:
+        return { ...at(data2), kind: "stackalloc", size: this.expression(data0) };
+      case 52:
+        return { ...at(data2), kind: "address-of", local: data0 };
+      default:
+        throw new CompileError(`AST expression ${index} has unknown kind ${kind}`);
+    }
+  }
+}
+
+/**
+ * Decodes the program a stage2 `parse` or `check` run left in `memory`, for an
+ * input of `inputLength` bytes with its output at `outputPtr`.
+ */
+export function readProgram(
+  memory: WebAssembly.Memory,
+  outputPtr: number,
+  inputLength: number,
+  layout: Stage2Layout = DEFAULT_STAGE2_LAYOUT,
+): Program {
+  const astBase = astProgramBase(outputPtr, inputLength, layout);
+  const reader = new TreeReader(memory, astExpressionTableBase(astBase, layout), layout);
+  const count = reader.word(astBase);
+  const functions = Array.from({ length: Math.max(count, 0) }, (_, index): FunctionItem => {
+    const entry = astBase + WORD_SIZE + index * layout.astFunctionEntrySize;
+    const field = (word: number): number => reader.word(entry + word * WORD_SIZE);
+    const paramCount = field(2);
+    const returnType = field(7);
+    const flags = field(8);
+    const paramTypes = field(6);
+    const constParams = flags & FUNCTION_FLAG_HAS_CONST_PARAMS ? field(9) : 0;
+    const moduleBase = field(11);
+    const moduleSource = reader.text(moduleBase, field(12));
+    const params = Array.from({ length: paramCount }, (_, param): Parameter => {
+      let name: string | null = null;
+      if (flags & FUNCTION_FLAG_PARAM_NAME_STARTS) {
+        const start = reader.word(paramTypes + (paramCount + param) * WORD_SIZE);
+        name = /^[A-Za-z_][A-Za-z0-9_]*/.exec(moduleSource.slice(start))?.[0] ?? null;
+      }
+      const maskWord = constParams > 0 ? reader.word(constParams + (1 + (param >> 5)) * WORD_SIZE) : 0;
+      return {
+        name,
+        type: reader.word(paramTypes + param * WORD_SIZE),
+        isConst: ((maskWord >>> (param & 31)) & 1) !== 0,
+      };
+    });
+    const bodyKind = field(3);
+    const bodyData = field(4);
+    const synthesized: NodeBase = { index: -1, type: returnType, offset: null };
+    const body: Expression =
+      bodyKind === BODY_KIND_LITERAL
+        ? { ...synthesized, kind: "literal", value: bodyData }
+        : bodyKind === BODY_KIND_CALL
+          ? reader.call(bodyData, synthesized)
+          : reader.expression(bodyData);
+    return {
+      name: reader.text(field(0), field(1)),
+      params,
+      returnType,
+      isConst: (flags & FUNCTION_FLAG_CONST) !== 0,
+      offset: field(14),
+      body,
+    };
+  });
+  return { functions };
+}
+
+/** The function names a parsed program declares and calls. */
+export interface CallNames {
+  readonly declared: ReadonlySet<string>;
+  /** Free functions only, leaving out methods and calls through parameters. */
+  readonly called: ReadonlySet<string>;
+}
+
+/**
+ * Reads the names of the functions in the AST a stage2 `parse` run left in
+ * `memory`, and of the free functions its calls name.  The calls come from the
+ * whole expression table, so constant initializers and anonymous functions
+ * count, without decoding any tree.
+ */
+export function readCallNames(
+  memory: WebAssembly.Memory,
+  outputPtr: number,
+  inputLength: number,
+  layout: Stage2Layout = DEFAULT_STAGE2_LAYOUT,
+): CallNames {
+  const astBase = astProgramBase(outputPtr, inputLength, layout);
+  const table = astExpressionTableBase(astBase, layout);
+  const reader = new TreeReader(memory, table, layout);
+  const declared = new Set<string>();
+  const called = new Set<string>();
+  const addCall = (metadata: number): void => {
+    if (reader.word(metadata + 3 * WORD_SIZE) >= CALLEE_UNRESOLVED) {
+      called.add(reader.text(reader.word(metadata), reader.word(metadata + WORD_SIZE)));
+    }
+  };
+  const functionCount = reader.word(astBase);
+  for (let index = 0; index < functionCount; index += 1) {
+    const entry = astBase + WORD_SIZE + index * layout.astFunctionEntrySize;
+    declared.add(reader.text(reader.word(entry), reader.word(entry + WORD_SIZE)));
+    if (reader.word(entry + 3 * WORD_SIZE) === BODY_KIND_CALL) {
+      addCall(reader.word(entry + 4 * WORD_SIZE));
+    }
+  }
+  const expressionCount = reader.word(table);
+  for (let index = 0; index < expressionCount; index += 1) {
+    const entry = table + WORD_SIZE + index * layout.astExprEntrySize;
+    if (reader.word(entry) === EXPRESSION_KIND_CALL) {
+      addCall(reader.word(entry + WORD_SIZE));
+    }
+  }
+  return { declared, called };
+}
+
+/** The expressions directly under `expression`, in source order. */
+export function childExpressions(expression: Expression): Expression[] {
+  switch (expression.kind) {
+    case "literal":
+    case "param":
+    case "local":
+    case "continue":
+    case "const-ref":
+    case "inline-wasm":
+    case "panic":
+    case "address-of":
+      return [];
+    case "call":
+      return [...expression.args];
+    case "binary":
+      return [expression.left, expression.right];
+    case "not":
+    case "assign":
+    case "cast":
+      return [expression.value];
+    case "let":
+      return [expression.init, expression.body];
+    case "sequence":
+      return [expression.first, expression.then];
+    case "if":
+      return [expression.condition, expression.then, ...(expression.else ? [expression.else] : [])];
+    case "loop":
+      return [expression.body];
+    case "break":
+    case "return":
+      return expression.value ? [expression.value] : [];
+    case "load":
+      return [expression.pointer];
+    case "store":
+      return [expression.pointer, expression.value];
+    case "array":
+    case "tuple":
+      return [...expression.elements];
+    case "array-repeat":
+      return [expression.element, ...(expression.lengthExpression ? [expression.lengthExpression] : [])];
+    case "array-get":
+      return [expression.array, expression.element];
+    case "array-set":
+      return [expression.array, expression.element, expression.value];
+    case "array-len":
+      return [expression.array];
+    case "tuple-get":
+      return [expression.tuple];
+    case "tuple-set":
+      return [expression.tuple, expression.value];
+    case "struct":
+      return [
+        expression.structType,
+        ...expression.fields.flatMap((field) =>
+          typeof field.label === "string" ? [field.value] : [field.label, field.value],
+        ),
+      ];
+    case "field":
+      return typeof expression.label === "string" ? [expression.struct] : [expression.struct, expression.label];
+    case "anonymous-function":
+      return [...expression.body];
+    case "stackalloc":
+      return [expression.size];
+  }
+}
+
+/**
+ * Callbacks for {@link walkProgram}.  Returning `false` from `enterExpression`
+ * skips the expression's children.
+ */
+export interface ProgramVisitor {
+  enterFunction?(item: FunctionItem): void;
+  leaveFunction?(item: FunctionItem): void;
+  enterExpression?(expression: Expression, item: FunctionItem): boolean | void;
+  leaveExpression?(expression: Expression, item: FunctionItem): void;
+}
+
+/** Visits every function of `program` and every expression under it, depth first. */
+export function walkProgram(program: Program, visitor: ProgramVisitor): void {
+  const walk = (expression: Expression, item: FunctionItem): void => {
+    if (visitor.enterExpression?.(expression, item) !== false) {
+      for (const child of childExpressions(expression)) {
+        walk(child, item);
+      }
+    }
+    visitor.leaveExpression?.(expression, item);
+  };
+  for (const item of program.functions) {
+    visitor.enterFunction?.(item);
+    walk(item.body, item);
+    visitor.leaveFunction?.(item);
+  }
+}
+
+let defaultPhases: Promise<Stage2Phases> | null = null;
+
+function phasesOrDefault(phases: Stage2Phases | undefined): Promise<Stage2Phases> {
+  if (phases) {
+    return Promise.resolve(phases);
+  }
+  defaultPhases ??= Stage2Phases.create();
+  return defaultPhases;
+}
+
+/**
+ * Parses `source` into its AST without checking it.  Uses the checked-in
+ * compiler unless `phases` wraps another.
+ */
+export async function parseProgram(source: string, phases?: Stage2Phases): Promise<Program> {
+  return (await phasesOrDefault(phases)).parseProgram(source);
+}
+
+/**
+ * Parses and checks `source`, returning the validated tree: calls name their
+ * callees, expressions carry type ids, and functions with const parameters
+ * have their specializations alongside them.
+ */
+export async function lowerToHir(source: string, phases?: Stage2Phases): Promise<Program> {
+  return (await phasesOrDefault(phases)).lowerToHir(source);
+}
@@ -0,0 +1,59 @@
+import { readFile, readdir } from "node:fs/promises";
+import { relative, resolve, sep } from "node:path";
+
+import type { SourceProvider } from "./sources";
+
+/**
+ * A provider that reads modules from a directory on disk: the module path
+ * `/lib/math.bp` is the file `lib/math.bp` under `root`.  Paths that would
+ * leave `root` read as missing.
+ */
+export class FileSystemSourceProvider implements SourceProvider {
+  readonly root: string;
+
+  constructor(root: string) {
+    this.root = resolve(root);
+  }
+
+  #filePath(path: string): string | null {
+    const file = resolve(this.root, `.${path}`);
+    const inside = relative(this.root, file);
+    return inside.startsWith(`..${sep}`) || inside === ".." ? null : file;
+  }
+
+  async readFile(path: string): Promise<string | null> {
+    const file = this.#filePath(path);
+    if (file === null) {
+      return null;
+    }
+    try {
+      return await readFile(file, "utf8");
+    } catch {
+      return null;
+    }
+  }
+
+  async readBinaryFile(path: string): Promise<Uint8Array | null> {
+    const file = this.#filePath(path);
+    if (file === null) {
+      return null;
+    }
+    try {
+      return new Uint8Array(await readFile(file));
+    } catch {
+      return null;
+    }
+  }
+
+  async listDirectory(path: string): Promise<string[]> {
+    const directory = this.#filePath(path);
+    if (directory === null) {
+      return [];
+    }
+    try {
+      return (await readdir(directory)).sort();
+    } catch {
+      return [];
+    }
+  }
+}
@@ -0,0 +1,133 @@
+import { CompileError, CompilerCrash, compile } from "./index";
+
+/** `accepted` when the input compiled, `rejected` when it failed cleanly. */
+export type FuzzOutcome = "accepted" | "rejected";
+
+export interface FuzzCrash {
+  readonly source: string;
+  readonly error: CompilerCrash;
+}
+
+export interface FuzzOptions {
+  /** Number of mutated inputs to compile. */
+  readonly iterations: number;
+  /** Seed for the mutation generator, so a run can be repeated exactly. */
+  readonly seed?: number;
+  /** Called with every input that crashed the compiler. */
+  readonly onCrash?: (crash: FuzzCrash) => void | Promise<void>;
+}
+
+export interface FuzzReport {
+  readonly accepted: number;
+  readonly rejected: number;
+  readonly crashes: ReadonlyArray<FuzzCrash>;
+}
+
+/**
+ * Compiles arbitrary bytes as the entry module, decoding them as UTF-8 with
+ * replacement characters. A compile error is an expected outcome; a
+ * {@link CompilerCrash} is rethrown, so callers only have to catch that one.
+ */
+export async function fuzzCompile(input: Uint8Array | string): Promise<FuzzOutcome> {
+  const source = typeof input === "string" ? input : new TextDecoder().decode(input);
+  try {
+    await compile(source);
+  } catch (error) {
+    if (error instanceof CompilerCrash) {
+      throw error;
+    }
+    if (error instanceof CompileError) {
+      return "rejected";
+    }
+    throw error;
+  }
+  return "accepted";
+}
+
+// Tokens worth splicing in: delimiters and keywords steer the parser into
+// its less common paths, and unterminated literals and comments test the
+// scanners.
+const FUZZ_TOKENS = [
+  "(", ")", "{", "}", "[", "]", ";", ",", ":", "->", "=", "==", "!", "-", "&", "<<", ">>",
+  "if ", "else ", "loop ", "while ", "break", "continue", "return", "let ", "mut ", "fn ",
+  "const ", "use ", "as ", "i32", "i64", "u8", "bool", "true", "0", "-2147483648",
+  "18446744073709551616", "0x", "'", "\"", "//", "/*", "*/", "#[", "\n", "é",
+];
+
+/** mulberry32: small, fast, and the same sequence on every host for a seed. */
+export function seededRandom(seed: number): () => number {
+  let state = seed >>> 0;
+  return () => {
+    state = (state + 0x6d2b79f5) >>> 0;
+    let value = state;
+    value = Math.imul(value ^ (value >>> 15), value | 1);
+    value ^= value + Math.imul(value ^ (value >>> 7), value | 61);
+    return ((value ^ (value >>> 14)) >>> 0) / 4294967296;
+  };
+}
+
+/** Applies one to four random edits to `source`. */
+export function mutateSource(source: string, random: () => number): string {
+  const pick = (limit: number): number => Math.floor(random() * limit);
+  let text = source;
+  const edits = 1 + pick(4);
+  for (let edit = 0; edit < edits; edit += 1) {
+    const start = pick(text.length + 1);
+    const end = Math.min(text.length, start + pick(16));
+    switch (pick(5)) {
+      case 0:
+        text = text.slice(0, start) + text.slice(end);
+        break;
+      case 1:
+        text = text.slice(0, start) + text.slice(start, end) + text.slice(start);
+        break;
+      case 2:
+        text = text.slice(0, start) + FUZZ_TOKENS[pick(FUZZ_TOKENS.length)] + text.slice(start);
+        break;
+      case 3: {
+        const code = start < text.length ? text.charCodeAt(start) ^ (1 << pick(7)) : 0;
+        text = text.slice(0, start) + String.fromCharCode(code) + text.slice(start + 1);
+        break;
+      }
+      default: {
+        // Deep nesting, past the parser's depth limit about half the time.
+        const depth = 1 + pick(512);
+        const open = random() < 0.5 ? "(" : "{ ";
+        const close = open === "(" ? ")" : " }";
+        text =
+          text.slice(0, start) + open.repeat(depth) + text.slice(start, end) + close.repeat(depth) + text.slice(end);
+        break;
+      }
+    }
+  }
+  return text;
+}
+
+/** Compiles mutations of `seeds` and collects the inputs that crash the compiler. */
+export async function fuzz(seeds: ReadonlyArray<string>, options: FuzzOptions): Promise<FuzzReport> {
+  if (seeds.length === 0) {
+    throw new Error("fuzz needs at least one seed input");
+  }
+  const random = seededRandom(options.seed ?? 1);
+  const crashes: FuzzCrash[] = [];
+  let accepted = 0;
+  let rejected = 0;
+  for (let iteration = 0; iteration < options.iterations; iteration += 1) {
+    const source = mutateSource(seeds[Math.floor(random() * seeds.length)], random);
+    try {
+      if ((await fuzzCompile(source)) === "accepted") {
+        accepted += 1;
+      } else {
+        rejected += 1;
+      }
+    } catch (error) {
+      if (!(error instanceof CompilerCrash)) {
+        throw error;
+      }
+      const crash = { source, error };
+      crashes.push(crash);
+      await options.onCrash?.(crash);
+    }
+  }
+  return { accepted, rejected, crashes };
+}
@@ -1,4 +1,98 @@
-import { fileURLToPath } from "node:url";
+import { buildSourceMap } from "./source_map";
+import { formatMessage, localizeMessage, matchMessage } from "./messages";
+import type { MessageCode } from "./messages";
+import type { SourceMap, SourceMapOptions } from "./source_map";
+import { lineColumnAt, tokenRangeAt } from "./span";
+import { instrumentWasmWithCoverage, readCoverageReport } from "./coverage";
+import type { CoverageBlock, CoverageReport, FunctionCoverage, LineCoverage } from "./coverage";
+import { instrumentWasmWithProfile, readFunctionProfiles } from "./profile";
+import type { FunctionProfile } from "./profile";
+import { symbolicateStack } from "./stack_trace";
+import { CORE_MODULE_PATH, PRELUDE_MODULE_PATH, readImplicitModule, selectImplicitModules } from "./core_library";
+import type { ImplicitModule } from "./core_library";
+import { libraryInterfaceSource, readLibraryArchive } from "./library_archive";
+import { link } from "./link";
+import { shareMemory } from "./shared_memory";
+import { stripUnusedMemory } from "./strip_memory";
+import { validateCompilation } from "./validate";
+import { checkWasmFeatures, wasmFeatureMask } from "./features";
+import type { WasmFeatures } from "./features";
+import { readCallNames } from "./frontend";
+import { OPTIMIZATION_PASSES } from "./optimize";
+import { applyHirPasses } from "./passes";
+import type { HirPass } from "./passes";
+import type { StackFrame } from "./stack_trace";
+
+export {
+  lineColumnAt,
+  mergeRanges,
+  offsetAt,
+  rangeContains,
+  rangeCovers,
+  rangesIntersect,
+  sourceRange,
+  tokenRangeAt,
+} from "./span";
+export type { LineColumn, SourceRange } from "./span";
+export { appendSourceMappingUrl, encodeVlq } from "./source_map";
+export type { SourceMap, SourceMapOptions } from "./source_map";
+export type { StackFrame } from "./stack_trace";
+export { PROFILE_TABLE_EXPORT, instrumentWasmWithProfile } from "./profile";
+export type { FunctionProfile, ProfiledWasm } from "./profile";
+export { COVERAGE_TABLE_EXPORT, instrumentWasmWithCoverage } from "./coverage";
+export type { CoverageBlock, CoverageReport, CoveredWasm, FunctionCoverage, LineCoverage } from "./coverage";
+export { validateCompilation } from "./validate";
+export {
+  MVP_WASM_FEATURES,
+  WASM_FEATURE_NAMES,
+  checkWasmFeatures,
+  findDisabledFeatureUse,
+  wasmFeatureEnabled,
+  wasmFeatureMask,
+} from "./features";
+export type { WasmFeature, WasmFeatures } from "./features";
+export { formatSizeReport, readSizeReport } from "./size_report";
+export {
+  BUILD_META_SECTION_NAME,
+  COMPILER_VERSION,
+  appendBuildMeta,
+  formatBuildMeta,
+  readBuildMeta,
+} from "./build_meta";
+export type { BuildMeta } from "./build_meta";
+export { formatWasmInspection, inspectWasm } from "./inspect";
+export type { ExternalKind, MemoryLimits, WasmExport, WasmImport, WasmInspection } from "./inspect";
+export { CORE_MODULE_PATH, PRELUDE_MODULE_PATH, readImplicitModule, selectImplicitModules } from "./core_library";
+export type { ImplicitItem, ImplicitModule } from "./core_library";
+export { link } from "./link";
+export {
+  LIBRARY_ARCHIVE_EXTENSION,
+  compileLibrary,
+  libraryInterfaceSource,
+  readLibraryArchive,
+  readLibraryInterface,
+  writeLibraryArchive,
+} from "./library_archive";
+export type { LibraryArchive, LibraryFunction, LibraryParameter } from "./library_archive";
+export type { LinkOptions } from "./link";
+export { shareMemory } from "./shared_memory";
+export { stripUnusedMemory } from "./strip_memory";
+export type { StrippedWasm } from "./strip_memory";
+export type { FunctionSize, SectionSize, SizeReport } from "./size_report";
+export { OPTIMIZATION_PASSES, branchFolding, commonSubexpressionElimination, constantPropagation } from "./optimize";
+export { runHirPasses } from "./passes";
+export type { HirPass } from "./passes";
+export {
+  DEFAULT_LOCALE,
+  MESSAGES,
+  availableLocales,
+  formatMessage,
+  hasLocale,
+  localizeMessage,
+  matchMessage,
+  registerLocale,
+} from "./messages";
+export type { MatchedMessage, MessageCatalog, MessageCode, MessageParams } from "./messages";
 
 export enum Target {
   Wasm = "wasm",
@@ -7,6 +101,153 @@ export enum Target {
 
 export const DEFAULT_TARGET = Target.Wasm;
 
+/** Pipeline stage a WGSL shader is written for. */
+export type WgslShaderStage = "compute" | "vertex" | "fragment";
+
+/** WebAssembly output.  It has no options yet. */
+export interface WasmTargetSpec {
+  readonly kind: Target.Wasm;
+}
+
+export interface WgslTargetSpec {
+  readonly kind: Target.Wgsl;
+  /** Defaults to `compute`. */
+  readonly stage?: WgslShaderStage;
+}
+
+/**
+ * A target together with its options.  APIs that take a target accept either
+ * a bare {@link Target}, meaning that target with default options, or a spec,
+ * so new per-target options do not change their signatures.
+ */
+export type TargetSpec = WasmTargetSpec | WgslTargetSpec;
+
+const WGSL_SHADER_STAGES: ReadonlyArray<WgslShaderStage> = ["compute", "vertex", "fragment"];
+
+export function toTargetSpec(target: Target | TargetSpec): TargetSpec {
+  if (typeof target !== "string") {
+    return target;
+  }
+  return target === Target.Wasm ? { kind: Target.Wasm } : { kind: Target.Wgsl };
+}
+
+/**
+ * Where the stage2 compiler keeps the regions the host reads, relative to
+ * the output pointer of a compilation.  The compiler exports each field as a
+ * global named after its `.bp` constant, such as `AST_MAX_FUNCTIONS`, and
+ * `instantiateStage2` reads them with {@link readStage2Layout}.
+ */
+export interface Stage2Layout {
+  /** Output bytes reserved past an input's length before the AST. */
+  readonly scratchInstrCapacity: number;
+  /** The least distance from the output pointer to the AST. */
+  readonly astOutputReserveMin: number;
+  readonly astMaxFunctions: number;
+  readonly astFunctionEntrySize: number;
+  /** Bytes of the AST's names section. */
+  readonly astNamesCapacity: number;
+  /** Words of the AST's call data section. */
+  readonly astCallDataCapacity: number;
+  readonly scratchTypesBaseOffset: number;
+  readonly scratchTypesCapacity: number;
+  /** Distance from the AST's constants section to its expression table. */
+  readonly astExpressionsOffset: number;
+  readonly astExprCapacity: number;
+  readonly astExprEntrySize: number;
+  /** The intern table's absolute address, or 0 for a compiler without one. */
+  readonly internTableBase: number;
+  readonly internHeaderSize: number;
+  readonly internCapacity: number;
+  readonly internRecordSize: number;
+}
+
+/** The layout of compilers built before they exported it. */
+export const DEFAULT_STAGE2_LAYOUT: Stage2Layout = {
+  scratchInstrCapacity: 131_072,
+  astOutputReserveMin: 937_984,
+  astMaxFunctions: 2_048,
+  astFunctionEntrySize: 68,
+  astNamesCapacity: 262_144,
+  astCallDataCapacity: 189_439,
+  scratchTypesBaseOffset: 888_832,
+  scratchTypesCapacity: 2_048,
+  astExpressionsOffset: 47_124,
+  astExprCapacity: 196_608,
+  astExprEntrySize: 20,
+  internTableBase: 0,
+  internHeaderSize: 16,
+  internCapacity: 4_096,
+  internRecordSize: 8,
+};
+
+const STAGE2_LAYOUT_GLOBALS: Readonly<Record<keyof Stage2Layout, string>> = {
+  scratchInstrCapacity: "SCRATCH_INSTR_CAPACITY",
+  astOutputReserveMin: "AST_OUTPUT_RESERVE_MIN",
+  astMaxFunctions: "AST_MAX_FUNCTIONS",
+  astFunctionEntrySize: "AST_FUNCTION_ENTRY_SIZE",
+  astNamesCapacity: "AST_NAMES_CAPACITY",
+  astCallDataCapacity: "AST_CALL_DATA_CAPACITY",
+  scratchTypesBaseOffset: "SCRATCH_TYPES_BASE_OFFSET",
+  scratchTypesCapacity: "SCRATCH_TYPES_CAPACITY",
+  astExpressionsOffset: "AST_EXPRESSIONS_OFFSET",
+  astExprCapacity: "AST_EXPR_CAPACITY",
+  astExprEntrySize: "AST_EXPR_ENTRY_SIZE",
+  internTableBase: "INTERN_TABLE_BASE",
+  internHeaderSize: "INTERN_HEADER_SIZE",
+  internCapacity: "INTERN_CAPACITY",
+  internRecordSize: "INTERN_RECORD_SIZE",
+};
+
+/**
+ * Reads a stage2 compiler's layout from its exported globals.  A field whose
+ * global is missing keeps its {@link DEFAULT_STAGE2_LAYOUT} value.
+ */
+export function readStage2Layout(exports: WebAssembly.Exports): Stage2Layout {
+  const layout: Record<string, number> = { ...DEFAULT_STAGE2_LAYOUT };
+  for (const [field, name] of Object.entries(STAGE2_LAYOUT_GLOBALS)) {
+    const global = exports[name];
+    if (global instanceof WebAssembly.Global) {
+      layout[field] = Number(global.value);
+    }
+  }
+  return layout as unknown as Stage2Layout;
+}
+
+// Header fields of the intern table, each a name id.
+const INTERN_COUNT_FIELD = 0;
+const INTERN_FAILURE_FUNCTION_FIELD = 4;
+const INTERN_FAILURE_IDENTIFIER_FIELD = 8;
+
+/**
+ * Reads the name with intern id `id` from a stage2 compiler's memory.  Ids
+ * start at 1.  Returns `null` for 0, for an id the table has not handed out,
+ * or when the compiler has no intern table.
+ */
+export function readInternedName(memory: WebAssembly.Memory, id: number, layout: Stage2Layout): string | null {
+  if (layout.internTableBase <= 0 || id <= 0 || id > layout.internCapacity) {
+    return null;
+  }
+  const view = new DataView(memory.buffer);
+  if (id > safeReadI32(view, layout.internTableBase + INTERN_COUNT_FIELD)) {
+    return null;
+  }
+  const record = layout.internTableBase + layout.internHeaderSize + (id - 1) * layout.internRecordSize;
+  const ptr = safeReadI32(view, record);
+  const length = safeReadI32(view, record + WORD_SIZE);
+  if (ptr <= 0 || length <= 0 || ptr + length > memory.buffer.byteLength) {
+    return null;
+  }
+  return decoder.decode(new Uint8Array(memory.buffer, ptr, length));
+}
+
+function readInternedField(memory: WebAssembly.Memory, field: number, layout: Stage2Layout): string | null {
+  if (layout.internTableBase <= 0) {
+    return null;
+  }
+  const id = safeReadI32(new DataView(memory.buffer), layout.internTableBase + field);
+  return readInternedName(memory, id, layout);
+}
+
 export const FUNCTION_ENTRY_SIZE = 68;
 export const FUNCTIONS_BASE_OFFSET = 851_968;
 export const STAGE1_MAX_FUNCTIONS = 512;
@@ -21,12 +262,26 @@ const decoder = new TextDecoder();
 const MEMORY_INTRINSICS_MODULE_PATH = "/stdlib/memory.bp";
 const memoryIntrinsicsSourceUrl = new URL("../stdlib/memory.bp", import.meta.url);
 let memoryIntrinsicsSourcePromise: Promise<string> | null = null;
+const ALLOCATOR_MODULE_PATH = "/stdlib/alloc.bp";
+const allocatorSourceUrl = new URL("../stdlib/alloc.bp", import.meta.url);
+let allocatorSourcePromise: Promise<string> | null = null;
+const SIMD_MODULE_PATH = "/stdlib/simd.bp";
+const simdSourceUrl = new URL("../stdlib/simd.bp", import.meta.url);
+let simdSourcePromise: Promise<string> | null = null;
+const ATOMICS_MODULE_PATH = "/stdlib/atomics.bp";
+const atomicsSourceUrl = new URL("../stdlib/atomics.bp", import.meta.url);
+let atomicsSourcePromise: Promise<string> | null = null;
+const coreSourceUrl = new URL("../stdlib/core.bp", import.meta.url);
+let coreSourcePromise: Promise<string> | null = null;
+const preludeSourceUrl = new URL("../stdlib/prelude.bp", import.meta.url);
+let preludeSourcePromise: Promise<string> | null = null;
+const DEFINES_MODULE_PATH = "/defines";
 
 const MODULE_STATE_BASE = 1_048_576;
 const MODULE_STORAGE_TOP_OFFSET = 4;
 const MODULE_PATH_PTR = 1_024;
 const MODULE_CONTENT_PTR = 4_096;
-const DEFAULT_ENTRY_MODULE_PATH = "/entry.bp";
+export const DEFAULT_ENTRY_MODULE_PATH = "/entry.bp";
 export const FAILURE_DETAIL_CAPACITY = 256;
 const SCRATCH_FAILURE_PATH_PTR_OFFSET = 4_048;
 const SCRATCH_FAILURE_PATH_LEN_OFFSET = 4_052;
@@ -38,10 +293,24 @@ const SCRATCH_TYPE_METADATA_DEBUG_EXTRA_OFFSET = 4_040;
 const TYPE_METADATA_DEBUG_LAST_CONTEXT_OFFSET = 5_020;
 const TYPE_METADATA_DEBUG_LAST_SUBJECT_OFFSET = 5_024;
 const TYPE_METADATA_DEBUG_LAST_EXTRA_OFFSET = 5_028;
+const COMPILE_ERROR_RECORD_OFFSET = 6_128;
+const COMPILE_ERROR_RECORD_FIELDS = 8;
+const WARNING_COUNT_OFFSET = 6_436;
+const WARNING_RECORDS_OFFSET = 6_440;
+const WARNING_RECORD_FIELDS = 4;
+const WARNING_CAPACITY = 64;
+const ATTRIBUTE_COUNT_OFFSET = 7_548;
+const ATTRIBUTE_RECORDS_OFFSET = 7_552;
+const ATTRIBUTE_RECORD_FIELDS = 6;
+const ATTRIBUTE_CAPACITY = 24;
+const SOURCE_MAP_COUNT_OFFSET = 8_136;
+const SOURCE_MAP_ENTRIES_PTR_OFFSET = 8_140;
+const SOURCE_MAP_ENTRY_FIELDS = 3;
+const SOURCE_MAP_CAPACITY = 262_144;
 const SCRATCH_MODULE_BASE_OFFSET = 4_080;
 const SCRATCH_MODULE_LEN_OFFSET = 4_084;
 const SCRATCH_MODULE_INDEX_OFFSET = 4_088;
-const MODULE_TABLE_OFFSET = 8;
+const MODULE_TABLE_OFFSET = 20;
 const MODULE_ENTRY_FIELD_COUNT = 6;
 const MODULE_ENTRY_SIZE = MODULE_ENTRY_FIELD_COUNT * 4;
 const MODULE_ENTRY_PATH_PTR_FIELD = 0;
@@ -50,9 +319,6 @@ const MODULE_ENTRY_CONTENT_PTR_FIELD = 2;
 const MODULE_ENTRY_CONTENT_LEN_FIELD = 3;
 const MODULE_ENTRY_LINE_INDEX_FIELD = 4;
 const WORD_SIZE = 4;
-const AST_MAX_FUNCTIONS = 1_024;
-const AST_FUNCTION_ENTRY_SIZE = 68;
-const AST_NAMES_CAPACITY = 131_072;
 const AST_CONSTANT_ENTRY_SIZE = 28;
 const AST_CONSTANT_ENTRY_NAME_OFFSET = 0;
 const AST_CONSTANT_ENTRY_NAME_LEN_OFFSET = 4;
@@ -60,50 +326,405 @@ const AST_CONSTANT_ENTRY_TYPE_OFFSET = 12;
 const AST_CONSTANT_ENTRY_EXPR_INDEX_OFFSET = 16;
 const AST_CONSTANT_ENTRY_EVAL_STATE_OFFSET = 20;
 const AST_CONSTANT_ENTRY_MODULE_INDEX_OFFSET = 24;
-const AST_CONSTANTS_CAPACITY = 1_024;
-const AST_CALL_DATA_CAPACITY =
-  131_072 - ((AST_CONSTANTS_CAPACITY * AST_CONSTANT_ENTRY_SIZE + WORD_SIZE) >> 2);
 const AST_CONSTANT_EVAL_STATE_EVALUATED = 2;
-const SCRATCH_INSTR_CAPACITY = 131_072;
-const SCRATCH_FN_BASE_OFFSET = 921_600;
 const AST_EXPR_ENTRY_SIZE = 20;
 const AST_EXPR_LOCATION_OFFSET = 12;
+const COMPILE_FLAG_CHECKED_DIVISION = 1;
+const COMPILE_FLAG_STRICT_DISCARD = 2;
+const COMPILE_FLAG_CONSTANT_TABLE = 4;
+const COMPILE_FLAG_LAYOUT_GLOBALS = 8;
+const COMPILE_FLAG_MEMORY_CANARIES = 16;
+const COMPILE_FLAG_GUARDED_STORES = 32;
+const COMPILE_FLAG_ADDRESS_SANITIZER = 64;
+const COMPILE_FLAGS_NESTING_DEPTH_SHIFT = 7;
+const COMPILE_FLAGS_MEMORY_PAGES_SHIFT = 16;
+const LANGUAGE_FLAG_INTEGER_CONDITIONS = 1;
+/** Memory size of emitted modules, in 64 KiB pages, when none is requested. */
+export const DEFAULT_MEMORY_PAGES = 256;
+/** Largest page count the compile flags can carry. */
+export const MAX_MEMORY_PAGES = 65_535;
+/** Nesting limit the parser applies when none is requested. */
+export const DEFAULT_MAX_NESTING_DEPTH = 256;
+/** Largest nesting limit the compile flags can carry. */
+export const MAX_NESTING_DEPTH = 511;
+const ADDRESS_SANITIZER_SHADOW_FREED = 0xfd;
+export const CONSTANT_TABLE_SECTION_NAME = "bootstrap.consts";
+// Indexed by builtin type id; 64-bit constants are not listed, so ids 4 and 8
+// never appear.
+const CONSTANT_TABLE_TYPE_NAMES: Readonly<Record<number, string>> = {
+  0: "i32",
+  1: "bool",
+  2: "i8",
+  3: "i16",
+  5: "u8",
+  6: "u16",
+  7: "u32",
+};
+const PANIC_SITE_MODULE_SHIFT = 24;
+const PANIC_SITE_OFFSET_MASK = (1 << PANIC_SITE_MODULE_SHIFT) - 1;
+const PANIC_MESSAGES: Readonly<Record<number, string>> = {
+  1: "attempt to divide by zero",
+  2: "attempt to calculate the remainder with a divisor of zero",
+  3: "attempt to divide with overflow",
+  4: "store outside writable memory",
+  5: "memory canary corrupted",
+  6: "invalid memory access",
+  7: "explicit panic",
+  8: "assertion failed",
+  9: "attempt to add with overflow",
+  10: "attempt to multiply with overflow",
+};
+const PANIC_CODE_INVALID_ACCESS = 6;
+const PANIC_CODE_EXPLICIT = 7;
+const PANIC_CODE_ASSERTION_FAILED = 8;
+// `panic`/`assert` store their message's address and length here before trapping.
+const PANIC_MESSAGE_PTR_ADDRESS = 1_008;
+const PANIC_MESSAGE_LEN_ADDRESS = 1_012;
+// Codes whose panic site is a linear memory address rather than a source offset.
+const ADDRESS_PANIC_CODES: ReadonlySet<number> = new Set([4, 5, PANIC_CODE_INVALID_ACCESS]);
 
 export interface CompilerModuleSource {
   readonly path: string;
   readonly source: string;
 }
 
+/** A precompiled library, read from the `.bpl` file a `use` item names. */
+export interface CompilerLibrary {
+  readonly path: string;
+  /** The archive's bytes; see {@link writeLibraryArchive}. */
+  readonly archive: Uint8Array;
+}
+
+/**
+ * Receives a compile's progress messages.  Level 1 messages name each phase
+ * with its result and time, and level 2 messages add the stage2 memory
+ * addresses and lengths behind them.
+ */
+export type CompileLogger = (level: 1 | 2, message: string) => void;
+
 export interface CompileOptions {
   readonly modules?: ReadonlyArray<CompilerModuleSource>;
+  /**
+   * Libraries to link in place of compiling their source.  Stage2 checks
+   * calls against each archive's interface, and the archive's compiled
+   * functions replace the placeholders it emits; see {@link link}.
+   */
+  readonly libraries?: ReadonlyArray<CompilerLibrary>;
   readonly entryPath?: string;
+  /**
+   * Leave out `/stdlib/core.bp`, the library every program can otherwise call
+   * without a `use`.  `/stdlib/prelude.bp` is still supplied.
+   */
+  readonly noStd?: boolean;
+  /**
+   * Names for `#[cfg(...)]` and `cfg!(...)` to test, each mapped to its value.
+   * `cfg(NAME)` holds for any name given here, and `cfg(NAME = "value")` only
+   * when the value matches; a name defined without a value maps to `""`.
+   */
+  readonly defines?: Readonly<Record<string, string>>;
+  /**
+   * Route integer `/` and `%` through checked helpers that record a panic
+   * code and source location before trapping on a zero divisor or on
+   * signed overflow.
+   */
+  readonly checkedDivision?: boolean;
+  /**
+   * Reject statements that ignore the result of a value-returning call unless
+   * the call is wrapped in `discard(...)`.
+   */
+  readonly strict?: boolean;
+  /**
+   * Accept integer `if` and `while` conditions as `!= 0`, with an
+   * integer-condition warning on each, instead of rejecting them.  This eases
+   * migrating code written before `bool` was its own type.
+   */
+  readonly integerConditions?: boolean;
+  /**
+   * Emit a `bootstrap.consts` custom section listing every `bool` and
+   * integer `const` of at most 32 bits so hosts can read values back with
+   * {@link readConstantTable}.
+   */
+  readonly constantTable?: boolean;
+  /**
+   * Export immutable `__data_end` and `__heap_base` globals marking where
+   * static data ends and where a host or in-module allocator may start.
+   */
+  readonly layoutGlobals?: boolean;
+  /**
+   * Place canary words below and above static data and export a
+   * `__check_canaries` function that traps if either has been overwritten.
+   */
+  readonly memoryCanaries?: boolean;
+  /**
+   * Check the address of every `store_u8`/`store_u16`/`store_i32` and trap
+   * on writes into the low guard region or over a canary word.
+   */
+  readonly guardStores?: boolean;
+  /**
+   * Check every load and store intrinsic against the shadow region that
+   * `/stdlib/alloc.bp` keeps for live allocations.
+   */
+  readonly addressSanitizer?: boolean;
+  /**
+   * Count each function's calls, returns, and own executed instructions in a
+   * table past the module's memory; see {@link instrumentWasmWithProfile}.
+   */
+  readonly profile?: boolean;
+  /**
+   * Count how often each straight-line run of code executes, in a table past
+   * the module's memory; see {@link instrumentWasmWithCoverage}.
+   */
+  readonly coverage?: boolean;
+  /**
+   * Compile the emitted module with the host's engine before returning it,
+   * and report a rejection as a `CompileError` that names the function at
+   * fault; see {@link validateCompilation}.
+   */
+  readonly validateOutput?: boolean;
+  /**
+   * The post-MVP wasm proposals the emitted module may use; all of them by
+   * default.  Codegen avoids disabled ones where it has an MVP alternative,
+   * and a module that still needs one fails with a `CompileError`; see
+   * {@link checkWasmFeatures}.
+   */
+  readonly features?: WasmFeatures;
+  /**
+   * Size of the emitted module's memory in 64 KiB pages, from 1 to
+   * {@link MAX_MEMORY_PAGES}.  The memory cannot grow, so this is both its
+   * minimum and maximum.  Defaults to {@link DEFAULT_MEMORY_PAGES}.
+   */
+  readonly memoryPages?: number;
+  /**
+   * Mark the memory as shared, for hosts that run the module on several
+   * threads with `/stdlib/atomics.bp`; see {@link shareMemory}.  Needs the
+   * threads feature.
+   */
+  readonly sharedMemory?: boolean;
+  /**
+   * How deeply operands, blocks, and `if` branches may nest, from 1 to
+   * {@link MAX_NESTING_DEPTH}.  Deeper input fails with "expression too deeply
+   * nested".  Defaults to {@link DEFAULT_MAX_NESTING_DEPTH}.
+   */
+  readonly maxNestingDepth?: number;
+  /**
+   * Rewrites of the checked program, run in order before it is emitted; see
+   * {@link HirPass}.  Needs a compiler that exports `checkFromPath` and
+   * `emitFromPath`.
+   */
+  readonly extraPasses?: ReadonlyArray<HirPass>;
+  /**
+   * Run {@link OPTIMIZATION_PASSES} over the checked program before any
+   * `extraPasses`.  Has the same compiler requirements as `extraPasses`.
+   * Unless `memoryPages` or `sharedMemory` is given, a module that never uses
+   * its memory also loses it; see {@link stripUnusedMemory}.
+   */
+  readonly optimize?: boolean;
+  /** Called with each progress message; see {@link CompileLogger}. */
+  readonly log?: CompileLogger;
+}
+
+export interface MemoryLayout {
+  readonly memoryBytes: number;
+  readonly dataEnd: number;
+  readonly heapBase: number;
+}
+
+export interface ConstantTableEntry {
+  readonly name: string;
+  readonly type: string;
+  readonly value: number;
+}
+
+/**
+ * How a value crosses an exported function boundary.  Scalars travel as wasm
+ * numbers; tuples, structs, and arrays travel as references to GC objects.
+ */
+export enum AbiValueKind {
+  I32 = "i32",
+  I64 = "i64",
+  F32 = "f32",
+  F64 = "f64",
+  StructRef = "struct-ref",
+  ArrayRef = "array-ref",
+  Ref = "ref",
+}
+
+export interface FunctionAbi {
+  readonly name: string;
+  readonly params: ReadonlyArray<AbiValueKind>;
+  /** Always one value; unit functions return an `i32` zero. */
+  readonly results: ReadonlyArray<AbiValueKind>;
+}
+
+export interface RuntimePanic {
+  readonly message: string;
+  readonly path: string;
+  readonly line: number;
+  readonly column: number;
+  /** Faulting address for memory guard panics, which carry no source location. */
+  readonly address?: number;
+}
+
+/** Pipeline stage reported in the stage2 compiler's error record. */
+export enum CompileErrorKind {
+  Module = "module",
+  Parse = "parse",
+  Constant = "constant",
+  Semantic = "semantic",
+  TypeMetadata = "type-metadata",
+  Emit = "emit",
+}
+
+const COMPILE_ERROR_KINDS: ReadonlyArray<CompileErrorKind> = [
+  CompileErrorKind.Module,
+  CompileErrorKind.Parse,
+  CompileErrorKind.Constant,
+  CompileErrorKind.Semantic,
+  CompileErrorKind.TypeMetadata,
+  CompileErrorKind.Emit,
+];
+
+export interface SourceSpan {
+  readonly path: string;
+  readonly line: number;
+  readonly column: number;
+  /** Byte offset into the module source. */
+  readonly offset: number;
+}
+
+/**
+ * Where the code from `offset` on in a compiled module came from, up to the
+ * next location.  `span` is `null` for code with no source, such as the
+ * runtime helpers.
+ */
+export interface CodeLocation {
+  /** Byte offset into the module. */
+  readonly offset: number;
+  readonly span: SourceSpan | null;
+}
+
+/** A function in the AST that a stage2 `parse` or `check` run built. */
+export interface AstFunction {
+  readonly name: string;
+  readonly paramCount: number;
+  /** The stage2 type id of the result. */
+  readonly returnType: number;
+}
+
+export interface CompileErrorRecord {
+  readonly kind: CompileErrorKind;
+  /** The failure message without its `path:line:column:` prefix. */
+  readonly message: string;
+  /** The message's catalog code, when it has one. */
+  readonly code?: MessageCode;
+  readonly span?: SourceSpan;
+}
+
+/** Non-fatal diagnostics reported by the stage2 compiler's semantic pass. */
+export enum CompileWarningKind {
+  UnusedLocal = "unused-local",
+  UnusedParameter = "unused-parameter",
+  UnreachableCode = "unreachable-code",
+  UnknownAttribute = "unknown-attribute",
+  ConditionAlwaysTrue = "condition-always-true",
+  ConditionAlwaysFalse = "condition-always-false",
+  IntegerCondition = "integer-condition",
+}
+
+const COMPILE_WARNING_KINDS: ReadonlyArray<CompileWarningKind> = [
+  CompileWarningKind.UnusedLocal,
+  CompileWarningKind.UnusedParameter,
+  CompileWarningKind.UnreachableCode,
+  CompileWarningKind.UnknownAttribute,
+  CompileWarningKind.ConditionAlwaysTrue,
+  CompileWarningKind.ConditionAlwaysFalse,
+  CompileWarningKind.IntegerCondition,
+];
+
+export interface CompileWarning {
+  readonly kind: CompileWarningKind;
+  readonly code: MessageCode;
+  readonly message: string;
+  readonly span: SourceSpan;
+  /** Text of the module that `span` points into. */
+  readonly source: string;
+}
+
+/** A `#[name]` or `#[name(...)]` attribute on a top-level item. */
+export interface ItemAttribute {
+  readonly name: string;
+  /** The raw text between the parentheses, or an empty string. */
+  readonly arguments: string;
+  /** Where the attribute's name starts. */
+  readonly span: SourceSpan;
+  /** The name of the function or constant the attribute applies to. */
+  readonly item: string;
 }
 
 export class CompileError extends Error {
   override readonly name = "CompileError";
+  /** Structured failure reported by the stage2 compiler, when there is one. */
+  readonly record?: CompileErrorRecord;
+  /** Text of the module that `span` points into, when the host loaded it. */
+  readonly source?: string;
 
-  constructor(message: string) {
+  constructor(message: string, record?: CompileErrorRecord, source?: string) {
     super(`error: ${message}`);
+    this.record = record;
+    this.source = source;
+  }
+
+  get kind(): CompileErrorKind | undefined {
+    return this.record?.kind;
+  }
+
+  get span(): SourceSpan | undefined {
+    return this.record?.span;
   }
 }
 
+/** The stage2 compiler trapped instead of reporting why the input failed. */
+export class CompilerCrash extends CompileError {}
+
 export class Compilation {
-  #target: Target;
+  #target: TargetSpec;
   #wasm: Uint8Array;
+  #modules: ReadonlyArray<CompilerModuleSource>;
+  #warnings: ReadonlyArray<CompileWarning>;
+  #attributes: ReadonlyArray<ItemAttribute>;
+  #locations: ReadonlyArray<CodeLocation>;
+  #coverage: ReadonlyArray<CoverageBlock>;
   #consumed = false;
 
-  constructor(target: Target, wasm: Uint8Array) {
-    this.#target = target;
+  constructor(
+    target: Target | TargetSpec,
+    wasm: Uint8Array,
+    modules: ReadonlyArray<CompilerModuleSource> = [],
+    warnings: ReadonlyArray<CompileWarning> = [],
+    attributes: ReadonlyArray<ItemAttribute> = [],
+    locations: ReadonlyArray<CodeLocation> = [],
+    coverage: ReadonlyArray<CoverageBlock> = [],
+  ) {
+    this.#target = toTargetSpec(target);
     this.#wasm = wasm;
+    this.#modules = modules;
+    this.#warnings = warnings;
+    this.#attributes = attributes;
+    this.#locations = locations;
+    this.#coverage = coverage;
   }
 
   #ensureWasmTarget(): void {
-    if (this.#target !== Target.Wasm) {
-      throw new CompileError(`target '${this.#target}' cannot be emitted as Wasm`);
+    if (this.#target.kind !== Target.Wasm) {
+      throw new CompileError(`target '${this.#target.kind}' cannot be emitted as Wasm`);
     }
   }
 
   get target(): Target {
+    return this.#target.kind;
+  }
+
+  get targetSpec(): TargetSpec {
     return this.#target;
   }
 
@@ -111,6 +732,114 @@ export class Compilation {
     return new Uint8Array(this.#wasm);
   }
 
+  /** Modules in the order the compiler registered them. */
+  get modules(): ReadonlyArray<CompilerModuleSource> {
+    return this.#modules;
+  }
+
+  /** Warnings from the semantic pass, in source order. */
+  get warnings(): ReadonlyArray<CompileWarning> {
+    return this.#warnings;
+  }
+
+  /** Attributes on top-level items, other than `doc`, in source order. */
+  get attributes(): ReadonlyArray<ItemAttribute> {
+    return this.#attributes;
+  }
+
+  /** Where the code section's instructions came from, in code order. */
+  get codeLocations(): ReadonlyArray<CodeLocation> {
+    return this.#locations;
+  }
+
+  /**
+   * The source of the instruction at byte `offset` of the module, or `null`
+   * when it is outside the code section or has no source.
+   */
+  locate(offset: number): SourceSpan | null {
+    let low = 0;
+    let high = this.#locations.length;
+    while (low < high) {
+      const middle = (low + high) >> 1;
+      if (this.#locations[middle]!.offset <= offset) {
+        low = middle + 1;
+      } else {
+        high = middle;
+      }
+    }
+    return low > 0 ? this.#locations[low - 1]!.span : null;
+  }
+
+  /** A source map from the module's code offsets back to `.bp` lines. */
+  sourceMap(options: SourceMapOptions = {}): SourceMap {
+    this.#ensureWasmTarget();
+    return buildSourceMap(this.#locations, this.#modules, DEFAULT_ENTRY_MODULE_PATH, options);
+  }
+
+  /** Names of the functions marked `#[test]`, in source order. */
+  get tests(): ReadonlyArray<string> {
+    return this.#attributes
+      .filter((attribute) => attribute.name === "test")
+      .map((attribute) => attribute.item);
+  }
+
+  /**
+   * Reads the panic state left behind by a trapped instance of this
+   * compilation.  Returns `null` when the module was not compiled with
+   * checked division or did not panic.
+   */
+  describePanic(instance: WebAssembly.Instance): RuntimePanic | null {
+    return describeRuntimePanic(instance, this.#modules);
+  }
+
+  /**
+   * Maps the Wasm frames in a trap's stack to function names and `.bp`
+   * spans, innermost first.  Engines that do not report instruction offsets
+   * get each function's own span instead.
+   */
+  symbolicate(error: unknown): StackFrame[] {
+    const stack = error instanceof Error ? error.stack : undefined;
+    if (!stack) {
+      return [];
+    }
+    return symbolicateStack(stack, this.#wasm, (offset) => this.locate(offset));
+  }
+
+  /**
+   * Runs the instance's `__check_canaries` export.  Returns the resulting
+   * panic, or `null` when both canaries are intact or the module was not
+   * compiled with `memoryCanaries`.
+   */
+  checkCanaries(instance: WebAssembly.Instance): RuntimePanic | null {
+    return checkMemoryCanaries(instance, this.#modules);
+  }
+
+  /**
+   * Reports the linear memory layout of an instance of this compilation.
+   * Returns `null` unless it was compiled with `layoutGlobals`.
+   */
+  describeLayout(instance: WebAssembly.Instance): MemoryLayout | null {
+    return describeMemoryLayout(instance);
+  }
+
+  /**
+   * Reads the per-function counters of an instance of this compilation.
+   * Returns `null` unless it was compiled with `profile`.
+   */
+  readProfile(instance: WebAssembly.Instance): FunctionProfile[] | null {
+    return readFunctionProfiles(instance, this.#wasm);
+  }
+
+  /**
+   * Reads the block counters of an instance of this compilation and maps
+   * them to functions and source lines.  Counts add to those of `base`, from
+   * an earlier instance.  Returns `null` unless it was compiled with
+   * `coverage`.
+   */
+  readCoverage(instance: WebAssembly.Instance, base: CoverageReport | null = null): CoverageReport | null {
+    return readCoverageReport(instance, this.#wasm, this.#coverage, this.#locations, base);
+  }
+
   toWasm(): Uint8Array {
     this.#ensureWasmTarget();
     return new Uint8Array(this.#wasm);
@@ -129,6 +858,7 @@ export class Compilation {
 }
 
 let compilerModulePromise: Promise<WebAssembly.Module> | null = null;
+let sharedCompilerPromise: Promise<Compiler> | null = null;
 
 export interface CompileFailureDetails {
   readonly producedLength: number;
@@ -136,6 +866,131 @@ export interface CompileFailureDetails {
   readonly instructionOffset: number;
   readonly compiledFunctions: number;
   readonly detail?: string;
+  /** The function stage2 was working on when it failed. */
+  readonly function?: string;
+  /** The identifier that last failed to resolve. */
+  readonly identifier?: string;
+}
+
+export function describeRuntimePanic(
+  instance: WebAssembly.Instance,
+  modules: ReadonlyArray<CompilerModuleSource>,
+): RuntimePanic | null {
+  const codeGlobal = instance.exports.__panic_code;
+  const siteGlobal = instance.exports.__panic_site;
+  if (!(codeGlobal instanceof WebAssembly.Global) || !(siteGlobal instanceof WebAssembly.Global)) {
+    return null;
+  }
+  const code = coerceToI32(codeGlobal.value as number | bigint);
+  if (code === 0) {
+    return null;
+  }
+  let message = PANIC_MESSAGES[code] ?? `panic code ${code}`;
+  if (code === PANIC_CODE_EXPLICIT || code === PANIC_CODE_ASSERTION_FAILED) {
+    const text = readPanicMessage(instance);
+    if (text !== null) {
+      message = code === PANIC_CODE_EXPLICIT ? text : `${message}: ${text}`;
+    }
+  }
+  const site = coerceToI32(siteGlobal.value as number | bigint);
+  if (ADDRESS_PANIC_CODES.has(code)) {
+    const entry = modules[modules.length - 1];
+    const path = entry ? entry.path : DEFAULT_ENTRY_MODULE_PATH;
+    const address = site >>> 0;
+    const detail =
+      code === PANIC_CODE_INVALID_ACCESS ? describeInvalidAccess(instance, address) : null;
+    return {
+      message: detail ? `${message} (${detail})` : message,
+      path,
+      line: 0,
+      column: 0,
+      address,
+    };
+  }
+  const moduleSlot = site >>> PANIC_SITE_MODULE_SHIFT;
+  const offset = site & PANIC_SITE_OFFSET_MASK;
+  // Slot 0 marks a site whose module was not recorded; fall back to the entry.
+  const module = moduleSlot > 0 ? modules[moduleSlot - 1] : modules[modules.length - 1];
+  if (!module) {
+    return { message, path: DEFAULT_ENTRY_MODULE_PATH, line: 0, column: 0 };
+  }
+  const position = lineColumnAt(module.source, offset);
+  return { message, path: module.path, line: position.line, column: position.column };
+}
+
+/**
+ * Reads the message a `panic("...")` or `assert(cond, "...")` left behind.
+ * Returns `null` when the last panic had no message.
+ */
+export function readPanicMessage(instance: WebAssembly.Instance): string | null {
+  const memory = instance.exports.memory;
+  if (!(memory instanceof WebAssembly.Memory)) {
+    return null;
+  }
+  const view = new DataView(memory.buffer);
+  const ptr = view.getUint32(PANIC_MESSAGE_PTR_ADDRESS, true);
+  const length = view.getUint32(PANIC_MESSAGE_LEN_ADDRESS, true);
+  if (length === 0 || ptr + length > memory.buffer.byteLength) {
+    return null;
+  }
+  return decoder.decode(new Uint8Array(memory.buffer, ptr, length));
+}
+
+// Classifies an address sanitizer violation from the faulting granule's shadow byte.
+function describeInvalidAccess(instance: WebAssembly.Instance, address: number): string | null {
+  const memory = instance.exports.memory;
+  if (!(memory instanceof WebAssembly.Memory)) {
+    return null;
+  }
+  // The shadow region is the top eighth of memory, wherever `memoryPages` puts it.
+  const byteLength = memory.buffer.byteLength;
+  const shadowAddress = byteLength - byteLength / 8 + (address >>> 3);
+  if (shadowAddress >= byteLength) {
+    return null;
+  }
+  const shadow = new Uint8Array(memory.buffer)[shadowAddress];
+  if (shadow === ADDRESS_SANITIZER_SHADOW_FREED) {
+    return "use after free";
+  }
+  return "outside any live allocation";
+}
+
+export function checkMemoryCanaries(
+  instance: WebAssembly.Instance,
+  modules: ReadonlyArray<CompilerModuleSource>,
+): RuntimePanic | null {
+  const check = instance.exports.__check_canaries;
+  if (typeof check !== "function") {
+    return null;
+  }
+  try {
+    check();
+  } catch (error) {
+    const panic = describeRuntimePanic(instance, modules);
+    if (panic) {
+      return panic;
+    }
+    throw error;
+  }
+  return null;
+}
+
+export function describeMemoryLayout(instance: WebAssembly.Instance): MemoryLayout | null {
+  const memory = instance.exports.memory;
+  const dataEndGlobal = instance.exports.__data_end;
+  const heapBaseGlobal = instance.exports.__heap_base;
+  if (
+    !(memory instanceof WebAssembly.Memory) ||
+    !(dataEndGlobal instanceof WebAssembly.Global) ||
+    !(heapBaseGlobal instanceof WebAssembly.Global)
+  ) {
+    return null;
+  }
+  return {
+    memoryBytes: memory.buffer.byteLength,
+    dataEnd: coerceToI32(dataEndGlobal.value as number | bigint),
+    heapBase: coerceToI32(heapBaseGlobal.value as number | bigint),
+  };
 }
 
 function safeReadI32(view: DataView, offset: number): number {
@@ -154,6 +1009,7 @@ export function describeCompilationFailure(
   outputPtr: number,
   producedLength: number,
   inputLength = -1,
+  layout: Stage2Layout = DEFAULT_STAGE2_LAYOUT,
 ): CompileFailureDetails {
   const view = new DataView(memory.buffer);
   const functions = safeReadI32(view, outputPtr + FUNCTIONS_COUNT_PTR_OFFSET);
@@ -210,6 +1066,7 @@ export function describeCompilationFailure(
     outputPtr,
     inputLength,
     detail,
+    layout,
   );
   if (improvedDetail) {
     detail = improvedDetail;
@@ -221,22 +1078,430 @@ export function describeCompilationFailure(
     instructionOffset: instrOffset,
     compiledFunctions,
     detail,
+    function: readInternedField(memory, INTERN_FAILURE_FUNCTION_FIELD, layout) ?? undefined,
+    identifier: readInternedField(memory, INTERN_FAILURE_IDENTIFIER_FIELD, layout) ?? undefined,
   };
 }
 
+/**
+ * Decodes the error record the stage2 compiler writes next to a failed
+ * compilation's detail buffer.  Returns `null` when no record was written.
+ */
+export function readCompileErrorRecord(
+  memory: WebAssembly.Memory,
+  outputPtr: number,
+): CompileErrorRecord | null {
+  const view = new DataView(memory.buffer);
+  const base = outputPtr + COMPILE_ERROR_RECORD_OFFSET;
+  if (outputPtr <= 0 || base + COMPILE_ERROR_RECORD_FIELDS * WORD_SIZE > view.byteLength) {
+    return null;
+  }
+  const [code, messagePtr, messageLen, pathPtr, pathLen, line, column, offset] = Array.from(
+    { length: COMPILE_ERROR_RECORD_FIELDS },
+    (_, index) => view.getInt32(base + index * WORD_SIZE, true),
+  );
+  const kind = COMPILE_ERROR_KINDS[code - 1];
+  if (!kind || messageLen < 0 || messagePtr + messageLen > view.byteLength) {
+    return null;
+  }
+  const message = decoder.decode(new Uint8Array(memory.buffer, messagePtr, messageLen));
+  const messageCode = matchMessage(message)?.code;
+  if (line <= 0 || column <= 0) {
+    return messageCode ? { kind, message, code: messageCode } : { kind, message };
+  }
+  let path = DEFAULT_ENTRY_MODULE_PATH;
+  if (pathPtr > 0 && pathLen > 0 && pathPtr + pathLen <= view.byteLength) {
+    path = decoder.decode(new Uint8Array(memory.buffer, pathPtr, pathLen));
+  }
+  const span = { path, line, column, offset };
+  return messageCode ? { kind, message, code: messageCode, span } : { kind, message, span };
+}
+
+const WARNING_MESSAGE_CODES: Readonly<Record<CompileWarningKind, MessageCode>> = {
+  [CompileWarningKind.UnusedLocal]: "W0001",
+  [CompileWarningKind.UnusedParameter]: "W0002",
+  [CompileWarningKind.UnreachableCode]: "W0003",
+  [CompileWarningKind.UnknownAttribute]: "W0004",
+  [CompileWarningKind.ConditionAlwaysTrue]: "W0005",
+  [CompileWarningKind.ConditionAlwaysFalse]: "W0006",
+  [CompileWarningKind.IntegerCondition]: "W0007",
+};
+
+// Warnings name the identifier at their offset, when the message has a slot
+// for one.
+function describeWarning(code: MessageCode, source: string, offset: number): string {
+  const name = /^[A-Za-z0-9_]+/.exec(source.slice(offset))?.[0] ?? "";
+  return formatMessage(code, { name });
+}
+
+/**
+ * Decodes the warnings the stage2 compiler recorded during its last
+ * compilation, sorted by module and then by position.  Records whose path is
+ * not among `modules` are skipped.
+ */
+export function readCompileWarnings(
+  memory: WebAssembly.Memory,
+  modules: ReadonlyArray<CompilerModuleSource>,
+): CompileWarning[] {
+  const view = new DataView(memory.buffer);
+  const count = Math.min(view.getInt32(WARNING_COUNT_OFFSET, true), WARNING_CAPACITY);
+  const warnings: CompileWarning[] = [];
+  for (let index = 0; index < count; index += 1) {
+    const base = WARNING_RECORDS_OFFSET + index * WARNING_RECORD_FIELDS * WORD_SIZE;
+    const [code, pathPtr, pathLen, offset] = Array.from(
+      { length: WARNING_RECORD_FIELDS },
+      (_, field) => view.getInt32(base + field * WORD_SIZE, true),
+    );
+    const kind = COMPILE_WARNING_KINDS[code - 1];
+    if (!kind || pathPtr <= 0 || pathLen <= 0 || pathPtr + pathLen > view.byteLength) {
+      continue;
+    }
+    const path = decoder.decode(new Uint8Array(memory.buffer, pathPtr, pathLen));
+    const module = modules.find((loaded) => loaded.path === path);
+    if (!module) {
+      continue;
+    }
+    const { line, column } = lineColumnAt(module.source, offset);
+    const messageCode = WARNING_MESSAGE_CODES[kind];
+    warnings.push({
+      kind,
+      code: messageCode,
+      message: describeWarning(messageCode, module.source, offset),
+      span: { path, line, column, offset },
+      source: module.source,
+    });
+  }
+  const moduleOrder = (warning: CompileWarning): number =>
+    modules.findIndex((loaded) => loaded.path === warning.span.path);
+  return warnings.sort(
+    (a, b) => moduleOrder(a) - moduleOrder(b) || a.span.offset - b.span.offset,
+  );
+}
+
+/**
+ * Decodes the attribute table the stage2 compiler filled while parsing,
+ * sorted by module and then by position.  Attributes in modules not among
+ * `modules` are skipped.
+ */
+export function readItemAttributes(
+  memory: WebAssembly.Memory,
+  modules: ReadonlyArray<CompilerModuleSource>,
+): ItemAttribute[] {
+  const view = new DataView(memory.buffer);
+  const count = Math.min(view.getInt32(ATTRIBUTE_COUNT_OFFSET, true), ATTRIBUTE_CAPACITY);
+  const attributes: ItemAttribute[] = [];
+  for (let index = 0; index < count; index += 1) {
+    const base = ATTRIBUTE_RECORDS_OFFSET + index * ATTRIBUTE_RECORD_FIELDS * WORD_SIZE;
+    const [moduleIndex, nameStart, nameLen, argsStart, argsLen, itemStart] = Array.from(
+      { length: ATTRIBUTE_RECORD_FIELDS },
+      (_, field) => view.getInt32(base + field * WORD_SIZE, true),
+    );
+    const path = resolveModulePath(memory, moduleIndex);
+    const module = modules.find((loaded) => loaded.path === path);
+    if (!path || !module || nameLen <= 0) {
+      continue;
+    }
+    const item = /^(?:const\s+)?(?:fn\s+)?([A-Za-z_][A-Za-z0-9_]*)/.exec(
+      module.source.slice(itemStart),
+    );
+    const { line, column } = lineColumnAt(module.source, nameStart);
+    attributes.push({
+      name: module.source.slice(nameStart, nameStart + nameLen),
+      arguments: argsLen > 0 ? module.source.slice(argsStart, argsStart + argsLen) : "",
+      span: { path, line, column, offset: nameStart },
+      item: item?.[1] ?? "",
+    });
+  }
+  const moduleOrder = (attribute: ItemAttribute): number =>
+    modules.findIndex((loaded) => loaded.path === attribute.span.path);
+  return attributes.sort(
+    (a, b) => moduleOrder(a) - moduleOrder(b) || a.span.offset - b.span.offset,
+  );
+}
+
+interface ModuleLines {
+  readonly path: string;
+  /** Offset of the first character of each line. */
+  readonly starts: number[];
+}
+
+function lineStarts(source: string): number[] {
+  const starts = [0];
+  for (let index = source.indexOf("\n"); index >= 0; index = source.indexOf("\n", index + 1)) {
+    starts.push(index + 1);
+  }
+  return starts;
+}
+
+/**
+ * Decodes the function table of the AST a stage2 run built for an input of
+ * `inputLength` bytes with its output at `outputPtr`.
+ */
+export function readAstFunctions(
+  memory: WebAssembly.Memory,
+  outputPtr: number,
+  inputLength: number,
+  layout: Stage2Layout = DEFAULT_STAGE2_LAYOUT,
+): AstFunction[] {
+  const view = new DataView(memory.buffer);
+  const astBase = astProgramBase(outputPtr, inputLength, layout);
+  const count = safeReadI32(view, astBase);
+  return Array.from({ length: Math.max(count, 0) }, (_, index) => {
+    const entryPtr = astBase + WORD_SIZE + index * layout.astFunctionEntrySize;
+    const namePtr = safeReadI32(view, entryPtr);
+    const nameLength = safeReadI32(view, entryPtr + WORD_SIZE);
+    const nameBytes =
+      namePtr > 0 && nameLength > 0 ? new Uint8Array(memory.buffer, namePtr, nameLength) : new Uint8Array();
+    return {
+      name: decoder.decode(nameBytes),
+      paramCount: safeReadI32(view, entryPtr + 2 * WORD_SIZE),
+      returnType: safeReadI32(view, entryPtr + 7 * WORD_SIZE),
+    };
+  });
+}
+
+/**
+ * Finds the AST a stage2 run built for an input of `inputLength` bytes with
+ * its output at `outputPtr`.
+ */
+export function astProgramBase(
+  outputPtr: number,
+  inputLength: number,
+  layout: Stage2Layout = DEFAULT_STAGE2_LAYOUT,
+): number {
+  return outputPtr + astOutputReserve(inputLength, layout);
+}
+
+/**
+ * Finds the expression table of the AST at `astBase`.  The table is a count
+ * followed by `layout.astExprEntrySize`-byte entries of a kind, three data
+ * words, and an extra word, and the type ids of the entries follow its
+ * capacity.
+ */
+export function astExpressionTableBase(astBase: number, layout: Stage2Layout = DEFAULT_STAGE2_LAYOUT): number {
+  return astConstantsCountPtr(astBase, layout) + layout.astExpressionsOffset;
+}
+
+/**
+ * Decodes the code locations the stage2 compiler recorded while writing the
+ * code section of its last compilation.  Locations in modules not among
+ * `modules` have a `null` span.
+ */
+export function readCodeLocations(
+  memory: WebAssembly.Memory,
+  modules: ReadonlyArray<CompilerModuleSource>,
+): CodeLocation[] {
+  const view = new DataView(memory.buffer);
+  const entriesPtr = view.getInt32(SOURCE_MAP_ENTRIES_PTR_OFFSET, true);
+  const count = Math.min(view.getInt32(SOURCE_MAP_COUNT_OFFSET, true), SOURCE_MAP_CAPACITY);
+  const entrySize = SOURCE_MAP_ENTRY_FIELDS * WORD_SIZE;
+  if (entriesPtr <= 0 || count <= 0 || entriesPtr + count * entrySize > view.byteLength) {
+    return [];
+  }
+  // Entries are many, so each module's line starts are found once and
+  // searched, rather than counting lines from the top for every entry.
+  const moduleLines = new Map<number, ModuleLines | null>();
+  const linesOf = (moduleIndex: number): ModuleLines | null => {
+    if (!moduleLines.has(moduleIndex)) {
+      const path = resolveModulePath(memory, moduleIndex);
+      const module = modules.find((loaded) => loaded.path === path);
+      moduleLines.set(moduleIndex, path && module ? { path, starts: lineStarts(module.source) } : null);
+    }
+    return moduleLines.get(moduleIndex) ?? null;
+  };
+  const locations: CodeLocation[] = [];
+  for (let index = 0; index < count; index += 1) {
+    const base = entriesPtr + index * entrySize;
+    const offset = view.getInt32(base, true);
+    const moduleIndex = view.getInt32(base + WORD_SIZE, true);
+    const sourceOffset = view.getInt32(base + 2 * WORD_SIZE, true);
+    const lines = moduleIndex >= 0 && sourceOffset >= 0 ? linesOf(moduleIndex) : null;
+    if (!lines) {
+      locations.push({ offset, span: null });
+      continue;
+    }
+    let low = 0;
+    let high = lines.starts.length;
+    while (high - low > 1) {
+      const middle = (low + high) >> 1;
+      if (lines.starts[middle]! <= sourceOffset) {
+        low = middle;
+      } else {
+        high = middle;
+      }
+    }
+    locations.push({
+      offset,
+      span: {
+        path: lines.path,
+        line: low + 1,
+        column: sourceOffset - lines.starts[low]! + 1,
+        offset: sourceOffset,
+      },
+    });
+  }
+  return locations;
+}
+
+export interface CompileErrorFormatOptions {
+  /** Shown in place of the default entry module path, e.g. the input file. */
+  readonly entryPath?: string;
+  /** Wrap the output in ANSI color codes, as for a terminal. */
+  readonly color?: boolean;
+  /** Render catalog messages in this locale instead of English. */
+  readonly locale?: string;
+}
+
+const ANSI_RESET = "\x1b[0m";
+const ANSI_BOLD = "\x1b[1m";
+const ANSI_BOLD_RED = "\x1b[1;31m";
+const ANSI_BOLD_YELLOW = "\x1b[1;33m";
+const ANSI_BOLD_BLUE = "\x1b[1;34m";
+
+function sourceLine(source: string, line: number): string | undefined {
+  return source.split(/\r\n|\r|\n/)[line - 1];
+}
+
+// Underlines the identifier or number starting at `column`, or one character.
+function caretWidth(text: string, column: number): number {
+  const range = tokenRangeAt(text, column - 1);
+  return Math.max(1, range.end - range.start);
+}
+
+function renderDiagnostic(
+  severity: string,
+  severityStyle: string,
+  message: string,
+  span: SourceSpan,
+  source: string | undefined,
+  options: CompileErrorFormatOptions,
+): string {
+  const paint = (style: string, text: string): string =>
+    options.color ? `${style}${text}${ANSI_RESET}` : text;
+  const path =
+    options.entryPath && span.path === DEFAULT_ENTRY_MODULE_PATH ? options.entryPath : span.path;
+  const gutter = " ".repeat(String(span.line).length);
+  const lines = [
+    `${paint(severityStyle, severity)}${paint(ANSI_BOLD, `: ${message}`)}`,
+    `${gutter}${paint(ANSI_BOLD_BLUE, "-->")} ${path}:${span.line}:${span.column}`,
+  ];
+  const text = source === undefined ? undefined : sourceLine(source, span.line);
+  if (text !== undefined) {
+    // Keep tabs in the padding so the caret lines up with the source text.
+    const padding = text.slice(0, span.column - 1).replace(/[^\t]/g, " ");
+    const carets = "^".repeat(caretWidth(text, span.column));
+    lines.push(
+      paint(ANSI_BOLD_BLUE, `${gutter} |`),
+      `${paint(ANSI_BOLD_BLUE, `${span.line} |`)} ${text}`,
+      `${paint(ANSI_BOLD_BLUE, `${gutter} |`)} ${padding}${paint(severityStyle, carets)}`,
+    );
+  }
+  return lines.join("\n");
+}
+
+/**
+ * Renders a compile error in the style of rustc: the message, an arrow line
+ * with the span's `path:line:column`, the offending source line, and a caret
+ * underline.  Errors without a span render as their message.
+ */
+export function formatCompileError(
+  error: CompileError,
+  options: CompileErrorFormatOptions = {},
+): string {
+  const record = error.record;
+  const span = record?.span;
+  if (!record || !span) {
+    return error.message.replace(/^error: (.*)$/s, (_, message: string) =>
+      `error: ${localizeMessage(message, options.locale)}`,
+    );
+  }
+  const message = localizeMessage(record.message, options.locale);
+  return renderDiagnostic("error", ANSI_BOLD_RED, message, span, error.source, options);
+}
+
+/** Renders a compile warning the same way `formatCompileError` renders errors. */
+export function formatCompileWarning(
+  warning: CompileWarning,
+  options: CompileErrorFormatOptions = {},
+): string {
+  return renderDiagnostic(
+    "warning",
+    ANSI_BOLD_YELLOW,
+    localizeMessage(warning.message, options.locale),
+    warning.span,
+    warning.source,
+    options,
+  );
+}
+
+// Reads a file that ships next to the sources.  Bun reads it from disk; other
+// hosts, such as the bundled playground, fetch the URL the bundler rewrote.
+// Returns null when the file does not exist.
+async function readAsset(url: URL): Promise<ArrayBuffer | null> {
+  if (typeof Bun !== "undefined") {
+    const file = Bun.file(url);
+    return (await file.exists()) ? file.arrayBuffer() : null;
+  }
+  const response = await fetch(url);
+  return response.ok ? response.arrayBuffer() : null;
+}
+
+async function readAssetText(url: URL): Promise<string> {
+  const bytes = await readAsset(url);
+  if (!bytes) {
+    throw new CompileError(`missing compiler asset '${url.href}'`);
+  }
+  return decoder.decode(bytes);
+}
+
 function loadMemoryIntrinsicsSource(): Promise<string> {
   if (!memoryIntrinsicsSourcePromise) {
-    const file = Bun.file(memoryIntrinsicsSourceUrl);
-    memoryIntrinsicsSourcePromise = file.text();
+    memoryIntrinsicsSourcePromise = readAssetText(memoryIntrinsicsSourceUrl);
   }
   return memoryIntrinsicsSourcePromise;
 }
 
+function loadAllocatorSource(): Promise<string> {
+  if (!allocatorSourcePromise) {
+    allocatorSourcePromise = readAssetText(allocatorSourceUrl);
+  }
+  return allocatorSourcePromise;
+}
+
+function loadSimdSource(): Promise<string> {
+  if (!simdSourcePromise) {
+    simdSourcePromise = readAssetText(simdSourceUrl);
+  }
+  return simdSourcePromise;
+}
+
+function loadAtomicsSource(): Promise<string> {
+  if (!atomicsSourcePromise) {
+    atomicsSourcePromise = readAssetText(atomicsSourceUrl);
+  }
+  return atomicsSourcePromise;
+}
+
+function loadCoreSource(): Promise<string> {
+  if (!coreSourcePromise) {
+    coreSourcePromise = readAssetText(coreSourceUrl);
+  }
+  return coreSourcePromise;
+}
+
+function loadPreludeSource(): Promise<string> {
+  if (!preludeSourcePromise) {
+    preludeSourcePromise = readAssetText(preludeSourceUrl);
+  }
+  return preludeSourcePromise;
+}
+
 function maybeFormatTypeMetadataFailure(
   memory: WebAssembly.Memory,
   outputPtr: number,
   inputLength: number,
   existingDetail: string | undefined,
+  layout: Stage2Layout,
 ): string | null {
   if (existingDetail && existingDetail !== "type metadata resolution failed") {
     return null;
@@ -256,7 +1521,7 @@ function maybeFormatTypeMetadataFailure(
       constantIndex = safeReadI32(view, TYPE_METADATA_DEBUG_LAST_SUBJECT_OFFSET);
       constantType = safeReadI32(view, TYPE_METADATA_DEBUG_LAST_EXTRA_OFFSET);
     } else {
-      const inferred = inferConstantMetadataFailure(memory, view, outputPtr, inputLength);
+      const inferred = inferConstantMetadataFailure(memory, view, outputPtr, inputLength, layout);
       if (!inferred) {
         return null;
       }
@@ -294,8 +1559,8 @@ function maybeFormatTypeMetadataFailure(
     return null;
   }
 
-  const astBase = outputPtr + astOutputReserve(clampedModuleLen);
-  const constantsCountPtr = astConstantsCountPtr(astBase);
+  const astBase = outputPtr + astOutputReserve(clampedModuleLen, layout);
+  const constantsCountPtr = astConstantsCountPtr(astBase, layout);
   if (constantsCountPtr < 0 || constantsCountPtr + WORD_SIZE > bufferLength) {
     return null;
   }
@@ -323,13 +1588,13 @@ function maybeFormatTypeMetadataFailure(
   }
 
   const locationOffset = Math.min(Math.max(nameStart, 0), sourceText.length);
-  const position = computeLineAndColumn(sourceText, locationOffset);
+  const position = lineColumnAt(sourceText, locationOffset);
   if (position.line <= 0 || position.column <= 0) {
     return null;
   }
 
   const path = resolveModulePath(memory, moduleIndex) ?? DEFAULT_ENTRY_MODULE_PATH;
-  return `${path}:${position.line}:${position.column}: const initializer type metadata resolution failed for '${nameText}'`;
+  return `${path}:${position.line}:${position.column}: ${formatMessage("E0401", { name: nameText })}`;
 }
 
 function inferConstantMetadataFailure(
@@ -337,6 +1602,7 @@ function inferConstantMetadataFailure(
   view: DataView,
   outputPtr: number,
   inputLength: number,
+  layout: Stage2Layout,
 ): { constantIndex: number; constantType: number; moduleIndex: number } | null {
   let effectiveLength = inputLength;
   if (effectiveLength <= 0) {
@@ -345,8 +1611,8 @@ function inferConstantMetadataFailure(
       effectiveLength = scratchLen;
     }
   }
-  const astBase = outputPtr + astOutputReserve(effectiveLength > 0 ? effectiveLength : 0);
-  const constantsCountPtr = astConstantsCountPtr(astBase);
+  const astBase = outputPtr + astOutputReserve(effectiveLength > 0 ? effectiveLength : 0, layout);
+  const constantsCountPtr = astConstantsCountPtr(astBase, layout);
   if (constantsCountPtr < 0 || constantsCountPtr + WORD_SIZE > memory.buffer.byteLength) {
     return null;
   }
@@ -372,30 +1638,28 @@ function inferConstantMetadataFailure(
   return null;
 }
 
-function astOutputReserve(inputLength: number): number {
-  const afterOutput = inputLength + SCRATCH_INSTR_CAPACITY;
-  const scratchEnd = SCRATCH_FN_BASE_OFFSET + 16_384;
-  return afterOutput > scratchEnd ? afterOutput : scratchEnd;
+function astOutputReserve(inputLength: number, layout: Stage2Layout): number {
+  return Math.max(inputLength + layout.scratchInstrCapacity, layout.astOutputReserveMin);
 }
 
-function astConstantsCountPtr(astBase: number): number {
-  return astCallDataBase(astBase) + AST_CALL_DATA_CAPACITY * WORD_SIZE;
+function astConstantsCountPtr(astBase: number, layout: Stage2Layout): number {
+  return astCallDataBase(astBase, layout) + layout.astCallDataCapacity * WORD_SIZE;
 }
 
-function astCallDataBase(astBase: number): number {
-  return astCallDataLenPtr(astBase) + WORD_SIZE;
+function astCallDataBase(astBase: number, layout: Stage2Layout): number {
+  return astCallDataLenPtr(astBase, layout) + WORD_SIZE;
 }
 
-function astCallDataLenPtr(astBase: number): number {
-  return astNamesBase(astBase) + AST_NAMES_CAPACITY;
+function astCallDataLenPtr(astBase: number, layout: Stage2Layout): number {
+  return astNamesBase(astBase, layout) + layout.astNamesCapacity;
 }
 
-function astNamesBase(astBase: number): number {
-  return astNamesLenPtr(astBase) + WORD_SIZE;
+function astNamesBase(astBase: number, layout: Stage2Layout): number {
+  return astNamesLenPtr(astBase, layout) + WORD_SIZE;
 }
 
-function astNamesLenPtr(astBase: number): number {
-  return astBase + WORD_SIZE + AST_MAX_FUNCTIONS * AST_FUNCTION_ENTRY_SIZE;
+function astNamesLenPtr(astBase: number, layout: Stage2Layout): number {
+  return astBase + WORD_SIZE + layout.astMaxFunctions * layout.astFunctionEntrySize;
 }
 
 function resolveModulePath(memory: WebAssembly.Memory, moduleIndex: number): string | null {
@@ -426,25 +1690,6 @@ function sliceByBounds(text: string, start: number, length: number): string {
   return text.slice(clampedStart, clampedEnd).trim();
 }
 
-function computeLineAndColumn(
-  text: string,
-  offset: number,
-): { line: number; column: number } {
-  const clampedOffset = Math.max(0, Math.min(offset, text.length));
-  let line = 1;
-  let column = 1;
-  for (let index = 0; index < clampedOffset; index += 1) {
-    const char = text.charCodeAt(index);
-    if (char === 10) {
-      line += 1;
-      column = 1;
-    } else if (char !== 13) {
-      column += 1;
-    }
-  }
-  return { line, column };
-}
-
 function growMemoryIfRequired(memory: WebAssembly.Memory, required: number) {
   const current = memory.buffer.byteLength;
   if (required <= current) {
@@ -482,12 +1727,11 @@ async function loadCompilerModule(): Promise<WebAssembly.Module> {
   if (!compilerModulePromise) {
     const wasmUrl = new URL("../compiler.wasm", import.meta.url);
     compilerModulePromise = (async () => {
-      const wasmFile = Bun.file(wasmUrl);
-      if (!(await wasmFile.exists())) {
-        const path = fileURLToPath(wasmUrl);
+      const wasmBytes = await readAsset(wasmUrl);
+      if (!wasmBytes) {
+        const path = wasmUrl.protocol === "file:" ? decodeURIComponent(wasmUrl.pathname) : wasmUrl.href;
         throw new CompileError(`stage2 compiler not found at '${path}'`);
       }
-      const wasmBytes = await wasmFile.arrayBuffer();
       return WebAssembly.compile(wasmBytes);
     })();
   }
@@ -495,10 +1739,97 @@ async function loadCompilerModule(): Promise<WebAssembly.Module> {
   return compilerModulePromise;
 }
 
-async function instantiateCompiler(): Promise<WebAssembly.Instance> {
-  const module = await loadCompilerModule();
+/** A stage2 phase export: `(inputPtr, inputLength, outputPtr) -> status`. */
+export type Stage2PhaseExport = (inputPtr: number, inputLength: number, outputPtr: number) => number | bigint;
+
+/**
+ * The exports the host drives the stage2 compiler through.  `instantiateStage2`
+ * provides them from the host's `WebAssembly` engine.  A host without one can
+ * run `compiler.wasm` on another engine, such as an interpreter, and pass a
+ * factory for this interface to `Compiler.create`.  Its `memory` must be the
+ * compiler's linear memory, zeroed on creation.
+ */
+export interface Stage2Executor {
+  readonly memory: WebAssembly.Memory;
+  readonly loadModuleFromSource: (pathPtr: number, contentPtr: number) => number | bigint;
+  readonly compileFromPath: (pathPtr: number) => number | bigint;
+  readonly setCompileFlags: (flags: number) => number | bigint;
+  /** Absent from compilers built before language flags existed. */
+  readonly setLanguageFlags?: (flags: number) => number | bigint;
+  /**
+   * Takes the enabled {@link WasmFeatures} as a mask.  Compilers built before
+   * it existed emit no post-MVP operators, so any feature set accepts them.
+   */
+  readonly setWasmFeatures?: (features: number) => number | bigint;
+  /** Defaults to {@link DEFAULT_STAGE2_LAYOUT}. */
+  readonly layout?: Stage2Layout;
+  /** Partial pipeline exports, which compilers built before them lack. */
+  readonly tokenize?: Stage2PhaseExport;
+  readonly parse?: Stage2PhaseExport;
+  readonly check?: Stage2PhaseExport;
+  /** Split `compileFromPath`, for running {@link CompileOptions.extraPasses}. */
+  readonly checkFromPath?: (pathPtr: number) => number | bigint;
+  readonly emitFromPath?: (pathPtr: number) => number | bigint;
+  /**
+   * Parses a loaded module, so the host can pick the parts of the implicit
+   * modules it calls.  Without it they are loaded whole.
+   */
+  readonly parseFromPath?: (pathPtr: number) => number | bigint;
+}
+
+/**
+ * Instantiates the checked-in `compiler.wasm`, or `wasm` when given, which
+ * must export the same interface, such as a compiler that stage2 just built.
+ */
+export async function instantiateStage2(wasm?: Uint8Array): Promise<Stage2Executor> {
+  const module = wasm ? await WebAssembly.compile(wasm) : await loadCompilerModule();
   const instance = await WebAssembly.instantiate(module, {});
-  return instance;
+  const memory = instance.exports.memory as WebAssembly.Memory | undefined;
+  const loadModuleFromSourceExport = instance.exports.loadModuleFromSource as
+    | ((pathPtr: number, contentPtr: number) => number | bigint)
+    | undefined;
+  const compileFromPathExport = instance.exports.compileFromPath as
+    | ((pathPtr: number) => number | bigint)
+    | undefined;
+  const setCompileFlagsExport = instance.exports.setCompileFlags as
+    | ((flags: number) => number | bigint)
+    | undefined;
+
+  if (!memory) {
+    throw new CompileError("stage2 compiler must export memory");
+  }
+  if (typeof loadModuleFromSourceExport !== "function") {
+    throw new CompileError("stage2 compiler missing module loading exports");
+  }
+  if (typeof compileFromPathExport !== "function") {
+    throw new CompileError("stage2 compiler missing module loading exports");
+  }
+  if (typeof setCompileFlagsExport !== "function") {
+    throw new CompileError("stage2 compiler missing compile flag export");
+  }
+  const phase = (name: string): Stage2PhaseExport | undefined => {
+    const exported = instance.exports[name];
+    return typeof exported === "function" ? (exported as Stage2PhaseExport) : undefined;
+  };
+  const unary = (name: string): ((argument: number) => number | bigint) | undefined => {
+    const exported = instance.exports[name];
+    return typeof exported === "function" ? (exported as (argument: number) => number | bigint) : undefined;
+  };
+  return {
+    memory,
+    loadModuleFromSource: loadModuleFromSourceExport,
+    compileFromPath: compileFromPathExport,
+    setCompileFlags: setCompileFlagsExport,
+    setLanguageFlags: unary("setLanguageFlags"),
+    setWasmFeatures: unary("setWasmFeatures"),
+    layout: readStage2Layout(instance.exports),
+    tokenize: phase("tokenize"),
+    parse: phase("parse"),
+    check: phase("check"),
+    checkFromPath: unary("checkFromPath"),
+    emitFromPath: unary("emitFromPath"),
+    parseFromPath: unary("parseFromPath"),
+  };
 }
 
 function readStageFailure(
@@ -506,70 +1837,266 @@ function readStageFailure(
   memory: WebAssembly.Memory,
   outputPtr: number,
   producedLen: number,
+  layout: Stage2Layout = DEFAULT_STAGE2_LAYOUT,
 ): string {
-  const description = describeCompilationFailure(memory, outputPtr, producedLen);
+  const description = describeCompilationFailure(memory, outputPtr, producedLen, -1, layout);
+  const names =
+    (description.function ? `, function=${description.function}` : "") +
+    (description.identifier ? `, identifier=${description.identifier}` : "");
   const detail = description.detail ? `, detail=\"${description.detail}\"` : "";
-  return `${stage} compilation failed (status ${producedLen}, functions=${description.functions}, instr_offset=${description.instructionOffset}, compiled_functions=${description.compiledFunctions}${detail})`;
+  return `${stage} compilation failed (status ${producedLen}, functions=${description.functions}, instr_offset=${description.instructionOffset}, compiled_functions=${description.compiledFunctions}${names}${detail})`;
 }
 
-export async function compile(
-  source: string,
-  target: Target = DEFAULT_TARGET,
-  options: CompileOptions = {},
-): Promise<Compilation> {
-  if (!source) {
-    throw new CompileError("source must not be empty");
+function compileFlagsFromOptions(options: CompileOptions): number {
+  let compileFlags = 0;
+  if (options.checkedDivision) {
+    compileFlags |= COMPILE_FLAG_CHECKED_DIVISION;
+  }
+  if (options.strict) {
+    compileFlags |= COMPILE_FLAG_STRICT_DISCARD;
+  }
+  if (options.constantTable) {
+    compileFlags |= COMPILE_FLAG_CONSTANT_TABLE;
+  }
+  if (options.layoutGlobals) {
+    compileFlags |= COMPILE_FLAG_LAYOUT_GLOBALS;
+  }
+  if (options.memoryCanaries) {
+    compileFlags |= COMPILE_FLAG_MEMORY_CANARIES;
+  }
+  if (options.guardStores) {
+    compileFlags |= COMPILE_FLAG_GUARDED_STORES;
+  }
+  if (options.addressSanitizer) {
+    compileFlags |= COMPILE_FLAG_ADDRESS_SANITIZER;
+  }
+  if (options.memoryPages !== undefined) {
+    const pages = options.memoryPages;
+    if (!Number.isInteger(pages) || pages < 1 || pages > MAX_MEMORY_PAGES) {
+      throw new CompileError(formatMessage("E0901", { max: MAX_MEMORY_PAGES }));
+    }
+    compileFlags |= pages << COMPILE_FLAGS_MEMORY_PAGES_SHIFT;
+  }
+  if (options.maxNestingDepth !== undefined) {
+    const depth = options.maxNestingDepth;
+    if (!Number.isInteger(depth) || depth < 1 || depth > MAX_NESTING_DEPTH) {
+      throw new CompileError(formatMessage("E0902", { max: MAX_NESTING_DEPTH }));
+    }
+    compileFlags |= depth << COMPILE_FLAGS_NESTING_DEPTH_SHIFT;
   }
+  return compileFlags;
+}
 
-  if (target !== Target.Wasm) {
-    throw new CompileError(`target '${target}' is not supported yet`);
+/**
+ * A stage2 compiler instance that is reused across compilations.
+ *
+ * The compiler keeps all of its state in linear memory and starts from zeroed
+ * memory, so clearing the memory between runs gives the same output as a fresh
+ * instance. Everything that awaits happens before the instance is touched, so
+ * concurrent `compile` calls never interleave inside it. An instance that
+ * crashes is dropped and replaced on the next call; one that reports a
+ * diagnostic is kept.
+ */
+export class Compiler {
+  #instantiate: () => Promise<Stage2Executor>;
+  #stage2: Stage2Executor | null;
+  #used = false;
+
+  private constructor(instantiate: () => Promise<Stage2Executor>, stage2: Stage2Executor) {
+    this.#instantiate = instantiate;
+    this.#stage2 = stage2;
   }
 
-  const entryPath = options.entryPath ?? DEFAULT_ENTRY_MODULE_PATH;
-  const extraModules = options.modules ?? [];
+  static async create(
+    instantiate: () => Promise<Stage2Executor> = instantiateStage2,
+  ): Promise<Compiler> {
+    return new Compiler(instantiate, await instantiate());
+  }
 
-  const instance = await instantiateCompiler();
-  const memory = instance.exports.memory as WebAssembly.Memory | undefined;
-  const loadModuleFromSourceExport = instance.exports.loadModuleFromSource as
-    | ((pathPtr: number, contentPtr: number) => number | bigint)
-    | undefined;
-  const compileFromPathExport = instance.exports.compileFromPath as
-    | ((pathPtr: number) => number | bigint)
-    | undefined;
+  async compile(
+    source: string,
+    target: Target | TargetSpec = DEFAULT_TARGET,
+    options: CompileOptions = {},
+  ): Promise<Compilation> {
+    if (!source) {
+      throw new CompileError(formatMessage("E0900"));
+    }
 
-  if (!memory) {
-    throw new CompileError("stage2 compiler must export memory");
+    const spec = toTargetSpec(target);
+    if (spec.kind !== Target.Wasm) {
+      throw new CompileError(formatMessage("E0904", { target: spec.kind }));
+    }
+
+    const compileFlags = compileFlagsFromOptions(options);
+    const memoryIntrinsicsSource = await loadMemoryIntrinsicsSource();
+    const allocatorSource = await loadAllocatorSource();
+    const simdSource = await loadSimdSource();
+    const atomicsSource = await loadAtomicsSource();
+    const implicitModules = await Promise.all(
+      [
+        { path: PRELUDE_MODULE_PATH, source: await loadPreludeSource() },
+        ...(options.noStd ? [] : [{ path: CORE_MODULE_PATH, source: await loadCoreSource() }]),
+      ].map(readImplicitModule),
+    );
+    const definesModule = definesModules(options.defines);
+    let stage2 = this.#stage2;
+    if (!stage2) {
+      const started = performance.now();
+      const created = await this.#instantiate();
+      options.log?.(1, `instantiated stage2 in ${elapsedSince(started)}`);
+      // Another call may have installed an instance while this one waited.
+      stage2 = this.#stage2 ?? created;
+      if (stage2 === created) {
+        this.#stage2 = created;
+        this.#used = false;
+      }
+    }
+
+    // No awaits from here on: the run below owns the instance until it returns.
+    if (this.#used) {
+      new Uint8Array(stage2.memory.buffer).fill(0);
+      options.log?.(2, `cleared ${stage2.memory.buffer.byteLength} bytes of stage2 memory for reuse`);
+    }
+    this.#used = true;
+    let compilation: Compilation;
+    try {
+      compilation = runStage2(
+        stage2,
+        source,
+        spec,
+        options,
+        compileFlags,
+        [
+          { path: MEMORY_INTRINSICS_MODULE_PATH, source: memoryIntrinsicsSource },
+          { path: ALLOCATOR_MODULE_PATH, source: allocatorSource },
+          { path: SIMD_MODULE_PATH, source: simdSource },
+          { path: ATOMICS_MODULE_PATH, source: atomicsSource },
+          ...definesModule,
+        ],
+        implicitModules,
+      );
+    } catch (error) {
+      if (error instanceof CompilerCrash || error instanceof WebAssembly.RuntimeError) {
+        this.#stage2 = null;
+      }
+      throw error;
+    }
+    if (options.features) {
+      checkWasmFeatures(compilation, options.features);
+    }
+    if (options.validateOutput) {
+      await validateCompilation(compilation);
+    }
+    return compilation;
   }
-  if (typeof loadModuleFromSourceExport !== "function") {
-    throw new CompileError("stage2 compiler missing module loading exports");
+}
+
+function elapsedSince(started: number): string {
+  return `${(performance.now() - started).toFixed(1)}ms`;
+}
+
+// The defines go to stage2 as a module of `NAME=value` lines, which the
+// parser reads when it evaluates a `cfg` predicate.
+function definesModules(defines: Readonly<Record<string, string>> | undefined): CompilerModuleSource[] {
+  const entries = Object.entries(defines ?? {});
+  if (entries.length === 0) {
+    return [];
   }
-  if (typeof compileFromPathExport !== "function") {
-    throw new CompileError("stage2 compiler missing module loading exports");
+  for (const [name, value] of entries) {
+    if (!/^[A-Za-z_][A-Za-z0-9_]*$/.test(name)) {
+      throw new CompileError(`define name '${name}' is not an identifier`);
+    }
+    if (/[\n\r"\\]/.test(value)) {
+      throw new CompileError(`define '${name}' has a value with a newline, quote, or backslash`);
+    }
   }
-  const memoryIntrinsicsSource = await loadMemoryIntrinsicsSource();
+  return [{ path: DEFINES_MODULE_PATH, source: entries.map(([name, value]) => `${name}=${value}\n`).join("") }];
+}
 
+function runStage2(
+  stage2: Stage2Executor,
+  source: string,
+  target: TargetSpec,
+  options: CompileOptions,
+  compileFlags: number,
+  stdlibModules: readonly CompilerModuleSource[],
+  implicitModules: readonly ImplicitModule[],
+): Compilation {
+  const entryPath = options.entryPath ?? DEFAULT_ENTRY_MODULE_PATH;
+  const extraModules = options.modules ?? [];
+  const memory = stage2.memory;
+  const log = options.log ?? (() => {});
+  const layout = stage2.layout ?? DEFAULT_STAGE2_LAYOUT;
+  stage2.setCompileFlags(compileFlags);
+  const languageFlags = options.integerConditions ? LANGUAGE_FLAG_INTEGER_CONDITIONS : 0;
+  log(2, `compile flags 0x${compileFlags.toString(16)}, language flags 0x${languageFlags.toString(16)}`);
+  if (stage2.setLanguageFlags) {
+    stage2.setLanguageFlags(languageFlags);
+  } else if (languageFlags !== 0) {
+    throw new CompileError("stage2 compiler does not support language flags");
+  }
+  stage2.setWasmFeatures?.(wasmFeatureMask(options.features ?? {}));
+  const loadedModules: CompilerModuleSource[] = [];
+  const stage2Failure = (
+    top: number,
+    status: number,
+    pending?: CompilerModuleSource,
+  ): CompileError => {
+    const record = readCompileErrorRecord(memory, top) ?? undefined;
+    const spanPath = record?.span?.path;
+    const module =
+      pending?.path === spanPath ? pending : loadedModules.find((loaded) => loaded.path === spanPath);
+    const message = readStageFailure("stage2", memory, top, status, stage2.layout);
+    return new CompileError(message, record, module?.source);
+  };
   const loadModule = (path: string, contents: string) => {
     writeModuleString(memory, MODULE_PATH_PTR, path);
     const contentLength = writeModuleString(memory, MODULE_CONTENT_PTR, contents);
     let status: number;
     try {
-      const result = loadModuleFromSourceExport(MODULE_PATH_PTR, MODULE_CONTENT_PTR);
+      const result = stage2.loadModuleFromSource(MODULE_PATH_PTR, MODULE_CONTENT_PTR);
       status = coerceToI32(result);
     } catch (error) {
       const detail = error instanceof Error ? error.message : String(error);
-      throw new CompileError(
-        `stage2 compiler failed to load module '${path}': ${detail}`,
-      );
+      throw new CompilerCrash(`stage2 compiler failed to load module '${path}': ${detail}`);
     }
+    log(
+      2,
+      `load ${path}: path at ${MODULE_PATH_PTR}, ${contentLength} content bytes at ${MODULE_CONTENT_PTR}, ` +
+        `status ${status}, storage top ${readModuleStorageTop(memory)}`,
+    );
     if (status < 0) {
-      const top = readModuleStorageTop(memory);
-      throw new CompileError(readStageFailure("stage2", memory, top, status));
+      throw stage2Failure(readModuleStorageTop(memory), status, { path, source: contents });
+    }
+    const existing = loadedModules.findIndex((module) => module.path === path);
+    if (existing >= 0) {
+      loadedModules[existing] = { path, source: contents };
+    } else {
+      loadedModules.push({ path, source: contents });
     }
   };
 
-  loadModule(MEMORY_INTRINSICS_MODULE_PATH, memoryIntrinsicsSource);
+  const loadStarted = performance.now();
+  for (const module of stdlibModules) {
+    loadModule(module.path, module.source);
+  }
+  const libraries = (options.libraries ?? []).map((library) => ({
+    path: library.path,
+    archive: readLibraryArchive(library.archive, library.path),
+  }));
+  for (const library of libraries) {
+    loadModule(library.path, libraryInterfaceSource(library.archive));
+  }
   for (const module of extraModules) {
-    if (module.path === MEMORY_INTRINSICS_MODULE_PATH) {
+    if (
+      module.path === MEMORY_INTRINSICS_MODULE_PATH ||
+      module.path === ALLOCATOR_MODULE_PATH ||
+      module.path === SIMD_MODULE_PATH ||
+      module.path === ATOMICS_MODULE_PATH ||
+      module.path === CORE_MODULE_PATH ||
+      module.path === PRELUDE_MODULE_PATH ||
+      module.path === DEFINES_MODULE_PATH
+    ) {
       continue;
     }
     if (module.path === entryPath) {
@@ -579,24 +2106,147 @@ export async function compile(
   }
 
   loadModule(entryPath, source);
+  log(1, `loaded ${loadedModules.length} modules in ${elapsedSince(loadStarted)}`);
+
+  const runPhase = (name: string, phase: (pathPtr: number) => number | bigint): number => {
+    const started = performance.now();
+    log(2, `${name}: entry path at ${MODULE_PATH_PTR}, storage top ${readModuleStorageTop(memory)}`);
+    let status: number;
+    try {
+      status = coerceToI32(phase(MODULE_PATH_PTR));
+    } catch (error) {
+      const detail = error instanceof Error ? error.message : String(error);
+      log(2, `${name}: trapped with storage top ${readModuleStorageTop(memory)}`);
+      throw new CompilerCrash(`stage2 compiler failed: ${detail}`);
+    }
+    log(1, `${name}: status ${status} in ${elapsedSince(started)}`);
+    return status;
+  };
+
+  const inputLength = encoder.encode(source).length;
+  // The program is parsed before the implicit modules are loaded, so the calls
+  // it leaves unresolved name the parts of them it needs.
+  let selected: CompilerModuleSource[] = [...implicitModules];
+  if (stage2.parseFromPath) {
+    const parsed = runPhase("parse", stage2.parseFromPath);
+    if (parsed <= 0) {
+      throw stage2Failure(readModuleStorageTop(memory), parsed);
+    }
+    selected = selectImplicitModules(
+      implicitModules,
+      readCallNames(memory, readModuleStorageTop(memory), inputLength, stage2.layout),
+    );
+  }
+  log(1, `implicit modules: ${selected.map((module) => module.path).join(", ") || "none"}`);
+  for (const module of selected) {
+    loadModule(module.path, module.source);
+  }
+  writeModuleString(memory, MODULE_PATH_PTR, entryPath);
 
   let producedLen: number;
-  try {
-    const result = compileFromPathExport(MODULE_PATH_PTR);
-    producedLen = coerceToI32(result);
-  } catch (error) {
-    const detail = error instanceof Error ? error.message : String(error);
-    throw new CompileError(`stage2 compiler failed: ${detail}`);
+  const passes = [...(options.optimize ? OPTIMIZATION_PASSES : []), ...(options.extraPasses ?? [])];
+  if (passes.length > 0) {
+    if (!stage2.checkFromPath || !stage2.emitFromPath) {
+      throw new CompileError("stage2 compiler cannot run extra passes");
+    }
+    const checked = runPhase("check", stage2.checkFromPath);
+    if (checked <= 0) {
+      throw stage2Failure(readModuleStorageTop(memory), checked);
+    }
+    const passesStarted = performance.now();
+    applyHirPasses(memory, readModuleStorageTop(memory), inputLength, passes, stage2.layout);
+    log(1, `passes ${passes.map((pass) => pass.name).join(", ")} in ${elapsedSince(passesStarted)}`);
+    producedLen = runPhase("emit", stage2.emitFromPath);
+  } else {
+    producedLen = runPhase("compile", stage2.compileFromPath);
   }
 
   const outputPtr = readModuleStorageTop(memory);
+  // The output is written below the AST, in a reserve sized from the entry
+  // module's length, so an output longer than the reserve overwrites the AST.
+  const reserve = astOutputReserve(inputLength, layout);
+  log(
+    2,
+    `output at ${outputPtr}, ${Math.max(producedLen, 0)} of ${reserve} reserved bytes; ` +
+      `AST at ${astProgramBase(outputPtr, inputLength, layout)} for a ${inputLength}-byte entry module`,
+  );
+  if (producedLen > reserve) {
+    log(2, `output runs ${producedLen - reserve} bytes past its reserve into the AST`);
+  }
   if (producedLen <= 0) {
-    throw new CompileError(readStageFailure("stage2", memory, outputPtr, producedLen));
+    throw stage2Failure(outputPtr, producedLen);
   }
 
   const view = new Uint8Array(memory.buffer);
-  const wasm = view.slice(outputPtr, outputPtr + producedLen);
-  return new Compilation(target, wasm);
+  let wasm = view.slice(outputPtr, outputPtr + producedLen);
+  const warnings = readCompileWarnings(memory, loadedModules);
+  const attributes = readItemAttributes(memory, loadedModules);
+  let locations = readCodeLocations(memory, loadedModules);
+  if (libraries.length > 0) {
+    if (target.kind !== Target.Wasm) {
+      throw new CompileError(`target '${target.kind}' cannot link precompiled libraries`);
+    }
+    wasm = link([wasm, ...libraries.map((library) => library.archive.wasm)], {
+      labels: [entryPath, ...libraries.map((library) => library.path)],
+      placeholders: [libraries.flatMap((library) => library.archive.functions.map((entry) => entry.name))],
+    });
+    // Linking renumbers the code, which the recorded locations do not follow.
+    locations = [];
+    log(1, `linked ${libraries.length} libraries into ${wasm.length} bytes`);
+  }
+  if (options.profile) {
+    if (target.kind !== Target.Wasm) {
+      throw new CompileError(`target '${target.kind}' cannot be profiled`);
+    }
+    const profiled = instrumentWasmWithProfile(wasm);
+    wasm = profiled.wasm;
+    locations = locations.map((location) => ({ ...location, offset: profiled.relocate(location.offset) }));
+  }
+  let blocks: CoverageBlock[] = [];
+  if (options.coverage) {
+    if (target.kind !== Target.Wasm) {
+      throw new CompileError(`target '${target.kind}' cannot be instrumented for coverage`);
+    }
+    const covered = instrumentWasmWithCoverage(wasm);
+    wasm = covered.wasm;
+    locations = locations.map((location) => ({ ...location, offset: covered.relocate(location.offset) }));
+    blocks = covered.blocks.map((block) => ({
+      ...block,
+      start: covered.relocate(block.start),
+      end: covered.relocate(block.end),
+    }));
+  }
+  if (
+    options.optimize &&
+    target.kind === Target.Wasm &&
+    options.memoryPages === undefined &&
+    !options.sharedMemory
+  ) {
+    const stripped = stripUnusedMemory(wasm);
+    wasm = stripped.wasm;
+    locations = locations.map((location) => ({ ...location, offset: stripped.relocate(location.offset) }));
+  }
+  if (options.sharedMemory) {
+    wasm = shareMemory(wasm);
+  }
+  log(1, `produced ${wasm.length} bytes with ${warnings.length} warnings`);
+  return new Compilation(target, wasm, loadedModules, warnings, attributes, locations, blocks);
+}
+
+export async function compile(
+  source: string,
+  target: Target | TargetSpec = DEFAULT_TARGET,
+  options: CompileOptions = {},
+): Promise<Compilation> {
+  sharedCompilerPromise ??= Compiler.create();
+  let compiler: Compiler;
+  try {
+    compiler = await sharedCompilerPromise;
+  } catch (error) {
+    sharedCompilerPromise = null;
+    throw error;
+  }
+  return compiler.compile(source, target, options);
 }
 
 export async function compileToWasm(
@@ -607,6 +2257,428 @@ export async function compileToWasm(
   return compilation.intoWasm();
 }
 
+/** Renders a runtime panic as `panicked at path:line:column: message`. */
+export function formatRuntimePanic(
+  panic: RuntimePanic,
+  options: CompileErrorFormatOptions = {},
+): string {
+  if (panic.address !== undefined) {
+    return `panicked at address 0x${panic.address.toString(16)}: ${panic.message}`;
+  }
+  const path =
+    options.entryPath && panic.path === DEFAULT_ENTRY_MODULE_PATH ? options.entryPath : panic.path;
+  return `panicked at ${path}:${panic.line}:${panic.column}: ${panic.message}`;
+}
+
+/** Renders trap frames innermost first, one `    at name (path:line:column)` line each. */
+export function formatStackTrace(
+  frames: ReadonlyArray<StackFrame>,
+  options: CompileErrorFormatOptions = {},
+): string {
+  return frames
+    .map((frame) => {
+      const name = frame.name ?? `wasm-function[${frame.functionIndex}]`;
+      if (!frame.span) {
+        return `    at ${name}`;
+      }
+      const path =
+        options.entryPath && frame.span.path === DEFAULT_ENTRY_MODULE_PATH
+          ? options.entryPath
+          : frame.span.path;
+      return `    at ${name} (${path}:${frame.span.line}:${frame.span.column})`;
+    })
+    .join("\n");
+}
+
+/**
+ * Formats a coverage report as an lcov tracefile, with one record per source
+ * file.  Functions and lines without a source path are left out.
+ */
+export function formatLcov(report: CoverageReport, options: CompileErrorFormatOptions = {}): string {
+  const files = new Map<string, { functions: FunctionCoverage[]; lines: LineCoverage[] }>();
+  const fileOf = (path: string): { functions: FunctionCoverage[]; lines: LineCoverage[] } => {
+    const file = files.get(path) ?? { functions: [], lines: [] };
+    files.set(path, file);
+    return file;
+  };
+  for (const line of report.lines) {
+    fileOf(line.path).lines.push(line);
+  }
+  for (const coverage of report.functions) {
+    if (coverage.span && coverage.name) {
+      fileOf(coverage.span.path).functions.push(coverage);
+    }
+  }
+  const out: string[] = [];
+  for (const [path, file] of files) {
+    out.push(
+      "TN:",
+      `SF:${options.entryPath && path === DEFAULT_ENTRY_MODULE_PATH ? options.entryPath : path}`,
+    );
+    for (const coverage of file.functions) {
+      out.push(`FN:${coverage.span!.line},${coverage.name}`);
+    }
+    for (const coverage of file.functions) {
+      out.push(`FNDA:${coverage.calls},${coverage.name}`);
+    }
+    out.push(
+      `FNF:${file.functions.length}`,
+      `FNH:${file.functions.filter((coverage) => coverage.calls > 0).length}`,
+    );
+    for (const line of file.lines) {
+      out.push(`DA:${line.line},${line.count}`);
+    }
+    out.push(
+      `LF:${file.lines.length}`,
+      `LH:${file.lines.filter((line) => line.count > 0).length}`,
+      "end_of_record",
+    );
+  }
+  return out.map((line) => `${line}\n`).join("");
+}
+
+export type RunStatus = "ok" | "compile-error" | "runtime-error";
+
+/**
+ * The outcome of `evaluate`.  Every field is plain data, so the result can be
+ * sent to a web or REPL frontend with `JSON.stringify`.
+ */
+export interface RunResult {
+  readonly status: RunStatus;
+  /** `main`'s return value in decimal, since 64-bit results do not fit JSON numbers. */
+  readonly value?: string;
+  /** The rendered compile error, panic, or trap message. */
+  readonly error?: string;
+  /** Where a recorded panic happened, when the program panicked. */
+  readonly panic?: RuntimePanic;
+  /** Rendered warnings, in source order. */
+  readonly warnings: ReadonlyArray<string>;
+}
+
+export interface EvaluateOptions extends CompileOptions {
+  /** Formatting for the rendered error and warnings. */
+  readonly format?: CompileErrorFormatOptions;
+}
+
+/**
+ * Compiles `source`, runs its `main`, and reports what happened without
+ * throwing for compile errors or traps.  Bootstrap programs have no imports,
+ * so there is no printed output to capture; `main`'s result is the output.
+ */
+export async function evaluate(source: string, options: EvaluateOptions = {}): Promise<RunResult> {
+  const format = options.format ?? {};
+  let compilation: Compilation;
+  try {
+    compilation = await compile(source, Target.Wasm, options);
+  } catch (error) {
+    if (!(error instanceof CompileError)) {
+      throw error;
+    }
+    return { status: "compile-error", error: formatCompileError(error, format), warnings: [] };
+  }
+  return runCompilation(compilation, format);
+}
+
+/**
+ * Runs the `main` of an already compiled program and reports its outcome the
+ * way `evaluate` does.  `warnings` replaces the rendered compilation warnings
+ * when given, for hosts that show only some of them.
+ */
+export async function runCompilation(
+  compilation: Compilation,
+  format: CompileErrorFormatOptions = {},
+  warnings: ReadonlyArray<string> = compilation.warnings.map((warning) =>
+    formatCompileWarning(warning, format),
+  ),
+): Promise<RunResult> {
+  const { instance } = await WebAssembly.instantiate(compilation.wasm, {});
+  const outcome = callExport(compilation, instance, "main", format);
+  if ("error" in outcome) {
+    const { error, panic } = outcome;
+    return panic
+      ? { status: "runtime-error", error, panic, warnings }
+      : { status: "runtime-error", error, warnings };
+  }
+  return { status: "ok", value: String(outcome.value), warnings };
+}
+
+type ExportOutcome =
+  | { readonly value: unknown }
+  | { readonly error: string; readonly panic?: RuntimePanic };
+
+// Calls a no-argument export and checks the canaries afterwards, turning a
+// trap or recorded panic into a rendered error.
+function callExport(
+  compilation: Compilation,
+  instance: WebAssembly.Instance,
+  name: string,
+  format: CompileErrorFormatOptions,
+): ExportOutcome {
+  const exported = instance.exports[name];
+  if (typeof exported !== "function") {
+    return { error: `wasm module does not export '${name}'` };
+  }
+  if (exported.length > 0) {
+    return { error: `'${name}' takes parameters, so it cannot be called without arguments` };
+  }
+  let value: unknown;
+  try {
+    value = (exported as () => unknown)();
+  } catch (error) {
+    const panic = compilation.describePanic(instance);
+    if (panic) {
+      return { error: formatRuntimePanic(panic, format), panic };
+    }
+    return { error: error instanceof Error ? error.message : String(error) };
+  }
+  const canaryPanic = compilation.checkCanaries(instance);
+  if (canaryPanic) {
+    return { error: formatRuntimePanic(canaryPanic, format), panic: canaryPanic };
+  }
+  return { value };
+}
+
+export interface TestResult {
+  readonly name: string;
+  readonly passed: boolean;
+  /** The rendered panic or trap of a failed test. */
+  readonly error?: string;
+  readonly panic?: RuntimePanic;
+}
+
+export interface RunTestsOptions {
+  /** Run only tests whose names contain this text. */
+  readonly filter?: string;
+  /** Formatting for rendered panics. */
+  readonly format?: CompileErrorFormatOptions;
+}
+
+/**
+ * Runs every `#[test]` function of `compilation`, each in a fresh instance so
+ * no test sees another's memory.  A test passes when it returns without
+ * trapping; its return value is ignored.
+ */
+export async function runTests(
+  compilation: Compilation,
+  options: RunTestsOptions = {},
+): Promise<TestResult[]> {
+  const module = await WebAssembly.compile(compilation.wasm);
+  const results: TestResult[] = [];
+  for (const name of compilation.tests) {
+    if (options.filter !== undefined && !name.includes(options.filter)) {
+      continue;
+    }
+    const instance = await WebAssembly.instantiate(module, {});
+    const outcome = callExport(compilation, instance, name, options.format ?? {});
+    if (!("error" in outcome)) {
+      results.push({ name, passed: true });
+    } else if (outcome.panic) {
+      results.push({ name, passed: false, error: outcome.error, panic: outcome.panic });
+    } else {
+      results.push({ name, passed: false, error: outcome.error });
+    }
+  }
+  return results;
+}
+
+/**
+ * Decodes the `bootstrap.consts` custom section of a module compiled with
+ * `constantTable`.  Returns an empty list when the section is absent.
+ */
+export function readConstantTable(wasm: Uint8Array): ConstantTableEntry[] {
+  const module = new WebAssembly.Module(wasm);
+  const sections = WebAssembly.Module.customSections(module, CONSTANT_TABLE_SECTION_NAME);
+  const entries: ConstantTableEntry[] = [];
+  for (const section of sections) {
+    const bytes = new Uint8Array(section);
+    let offset = 0;
+    const readLeb = (signed: boolean): number => {
+      let result = 0;
+      let shift = 0;
+      let byte: number;
+      do {
+        if (offset >= bytes.length) {
+          throw new CompileError("constant table section is truncated");
+        }
+        byte = bytes[offset++]!;
+        result |= (byte & 0x7f) << shift;
+        shift += 7;
+      } while (byte & 0x80);
+      if (signed && shift < 32 && (byte & 0x40) !== 0) {
+        result |= -1 << shift;
+      }
+      return signed ? result : result >>> 0;
+    };
+    const count = readLeb(false);
+    for (let index = 0; index < count; index += 1) {
+      const nameLength = readLeb(false);
+      const name = decoder.decode(bytes.subarray(offset, offset + nameLength));
+      offset += nameLength;
+      const typeId = bytes[offset++] ?? -1;
+      const raw = readLeb(true);
+      const type = CONSTANT_TABLE_TYPE_NAMES[typeId] ?? `type#${typeId}`;
+      const value = type === "u32" ? raw >>> 0 : raw;
+      entries.push({ name, type, value });
+    }
+  }
+  return entries;
+}
+
+const WASM_SECTION_TYPE = 1;
+const WASM_SECTION_IMPORT = 2;
+const WASM_SECTION_FUNCTION = 3;
+const WASM_SECTION_EXPORT = 7;
+
+const NUMERIC_ABI_KINDS: ReadonlyMap<number, AbiValueKind> = new Map([
+  [0x7f, AbiValueKind.I32],
+  [0x7e, AbiValueKind.I64],
+  [0x7d, AbiValueKind.F32],
+  [0x7c, AbiValueKind.F64],
+]);
+
+/**
+ * Reads the calling convention of every exported function from a compiled
+ * module, so hosts and generated bindings can check what they pass and expect
+ * back.  See "Calling Convention" in docs/compilation-pipeline.md.
+ */
+export function readFunctionAbi(wasm: Uint8Array): FunctionAbi[] {
+  let offset = 8;
+  const readByte = (): number => {
+    if (offset >= wasm.length) {
+      throw new CompileError("wasm module is truncated");
+    }
+    return wasm[offset++]!;
+  };
+  const readLeb = (signed: boolean): number => {
+    let result = 0;
+    let shift = 0;
+    let byte: number;
+    do {
+      byte = readByte();
+      result |= (byte & 0x7f) << shift;
+      shift += 7;
+    } while (byte & 0x80);
+    if (signed && shift < 32 && (byte & 0x40) !== 0) {
+      result |= -1 << shift;
+    }
+    return signed ? result : result >>> 0;
+  };
+  const readName = (): string => {
+    const length = readLeb(false);
+    offset += length;
+    return decoder.decode(wasm.subarray(offset - length, offset));
+  };
+
+  // Composite kinds are resolved after the whole type section is read, since
+  // a signature may refer to a struct or array defined after it.
+  const composites: number[] = [];
+  const signatures: { params: number[]; results: number[] }[] = [];
+  const functionTypes: number[] = [];
+  const exports: { name: string; func: number }[] = [];
+  const readValueType = (): number => {
+    const byte = readByte();
+    if (byte === 0x63 || byte === 0x64) {
+      return readLeb(true);
+    }
+    return NUMERIC_ABI_KINDS.has(byte) ? 0x100 | byte : -1;
+  };
+  const readCompositeType = (typeIndex: number): void => {
+    const form = readByte();
+    composites[typeIndex] = form;
+    if (form === 0x60) {
+      const params = Array.from({ length: readLeb(false) }, readValueType);
+      const results = Array.from({ length: readLeb(false) }, readValueType);
+      signatures[typeIndex] = { params, results };
+    } else if (form === 0x5f || form === 0x5e) {
+      const fields = form === 0x5f ? readLeb(false) : 1;
+      for (let field = 0; field < fields; field += 1) {
+        readValueType();
+        readByte();
+      }
+    } else {
+      throw new CompileError(`unsupported composite type 0x${form.toString(16)}`);
+    }
+  };
+
+  while (offset < wasm.length) {
+    const id = readByte();
+    const size = readLeb(false);
+    const end = offset + size;
+    if (id === WASM_SECTION_TYPE) {
+      let typeIndex = 0;
+      const count = readLeb(false);
+      for (let entry = 0; entry < count; entry += 1) {
+        let group = 1;
+        if (wasm[offset] === 0x4e) {
+          readByte();
+          group = readLeb(false);
+        }
+        for (let member = 0; member < group; member += 1) {
+          if (wasm[offset] === 0x50 || wasm[offset] === 0x4f) {
+            readByte();
+            const supertypes = readLeb(false);
+            for (let supertype = 0; supertype < supertypes; supertype += 1) {
+              readLeb(false);
+            }
+          }
+          readCompositeType(typeIndex);
+          typeIndex += 1;
+        }
+      }
+    } else if (id === WASM_SECTION_IMPORT) {
+      const count = readLeb(false);
+      for (let entry = 0; entry < count; entry += 1) {
+        readName();
+        readName();
+        if (readByte() !== 0) {
+          throw new CompileError("only function imports are supported");
+        }
+        functionTypes.push(readLeb(false));
+      }
+    } else if (id === WASM_SECTION_FUNCTION) {
+      const count = readLeb(false);
+      for (let entry = 0; entry < count; entry += 1) {
+        functionTypes.push(readLeb(false));
+      }
+    } else if (id === WASM_SECTION_EXPORT) {
+      const count = readLeb(false);
+      for (let entry = 0; entry < count; entry += 1) {
+        const name = readName();
+        const kind = readByte();
+        const index = readLeb(false);
+        if (kind === 0) {
+          exports.push({ name, func: index });
+        }
+      }
+    }
+    offset = end;
+  }
+
+  const abiKind = (encoded: number): AbiValueKind => {
+    if (encoded >= 0x100) {
+      return NUMERIC_ABI_KINDS.get(encoded & 0xff)!;
+    }
+    if (encoded >= 0 && composites[encoded] === 0x5f) {
+      return AbiValueKind.StructRef;
+    }
+    if (encoded >= 0 && composites[encoded] === 0x5e) {
+      return AbiValueKind.ArrayRef;
+    }
+    return AbiValueKind.Ref;
+  };
+  return exports.map(({ name, func }) => {
+    const signature = signatures[functionTypes[func] ?? -1];
+    if (!signature) {
+      throw new CompileError(`export '${name}' has no function signature`);
+    }
+    return {
+      name,
+      params: signature.params.map(abiKind),
+      results: signature.results.map(abiKind),
+    };
+  });
+}
+
 export function parseTarget(value: string): Target {
   switch (value) {
     case "wasm":
@@ -617,3 +2689,20 @@ export function parseTarget(value: string): Target {
       throw new CompileError(`unsupported compilation target '${value}'`);
   }
 }
+
+/** Parses `wasm`, `wgsl`, or `wgsl:<stage>`, as given to `--target`. */
+export function parseTargetSpec(value: string): TargetSpec {
+  const [name, option, ...rest] = value.split(":");
+  const target = parseTarget(name);
+  if (option === undefined) {
+    return toTargetSpec(target);
+  }
+  if (target === Target.Wgsl && rest.length === 0) {
+    const stage = WGSL_SHADER_STAGES.find((candidate) => candidate === option);
+    if (stage) {
+      return { kind: Target.Wgsl, stage };
+    }
+    throw new CompileError(`unsupported WGSL shader stage '${option}'`);
+  }
+  throw new CompileError(`unsupported options for target '${target}': '${value}'`);
+}
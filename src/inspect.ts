@@ -0,0 +1,157 @@
+import { formatBuildMeta, readBuildMeta } from "./build_meta";
+import type { BuildMeta } from "./build_meta";
+import { readSizeReport } from "./size_report";
+import type { SectionSize } from "./size_report";
+import { WasmReader } from "./wasm_reader";
+
+/** The kinds of external an import or export can name. */
+export type ExternalKind = "func" | "table" | "memory" | "global" | "tag";
+
+const EXTERNAL_KINDS: ReadonlyArray<ExternalKind> = ["func", "table", "memory", "global", "tag"];
+
+export interface MemoryLimits {
+  /** Initial size in 64 KiB pages. */
+  readonly minimum: number;
+  /** Largest size in pages, or `null` when the memory may grow without bound. */
+  readonly maximum: number | null;
+  readonly shared: boolean;
+}
+
+export interface WasmImport {
+  readonly module: string;
+  readonly name: string;
+  readonly kind: ExternalKind;
+}
+
+export interface WasmExport {
+  readonly name: string;
+  readonly kind: ExternalKind;
+  /** Index in the kind's index space, counting imports first. */
+  readonly index: number;
+}
+
+export interface WasmInspection {
+  readonly imports: ReadonlyArray<WasmImport>;
+  readonly exports: ReadonlyArray<WasmExport>;
+  /** Limits of memory 0, whether defined or imported, or `null` without one. */
+  readonly memory: MemoryLimits | null;
+  readonly importedFunctions: number;
+  readonly definedFunctions: number;
+  /** Sections in module order, as `readSizeReport` measures them. */
+  readonly sections: ReadonlyArray<SectionSize>;
+  /** The `bootstrap.meta` section, when the module has one. */
+  readonly meta: BuildMeta | null;
+}
+
+function readLimits(reader: WasmReader): MemoryLimits {
+  const flags = reader.readByte();
+  const minimum = reader.readLeb();
+  const maximum = flags & 0x01 ? reader.readLeb() : null;
+  return { minimum, maximum, shared: (flags & 0x02) !== 0 };
+}
+
+/**
+ * Summarizes any Wasm module, not only ones this compiler wrote: what it
+ * imports and exports, its memory, how many functions it has, and how big
+ * each section is.
+ */
+export function inspectWasm(wasm: Uint8Array): WasmInspection {
+  const reader = new WasmReader(wasm, 8);
+  const decoder = new TextDecoder();
+  const readName = (): string => {
+    const length = reader.readLeb();
+    reader.offset += length;
+    return decoder.decode(wasm.subarray(reader.offset - length, reader.offset));
+  };
+  const readKind = (): ExternalKind => {
+    const byte = reader.readByte();
+    const kind = EXTERNAL_KINDS[byte];
+    if (!kind) {
+      throw new Error(`unknown external kind 0x${byte.toString(16)}`);
+    }
+    return kind;
+  };
+  const imports: WasmImport[] = [];
+  const exports: WasmExport[] = [];
+  let memory: MemoryLimits | null = null;
+  let importedFunctions = 0;
+  let definedFunctions = 0;
+  while (reader.offset < wasm.length) {
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    const end = reader.offset + size;
+    if (id === 2) {
+      for (let count = reader.readLeb(); count > 0; count -= 1) {
+        const module = readName();
+        const name = readName();
+        const kind = readKind();
+        if (kind === "func") {
+          reader.skipLeb();
+          importedFunctions += 1;
+        } else if (kind === "table") {
+          reader.skipValueType();
+          readLimits(reader);
+        } else if (kind === "memory") {
+          const limits = readLimits(reader);
+          memory ??= limits;
+        } else if (kind === "global") {
+          reader.skipValueType();
+          reader.readByte();
+        } else {
+          reader.readByte();
+          reader.skipLeb();
+        }
+        imports.push({ module, name, kind });
+      }
+    } else if (id === 3) {
+      definedFunctions = reader.readLeb();
+    } else if (id === 5) {
+      if (reader.readLeb() > 0) {
+        memory ??= readLimits(reader);
+      }
+    } else if (id === 7) {
+      for (let count = reader.readLeb(); count > 0; count -= 1) {
+        const name = readName();
+        const kind = readKind();
+        exports.push({ name, kind, index: reader.readLeb() });
+      }
+    }
+    reader.offset = end;
+  }
+  return {
+    imports,
+    exports,
+    memory,
+    importedFunctions,
+    definedFunctions,
+    sections: readSizeReport(wasm).sections,
+    meta: readBuildMeta(wasm),
+  };
+}
+
+function formatLimits(limits: MemoryLimits): string {
+  const maximum = limits.maximum === null ? "no maximum" : `max ${limits.maximum} pages`;
+  return `${limits.minimum} pages, ${maximum}${limits.shared ? ", shared" : ""}`;
+}
+
+/** Renders an inspection as text, as `bootstrapc inspect` prints it. */
+export function formatWasmInspection(inspection: WasmInspection): string {
+  const lines = [`imports: ${inspection.imports.length}`];
+  for (const entry of inspection.imports) {
+    lines.push(`  ${entry.module}.${entry.name}: ${entry.kind}`);
+  }
+  lines.push(`exports: ${inspection.exports.length}`);
+  for (const entry of inspection.exports) {
+    lines.push(`  ${entry.name}: ${entry.kind} ${entry.index}`);
+  }
+  lines.push(`memory: ${inspection.memory ? formatLimits(inspection.memory) : "none"}`);
+  lines.push(`functions: ${inspection.importedFunctions} imported, ${inspection.definedFunctions} defined`);
+  lines.push("sections:");
+  for (const section of inspection.sections) {
+    lines.push(`${String(section.size).padStart(10)}  ${section.name}`);
+  }
+  if (inspection.meta) {
+    lines.push(formatBuildMeta(inspection.meta));
+  }
+  return lines.join("\n");
+}
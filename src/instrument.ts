@@ -0,0 +1,309 @@
+import { CompileError } from "./index";
+import { WasmReader, encodeSignedLeb, encodeUnsignedLeb } from "./wasm_reader";
+
+const WASM_PAGE_SIZE = 65_536;
+const WASM_MAX_PAGES = 65_536;
+
+export interface ModuleShape {
+  readonly sections: ReadonlyArray<{ id: number; start: number; end: number }>;
+  readonly importedFunctions: number;
+  readonly definedFunctions: number;
+  readonly globalCount: number;
+  /** Pages of the module's only memory, or -1 without exactly one. */
+  readonly memoryPages: number;
+}
+
+export function readModuleShape(wasm: Uint8Array): ModuleShape {
+  const reader = new WasmReader(wasm, 8);
+  const sections: { id: number; start: number; end: number }[] = [];
+  let importedFunctions = 0;
+  let definedFunctions = 0;
+  let globalCount = 0;
+  let memoryPages = -1;
+  while (reader.offset < wasm.length) {
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    const section = { id, start: reader.offset, end: reader.offset + size };
+    sections.push(section);
+    if (id === 2) {
+      importedFunctions = reader.readLeb();
+    } else if (id === 3) {
+      definedFunctions = reader.readLeb();
+    } else if (id === 5 && reader.readLeb() === 1) {
+      reader.readByte();
+      memoryPages = reader.readLeb();
+    } else if (id === 6) {
+      globalCount = reader.readLeb();
+    }
+    reader.offset = section.end;
+  }
+  return { sections, importedFunctions, definedFunctions, globalCount, memoryPages };
+}
+
+// `i32.const 0; i32.const 0; load offset=address; const delta; add; store offset=address`
+export function counterIncrement(address: number, wide: boolean, delta: number): number[] {
+  const memArg = [wide ? 3 : 2, ...encodeUnsignedLeb(address)];
+  return [
+    ...[0x41, 0x00, 0x41, 0x00],
+    ...[wide ? 0x29 : 0x28, ...memArg],
+    ...[wide ? 0x42 : 0x41, ...encodeSignedLeb(delta)],
+    wide ? 0x7c : 0x6a,
+    ...[wide ? 0x37 : 0x36, ...memArg],
+  ];
+}
+
+export interface InstrumentedWasm {
+  readonly wasm: Uint8Array;
+  /** Maps an offset in the original module to the same instruction in `wasm`. */
+  readonly relocate: (offset: number) => number;
+}
+
+/**
+ * Code a rewrite inserts into each defined function, by its index among the
+ * defined functions.  Each hook returns the code to insert.
+ */
+export interface BodyHooks {
+  /** Runs on entry, after the locals. */
+  readonly enter: (body: number) => number[];
+  /**
+   * Runs before the straight-line run of code at `[start, stop)` in the
+   * original module, which has `length` counted instructions.
+   */
+  readonly run: (body: number, start: number, stop: number, length: number) => number[];
+  /** Runs before each `return` and at the end of the body. */
+  readonly exit: (body: number) => number[];
+}
+
+/** What a counter table instrumentation adds to each defined function. */
+export interface CounterHooks extends BodyHooks {
+  /** Bytes the table needs, asked once every body has been instrumented. */
+  readonly tableSize: () => number;
+}
+
+/** Globals and exports a rewrite appends to a module. */
+export interface ModuleAdditions {
+  /** Encoded global entries, numbered after the module's own globals. */
+  readonly globals: ReadonlyArray<ReadonlyArray<number>>;
+  /** Exports of the added globals, by their index among `globals`. */
+  readonly exports: ReadonlyArray<{ readonly name: string; readonly global: number }>;
+  /** The new size of the module's only memory, or undefined to keep it. */
+  readonly memoryPages?: number;
+}
+
+/**
+ * Rewrites every function body of a module with the code `hooks` insert, then
+ * appends what `additions` returns once every body is done.  Runs end after
+ * any instruction that may transfer control, and the structural `else` and
+ * `end` are not counted.  The profiler, the coverage counters, and the test
+ * harness's fuel all count this way.
+ */
+export function instrumentModule(
+  wasm: Uint8Array,
+  hooks: BodyHooks,
+  additions: () => ModuleAdditions,
+): InstrumentedWasm {
+  const shape = readModuleShape(wasm);
+  const reader = new WasmReader(wasm);
+  // Where each copied stretch of code starts, in the original module and in
+  // the code section payload.
+  const originalStarts: number[] = [];
+  const payloadStarts: number[] = [];
+
+  const instrumentBody = (index: number, sizeStart: number, end: number, payload: number[]): void => {
+    const body: number[] = [];
+    const copies: number[] = [];
+    const copy = (start: number, stop: number): void => {
+      copies.push(start, body.length);
+      for (let offset = start; offset < stop; offset += 1) {
+        body.push(wasm[offset]!);
+      }
+    };
+    const localsStart = reader.offset;
+    const localGroups = reader.readLeb();
+    for (let group = 0; group < localGroups; group += 1) {
+      reader.skipLeb();
+      reader.skipValueType();
+    }
+    copy(localsStart, reader.offset);
+    body.push(...hooks.enter(index));
+
+    let runStart = reader.offset;
+    let runLength = 0;
+    const flushRun = (stop: number): void => {
+      body.push(...hooks.run(index, runStart, stop, runLength));
+      copy(runStart, stop);
+      runStart = stop;
+      runLength = 0;
+    };
+    while (reader.offset < end) {
+      const instruction = reader.offset;
+      const opcode = reader.readByte();
+      const sub = reader.skipImmediates(opcode);
+      if (opcode === 0x0f || (opcode === 0x0b && reader.offset === end)) {
+        // The exit itself is copied with the next run, after the count.
+        runLength += opcode === 0x0f ? 1 : 0;
+        flushRun(instruction);
+        body.push(...hooks.exit(index));
+        continue;
+      }
+      const counted = opcode !== 0x05 && opcode !== 0x0b;
+      const endsRun =
+        !counted ||
+        opcode === 0x03 ||
+        opcode === 0x04 ||
+        opcode === 0x0d ||
+        opcode === 0xd5 ||
+        opcode === 0xd6 ||
+        (opcode === 0xfb && (sub === 24 || sub === 25));
+      if (counted) {
+        runLength += 1;
+      }
+      if (endsRun) {
+        flushRun(reader.offset);
+      }
+    }
+    flushRun(end);
+
+    const size = encodeUnsignedLeb(body.length);
+    // Locations at a function's start point at its size prefix.
+    originalStarts.push(sizeStart);
+    payloadStarts.push(payload.length);
+    const bodyStart = payload.length + size.length;
+    for (let index = 0; index < copies.length; index += 2) {
+      originalStarts.push(copies[index]!);
+      payloadStarts.push(bodyStart + copies[index + 1]!);
+    }
+    payload.push(...size);
+    for (const byte of body) {
+      payload.push(byte);
+    }
+  };
+
+  // The code comes last but can decide what is added before it, such as the
+  // size of a counter table that the memory section needs.
+  const code: number[] = [];
+  for (const section of shape.sections) {
+    if (section.id === 10) {
+      reader.offset = section.start;
+      const count = reader.readLeb();
+      code.push(...encodeUnsignedLeb(count));
+      for (let body = 0; body < count; body += 1) {
+        const sizeStart = reader.offset;
+        const size = reader.readLeb();
+        instrumentBody(body, sizeStart, reader.offset + size, code);
+      }
+    }
+  }
+  const added = additions();
+
+  const out: number[] = [...wasm.subarray(0, 8)];
+  let codeStart = 0;
+  const pushSection = (id: number, payload: ArrayLike<number>): void => {
+    out.push(id, ...encodeUnsignedLeb(payload.length));
+    for (let index = 0; index < payload.length; index += 1) {
+      out.push(payload[index]!);
+    }
+  };
+  const globalEntries = added.globals.flat();
+  const exportEntries = added.exports.flatMap(({ name, global }) => {
+    const bytes = new TextEncoder().encode(name);
+    return [...encodeUnsignedLeb(bytes.length), ...bytes, 0x03, ...encodeUnsignedLeb(shape.globalCount + global)];
+  });
+  let addedGlobals = added.globals.length === 0;
+  for (const section of shape.sections) {
+    reader.offset = section.start;
+    // The global section precedes exports and everything after them.
+    if (!addedGlobals && section.id >= 7 && section.id <= 12) {
+      pushSection(6, [...encodeUnsignedLeb(added.globals.length), ...globalEntries]);
+      addedGlobals = true;
+    }
+    if (section.id === 5 && added.memoryPages !== undefined) {
+      const pages = encodeUnsignedLeb(added.memoryPages);
+      pushSection(5, [1, 1, ...pages, ...pages]);
+    } else if (section.id === 6) {
+      reader.readLeb();
+      pushSection(6, [
+        ...encodeUnsignedLeb(shape.globalCount + added.globals.length),
+        ...wasm.subarray(reader.offset, section.end),
+        ...globalEntries,
+      ]);
+      addedGlobals = true;
+    } else if (section.id === 7) {
+      const count = reader.readLeb();
+      pushSection(7, [
+        ...encodeUnsignedLeb(count + added.exports.length),
+        ...wasm.subarray(reader.offset, section.end),
+        ...exportEntries,
+      ]);
+    } else if (section.id === 10) {
+      codeStart = out.length + 1 + encodeUnsignedLeb(code.length).length;
+      pushSection(10, code);
+    } else {
+      pushSection(section.id, wasm.subarray(section.start, section.end));
+    }
+  }
+
+  const relocate = (offset: number): number => {
+    let low = 0;
+    let high = originalStarts.length;
+    while (low < high) {
+      const middle = (low + high) >> 1;
+      if (originalStarts[middle]! <= offset) {
+        low = middle + 1;
+      } else {
+        high = middle;
+      }
+    }
+    if (low === 0) {
+      return offset;
+    }
+    return codeStart + payloadStarts[low - 1]! + offset - originalStarts[low - 1]!;
+  };
+  return { wasm: new Uint8Array(out), relocate };
+}
+
+/**
+ * Rewrites every function body of a module with counters in a table in pages
+ * appended to the module's memory, so no program data overlaps them.  An
+ * immutable global exported as `exportName` holds the table's address, which
+ * `hooks` gets to place its counters.  Runs are those of
+ * {@link instrumentModule}.
+ */
+export function instrumentWithCounters(
+  wasm: Uint8Array,
+  kind: string,
+  exportName: string,
+  hooks: (tableBase: number) => CounterHooks,
+): InstrumentedWasm {
+  const shape = readModuleShape(wasm);
+  if (shape.memoryPages < 0) {
+    throw new CompileError(`${kind} instrumentation needs a module with one memory`);
+  }
+  const tableBase = shape.memoryPages * WASM_PAGE_SIZE;
+  const counters = hooks(tableBase);
+  return instrumentModule(wasm, counters, () => {
+    const tablePages = Math.ceil(counters.tableSize() / WASM_PAGE_SIZE);
+    if (shape.memoryPages + tablePages > WASM_MAX_PAGES) {
+      throw new CompileError(`the ${kind} table does not fit past the module's memory`);
+    }
+    return {
+      globals: [[0x7f, 0x00, 0x41, ...encodeSignedLeb(tableBase | 0), 0x0b]],
+      exports: [{ name: exportName, global: 0 }],
+      memoryPages: shape.memoryPages + tablePages,
+    };
+  });
+}
+
+/**
+ * A view of an instance's memory from the address in its `exportName`
+ * global, or `null` when the instance has no such table.
+ */
+export function readCounterTable(instance: WebAssembly.Instance, exportName: string): DataView | null {
+  const exports = instance.exports as Record<string, unknown>;
+  const table = exports[exportName];
+  const memory = exports.memory;
+  if (!(table instanceof WebAssembly.Global) || !(memory instanceof WebAssembly.Memory)) {
+    return null;
+  }
+  return new DataView(memory.buffer, Number(table.value) >>> 0);
+}
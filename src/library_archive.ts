@@ -0,0 +1,143 @@
+import { CompileError, DEFAULT_TARGET, compile } from "./index";
+import type { CompileOptions } from "./index";
+import { blankComments } from "./sources";
+import { WasmReader, encodeUnsignedLeb } from "./wasm_reader";
+
+/** The extension `use` items give precompiled libraries. */
+export const LIBRARY_ARCHIVE_EXTENSION = ".bpl";
+
+const LIBRARY_ARCHIVE_MAGIC = [0x00, 0x62, 0x70, 0x6c];
+const LIBRARY_ARCHIVE_VERSION = 1;
+
+export interface LibraryParameter {
+  readonly name: string;
+  /** The type as the library's source spells it. */
+  readonly type: string;
+}
+
+export interface LibraryFunction {
+  readonly name: string;
+  readonly params: ReadonlyArray<LibraryParameter>;
+  /** The return type as the source spells it, or `null` without a `->`. */
+  readonly returnType: string | null;
+}
+
+/** A compiled module together with the signatures of the functions it offers. */
+export interface LibraryArchive {
+  readonly functions: ReadonlyArray<LibraryFunction>;
+  readonly wasm: Uint8Array;
+}
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+/**
+ * Encodes an archive: the magic `\0bpl`, a 4-byte little-endian version, the
+ * interface as length-prefixed JSON, and then the module's bytes.
+ */
+export function writeLibraryArchive(archive: LibraryArchive): Uint8Array {
+  const functions = archive.functions.map((entry) => ({
+    name: entry.name,
+    params: entry.params.map((param) => ({ name: param.name, type: param.type })),
+    returnType: entry.returnType,
+  }));
+  const header = encoder.encode(JSON.stringify(functions));
+  const prefix = [...LIBRARY_ARCHIVE_MAGIC, LIBRARY_ARCHIVE_VERSION, 0, 0, 0, ...encodeUnsignedLeb(header.length)];
+  const result = new Uint8Array(prefix.length + header.length + archive.wasm.length);
+  result.set(prefix);
+  result.set(header, prefix.length);
+  result.set(archive.wasm, prefix.length + header.length);
+  return result;
+}
+
+/** Decodes an archive `writeLibraryArchive` wrote.  `path` names it in errors. */
+export function readLibraryArchive(bytes: Uint8Array, path = "library"): LibraryArchive {
+  if (bytes.length < 8 || LIBRARY_ARCHIVE_MAGIC.some((byte, index) => bytes[index] !== byte)) {
+    throw new CompileError(`'${path}' is not a library archive`);
+  }
+  const version = new DataView(bytes.buffer, bytes.byteOffset).getUint32(4, true);
+  if (version !== LIBRARY_ARCHIVE_VERSION) {
+    throw new CompileError(`'${path}' is a version ${version} library archive, but only version 1 is supported`);
+  }
+  const reader = new WasmReader(bytes, 8);
+  const length = reader.readLeb();
+  const functions = JSON.parse(decoder.decode(bytes.subarray(reader.offset, reader.offset + length))) as LibraryFunction[];
+  return { functions, wasm: bytes.slice(reader.offset + length) };
+}
+
+// Splits at the commas outside brackets, dropping a trailing empty entry.
+function splitTopLevel(text: string): string[] {
+  const parts: string[] = [];
+  let depth = 0;
+  let start = 0;
+  for (let index = 0; index < text.length; index += 1) {
+    const char = text[index]!;
+    if (char === "(" || char === "[" || char === "<") {
+      depth += 1;
+    } else if (char === ")" || char === "]" || (char === ">" && text[index - 1] !== "-")) {
+      depth -= 1;
+    } else if (char === "," && depth === 0) {
+      parts.push(text.slice(start, index).trim());
+      start = index + 1;
+    }
+  }
+  parts.push(text.slice(start).trim());
+  return parts.filter((part) => part.length > 0);
+}
+
+const FUNCTION_PATTERN = /^fn\s+([A-Za-z_][A-Za-z0-9_]*)\s*(<?)/gm;
+
+/**
+ * The signatures of the top-level functions of a library's source.  Generic
+ * functions and functions with const parameters are left out, because they
+ * are specialized for each caller and so have no single compiled body.
+ */
+export function readLibraryInterface(source: string): LibraryFunction[] {
+  const text = blankComments(source);
+  const functions: LibraryFunction[] = [];
+  for (const match of text.matchAll(FUNCTION_PATTERN)) {
+    const open = match.index + match[0].length;
+    if (match[2] === "<" || text[open] !== "(") {
+      continue;
+    }
+    let close = open + 1;
+    for (let depth = 1; depth > 0 && close < text.length; close += 1) {
+      depth += text[close] === "(" ? 1 : text[close] === ")" ? -1 : 0;
+    }
+    const params = splitTopLevel(text.slice(open + 1, close - 1)).map((param) => {
+      const colon = param.indexOf(":");
+      return { name: param.slice(0, colon).trim(), type: param.slice(colon + 1).trim() };
+    });
+    if (params.some((param) => param.name.startsWith("const "))) {
+      continue;
+    }
+    const rest = text.slice(close, text.indexOf("{", close)).trim();
+    functions.push({
+      name: match[1]!,
+      params,
+      returnType: rest.startsWith("->") ? rest.slice(2).trim() : null,
+    });
+  }
+  return functions;
+}
+
+/**
+ * The source stage2 compiles for a `use` of the archive: each function with
+ * its signature and an `unreachable` body, which linking replaces with the
+ * archive's compiled body.
+ */
+export function libraryInterfaceSource(archive: LibraryArchive): string {
+  return archive.functions
+    .map((entry) => {
+      const params = entry.params.map((param) => `${param.name}: ${param.type}`).join(", ");
+      const returnType = entry.returnType === null ? "" : ` -> ${entry.returnType}`;
+      return `fn ${entry.name}(${params})${returnType} {\n    inline_wasm([0x00])\n}\n`;
+    })
+    .join("\n");
+}
+
+/** Compiles a library's source to Wasm and packs it with its interface. */
+export async function compileLibrary(source: string, options: CompileOptions = {}): Promise<Uint8Array> {
+  const compilation = await compile(source, DEFAULT_TARGET, options);
+  return writeLibraryArchive({ functions: readLibraryInterface(source), wasm: compilation.wasm });
+}
@@ -0,0 +1,579 @@
+import { CompileError } from "./index";
+import { SECTION_NAMES, WasmReader, encodeSignedLeb, encodeUnsignedLeb } from "./wasm_reader";
+
+interface Range {
+  readonly start: number;
+  readonly end: number;
+}
+
+interface LinkExport {
+  readonly name: string;
+  readonly kind: number;
+  readonly index: number;
+}
+
+interface LinkImport {
+  readonly module: string;
+  readonly name: string;
+  readonly type: number;
+}
+
+// The parts of one input module the linker merges, as offsets into its bytes.
+interface LinkInput {
+  readonly wasm: Uint8Array;
+  readonly label: string;
+  readonly types: Range[];
+  readonly imports: LinkImport[];
+  /** Type index of each defined function. */
+  readonly functions: number[];
+  memory: Range | null;
+  globals: Range | null;
+  globalCount: number;
+  exports: LinkExport[];
+  /** Names of the defined functions another module's exports replace, by function index. */
+  readonly placeholders: Map<number, string>;
+  code: Range | null;
+  data: Range | null;
+  dataCount: number;
+  hasDataCount: boolean;
+}
+
+const EXPORT_FUNC = 0;
+const EXPORT_MEMORY = 2;
+const EXPORT_GLOBAL = 3;
+
+function readInput(wasm: Uint8Array, label: string): LinkInput {
+  const reader = new WasmReader(wasm, 8);
+  const decoder = new TextDecoder();
+  const readName = (): string => {
+    const length = reader.readLeb();
+    reader.offset += length;
+    return decoder.decode(wasm.subarray(reader.offset - length, reader.offset));
+  };
+  const input: LinkInput = {
+    wasm,
+    label,
+    types: [],
+    imports: [],
+    functions: [],
+    memory: null,
+    globals: null,
+    globalCount: 0,
+    exports: [],
+    placeholders: new Map(),
+    code: null,
+    data: null,
+    dataCount: 0,
+    hasDataCount: false,
+  };
+  while (reader.offset < wasm.length) {
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    const end = reader.offset + size;
+    if (id === 1) {
+      for (let count = reader.readLeb(); count > 0; count -= 1) {
+        const start = reader.offset;
+        skipTypeEntry(reader, label);
+        input.types.push({ start, end: reader.offset });
+      }
+    } else if (id === 2) {
+      for (let count = reader.readLeb(); count > 0; count -= 1) {
+        const module = readName();
+        const name = readName();
+        if (reader.readByte() !== EXPORT_FUNC) {
+          throw new CompileError(`${label}: linking supports only function imports, but '${module}.${name}' is not one`);
+        }
+        input.imports.push({ module, name, type: reader.readLeb() });
+      }
+    } else if (id === 3) {
+      for (let count = reader.readLeb(); count > 0; count -= 1) {
+        input.functions.push(reader.readLeb());
+      }
+    } else if (id === 5) {
+      const count = reader.readLeb();
+      if (count > 1) {
+        throw new CompileError(`${label}: linking supports at most one memory`);
+      }
+      input.memory = count === 1 ? { start: reader.offset, end } : null;
+    } else if (id === 6) {
+      input.globalCount = reader.readLeb();
+      input.globals = { start: reader.offset, end };
+    } else if (id === 7) {
+      for (let count = reader.readLeb(); count > 0; count -= 1) {
+        const name = readName();
+        const kind = reader.readByte();
+        input.exports.push({ name, kind, index: reader.readLeb() });
+      }
+    } else if (id === 10) {
+      reader.readLeb();
+      input.code = { start: reader.offset, end };
+    } else if (id === 11) {
+      input.dataCount = reader.readLeb();
+      input.data = { start: reader.offset, end };
+    } else if (id === 12) {
+      input.hasDataCount = true;
+    } else if (id !== 0) {
+      throw new CompileError(`${label}: linking does not support the ${SECTION_NAMES[id] ?? `#${id}`} section`);
+    }
+    reader.offset = end;
+  }
+  return input;
+}
+
+function skipTypeEntry(reader: WasmReader, label: string): void {
+  const form = reader.readByte();
+  if (form === 0x60) {
+    for (let list = 0; list < 2; list += 1) {
+      for (let count = reader.readLeb(); count > 0; count -= 1) {
+        reader.skipValueType();
+      }
+    }
+  } else if (form === 0x5e) {
+    reader.skipValueType();
+    reader.readByte();
+  } else if (form === 0x5f) {
+    for (let count = reader.readLeb(); count > 0; count -= 1) {
+      reader.skipValueType();
+      reader.readByte();
+    }
+  } else {
+    throw new CompileError(`${label}: linking does not support type form 0x${form.toString(16)}`);
+  }
+}
+
+// Copies one input module's entries into the linked module, renumbering the
+// types, functions, globals, and data segments they refer to.
+class Renumberer {
+  readonly reader: WasmReader;
+  readonly types: number[] = [];
+  readonly functions: number[] = [];
+  globalBase = 0;
+  dataBase = 0;
+
+  constructor(wasm: Uint8Array) {
+    this.reader = new WasmReader(wasm);
+  }
+
+  copy(start: number, out: number[]): void {
+    for (let offset = start; offset < this.reader.offset; offset += 1) {
+      out.push(this.reader.bytes[offset]!);
+    }
+  }
+
+  mapType(index: number): number {
+    const mapped = this.types[index];
+    if (mapped === undefined) {
+      throw new CompileError("linking does not support types that refer to themselves or to later types");
+    }
+    return mapped;
+  }
+
+  typeIndex(out: number[]): void {
+    out.push(...encodeUnsignedLeb(this.mapType(this.reader.readLeb())));
+  }
+
+  heapType(out: number[]): void {
+    const start = this.reader.offset;
+    const index = this.reader.readSignedLeb();
+    if (index >= 0) {
+      out.push(...encodeSignedLeb(this.mapType(index)));
+    } else {
+      this.copy(start, out);
+    }
+  }
+
+  valueType(out: number[]): void {
+    const byte = this.reader.readByte();
+    out.push(byte);
+    if (byte === 0x63 || byte === 0x64) {
+      this.heapType(out);
+    }
+  }
+
+  blockType(out: number[]): void {
+    const byte = this.reader.bytes[this.reader.offset];
+    if (byte === 0x63 || byte === 0x64) {
+      this.valueType(out);
+    } else {
+      this.heapType(out);
+    }
+  }
+
+  typeEntry(out: number[]): void {
+    const form = this.reader.readByte();
+    out.push(form);
+    if (form === 0x60) {
+      for (let list = 0; list < 2; list += 1) {
+        const count = this.reader.readLeb();
+        out.push(...encodeUnsignedLeb(count));
+        for (let index = 0; index < count; index += 1) {
+          this.valueType(out);
+        }
+      }
+    } else {
+      const count = form === 0x5e ? 1 : this.reader.readLeb();
+      if (form === 0x5f) {
+        out.push(...encodeUnsignedLeb(count));
+      }
+      for (let index = 0; index < count; index += 1) {
+        this.valueType(out);
+        out.push(this.reader.readByte());
+      }
+    }
+  }
+
+  // Copies one instruction and returns its opcode.
+  instruction(out: number[]): number {
+    const reader = this.reader;
+    const start = reader.offset;
+    const opcode = reader.readByte();
+    if (opcode === 0x02 || opcode === 0x03 || opcode === 0x04) {
+      out.push(opcode);
+      this.blockType(out);
+    } else if (opcode === 0x10 || opcode === 0x12 || opcode === 0xd2) {
+      out.push(opcode, ...encodeUnsignedLeb(this.functions[reader.readLeb()]!));
+    } else if (opcode === 0x11 || opcode === 0x13 || opcode === 0x14 || opcode === 0x15) {
+      out.push(opcode);
+      this.typeIndex(out);
+      if (opcode <= 0x13) {
+        const table = reader.offset;
+        reader.skipLeb();
+        this.copy(table, out);
+      }
+    } else if (opcode === 0x1c) {
+      const count = reader.readLeb();
+      out.push(opcode, ...encodeUnsignedLeb(count));
+      for (let index = 0; index < count; index += 1) {
+        this.valueType(out);
+      }
+    } else if (opcode === 0x23 || opcode === 0x24) {
+      out.push(opcode, ...encodeUnsignedLeb(this.globalBase + reader.readLeb()));
+    } else if (opcode === 0xd0) {
+      out.push(opcode);
+      this.heapType(out);
+    } else if (opcode === 0xfb) {
+      const sub = reader.readLeb();
+      this.copy(start, out);
+      if (sub <= 14 || (sub >= 16 && sub <= 19)) {
+        this.typeIndex(out);
+        const rest = reader.offset;
+        if (sub === 17) {
+          this.typeIndex(out);
+        } else if (sub === 9 || sub === 18) {
+          out.push(...encodeUnsignedLeb(this.dataBase + reader.readLeb()));
+        } else if ((sub >= 2 && sub <= 5) || sub === 8 || sub === 10 || sub === 19) {
+          reader.skipLeb();
+          this.copy(rest, out);
+        }
+      } else if (sub >= 20 && sub <= 23) {
+        this.heapType(out);
+      } else if (sub === 24 || sub === 25) {
+        const flags = reader.offset;
+        reader.readByte();
+        reader.skipLeb();
+        this.copy(flags, out);
+        this.heapType(out);
+        this.heapType(out);
+      }
+    } else if (opcode === 0xfc && (reader.bytes[reader.offset] === 8 || reader.bytes[reader.offset] === 9)) {
+      const sub = reader.readLeb();
+      out.push(opcode, sub, ...encodeUnsignedLeb(this.dataBase + reader.readLeb()));
+      if (sub === 8) {
+        const memory = reader.offset;
+        reader.skipLeb();
+        this.copy(memory, out);
+      }
+    } else {
+      reader.skipImmediates(opcode);
+      this.copy(start, out);
+    }
+    return opcode;
+  }
+
+  constantExpression(out: number[]): void {
+    while (this.instruction(out) !== 0x0b) {}
+  }
+
+  body(out: number[]): void {
+    const reader = this.reader;
+    const size = reader.readLeb();
+    const end = reader.offset + size;
+    const body: number[] = [];
+    const groups = reader.readLeb();
+    body.push(...encodeUnsignedLeb(groups));
+    for (let group = 0; group < groups; group += 1) {
+      const count = reader.offset;
+      reader.skipLeb();
+      this.copy(count, body);
+      this.valueType(body);
+    }
+    while (reader.offset < end) {
+      this.instruction(body);
+    }
+    out.push(...encodeUnsignedLeb(body.length));
+    for (const byte of body) {
+      out.push(byte);
+    }
+  }
+}
+
+export interface LinkOptions {
+  /** Names for the modules in errors, by index.  The default is `module <index>`. */
+  readonly labels?: ReadonlyArray<string>;
+  /**
+   * For each module, the exported functions it only defines as placeholders.
+   * Each is dropped, and calls to it go to the function another module
+   * exports under the same name, as if it were an import.
+   */
+  readonly placeholders?: ReadonlyArray<ReadonlyArray<string>>;
+}
+
+/**
+ * Combines separately compiled modules into one.  Identical types are merged,
+ * defined functions keep their order module by module, and each function
+ * import is resolved to the function another module exports under the same
+ * name.  Imports nothing exports stay imports of the linked module.  The
+ * modules share one memory, so they must declare the same limits, and data
+ * segments keep their addresses.  Custom sections are dropped.
+ */
+export function link(modules: ReadonlyArray<Uint8Array>, options: LinkOptions = {}): Uint8Array {
+  const inputs = modules.map((wasm, index) => readInput(wasm, options.labels?.[index] ?? `module ${index}`));
+  inputs.forEach((input, moduleIndex) => {
+    for (const name of options.placeholders?.[moduleIndex] ?? []) {
+      const entry = input.exports.find(
+        (candidate) =>
+          candidate.name === name && candidate.kind === EXPORT_FUNC && candidate.index >= input.imports.length,
+      );
+      if (!entry) {
+        throw new CompileError(`${input.label} defines no function '${name}' to replace`);
+      }
+      input.placeholders.set(entry.index, name);
+      input.exports = input.exports.filter((candidate) => candidate !== entry);
+    }
+  });
+  const renumberers = inputs.map((input) => new Renumberer(input.wasm));
+
+  // Types, in input order, each referring only to types before it.
+  const types: number[][] = [];
+  const typeIndexes = new Map<string, number>();
+  inputs.forEach((input, moduleIndex) => {
+    const renumberer = renumberers[moduleIndex]!;
+    for (const range of input.types) {
+      renumberer.reader.offset = range.start;
+      const entry: number[] = [];
+      renumberer.typeEntry(entry);
+      const key = entry.join(",");
+      let index = typeIndexes.get(key);
+      if (index === undefined) {
+        index = types.length;
+        types.push(entry);
+        typeIndexes.set(key, index);
+      }
+      renumberer.types.push(index);
+    }
+  });
+
+  const exported = new Map<string, { readonly input: number; readonly entry: LinkExport }>();
+  inputs.forEach((input, moduleIndex) => {
+    for (const entry of input.exports) {
+      const previous = exported.get(entry.name);
+      if (previous === undefined) {
+        exported.set(entry.name, { input: moduleIndex, entry });
+      } else if (previous.entry.kind !== EXPORT_MEMORY || entry.kind !== EXPORT_MEMORY) {
+        throw new CompileError(
+          `export '${entry.name}' is defined by both ${inputs[previous.input]!.label} and ${input.label}`,
+        );
+      }
+    }
+  });
+
+  // Imports no other module exports stay imports, one per name and type.
+  const imports: LinkImport[] = [];
+  const importIndexes = new Map<string, number>();
+  const resolved = inputs.map((input, moduleIndex) =>
+    input.imports.map((entry) => {
+      const target = exported.get(entry.name);
+      if (target && target.input !== moduleIndex && target.entry.kind === EXPORT_FUNC) {
+        return target;
+      }
+      const type = renumberers[moduleIndex]!.types[entry.type]!;
+      const key = `${entry.module}\u0000${entry.name}`;
+      const index = importIndexes.get(key);
+      if (index === undefined) {
+        importIndexes.set(key, imports.length);
+        imports.push({ module: entry.module, name: entry.name, type });
+      } else if (imports[index]!.type !== type) {
+        throw new CompileError(`import '${entry.module}.${entry.name}' is declared with different types`);
+      }
+      return null;
+    }),
+  );
+
+  let nextFunction = imports.length;
+  const definedIndexes = inputs.map((input) =>
+    input.functions.map((_, index) =>
+      input.placeholders.has(input.imports.length + index) ? -1 : nextFunction++,
+    ),
+  );
+  const functionType = (moduleIndex: number, index: number): number => {
+    const input = inputs[moduleIndex]!;
+    const local = index < input.imports.length ? input.imports[index]!.type : input.functions[index - input.imports.length]!;
+    return renumberers[moduleIndex]!.types[local]!;
+  };
+  const functionIndex = (moduleIndex: number, index: number, depth = 0): number => {
+    const input = inputs[moduleIndex]!;
+    let name: string;
+    let target: { readonly input: number; readonly entry: LinkExport } | null | undefined;
+    if (index >= input.imports.length) {
+      const placeholder = input.placeholders.get(index);
+      if (placeholder === undefined) {
+        return definedIndexes[moduleIndex]![index - input.imports.length]!;
+      }
+      name = placeholder;
+      target = exported.get(name);
+      if (!target || target.entry.kind !== EXPORT_FUNC) {
+        throw new CompileError(`no module exports '${name}' to replace the placeholder in ${input.label}`);
+      }
+    } else {
+      const entry = input.imports[index]!;
+      name = entry.name;
+      target = resolved[moduleIndex]![index];
+      if (!target) {
+        return importIndexes.get(`${entry.module}\u0000${entry.name}`)!;
+      }
+    }
+    if (depth > inputs.length) {
+      throw new CompileError(`'${name}' in ${input.label} resolves to itself`);
+    }
+    if (functionType(target.input, target.entry.index) !== functionType(moduleIndex, index)) {
+      throw new CompileError(
+        `${input.label} uses '${name}' with a different type than ${inputs[target.input]!.label} exports it`,
+      );
+    }
+    return functionIndex(target.input, target.entry.index, depth + 1);
+  };
+
+  let memory: Uint8Array | null = null;
+  let globalCount = 0;
+  let dataCount = 0;
+  inputs.forEach((input, moduleIndex) => {
+    const renumberer = renumberers[moduleIndex]!;
+    const count = input.imports.length + input.functions.length;
+    for (let index = 0; index < count; index += 1) {
+      renumberer.functions.push(functionIndex(moduleIndex, index));
+    }
+    renumberer.globalBase = globalCount;
+    renumberer.dataBase = dataCount;
+    globalCount += input.globalCount;
+    dataCount += input.dataCount;
+    if (input.memory) {
+      const limits = input.wasm.subarray(input.memory.start, input.memory.end);
+      if (memory === null) {
+        memory = limits;
+      } else if (limits.join(",") !== memory.join(",")) {
+        throw new CompileError(`${input.label} declares different memory limits than the modules before it`);
+      }
+    }
+  });
+
+  const out: number[] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+  const pushSection = (id: number, count: number, payload: ArrayLike<number>): void => {
+    if (count === 0) {
+      return;
+    }
+    const countBytes = encodeUnsignedLeb(count);
+    out.push(id, ...encodeUnsignedLeb(countBytes.length + payload.length), ...countBytes);
+    for (let index = 0; index < payload.length; index += 1) {
+      out.push(payload[index]!);
+    }
+  };
+  const encoder = new TextEncoder();
+  const name = (text: string): number[] => {
+    const bytes = encoder.encode(text);
+    return [...encodeUnsignedLeb(bytes.length), ...bytes];
+  };
+
+  pushSection(1, types.length, types.flat());
+  pushSection(
+    2,
+    imports.length,
+    imports.flatMap((entry) => [...name(entry.module), ...name(entry.name), EXPORT_FUNC, ...encodeUnsignedLeb(entry.type)]),
+  );
+  pushSection(
+    3,
+    nextFunction - imports.length,
+    inputs.flatMap((input, moduleIndex) =>
+      input.functions.flatMap((type, index) =>
+        definedIndexes[moduleIndex]![index]! < 0 ? [] : encodeUnsignedLeb(renumberers[moduleIndex]!.types[type]!),
+      ),
+    ),
+  );
+  if (memory !== null) {
+    pushSection(5, 1, memory);
+  }
+
+  const globals: number[] = [];
+  const code: number[] = [];
+  const data: number[] = [];
+  inputs.forEach((input, moduleIndex) => {
+    const renumberer = renumberers[moduleIndex]!;
+    const reader = renumberer.reader;
+    if (input.globals) {
+      reader.offset = input.globals.start;
+      for (let index = 0; index < input.globalCount; index += 1) {
+        renumberer.valueType(globals);
+        globals.push(reader.readByte());
+        renumberer.constantExpression(globals);
+      }
+    }
+    if (input.code) {
+      reader.offset = input.code.start;
+      for (let index = 0; index < input.functions.length; index += 1) {
+        if (definedIndexes[moduleIndex]![index]! < 0) {
+          const size = reader.readLeb();
+          reader.offset += size;
+        } else {
+          renumberer.body(code);
+        }
+      }
+    }
+    if (input.data) {
+      reader.offset = input.data.start;
+      for (let index = 0; index < input.dataCount; index += 1) {
+        const flags = reader.readLeb();
+        data.push(...encodeUnsignedLeb(flags));
+        if (flags === 2) {
+          const memoryIndex = reader.offset;
+          reader.skipLeb();
+          renumberer.copy(memoryIndex, data);
+        }
+        if (flags !== 1) {
+          renumberer.constantExpression(data);
+        }
+        const bytes = reader.offset;
+        const size = reader.readLeb();
+        reader.offset += size;
+        renumberer.copy(bytes, data);
+      }
+    }
+  });
+  pushSection(6, globalCount, globals);
+
+  const exports: number[] = [];
+  for (const { input: moduleIndex, entry } of exported.values()) {
+    let index = 0;
+    if (entry.kind === EXPORT_FUNC) {
+      index = functionIndex(moduleIndex, entry.index);
+    } else if (entry.kind === EXPORT_GLOBAL) {
+      index = renumberers[moduleIndex]!.globalBase + entry.index;
+    }
+    exports.push(...name(entry.name), entry.kind, ...encodeUnsignedLeb(index));
+  }
+  pushSection(7, exported.size, exports);
+  if (inputs.some((input) => input.hasDataCount)) {
+    out.push(12, ...encodeUnsignedLeb(encodeUnsignedLeb(dataCount).length), ...encodeUnsignedLeb(dataCount));
+  }
+  pushSection(10, nextFunction - imports.length, code);
+  pushSection(11, dataCount, data);
+  return new Uint8Array(out);
+}
@@ -0,0 +1,398 @@
+// The catalog of user-facing diagnostic messages.  Each message has a stable
+// code and an English template, which is the canonical text: the stage2
+// compiler writes exactly these strings into its failure detail, and the host
+// writes them with `formatMessage`.  `{name}` in a template stands for a
+// parameter.  Other locales translate the templates by code, and a message a
+// locale leaves out falls back to English.
+//
+// Codes are grouped by the stage that usually reports them: E00xx for module
+// loading, E01xx for parsing, E02xx for constant evaluation, E03xx for
+// semantic validation, E04xx for type metadata, E05xx for emission, E09xx for
+// checks the host makes, and W00xx for warnings.
+
+export const MESSAGES = {
+  E0001: "module compilation failed",
+  E0002: "module content missing",
+  E0003: "module has not been loaded",
+  E0004: "module import not found",
+  E0005: "module path exceeds maximum length",
+  E0006: "module path missing",
+  E0007: "module storage allocation failed",
+  E0008: "module table capacity reached",
+  E0009: "cached module entry missing content",
+  E0010: "failed to reserve linear memory for module compilation",
+  E0011: "failed to reserve linear memory for module storage",
+  E0012: "function limit exceeded",
+  E0013: "attribute limit exceeded",
+
+  E0100: "parsing source failed",
+  E0101: "file contains no items",
+  E0102: "unterminated block comment",
+  E0103: "unexpected block comment terminator",
+  E0104: "character literal must have one character",
+  E0105: "expression too deeply nested",
+  E0106: "block must end with expression",
+  E0107: "expected `while` after `do` loop body",
+  E0108: "loop labels must precede `loop`, `while`, or `do`",
+  E0109: "too many nested loop labels",
+  E0110: "unknown loop label",
+  E0111: "break statements must be inside loop",
+  E0112: "continue statements must be inside loops",
+  E0113: "duplicate parameter name",
+  E0114: "duplicate local declaration",
+  E0115: "duplicate function declaration",
+  E0116: "duplicate constant declaration",
+  E0117: "function name conflicts with constant",
+  E0118: "if expression condition parse failed",
+  E0119: "array literal length expression parse failed",
+  E0120: "panic message must be a string literal",
+  E0121: "inline_wasm argument must be an array literal of u8 values",
+  E0122: "identifier not found",
+  E0123: "while loops cannot break with values",
+  E0124: "tuple pattern requires tuple value",
+  E0125: "tuple pattern does not match tuple element count",
+  E0126: "unknown method",
+  E0127: "malformed cfg predicate",
+
+  E0200: "const call evaluation failed",
+  E0201: "const initializer must be compile-time evaluable",
+  E0202: "const parameter arguments must be compile-time constants",
+  E0203: "const parameter template expected type mismatch",
+  E0204: "const parameter template resolution failed",
+  E0205: "const specialization cloning failed",
+  E0206: "const specialization function limit exceeded",
+  E0207: "const argument metadata capacity exceeded",
+  E0208: "const functions may only call const functions",
+  E0209: "function values are only permitted in const contexts",
+  E0210: "anonymous functions returned from const fn must accept only const parameters",
+  E0211: "struct intrinsic expects three arguments",
+  E0212: "struct intrinsic must be evaluated in const context",
+  E0213: "struct intrinsic properties must be an array of field tuples",
+  E0214: "type annotations require const type values",
+  E0215: "array literal length requires const parameters",
+  E0216: "array literal length must be non-negative",
+  E0217: "array literal length metadata allocation failed",
+  E0218: "array length must be non-negative",
+  E0219: "generic parameter cannot be inferred from call arguments",
+  E0220: "generic parameters only accept primitive types",
+
+  E0300: "call argument count mismatch",
+  E0301: "call argument type mismatch",
+  E0302: "call references undefined function",
+  E0303: "cannot assign to immutable local",
+  E0304: "local assignment type mismatch",
+  E0305: "local initializer type mismatch",
+  E0306: "boolean local initializer must be boolean",
+  E0307: "unit function results cannot initialize locals",
+  E0308: "return expression type does not match function return type",
+  E0309: "if condition type mismatch",
+  E0310: "if condition is an integer, not `bool`; compare it with `!= 0`",
+  E0311: "if branches type mismatch",
+  E0312: "if expressions used as values require an else branch",
+  E0313: "loop break type mismatch",
+  E0314: "loop break value type mismatch",
+  E0315: "loop expressions used as values must break with a value",
+  E0316: "logical not operand must be boolean",
+  E0317: "logical operators require boolean operands",
+  E0318: "binary operator operands must be integers",
+  E0319: "binary operator operands must have matching type",
+  E0320: "array element assignment type mismatch",
+  E0321: "array expression type does not match expected array type",
+  E0322: "array index must be non-negative",
+  E0323: "array index out of bounds",
+  E0324: "array index requires array operand",
+  E0325: "array index requires integer indices",
+  E0326: "array literal elements must have uniform type",
+  E0327: "array type table capacity exceeded",
+  E0328: "tuple field access requires tuple operand",
+  E0329: "tuple field assignment type mismatch",
+  E0330: "tuple field index out of bounds",
+  E0331: "struct field identifier not found",
+  E0332: "struct literal base must be a struct type",
+  E0333: "struct literal field name does not match canonical field {field}",
+  E0334: "struct literal field value type does not match canonical field {field}",
+  E0335: "struct literal missing field {field}",
+  E0336: "struct literal specifies too many fields",
+  E0337: "duplicate struct field {field}",
+  E0338: "address-of is only allowed in runtime functions",
+  E0339: "address-of operand must be a local variable",
+  E0340: "address-of requires a local of 32 bits or narrower",
+  E0341: "stackalloc is only allowed in runtime functions",
+  E0342: "stackalloc size must be i32",
+  E0343: "unused result of value-returning call; wrap it in `discard(...)`",
+  E0344: "method receiver must be an integer",
+  E0345: "`abs` needs a signed integer receiver",
+  E0346: "integer methods are not available in const functions",
+  E0347: "bit intrinsics need a 32- or 64-bit integer operand",
+  E0348: "integer intrinsics are not available in const functions",
+
+  E0400: "type metadata resolution failed",
+  E0401: "const initializer type metadata resolution failed for '{name}'",
+
+  E0500: "failed to emit WebAssembly type section",
+  E0501: "failed to emit WebAssembly function section",
+  E0502: "failed to emit WebAssembly memory section",
+  E0503: "failed to emit WebAssembly export section",
+  E0504: "failed to emit WebAssembly code section",
+  E0505: "failed to emit compile-time constant table",
+
+  E0900: "source must not be empty",
+  E0901: "memoryPages must be an integer from 1 to {max}",
+  E0902: "maxNestingDepth must be an integer from 1 to {max}",
+  E0903: "entry module '{path}' not found",
+  E0904: "target '{target}' is not supported yet",
+
+  W0001: "unused variable `{name}`",
+  W0002: "unused parameter `{name}`",
+  W0003: "unreachable code",
+  W0004: "unknown attribute `{name}`",
+  W0005: "condition is always `true`, so the `else` branch never runs",
+  W0006: "condition is always `false`, so this branch never runs",
+  W0007: "integer condition is treated as `!= 0`; compare it explicitly",
+} as const;
+
+export type MessageCode = keyof typeof MESSAGES;
+
+export type MessageParams = Readonly<Record<string, string | number>>;
+
+export type MessageCatalog = Readonly<Partial<Record<MessageCode, string>>>;
+
+const SPANISH: MessageCatalog = {
+  E0001: "la compilación del módulo falló",
+  E0002: "falta el contenido del módulo",
+  E0003: "el módulo no se ha cargado",
+  E0004: "no se encontró el módulo importado",
+  E0005: "la ruta del módulo supera la longitud máxima",
+  E0006: "falta la ruta del módulo",
+  E0007: "no se pudo reservar almacenamiento para el módulo",
+  E0008: "se alcanzó la capacidad de la tabla de módulos",
+  E0009: "a la entrada del módulo en caché le falta el contenido",
+  E0010: "no se pudo reservar memoria lineal para compilar el módulo",
+  E0011: "no se pudo reservar memoria lineal para almacenar el módulo",
+  E0012: "se superó el límite de funciones",
+  E0013: "se superó el límite de atributos",
+
+  E0100: "no se pudo analizar el código fuente",
+  E0101: "el archivo no contiene elementos",
+  E0102: "comentario de bloque sin cerrar",
+  E0103: "cierre de comentario de bloque inesperado",
+  E0104: "un literal de carácter debe tener un solo carácter",
+  E0105: "expresión anidada demasiado profundamente",
+  E0106: "el bloque debe terminar con una expresión",
+  E0107: "se esperaba `while` después del cuerpo de un bucle `do`",
+  E0108: "las etiquetas de bucle deben preceder a `loop`, `while` o `do`",
+  E0109: "demasiadas etiquetas de bucle anidadas",
+  E0110: "etiqueta de bucle desconocida",
+  E0111: "las sentencias break deben estar dentro de un bucle",
+  E0112: "las sentencias continue deben estar dentro de un bucle",
+  E0113: "nombre de parámetro duplicado",
+  E0114: "declaración de variable local duplicada",
+  E0115: "declaración de función duplicada",
+  E0116: "declaración de constante duplicada",
+  E0117: "el nombre de la función coincide con el de una constante",
+  E0118: "no se pudo analizar la condición de la expresión if",
+  E0119: "no se pudo analizar la expresión de longitud del literal de arreglo",
+  E0120: "el mensaje de panic debe ser un literal de cadena",
+  E0121: "el argumento de inline_wasm debe ser un literal de arreglo de valores u8",
+  E0122: "no se encontró el identificador",
+  E0123: "los bucles while no pueden salir con un valor",
+  E0124: "el patrón de tupla requiere un valor de tupla",
+  E0125: "el patrón de tupla no coincide con el número de elementos de la tupla",
+  E0126: "método desconocido",
+  E0127: "predicado cfg mal formado",
+
+  E0200: "falló la evaluación de la llamada constante",
+  E0201: "el inicializador de una constante debe poder evaluarse en tiempo de compilación",
+  E0202: "los argumentos de parámetros const deben ser constantes de tiempo de compilación",
+  E0203: "el tipo esperado por la plantilla del parámetro const no coincide",
+  E0204: "no se pudo resolver la plantilla del parámetro const",
+  E0205: "no se pudo clonar la especialización const",
+  E0206: "se superó el límite de funciones especializadas por const",
+  E0207: "se superó la capacidad de metadatos de argumentos const",
+  E0208: "las funciones const solo pueden llamar a funciones const",
+  E0209: "los valores de función solo se permiten en contextos const",
+  E0210: "las funciones anónimas devueltas por una const fn solo pueden aceptar parámetros const",
+  E0211: "el intrínseco struct espera tres argumentos",
+  E0212: "el intrínseco struct debe evaluarse en un contexto const",
+  E0213: "las propiedades del intrínseco struct deben ser un arreglo de tuplas de campos",
+  E0214: "las anotaciones de tipo requieren valores de tipo const",
+  E0215: "la longitud del literal de arreglo requiere parámetros const",
+  E0216: "la longitud del literal de arreglo no puede ser negativa",
+  E0217: "no se pudieron reservar los metadatos de longitud del literal de arreglo",
+  E0218: "la longitud del arreglo no puede ser negativa",
+  E0219: "no se puede inferir el parámetro genérico a partir de los argumentos de la llamada",
+  E0220: "los parámetros genéricos solo aceptan tipos primitivos",
+
+  E0300: "el número de argumentos de la llamada no coincide",
+  E0301: "el tipo de un argumento de la llamada no coincide",
+  E0302: "la llamada hace referencia a una función no definida",
+  E0303: "no se puede asignar a una variable local inmutable",
+  E0304: "el tipo de la asignación a la variable local no coincide",
+  E0305: "el tipo del inicializador de la variable local no coincide",
+  E0306: "el inicializador de una variable local booleana debe ser booleano",
+  E0307: "el resultado de una función sin valor no puede inicializar variables locales",
+  E0308: "el tipo de la expresión return no coincide con el tipo de retorno de la función",
+  E0309: "el tipo de la condición del if no coincide",
+  E0310: "la condición del if es un entero, no `bool`; compárela con `!= 0`",
+  E0311: "los tipos de las ramas del if no coinciden",
+  E0312: "las expresiones if usadas como valores requieren una rama else",
+  E0313: "el tipo de break del bucle no coincide",
+  E0314: "el tipo del valor de break del bucle no coincide",
+  E0315: "los bucles usados como valores deben salir con un valor",
+  E0316: "el operando del not lógico debe ser booleano",
+  E0317: "los operadores lógicos requieren operandos booleanos",
+  E0318: "los operandos del operador binario deben ser enteros",
+  E0319: "los operandos del operador binario deben tener el mismo tipo",
+  E0320: "el tipo de la asignación al elemento del arreglo no coincide",
+  E0321: "el tipo de la expresión de arreglo no coincide con el tipo de arreglo esperado",
+  E0322: "el índice del arreglo no puede ser negativo",
+  E0323: "índice de arreglo fuera de rango",
+  E0324: "la indexación requiere un operando de tipo arreglo",
+  E0325: "la indexación requiere índices enteros",
+  E0326: "los elementos del literal de arreglo deben tener el mismo tipo",
+  E0327: "se superó la capacidad de la tabla de tipos de arreglo",
+  E0328: "el acceso a un campo de tupla requiere un operando de tipo tupla",
+  E0329: "el tipo de la asignación al campo de la tupla no coincide",
+  E0330: "índice de campo de tupla fuera de rango",
+  E0331: "no se encontró el identificador del campo del struct",
+  E0332: "la base del literal de struct debe ser un tipo struct",
+  E0333: "el nombre del campo del literal de struct no coincide con el campo canónico {field}",
+  E0334: "el tipo del valor del campo del literal de struct no coincide con el campo canónico {field}",
+  E0335: "falta el campo {field} en el literal de struct",
+  E0336: "el literal de struct especifica demasiados campos",
+  E0337: "campo de struct duplicado {field}",
+  E0338: "la toma de dirección solo se permite en funciones de tiempo de ejecución",
+  E0339: "el operando de la toma de dirección debe ser una variable local",
+  E0340: "la toma de dirección requiere una variable local de 32 bits o menos",
+  E0341: "stackalloc solo se permite en funciones de tiempo de ejecución",
+  E0342: "el tamaño de stackalloc debe ser i32",
+  E0343: "resultado sin usar de una llamada que devuelve un valor; envuélvala en `discard(...)`",
+  E0344: "el receptor del método debe ser un entero",
+  E0345: "`abs` necesita un receptor entero con signo",
+  E0346: "los métodos de enteros no están disponibles en funciones const",
+  E0347: "los intrínsecos de bits necesitan un operando entero de 32 o 64 bits",
+  E0348: "los intrínsecos de enteros no están disponibles en funciones const",
+
+  E0400: "no se pudieron resolver los metadatos de tipo",
+  E0401: "no se pudieron resolver los metadatos de tipo del inicializador de la constante '{name}'",
+
+  E0500: "no se pudo emitir la sección de tipos de WebAssembly",
+  E0501: "no se pudo emitir la sección de funciones de WebAssembly",
+  E0502: "no se pudo emitir la sección de memoria de WebAssembly",
+  E0503: "no se pudo emitir la sección de exportaciones de WebAssembly",
+  E0504: "no se pudo emitir la sección de código de WebAssembly",
+  E0505: "no se pudo emitir la tabla de constantes de tiempo de compilación",
+
+  E0900: "el código fuente no puede estar vacío",
+  E0901: "memoryPages debe ser un entero de 1 a {max}",
+  E0902: "maxNestingDepth debe ser un entero de 1 a {max}",
+  E0903: "no se encontró el módulo de entrada '{path}'",
+  E0904: "el destino '{target}' aún no está soportado",
+
+  W0001: "variable sin usar `{name}`",
+  W0002: "parámetro sin usar `{name}`",
+  W0003: "código inalcanzable",
+  W0004: "atributo desconocido `{name}`",
+  W0005: "la condición siempre es `true`, así que la rama `else` nunca se ejecuta",
+  W0006: "la condición siempre es `false`, así que esta rama nunca se ejecuta",
+  W0007: "la condición entera se trata como `!= 0`; compárela explícitamente",
+};
+
+const LOCALES = new Map<string, MessageCatalog>([
+  ["en", {}],
+  ["es", SPANISH],
+]);
+
+export const DEFAULT_LOCALE = "en";
+
+/** The locales `formatMessage` accepts, in the order they were added. */
+export function availableLocales(): string[] {
+  return [...LOCALES.keys()];
+}
+
+/**
+ * Adds a locale or replaces one.  Codes `messages` leaves out are shown in
+ * English.
+ */
+export function registerLocale(locale: string, messages: MessageCatalog): void {
+  LOCALES.set(locale, messages);
+}
+
+/** Whether `formatMessage` knows `locale`. */
+export function hasLocale(locale: string): boolean {
+  return LOCALES.has(locale);
+}
+
+function interpolate(template: string, params: MessageParams): string {
+  return template.replace(/\{([A-Za-z]+)\}/g, (placeholder, name: string) =>
+    name in params ? String(params[name]) : placeholder,
+  );
+}
+
+/**
+ * Renders the message for `code` in `locale`, falling back to English when
+ * the locale is unknown or has no translation for it.
+ */
+export function formatMessage(
+  code: MessageCode,
+  params: MessageParams = {},
+  locale: string = DEFAULT_LOCALE,
+): string {
+  return interpolate(LOCALES.get(locale)?.[code] ?? MESSAGES[code], params);
+}
+
+export interface MatchedMessage {
+  readonly code: MessageCode;
+  readonly params: MessageParams;
+}
+
+// Turns each English template into an anchored pattern whose groups capture
+// its parameters.  A parameter may be empty, since the compiler leaves a
+// field name out when it has none to report.
+const MESSAGE_PATTERNS: ReadonlyArray<{ code: MessageCode; pattern: RegExp; names: string[] }> = (
+  Object.keys(MESSAGES) as MessageCode[]
+).map((code) => {
+  const names: string[] = [];
+  const source = MESSAGES[code]
+    .split(/(\{[A-Za-z]+\})/)
+    .map((part) => {
+      const placeholder = /^\{([A-Za-z]+)\}$/.exec(part);
+      if (placeholder) {
+        names.push(placeholder[1]!);
+        return "(.*)";
+      }
+      return part.replace(/[.*+?^${}()|[\]\\]/g, "\\$&");
+    })
+    .join("");
+  return { code, pattern: new RegExp(`^${source}$`), names };
+});
+
+/**
+ * Finds the catalog entry an English message was rendered from, with the
+ * parameters it was given.  Returns `null` for text outside the catalog.
+ */
+export function matchMessage(message: string): MatchedMessage | null {
+  for (const { code, pattern, names } of MESSAGE_PATTERNS) {
+    const match = pattern.exec(message);
+    if (match) {
+      const params: Record<string, string> = {};
+      names.forEach((name, index) => {
+        params[name] = match[index + 1]!;
+      });
+      return { code, params };
+    }
+  }
+  return null;
+}
+
+/**
+ * Renders an English catalog message in `locale`.  Text outside the catalog
+ * is returned unchanged.
+ */
+export function localizeMessage(message: string, locale: string = DEFAULT_LOCALE): string {
+  if (locale === DEFAULT_LOCALE) {
+    return message;
+  }
+  const matched = matchMessage(message);
+  return matched ? formatMessage(matched.code, matched.params, locale) : message;
+}
@@ -0,0 +1,433 @@
+// Optimization passes over the checked tree.  `compile` with `optimize` runs
+// `OPTIMIZATION_PASSES` ahead of any `extraPasses`, so stage2 emits the
+// rewritten bodies; each pass is also an ordinary `HirPass` a caller can run
+// on its own.
+//
+// The rewrites are built per call rather than as subclasses of `Transformer`:
+// `./index` loads this module, and a class extending `Transformer` at the top
+// level would fail whenever `./visit` is the module that started the load.
+
+import { BUILTIN_TYPES, childExpressions } from "./frontend";
+import type {
+  BinaryOperator,
+  Expression,
+  FunctionItem,
+  LetExpression,
+  LiteralExpression,
+  Program,
+} from "./frontend";
+import type { HirPass } from "./passes";
+import { Transformer } from "./visit";
+
+// Operators that cannot trap or have side effects, so evaluating them once
+// earlier gives the same value as evaluating them each time.
+const PURE_OPERATORS: ReadonlySet<BinaryOperator> = new Set<BinaryOperator>([
+  "+",
+  "-",
+  "*",
+  "==",
+  "!=",
+  "<",
+  ">",
+  "<=",
+  ">=",
+  "|",
+  "&",
+  "<<",
+  ">>",
+]);
+
+/**
+ * Returns `expression` with every node `replace` maps to something else
+ * swapped out.  `replace` sees each node before its children are rewritten.
+ */
+function rewrite(expression: Expression, replace: (node: Expression) => Expression | null): Expression {
+  const transformer = new Transformer();
+  const walk = transformer.transformExpression.bind(transformer);
+  transformer.transformExpression = (node) => replace(node) ?? walk(node);
+  return transformer.transformExpression(expression);
+}
+
+// Folding handles the 32-bit types and `bool`, whose emitted arithmetic wraps
+// the way `| 0` does.  8- and 16-bit results keep their full `i32` value at
+// run time, and 64-bit literals need a cast to emit, so those are left alone.
+function foldable(type: number): boolean {
+  const name = BUILTIN_TYPES[type];
+  return name === "i32" || name === "u32" || name === "bool";
+}
+
+function isUnsigned(type: number): boolean {
+  return BUILTIN_TYPES[type] === "u32";
+}
+
+// The value of `left operator right` on operands of type `type`, or null when
+// it traps at run time and so must stay in the code.
+function foldBinary(operator: BinaryOperator, type: number, left: number, right: number): number | null {
+  const unsigned = isUnsigned(type);
+  const compare = (a: number, b: number): number => (unsigned ? (a >>> 0) - (b >>> 0) : a - b);
+  switch (operator) {
+    case "+":
+      return (left + right) | 0;
+    case "-":
+      return (left - right) | 0;
+    case "*":
+      return Math.imul(left, right);
+    case "/":
+    case "%": {
+      if (right === 0 || (!unsigned && left === -0x80000000 && right === -1 && operator === "/")) {
+        return null;
+      }
+      const [a, b] = unsigned ? [left >>> 0, right >>> 0] : [left, right];
+      return (operator === "/" ? Math.trunc(a / b) : a % b) | 0;
+    }
+    case "|":
+      return left | right;
+    case "&":
+      return left & right;
+    case "<<":
+      return left << right;
+    case ">>":
+      return unsigned ? (left >>> right) | 0 : left >> right;
+    case "==":
+      return left === right ? 1 : 0;
+    case "!=":
+      return left !== right ? 1 : 0;
+    case "<":
+      return compare(left, right) < 0 ? 1 : 0;
+    case ">":
+      return compare(left, right) > 0 ? 1 : 0;
+    case "<=":
+      return compare(left, right) <= 0 ? 1 : 0;
+    case ">=":
+      return compare(left, right) >= 0 ? 1 : 0;
+    default:
+      return null;
+  }
+}
+
+// `expression` as a literal of its own type, when its operands fold.
+function foldExpression(expression: Expression): LiteralExpression | null {
+  const literal = (value: number): LiteralExpression => ({
+    index: expression.index,
+    type: expression.type,
+    offset: expression.offset,
+    kind: "literal",
+    value,
+  });
+  switch (expression.kind) {
+    case "binary": {
+      const { left, right } = expression;
+      if (left.kind !== "literal" || right.kind !== "literal" || !foldable(left.type) || !foldable(expression.type)) {
+        return null;
+      }
+      const value = foldBinary(expression.operator, left.type, left.value | 0, right.value | 0);
+      return value === null ? null : literal(value);
+    }
+    case "not":
+      return expression.value.kind === "literal" && foldable(expression.value.type)
+        ? literal(expression.value.value === 0 ? 1 : 0)
+        : null;
+    case "cast":
+      return expression.value.kind === "literal" &&
+        foldable(expression.value.type) &&
+        foldable(expression.type) &&
+        BUILTIN_TYPES[expression.type] !== "bool"
+        ? literal(expression.value.value | 0)
+        : null;
+    default:
+      return null;
+  }
+}
+
+// Parameters and locals that are assigned or have their address taken
+// anywhere in `body`.  Reads of anything else have one value per binding.
+function mutatedVariables(body: Expression): Set<number> {
+  const mutated = new Set<number>();
+  const visit = (expression: Expression): void => {
+    if (expression.kind === "assign" || expression.kind === "address-of") {
+      mutated.add(expression.local);
+    }
+    childExpressions(expression).forEach(visit);
+  };
+  visit(body);
+  return mutated;
+}
+
+interface Occurrences {
+  readonly nodes: Expression[];
+  readonly size: number;
+  readonly locals: ReadonlySet<number>;
+}
+
+// Finds the pure expressions in `body`, keyed by their structure.  Nodes under
+// an anonymous function are left out, since its body runs elsewhere.
+function pureExpressions(body: Expression, mutated: ReadonlySet<number>): Map<string, Occurrences> {
+  const found = new Map<string, Occurrences>();
+  const visit = (expression: Expression): { key: string; size: number; locals: Set<number>; reads: boolean } | null => {
+    if (expression.kind === "anonymous-function") {
+      return null;
+    }
+    const children = childExpressions(expression).map(visit);
+    const pure = children.every((child) => child !== null);
+    let key: string | null = null;
+    switch (expression.kind) {
+      case "literal":
+        return { key: `${expression.value}:${expression.type}`, size: 1, locals: new Set(), reads: false };
+      case "param":
+        return mutated.has(expression.param)
+          ? null
+          : { key: `p${expression.param}`, size: 1, locals: new Set(), reads: true };
+      case "local":
+        return mutated.has(expression.local)
+          ? null
+          : { key: `l${expression.local}`, size: 1, locals: new Set([expression.local]), reads: true };
+      case "binary":
+        key = PURE_OPERATORS.has(expression.operator) ? expression.operator : null;
+        break;
+      case "not":
+        key = "!";
+        break;
+      case "cast":
+        key = `as${expression.targetType}`;
+        break;
+    }
+    if (key === null || !pure) {
+      return null;
+    }
+    const parts = children as ReadonlyArray<{ key: string; size: number; locals: Set<number>; reads: boolean }>;
+    const summary = {
+      key: `(${key}:${expression.type} ${parts.map((part) => part.key).join(" ")})`,
+      size: 1 + parts.reduce((total, part) => total + part.size, 0),
+      locals: new Set(parts.flatMap((part) => [...part.locals])),
+      reads: parts.some((part) => part.reads),
+    };
+    // Expressions of constants alone are left for constant folding.
+    if (summary.reads) {
+      const entry = found.get(summary.key) ?? { nodes: [], size: summary.size, locals: summary.locals };
+      entry.nodes.push(expression);
+      found.set(summary.key, entry);
+    }
+    return summary;
+  };
+  visit(body);
+  return found;
+}
+
+// The `let`s binding each local, with how deeply each is nested.
+function bindings(body: Expression): Map<number, { readonly node: LetExpression; readonly depth: number }> {
+  const lets = new Map<number, { readonly node: LetExpression; readonly depth: number }>();
+  const visit = (expression: Expression, depth: number): void => {
+    if (expression.kind === "let") {
+      lets.set(expression.local, { node: expression, depth });
+    }
+    childExpressions(expression).forEach((child) => visit(child, depth + 1));
+  };
+  visit(body, 0);
+  return lets;
+}
+
+function countLets(body: Expression): number {
+  return (body.kind === "let" ? 1 : 0) + childExpressions(body).reduce((total, child) => total + countLets(child), 0);
+}
+
+// Computes the largest repeated pure expression once into a new local, read
+// back at each place it appeared, until no expression repeats.
+function eliminateCommonSubexpressions(item: FunctionItem): FunctionItem {
+  // A body that is a bare call has no entry of its own to wrap.
+  if (item.body.kind === "call" && item.body.index < 0) {
+    return item;
+  }
+  let body = item.body;
+  const mutated = mutatedVariables(body);
+  for (;;) {
+    const repeated = [...pureExpressions(body, mutated).values()].filter((entry) => entry.nodes.length > 1);
+    if (repeated.length === 0) {
+      return body === item.body ? item : { ...item, body };
+    }
+    const chosen = repeated.reduce((best, entry) => (entry.size > best.size ? entry : best));
+    const [first] = chosen.nodes as [Expression];
+    const local = item.params.length + countLets(body);
+    const read: Expression = { index: -1, type: first.type, offset: null, kind: "local", local };
+    const occurrences = new Set(chosen.nodes);
+    const bind = (scope: Expression): Expression => ({
+      index: -1,
+      type: scope.type,
+      offset: null,
+      kind: "let",
+      local,
+      init: first,
+      body: scope,
+    });
+
+    // Every occurrence is inside the scope of each local it reads, so the
+    // innermost of those `let` bodies holds them all.  An expression of
+    // parameters alone is computed at the top of the function.
+    const lets = bindings(body);
+    const scopes = [...chosen.locals].map((used) => lets.get(used)!);
+    const innermost = scopes.reduce<(typeof scopes)[number] | null>(
+      (best, scope) => (best === null || scope.depth > best.depth ? scope : best),
+      null,
+    );
+    body = rewrite(body, (node) => {
+      if (occurrences.has(node)) {
+        return read;
+      }
+      if (node !== innermost?.node) {
+        return null;
+      }
+      return { ...node, body: bind(rewrite(node.body, (inner) => (occurrences.has(inner) ? read : null))) };
+    });
+    if (innermost === null) {
+      body = bind(body);
+    }
+  }
+}
+
+// `item` with `body`, which may have lost some of the `let`s its old body
+// had, and its locals numbered from the parameter count again.
+function renumberLocals(item: FunctionItem, body: Expression): FunctionItem {
+  const numbers = new Map<number, number>();
+  const number = (expression: Expression): void => {
+    if (expression.kind === "let") {
+      numbers.set(expression.local, item.params.length + numbers.size);
+    }
+    childExpressions(expression).forEach(number);
+  };
+  number(body);
+  const renumber = (expression: Expression): Expression =>
+    rewrite(expression, (node) => {
+      const local = "local" in node ? (numbers.get(node.local) ?? node.local) : undefined;
+      if (local === undefined || local === node.local) {
+        return null;
+      }
+      switch (node.kind) {
+        case "let":
+          return { ...node, local, init: renumber(node.init), body: renumber(node.body) };
+        case "assign":
+          return { ...node, local, value: renumber(node.value) };
+        default:
+          return { ...node, local };
+      }
+    });
+  return { ...item, body: renumber(body) };
+}
+
+// Folds `body` with the value of every immutable local whose initializer
+// folds to a literal, dropping those locals' `let`s.
+function propagateConstants(item: FunctionItem): FunctionItem {
+  const mutated = mutatedVariables(item.body);
+  const constants = new Map<number, LiteralExpression>();
+  const fold = (expression: Expression): Expression =>
+    rewrite(expression, (node) => {
+      switch (node.kind) {
+        case "local": {
+          const value = constants.get(node.local);
+          return value ? { ...value, index: node.index, offset: node.offset } : null;
+        }
+        case "let": {
+          // Every read of a propagated local becomes a literal, so its `let`
+          // would only store a value nothing loads.
+          const init = fold(node.init);
+          if (init.kind === "literal" && foldable(init.type) && !mutated.has(node.local)) {
+            constants.set(node.local, init);
+            return fold(node.body);
+          }
+          const body = fold(node.body);
+          return init === node.init && body === node.body ? node : { ...node, init, body };
+        }
+        case "binary":
+        case "not":
+        case "cast": {
+          const folded = rewrite(node, (inner) => (inner === node ? null : fold(inner)));
+          return foldExpression(folded) ?? folded;
+        }
+        default:
+          return null;
+      }
+    });
+  const folded = fold(item.body);
+  if (folded === item.body) {
+    return item;
+  }
+
+  return renumberLocals(item, folded);
+}
+
+// Whether `expression` has a `break` or `continue` to a loop outside it.
+function escapesLoop(expression: Expression, loops = 0): boolean {
+  switch (expression.kind) {
+    case "break":
+    case "continue":
+      if (expression.depth >= loops) {
+        return true;
+      }
+      break;
+    case "loop":
+      return escapesLoop(expression.body, loops + 1);
+    case "anonymous-function":
+      return false;
+  }
+  return childExpressions(expression).some((child) => escapesLoop(child, loops));
+}
+
+// Replaces each `if` whose condition is a literal with the branch it takes.
+// The other branch never runs, so nothing in it needs to be kept.  Stage2
+// stored each `break` and `continue` as a count of the blocks it leaves, `if`
+// blocks included, so a taken branch that leaves a loop around the `if` keeps
+// its `if`.  The exception is `while false`, whose loop is dropped whole.
+function foldBranches(item: FunctionItem): FunctionItem {
+  const fold = (expression: Expression): Expression =>
+    rewrite(expression, (node) => {
+      if (node.kind === "loop" && node.body.kind === "if" && node.body.condition.kind === "literal") {
+        const exit = node.body.else;
+        if (node.body.condition.value === 0 && exit?.kind === "break" && exit.depth === 0 && !exit.value) {
+          return { index: node.index, type: node.type, offset: node.offset, kind: "literal", value: 0 };
+        }
+      }
+      if (node.kind !== "if" || node.condition.kind !== "literal") {
+        return null;
+      }
+      const taken = node.condition.value !== 0 ? node.then : node.else;
+      return taken && !escapesLoop(taken) ? fold(taken) : null;
+    });
+  const folded = fold(item.body);
+  return folded === item.body ? item : renumberLocals(item, folded);
+}
+
+/**
+ * Computes each pure expression (arithmetic, comparisons, casts, and reads of
+ * variables that are never assigned) that appears more than once in a
+ * function into a new local and reads it back.  Division and remainder can
+ * trap, so they are never moved.
+ */
+export const commonSubexpressionElimination: HirPass = {
+  name: "common-subexpression-elimination",
+  run: (program: Program): Program => ({ functions: program.functions.map(eliminateCommonSubexpressions) }),
+};
+
+/**
+ * Replaces reads of each immutable local whose initializer is a constant with
+ * that constant, folds the 32-bit and `bool` arithmetic this leaves on
+ * literals, and removes the locals.  Operations that trap, such as division by
+ * zero, stay in the code.
+ */
+export const constantPropagation: HirPass = {
+  name: "constant-propagation",
+  run: (program: Program): Program => ({ functions: program.functions.map(propagateConstants) }),
+};
+
+/**
+ * Replaces each `if` whose condition is a literal, such as one constant
+ * propagation folded, with its taken branch, so no `if` block is emitted.
+ */
+export const branchFolding: HirPass = {
+  name: "branch-folding",
+  run: (program: Program): Program => ({ functions: program.functions.map(foldBranches) }),
+};
+
+/** The passes `compile` runs when `optimize` is set, in order. */
+export const OPTIMIZATION_PASSES: ReadonlyArray<HirPass> = [
+  constantPropagation,
+  branchFolding,
+  commonSubexpressionElimination,
+];
@@ -0,0 +1,433 @@
+// Passes that library users run over the checked tree before it is emitted.
+// `compile` with `extraPasses` stops stage2 after its check phase, decodes the
+// tree with `readProgram`, and runs the passes in order.  The functions whose
+// bodies changed are written back into the compiler's AST, and stage2 then
+// emits the result as it would have emitted the original.
+//
+// The tree is written back in place.  A node a pass left alone keeps its
+// entry, a node rebuilt from an existing one (same `index`) overwrites that
+// entry, and any other node gets a new entry.  Calls, lists, structs, and the
+// other kinds that keep their children in call data can only be rebuilt with
+// the same shape, since the host cannot allocate call data.
+
+import { BINARY_OPERATORS, childExpressions, readProgram } from "./frontend";
+import type { CallExpression, Expression, FieldLabel, FunctionItem, Program } from "./frontend";
+import { CompileError, DEFAULT_STAGE2_LAYOUT, astExpressionTableBase, astProgramBase } from "./index";
+import type { Stage2Layout } from "./index";
+
+/** A rewrite of the checked program that runs between validation and emission. */
+export interface HirPass {
+  /** Names the pass in errors. */
+  readonly name: string;
+  /**
+   * Returns the program to emit, or nothing to keep `program`.  Only function
+   * bodies may change, and every node's `type` must be the stage2 type id of
+   * its value.  Throwing fails the compile.
+   */
+  run(program: Program): Program | void;
+}
+
+const WORD_SIZE = 4;
+
+const FUNCTION_PARAM_COUNT_WORD = 2;
+const FUNCTION_BODY_KIND_WORD = 3;
+const FUNCTION_BODY_DATA_WORD = 4;
+const FUNCTION_LOCALS_WORD = 5;
+const FUNCTION_FLAGS_WORD = 8;
+const FUNCTION_FLAG_SHADOW_STACK_FRAME = 8;
+
+const BODY_KIND_CALL = 1;
+const BODY_KIND_EXPRESSION = 2;
+
+const CALL_ARG_COUNT_OFFSET = 2 * WORD_SIZE;
+const CALL_CALLEE_OFFSET = 3 * WORD_SIZE;
+const CALL_ARGS_OFFSET = 4 * WORD_SIZE;
+
+const STRUCT_LITERAL_FIELD_WORDS = 5;
+const STRUCT_LABEL_DATA_OFFSET = WORD_SIZE;
+const STRUCT_FIELD_VALUE_OFFSET = 4 * WORD_SIZE;
+
+// Built on first use, since `./frontend` imports `./index`, which imports
+// this module before `./frontend` has finished loading.
+let binaryKinds: ReadonlyMap<string, number> | null = null;
+
+function binaryKind(operator: string): number {
+  binaryKinds ??= new Map([...BINARY_OPERATORS].map(([kind, name]) => [name, kind]));
+  return binaryKinds.get(operator)!;
+}
+
+/**
+ * Runs `passes` over `program` in order.  A pass that throws anything but a
+ * `CompileError` is reported by name.
+ */
+export function runHirPasses(program: Program, passes: ReadonlyArray<HirPass>): Program {
+  let current = program;
+  for (const pass of passes) {
+    let result: Program | void;
+    try {
+      result = pass.run(current);
+    } catch (error) {
+      if (error instanceof CompileError) {
+        throw error;
+      }
+      const detail = error instanceof Error ? error.message : String(error);
+      throw new CompileError(`pass '${pass.name}' failed: ${detail}`);
+    }
+    current = result ?? current;
+  }
+  return current;
+}
+
+function collect(program: Program): Set<Expression> {
+  const nodes = new Set<Expression>();
+  const visit = (expression: Expression): void => {
+    if (!nodes.has(expression)) {
+      nodes.add(expression);
+      childExpressions(expression).forEach(visit);
+    }
+  };
+  program.functions.forEach((item) => visit(item.body));
+  return nodes;
+}
+
+function unsupported(expression: Expression): CompileError {
+  return new CompileError(
+    `extra passes cannot write a new '${expression.kind}' expression; rebuild an existing one with the same shape`,
+  );
+}
+
+class TreeWriter {
+  readonly #view: DataView;
+  readonly #table: number;
+  readonly #layout: Stage2Layout;
+  // Entries of the original tree by index, and the ones the new tree still
+  // uses unchanged, whose entries must be left alone.
+  readonly #originals = new Map<number, Expression>();
+  readonly #kept: Set<Expression>;
+  readonly #reused = new Set<number>();
+
+  constructor(
+    memory: WebAssembly.Memory,
+    table: number,
+    layout: Stage2Layout,
+    original: Program,
+    program: Program,
+  ) {
+    this.#view = new DataView(memory.buffer);
+    this.#table = table;
+    this.#layout = layout;
+    const originals = collect(original);
+    for (const node of originals) {
+      if (node.index >= 0) {
+        this.#originals.set(node.index, node);
+      }
+    }
+    this.#kept = new Set([...collect(program)].filter((node) => node.index >= 0 && originals.has(node)));
+  }
+
+  word(ptr: number): number {
+    return this.#view.getInt32(ptr, true);
+  }
+
+  setWord(ptr: number, value: number): void {
+    this.#view.setInt32(ptr, value, true);
+  }
+
+  #entry(index: number): number {
+    return this.#table + WORD_SIZE + index * this.#layout.astExprEntrySize;
+  }
+
+  #allocate(): number {
+    const count = this.word(this.#table);
+    if (count >= this.#layout.astExprCapacity) {
+      throw new CompileError("extra passes ran out of AST expression entries");
+    }
+    this.setWord(this.#table, count + 1);
+    return count;
+  }
+
+  // The original node `expression` was rebuilt from, when it may take over
+  // that node's entry.
+  #origin(expression: Expression): Expression | null {
+    const origin = expression.index >= 0 ? this.#originals.get(expression.index) : undefined;
+    if (!origin || this.#kept.has(origin) || this.#reused.has(expression.index)) {
+      return null;
+    }
+    this.#reused.add(expression.index);
+    return origin;
+  }
+
+  /** Writes `expression` and the nodes under it, returning its entry index. */
+  write(expression: Expression): number {
+    if (this.#kept.has(expression)) {
+      return expression.index;
+    }
+    const origin = this.#origin(expression);
+    const index = origin ? expression.index : this.#allocate();
+    const entry = this.#entry(index);
+    const sameKind = origin !== null && origin.kind === expression.kind;
+    const words = Array.from({ length: 5 }, (_, word) => (sameKind ? this.word(entry + word * WORD_SIZE) : 0));
+    this.#encode(expression, words, sameKind);
+    words.forEach((value, word) => this.setWord(entry + word * WORD_SIZE, value));
+    const types = this.#table + WORD_SIZE + this.#layout.astExprCapacity * this.#layout.astExprEntrySize;
+    this.setWord(types + index * WORD_SIZE, expression.type);
+    return index;
+  }
+
+  // Writes a call's arguments and callee into its existing call metadata.
+  writeCall(metadata: number, expression: CallExpression): void {
+    if (this.word(metadata + CALL_ARG_COUNT_OFFSET) !== expression.args.length) {
+      throw unsupported(expression);
+    }
+    const args = expression.args.map((arg) => this.write(arg));
+    args.forEach((arg, position) => this.setWord(metadata + CALL_ARGS_OFFSET + position * WORD_SIZE, arg));
+    this.setWord(metadata + CALL_CALLEE_OFFSET, expression.callee);
+  }
+
+  #writeList(ptr: number, count: number, expressions: ReadonlyArray<Expression>, owner: Expression): void {
+    if (count !== expressions.length) {
+      throw unsupported(owner);
+    }
+    const indices = expressions.map((expression) => this.write(expression));
+    indices.forEach((index, position) => this.setWord(ptr + position * WORD_SIZE, index));
+  }
+
+  #writeLabel(ptr: number, label: FieldLabel, before: FieldLabel, owner: Expression): void {
+    if ((typeof label === "string") !== (typeof before === "string")) {
+      throw unsupported(owner);
+    }
+    if (typeof label !== "string") {
+      this.setWord(ptr + STRUCT_LABEL_DATA_OFFSET, this.write(label));
+    }
+  }
+
+  // Fills `words` ({kind, data0, data1, data2, extra}) with `expression`'s
+  // fields.  `words` holds the entry being replaced when `sameKind` is set,
+  // so fields the tree does not expose, such as loop flags, carry over.
+  #encode(expression: Expression, words: number[], sameKind: boolean): void {
+    const child = (node: Expression): number => this.write(node);
+    const optional = (node: Expression | null): number => (node ? this.write(node) : -1);
+    const at = expression.offset ?? -1;
+    const set = (kind: number, ...data: ReadonlyArray<number | undefined>): void => {
+      words[0] = kind;
+      data.forEach((value, position) => {
+        if (value !== undefined) {
+          words[position + 1] = value;
+        }
+      });
+    };
+    switch (expression.kind) {
+      case "literal":
+        return set(0, expression.value);
+      case "binary":
+        return set(binaryKind(expression.operator), child(expression.left), child(expression.right), at);
+      case "not":
+        return set(22, child(expression.value), undefined, at);
+      case "param":
+        return set(6, expression.param);
+      case "local":
+        return set(8, expression.local);
+      case "let":
+        return set(9, expression.local, child(expression.init), child(expression.body));
+      case "assign":
+        return set(10, expression.local, child(expression.value));
+      case "sequence":
+        return set(
+          11,
+          child(expression.first),
+          child(expression.then),
+          (words[3]! & ~1) | (expression.discard ? 1 : 0),
+        );
+      case "if":
+        return set(7, child(expression.condition), child(expression.then), optional(expression.else));
+      case "loop":
+        return set(12, child(expression.body));
+      case "break":
+        return set(13, sameKind ? undefined : -1, optional(expression.value), at, expression.depth);
+      case "continue":
+        return set(24, sameKind ? undefined : -1, undefined, at, expression.depth);
+      case "return":
+        return set(23, optional(expression.value), expression.value ? 0 : 1, at);
+      case "load":
+        return set(29 + Math.log2(expression.width), child(expression.pointer));
+      case "store":
+        return set(32 + Math.log2(expression.width), child(expression.pointer), child(expression.value));
+      case "array-get":
+        return set(36, child(expression.array), child(expression.element), at);
+      case "array-set":
+        return set(44, child(expression.array), child(expression.element), child(expression.value));
+      case "array-len":
+        return set(38, child(expression.array));
+      case "cast":
+        return set(39, child(expression.value), expression.targetType);
+      case "tuple-get":
+        return set(41, child(expression.tuple), expression.field, at);
+      case "tuple-set":
+        return set(45, child(expression.tuple), expression.field, child(expression.value));
+      case "stackalloc":
+        return set(51, child(expression.size), undefined, at);
+      case "address-of":
+        return set(52, expression.local, undefined, at);
+    }
+
+    // The remaining kinds keep their children or payload in call data, so
+    // they can only take over an entry of the same kind.
+    if (!sameKind) {
+      throw unsupported(expression);
+    }
+    const origin = this.#originals.get(expression.index)!;
+    switch (expression.kind) {
+      case "call":
+        this.writeCall(words[1]!, expression);
+        return set(1, undefined, undefined, at);
+      case "array":
+        this.#writeList(words[1]!, words[2]!, expression.elements, expression);
+        return set(37, undefined, undefined, undefined, at);
+      case "tuple":
+        this.#writeList(words[1]!, words[2]!, expression.elements, expression);
+        return;
+      case "anonymous-function":
+        this.#writeList(words[2]!, words[3]!, expression.body, expression);
+        return set(49, undefined, undefined, undefined, at);
+      case "array-repeat": {
+        const lengthSlot = words[3]!;
+        if ((lengthSlot > 0) !== (expression.lengthExpression !== null)) {
+          throw unsupported(expression);
+        }
+        if (expression.lengthExpression) {
+          this.setWord(lengthSlot, this.write(expression.lengthExpression));
+        }
+        return set(35, child(expression.element), expression.length ?? -1, undefined, at);
+      }
+      case "struct": {
+        const before = origin.kind === "struct" ? origin.fields : [];
+        if (words[3] !== expression.fields.length) {
+          throw unsupported(expression);
+        }
+        expression.fields.forEach((field, position) => {
+          const fieldPtr = words[2]! + (1 + position * STRUCT_LITERAL_FIELD_WORDS) * WORD_SIZE;
+          this.#writeLabel(fieldPtr, field.label, before[position]!.label, expression);
+          this.setWord(fieldPtr + STRUCT_FIELD_VALUE_OFFSET, this.write(field.value));
+        });
+        return set(47, child(expression.structType), undefined, undefined, at);
+      }
+      case "field":
+        if (origin.kind === "field") {
+          this.#writeLabel(words[2]!, expression.label, origin.label, expression);
+        }
+        return set(48, child(expression.struct), undefined, at);
+      case "const-ref":
+        if (origin.kind !== "const-ref" || origin.name !== expression.name) {
+          throw unsupported(expression);
+        }
+        return;
+      case "inline-wasm":
+        if (origin.kind !== "inline-wasm" || origin.bytes.join() !== expression.bytes.join()) {
+          throw unsupported(expression);
+        }
+        return;
+      case "panic":
+        if (origin.kind !== "panic" || origin.message !== expression.message || origin.code !== expression.code) {
+          throw unsupported(expression);
+        }
+        return;
+    }
+  }
+
+  /** Writes a function's new body and sizes its locals and flags to fit it. */
+  writeFunction(entry: number, item: FunctionItem): void {
+    const field = (word: number): number => entry + word * WORD_SIZE;
+    const body = item.body;
+
+    // Each `let` declares one local, and the emitter expects them numbered
+    // right after the parameters without gaps.
+    const paramCount = this.word(field(FUNCTION_PARAM_COUNT_WORD));
+    const declared = new Set<number>();
+    const used: number[] = [];
+    let usesStack = false;
+    const visit = (expression: Expression): void => {
+      if (expression.kind === "let") {
+        if (declared.has(expression.local)) {
+          throw new CompileError(`extra passes declared local ${expression.local} of '${item.name}' twice`);
+        }
+        declared.add(expression.local);
+      } else if (expression.kind === "local" || expression.kind === "assign" || expression.kind === "address-of") {
+        used.push(expression.local);
+      }
+      usesStack ||= expression.kind === "stackalloc" || expression.kind === "address-of";
+      childExpressions(expression).forEach(visit);
+    };
+    visit(body);
+    const numbered = (local: number): boolean => local >= paramCount && local < paramCount + declared.size;
+    const stray = [...declared, ...used].find((local) => !numbered(local));
+    if (stray !== undefined) {
+      throw new CompileError(
+        `extra passes must number the locals of '${item.name}' from ${paramCount} without gaps; found local ${stray}`,
+      );
+    }
+
+    if (this.word(field(FUNCTION_BODY_KIND_WORD)) === BODY_KIND_CALL && body.kind === "call" && body.index < 0) {
+      this.writeCall(this.word(field(FUNCTION_BODY_DATA_WORD)), body);
+    } else {
+      const index = this.write(body);
+      this.setWord(field(FUNCTION_BODY_KIND_WORD), BODY_KIND_EXPRESSION);
+      this.setWord(field(FUNCTION_BODY_DATA_WORD), index);
+    }
+    this.setWord(field(FUNCTION_LOCALS_WORD), declared.size);
+    if (usesStack) {
+      const flags = this.word(field(FUNCTION_FLAGS_WORD));
+      this.setWord(field(FUNCTION_FLAGS_WORD), flags | FUNCTION_FLAG_SHADOW_STACK_FRAME);
+    }
+  }
+}
+
+/**
+ * Writes the bodies `program` changed relative to `original`, the tree
+ * `readProgram` decoded from the same memory, back into the AST there.
+ */
+export function writeProgram(
+  memory: WebAssembly.Memory,
+  outputPtr: number,
+  inputLength: number,
+  original: Program,
+  program: Program,
+  layout: Stage2Layout = DEFAULT_STAGE2_LAYOUT,
+): void {
+  if (program === original) {
+    return;
+  }
+  if (program.functions.length !== original.functions.length) {
+    throw new CompileError("extra passes may not add or remove functions");
+  }
+  const astBase = astProgramBase(outputPtr, inputLength, layout);
+  const writer = new TreeWriter(memory, astExpressionTableBase(astBase, layout), layout, original, program);
+  program.functions.forEach((item, index) => {
+    const before = original.functions[index]!;
+    if (item.body === before.body) {
+      return;
+    }
+    if (
+      item.name !== before.name ||
+      item.params.length !== before.params.length ||
+      item.returnType !== before.returnType
+    ) {
+      throw new CompileError(`extra passes may only change the body of function '${before.name}'`);
+    }
+    writer.writeFunction(astBase + WORD_SIZE + index * layout.astFunctionEntrySize, item);
+  });
+}
+
+/**
+ * Runs `passes` over the checked program stage2 left in `memory` and writes
+ * the result back for emission.
+ */
+export function applyHirPasses(
+  memory: WebAssembly.Memory,
+  outputPtr: number,
+  inputLength: number,
+  passes: ReadonlyArray<HirPass>,
+  layout: Stage2Layout = DEFAULT_STAGE2_LAYOUT,
+): void {
+  const original = readProgram(memory, outputPtr, inputLength, layout);
+  writeProgram(memory, outputPtr, inputLength, original, runHirPasses(original, passes), layout);
+}
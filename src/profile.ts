@@ -0,0 +1,75 @@
+import { counterIncrement, instrumentWithCounters, readCounterTable, readModuleShape } from "./instrument";
+import type { InstrumentedWasm } from "./instrument";
+import { readFunctionNames } from "./wasm_reader";
+
+/** Name of the exported global holding the profile table's address. */
+export const PROFILE_TABLE_EXPORT = "__profile_table";
+// One record per defined function: the calls and returns as `u32`s, then the
+// self instructions as a `u64`.
+const PROFILE_RECORD_SIZE = 16;
+const PROFILE_RETURNS_OFFSET = 4;
+const PROFILE_INSTRUCTIONS_OFFSET = 8;
+
+export interface FunctionProfile {
+  readonly functionIndex: number;
+  readonly name: string | null;
+  /** How many times the function was entered. */
+  readonly calls: number;
+  /** How many of those calls returned; a trap unwinds the rest. */
+  readonly returns: number;
+  /** Instructions executed in the function's own body, not in its callees. */
+  readonly selfInstructions: number;
+}
+
+export type ProfiledWasm = InstrumentedWasm;
+
+/**
+ * Adds per-function counters to a module, in a table exported as
+ * `__profile_table`; see {@link instrumentWithCounters}.  Every function
+ * counts its calls on entry and its returns at each `return` and at the end
+ * of its body.  At the start of every straight-line run of code it also adds
+ * the run's instruction count to its own total.
+ */
+export function instrumentWasmWithProfile(wasm: Uint8Array): ProfiledWasm {
+  const functions = readModuleShape(wasm).definedFunctions;
+  return instrumentWithCounters(wasm, "profile", PROFILE_TABLE_EXPORT, (tableBase) => {
+    const record = (body: number): number => tableBase + body * PROFILE_RECORD_SIZE;
+    return {
+      enter: (body) => counterIncrement(record(body), false, 1),
+      run: (body, _start, _stop, length) =>
+        length > 0 ? counterIncrement(record(body) + PROFILE_INSTRUCTIONS_OFFSET, true, length) : [],
+      exit: (body) => counterIncrement(record(body) + PROFILE_RETURNS_OFFSET, false, 1),
+      tableSize: () => functions * PROFILE_RECORD_SIZE,
+    };
+  });
+}
+
+/**
+ * Reads the counters of an instance of a module instrumented with
+ * {@link instrumentWasmWithProfile}, one entry per defined function in index
+ * order.  Returns `null` when the module has no profile table.
+ */
+export function readFunctionProfiles(
+  instance: WebAssembly.Instance,
+  wasm: Uint8Array,
+): FunctionProfile[] | null {
+  const view = readCounterTable(instance, PROFILE_TABLE_EXPORT);
+  if (!view) {
+    return null;
+  }
+  const shape = readModuleShape(wasm);
+  const names = readFunctionNames(wasm);
+  const profiles: FunctionProfile[] = [];
+  for (let index = 0; index < shape.definedFunctions; index += 1) {
+    const record = index * PROFILE_RECORD_SIZE;
+    const functionIndex = shape.importedFunctions + index;
+    profiles.push({
+      functionIndex,
+      name: names.get(functionIndex) ?? null,
+      calls: view.getUint32(record, true),
+      returns: view.getUint32(record + PROFILE_RETURNS_OFFSET, true),
+      selfInstructions: Number(view.getBigUint64(record + PROFILE_INSTRUCTIONS_OFFSET, true)),
+    });
+  }
+  return profiles;
+}
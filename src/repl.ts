@@ -0,0 +1,110 @@
+import { EXPRESSION_TYPES, isReturnTypeMismatch } from "./expression";
+import {
+  CompileError,
+  compile,
+  formatCompileError,
+  formatCompileWarning,
+  runCompilation,
+} from "./index";
+import type { Compilation, CompileErrorFormatOptions, CompileWarning } from "./index";
+
+/** Shown in diagnostics in place of the synthesized module's path. */
+export const REPL_ENTRY_PATH = "<repl>";
+
+const DEFINITION_PATTERN = /^(#\[|fn\s|const\s|use\s)/;
+
+export type ReplOutcome =
+  | { readonly kind: "defined"; readonly warnings: ReadonlyArray<string> }
+  | { readonly kind: "value"; readonly value: string; readonly type: string; readonly warnings: ReadonlyArray<string> }
+  | { readonly kind: "error"; readonly message: string };
+
+/**
+ * Accepts top-level items and expressions one at a time.  Items are kept and
+ * compiled together with every later input.  An expression becomes the body
+ * of a synthetic `main`, which is compiled and run against the items so far.
+ */
+export class ReplSession {
+  #items: string[] = [];
+  #format: CompileErrorFormatOptions;
+
+  constructor(format: CompileErrorFormatOptions = {}) {
+    this.#format = { ...format, entryPath: REPL_ENTRY_PATH };
+  }
+
+  /** The items defined so far, in the order they were entered. */
+  get items(): ReadonlyArray<string> {
+    return this.#items;
+  }
+
+  reset(): void {
+    this.#items = [];
+  }
+
+  async submit(input: string): Promise<ReplOutcome> {
+    const text = input.trim();
+    if (!text) {
+      return { kind: "defined", warnings: [] };
+    }
+    if (DEFINITION_PATTERN.test(text)) {
+      return this.#define(text);
+    }
+    return this.#evaluate(text);
+  }
+
+  async #define(item: string): Promise<ReplOutcome> {
+    const { source, start } = this.#program(item);
+    try {
+      const compilation = await compile(source);
+      this.#items.push(item);
+      return { kind: "defined", warnings: this.#warningsFrom(compilation.warnings, start) };
+    } catch (error) {
+      return this.#failure(error);
+    }
+  }
+
+  async #evaluate(expression: string): Promise<ReplOutcome> {
+    let firstError: ReplOutcome | null = null;
+    for (const type of EXPRESSION_TYPES) {
+      const { source, start } = this.#program(`fn main() -> ${type} {\n${expression}\n}`);
+      let compilation: Compilation;
+      try {
+        compilation = await compile(source);
+      } catch (error) {
+        firstError ??= this.#failure(error);
+        if (isReturnTypeMismatch(error)) {
+          continue;
+        }
+        return firstError;
+      }
+      const warnings = this.#warningsFrom(compilation.warnings, start);
+      const result = await runCompilation(compilation, this.#format, warnings);
+      if (result.status !== "ok") {
+        return { kind: "error", message: result.error ?? "evaluation failed" };
+      }
+      const raw = result.value ?? "";
+      const value = type === "bool" ? String(raw !== "0") : raw;
+      return { kind: "value", value, type, warnings };
+    }
+    return firstError ?? { kind: "error", message: "expression has no supported result type" };
+  }
+
+  #program(input: string): { source: string; start: number } {
+    const prefix = this.#items.map((item) => `${item}\n\n`).join("");
+    return { source: `${prefix}${input}\n`, start: prefix.length };
+  }
+
+  // Only warnings inside the new input are shown, so earlier items do not
+  // repeat theirs on every line.
+  #warningsFrom(warnings: ReadonlyArray<CompileWarning>, start: number): string[] {
+    return warnings
+      .filter((warning) => warning.span.offset >= start)
+      .map((warning) => formatCompileWarning(warning, this.#format));
+  }
+
+  #failure(error: unknown): ReplOutcome {
+    if (!(error instanceof CompileError)) {
+      throw error;
+    }
+    return { kind: "error", message: formatCompileError(error, this.#format) };
+  }
+}
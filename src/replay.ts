@@ -0,0 +1,138 @@
+import { createHash } from "node:crypto";
+import { mkdir } from "node:fs/promises";
+import { basename, extname, join } from "node:path";
+
+import { CompileError, compile, parseTarget, toTargetSpec } from "./index";
+import type { CompileErrorRecord, CompileOptions, CompilerModuleSource, Target, TargetSpec } from "./index";
+
+const CASE_FILE = "case.json";
+const ENTRY_FILE = "entry.bp";
+const MODULES_DIR = "modules";
+
+/** Compile options that can be saved as JSON; modules are stored as files. */
+export type RecordedOptions = Omit<CompileOptions, "modules">;
+
+/** Everything needed to rerun a failed compile, as saved by `--record`. */
+export interface ReplayCase {
+  /** The path the input was read from, for display only. */
+  readonly inputPath: string;
+  readonly source: string;
+  readonly target: TargetSpec;
+  readonly options: RecordedOptions;
+  readonly modules: ReadonlyArray<CompilerModuleSource>;
+  /** The error message and stage2 failure record of the original failure. */
+  readonly failure: { readonly message: string; readonly record?: CompileErrorRecord };
+}
+
+export type ReplayStatus = "reproduced" | "changed" | "fixed";
+
+export interface ReplayOutcome {
+  /**
+   * `reproduced` when the compile fails with the recorded error, `changed`
+   * when it fails with a different one, and `fixed` when it now succeeds.
+   */
+  readonly status: ReplayStatus;
+  readonly error?: CompileError;
+}
+
+interface CaseFile {
+  readonly inputPath: string;
+  // Cases recorded before target specs existed store a bare target name.
+  readonly target: Target | TargetSpec;
+  readonly options: RecordedOptions;
+  readonly modules: ReadonlyArray<{ readonly path: string; readonly file: string }>;
+  readonly failure: ReplayCase["failure"];
+}
+
+// Cases are named after the input and a hash of what was compiled, so
+// recording the same failure twice overwrites one case instead of adding two.
+function caseName(replayCase: ReplayCase): string {
+  const hash = createHash("sha256");
+  const { source, target, options, modules } = replayCase;
+  hash.update(JSON.stringify([source, target, options, modules]));
+  const stem = basename(replayCase.inputPath, extname(replayCase.inputPath)) || "input";
+  return `${stem}-${hash.digest("hex").slice(0, 12)}`;
+}
+
+/** Saves `replayCase` under `directory` and returns the case's own directory. */
+export async function writeReplayCase(directory: string, replayCase: ReplayCase): Promise<string> {
+  const caseDirectory = join(directory, caseName(replayCase));
+  await mkdir(caseDirectory, { recursive: true });
+  if (replayCase.modules.length > 0) {
+    await mkdir(join(caseDirectory, MODULES_DIR), { recursive: true });
+  }
+  await Bun.write(join(caseDirectory, ENTRY_FILE), replayCase.source);
+  const modules = replayCase.modules.map((module, index) => ({
+    path: module.path,
+    file: join(MODULES_DIR, `${String(index).padStart(3, "0")}-${basename(module.path)}`),
+  }));
+  for (const [index, module] of replayCase.modules.entries()) {
+    await Bun.write(join(caseDirectory, modules[index].file), module.source);
+  }
+  const caseFile: CaseFile = {
+    inputPath: replayCase.inputPath,
+    target: replayCase.target,
+    options: replayCase.options,
+    modules,
+    failure: replayCase.failure,
+  };
+  await Bun.write(join(caseDirectory, CASE_FILE), `${JSON.stringify(caseFile, null, 2)}\n`);
+  return caseDirectory;
+}
+
+export async function readReplayCase(caseDirectory: string): Promise<ReplayCase> {
+  const caseFile = JSON.parse(await Bun.file(join(caseDirectory, CASE_FILE)).text()) as CaseFile;
+  const modules: CompilerModuleSource[] = [];
+  for (const module of caseFile.modules) {
+    const source = await Bun.file(join(caseDirectory, module.file)).text();
+    modules.push({ path: module.path, source });
+  }
+  return {
+    inputPath: caseFile.inputPath,
+    source: await Bun.file(join(caseDirectory, ENTRY_FILE)).text(),
+    target: typeof caseFile.target === "string" ? toTargetSpec(parseTarget(caseFile.target)) : caseFile.target,
+    options: caseFile.options,
+    modules,
+    failure: caseFile.failure,
+  };
+}
+
+/** Builds the case for a compile of `source` that threw `error`. */
+export function replayCaseFromFailure(
+  inputPath: string,
+  source: string,
+  target: Target | TargetSpec,
+  options: CompileOptions,
+  error: CompileError,
+): ReplayCase {
+  const { modules = [], ...recorded } = options;
+  const failure = error.record
+    ? { message: error.message, record: error.record }
+    : { message: error.message };
+  return { inputPath, source, target: toTargetSpec(target), options: recorded, modules, failure };
+}
+
+// Failure records are compared instead of messages, since a message also
+// carries counters that change whenever the compiler does.
+function sameFailure(error: CompileError, failure: ReplayCase["failure"]): boolean {
+  if (!error.record || !failure.record) {
+    return !error.record && !failure.record && error.message === failure.message;
+  }
+  return JSON.stringify(error.record) === JSON.stringify(failure.record);
+}
+
+/** Compiles a recorded case with the current compiler. */
+export async function replay(replayCase: ReplayCase): Promise<ReplayOutcome> {
+  try {
+    await compile(replayCase.source, replayCase.target, {
+      ...replayCase.options,
+      modules: replayCase.modules,
+    });
+  } catch (error) {
+    if (!(error instanceof CompileError)) {
+      throw error;
+    }
+    return { status: sameFailure(error, replayCase.failure) ? "reproduced" : "changed", error };
+  }
+  return { status: "fixed" };
+}
@@ -0,0 +1,32 @@
+import { CompileError } from "./index";
+import { WasmReader } from "./wasm_reader";
+
+const MEMORY_SECTION_ID = 5;
+const LIMITS_MIN_MAX = 0x01;
+const LIMITS_SHARED_MIN_MAX = 0x03;
+
+/**
+ * Marks the module's memory as shared, so a host can hand it to several
+ * threads.  Bootstrap memories already have a maximum, which shared memories
+ * need, so only the limits flag changes and every other byte keeps its
+ * offset.  A module without memory is returned unchanged.
+ */
+export function shareMemory(wasm: Uint8Array): Uint8Array {
+  const reader = new WasmReader(wasm, 8);
+  while (reader.offset < wasm.length) {
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    const end = reader.offset + size;
+    if (id === MEMORY_SECTION_ID && reader.readLeb() === 1) {
+      const flags = reader.offset;
+      if (wasm[flags] !== LIMITS_MIN_MAX) {
+        throw new CompileError("shared memory needs a memory with a maximum size");
+      }
+      const shared = wasm.slice();
+      shared[flags] = LIMITS_SHARED_MIN_MAX;
+      return shared;
+    }
+    reader.offset = end;
+  }
+  return wasm;
+}
@@ -0,0 +1,81 @@
+import { SECTION_NAMES, WasmReader, readFunctionBodies, readFunctionNames } from "./wasm_reader";
+
+export interface SectionSize {
+  /** The section's name, or `custom:<name>` for a custom section. */
+  readonly name: string;
+  /** Bytes the section takes, counting its id and size prefix. */
+  readonly size: number;
+}
+
+export interface FunctionSize {
+  readonly functionIndex: number;
+  readonly name: string | null;
+  /** Bytes the function's body takes in the code section, counting its size prefix. */
+  readonly size: number;
+}
+
+export interface SizeReport {
+  /** The module's length, including the 8-byte header. */
+  readonly total: number;
+  /** Sections in module order. */
+  readonly sections: ReadonlyArray<SectionSize>;
+  /** Defined functions in index order. */
+  readonly functions: ReadonlyArray<FunctionSize>;
+}
+
+/** Measures each section and each function body of a module. */
+export function readSizeReport(wasm: Uint8Array): SizeReport {
+  const reader = new WasmReader(wasm, 8);
+  const decoder = new TextDecoder();
+  const sections: SectionSize[] = [];
+  while (reader.offset < wasm.length) {
+    const start = reader.offset;
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    const end = reader.offset + size;
+    let name = SECTION_NAMES[id] ?? `section#${id}`;
+    if (id === 0) {
+      const nameLength = reader.readLeb();
+      name = `custom:${decoder.decode(wasm.subarray(reader.offset, reader.offset + nameLength))}`;
+    }
+    sections.push({ name, size: end - start });
+    reader.offset = end;
+  }
+
+  const names = readFunctionNames(wasm);
+  const functions: FunctionSize[] = [];
+  for (const [functionIndex, offset] of readFunctionBodies(wasm)) {
+    const body = new WasmReader(wasm, offset);
+    const size = body.readLeb();
+    functions.push({ functionIndex, name: names.get(functionIndex) ?? null, size: body.offset + size - offset });
+  }
+  return { total: wasm.length, sections, functions };
+}
+
+function percent(part: number, whole: number): string {
+  return `${((whole > 0 ? part / whole : 0) * 100).toFixed(1).padStart(7)}%`;
+}
+
+/**
+ * Renders a report as text: the module total, every section, then the
+ * `rows` biggest functions with their share of the code section.
+ */
+export function formatSizeReport(report: SizeReport, rows = 20): string {
+  const lines = [`size: ${report.total} bytes, ${report.functions.length} functions`];
+  lines.push(`${"bytes".padStart(10)}${"share".padStart(8)}  section`);
+  for (const section of report.sections) {
+    lines.push(`${String(section.size).padStart(10)}${percent(section.size, report.total)}  ${section.name}`);
+  }
+  const code = report.functions.reduce((sum, entry) => sum + entry.size, 0);
+  const biggest = [...report.functions].sort((a, b) => b.size - a.size || a.functionIndex - b.functionIndex);
+  lines.push(`${"bytes".padStart(10)}${"code".padStart(8)}  function`);
+  for (const entry of biggest.slice(0, rows)) {
+    const name = entry.name ?? `wasm-function[${entry.functionIndex}]`;
+    lines.push(`${String(entry.size).padStart(10)}${percent(entry.size, code)}  ${name}`);
+  }
+  if (biggest.length > rows) {
+    const rest = biggest.slice(rows).reduce((sum, entry) => sum + entry.size, 0);
+    lines.push(`... ${biggest.length - rows} more, ${rest} bytes`);
+  }
+  return lines.join("\n");
+}
@@ -0,0 +1,116 @@
+import type { CodeLocation, CompilerModuleSource } from "./index";
+
+/** A version 3 source map, the format browser and wasmtime debuggers read. */
+export interface SourceMap {
+  readonly version: 3;
+  /** The name of the generated `.wasm` file, when known. */
+  readonly file?: string;
+  readonly sources: ReadonlyArray<string>;
+  readonly sourcesContent: ReadonlyArray<string>;
+  readonly names: ReadonlyArray<string>;
+  /**
+   * One line of segments.  A Wasm module is treated as a single line whose
+   * columns are byte offsets into the module.
+   */
+  readonly mappings: string;
+}
+
+export interface SourceMapOptions {
+  readonly file?: string;
+  /** Shown in place of the default entry module path, e.g. the input file. */
+  readonly entryPath?: string;
+}
+
+const BASE64_DIGITS = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/** Encodes a signed integer as a base64 VLQ, as source map segments use. */
+export function encodeVlq(value: number): string {
+  let rest = value < 0 ? (-value << 1) | 1 : value << 1;
+  let encoded = "";
+  do {
+    let digit = rest & 31;
+    rest >>>= 5;
+    if (rest > 0) {
+      digit |= 32;
+    }
+    encoded += BASE64_DIGITS[digit];
+  } while (rest > 0);
+  return encoded;
+}
+
+/**
+ * Builds a source map from a compilation's code locations.  Locations
+ * without a span, such as runtime helpers, become segments with no source so
+ * a debugger does not attribute them to the code before them.
+ */
+export function buildSourceMap(
+  locations: ReadonlyArray<CodeLocation>,
+  modules: ReadonlyArray<CompilerModuleSource>,
+  defaultEntryPath: string,
+  options: SourceMapOptions = {},
+): SourceMap {
+  const sourceIndices = new Map<string, number>();
+  const sources: string[] = [];
+  const sourcesContent: string[] = [];
+  const segments: string[] = [];
+  let previousOffset = 0;
+  let previousSource = 0;
+  let previousLine = 0;
+  let previousColumn = 0;
+  for (const location of locations) {
+    let segment = encodeVlq(location.offset - previousOffset);
+    previousOffset = location.offset;
+    const span = location.span;
+    if (span) {
+      let sourceIndex = sourceIndices.get(span.path);
+      if (sourceIndex === undefined) {
+        sourceIndex = sources.length;
+        sourceIndices.set(span.path, sourceIndex);
+        const renamed = span.path === defaultEntryPath ? options.entryPath : undefined;
+        sources.push(renamed ?? span.path);
+        sourcesContent.push(modules.find((module) => module.path === span.path)?.source ?? "");
+      }
+      segment +=
+        encodeVlq(sourceIndex - previousSource) +
+        encodeVlq(span.line - 1 - previousLine) +
+        encodeVlq(span.column - 1 - previousColumn);
+      previousSource = sourceIndex;
+      previousLine = span.line - 1;
+      previousColumn = span.column - 1;
+    }
+    segments.push(segment);
+  }
+  return {
+    version: 3,
+    ...(options.file === undefined ? {} : { file: options.file }),
+    sources,
+    sourcesContent,
+    names: [],
+    mappings: segments.join(","),
+  };
+}
+
+/**
+ * Appends a `sourceMappingURL` custom section naming `url`, which is how a
+ * Wasm module points debuggers at its source map.
+ */
+export function appendSourceMappingUrl(wasm: Uint8Array, url: string): Uint8Array {
+  const encoder = new TextEncoder();
+  const leb = (value: number): number[] => {
+    const bytes: number[] = [];
+    do {
+      const byte = value & 0x7f;
+      value >>>= 7;
+      bytes.push(value > 0 ? byte | 0x80 : byte);
+    } while (value > 0);
+    return bytes;
+  };
+  const name = encoder.encode("sourceMappingURL");
+  const value = encoder.encode(url);
+  const payload = [...leb(name.length), ...name, ...leb(value.length), ...value];
+  const section = new Uint8Array([0, ...leb(payload.length), ...payload]);
+  const result = new Uint8Array(wasm.length + section.length);
+  result.set(wasm);
+  result.set(section, wasm.length);
+  return result;
+}
@@ -0,0 +1,205 @@
+import { DEFAULT_ENTRY_MODULE_PATH, DEFAULT_TARGET, CompileError, LIBRARY_ARCHIVE_EXTENSION, compile } from "./index";
+import { formatMessage } from "./messages";
+import type { Compilation, CompileOptions, CompilerLibrary, CompilerModuleSource, Target, TargetSpec } from "./index";
+
+/**
+ * Where module sources come from.  Paths are absolute module paths such as
+ * `/lib/math.bp`, the same paths `use` resolves to.  A provider only has to
+ * answer for the files it has; the stdlib modules are always supplied by the
+ * host.
+ */
+export interface SourceProvider {
+  /** The module's source, or `null` when there is no such file. */
+  readFile(path: string): Promise<string | null>;
+  /** Names of the files and directories directly inside `path`, sorted. */
+  listDirectory(path: string): Promise<string[]>;
+  /** The file's bytes, or `null` when there is no such file.  Needed for `.bpl` libraries. */
+  readBinaryFile?(path: string): Promise<Uint8Array | null>;
+}
+
+/** A provider backed by a map of module paths to sources, for tests and the playground. */
+export class MemorySourceProvider implements SourceProvider {
+  readonly #files: Map<string, string>;
+
+  constructor(files: Iterable<CompilerModuleSource> = []) {
+    this.#files = new Map(Array.from(files, (file): [string, string] => [file.path, file.source]));
+  }
+
+  /** Adds or replaces a file, so an editor can keep the provider in sync. */
+  writeFile(path: string, source: string): void {
+    this.#files.set(path, source);
+  }
+
+  deleteFile(path: string): boolean {
+    return this.#files.delete(path);
+  }
+
+  async readFile(path: string): Promise<string | null> {
+    return this.#files.get(path) ?? null;
+  }
+
+  async listDirectory(path: string): Promise<string[]> {
+    const prefix = path.endsWith("/") ? path : `${path}/`;
+    const names = new Set<string>();
+    for (const file of this.#files.keys()) {
+      if (file.startsWith(prefix)) {
+        names.add(file.slice(prefix.length).split("/")[0]!);
+      }
+    }
+    return [...names].sort();
+  }
+}
+
+/**
+ * Resolves a `use` path the way stage2 does: an absolute path is taken as it
+ * is, and a relative one is joined to the importing module's directory with
+ * `.` and `..` segments applied.  Returns `null` for a path stage2 rejects.
+ */
+export function resolveImportPath(importer: string, specifier: string): string | null {
+  if (specifier.length === 0 || specifier.endsWith("/")) {
+    return null;
+  }
+  if (specifier.startsWith("/")) {
+    return specifier;
+  }
+  const segments = importer.split("/").slice(0, -1);
+  for (const segment of specifier.split("/")) {
+    if (segment.length === 0 || segment.includes("\\")) {
+      return null;
+    }
+    if (segment === "..") {
+      if (segments.length <= 1) {
+        return null;
+      }
+      segments.pop();
+    } else if (segment !== ".") {
+      segments.push(segment);
+    }
+  }
+  return segments.join("/");
+}
+
+// Blanks comments, which nest, so a commented-out `use` is not followed.
+// Newlines are kept, and string literals are skipped so `//` inside one is
+// not taken for a comment.
+export function blankComments(source: string): string {
+  let result = "";
+  let depth = 0;
+  let index = 0;
+  while (index < source.length) {
+    const pair = source.slice(index, index + 2);
+    if (pair === "/*") {
+      depth += 1;
+      result += "  ";
+      index += 2;
+    } else if (depth > 0 && pair === "*/") {
+      depth -= 1;
+      result += "  ";
+      index += 2;
+    } else if (depth > 0) {
+      result += source[index] === "\n" ? "\n" : " ";
+      index += 1;
+    } else if (pair === "//") {
+      while (index < source.length && source[index] !== "\n") {
+        result += " ";
+        index += 1;
+      }
+    } else if (source[index] === "\"") {
+      const start = index;
+      index += 1;
+      while (index < source.length && source[index] !== "\"") {
+        index += source[index] === "\\" ? 2 : 1;
+      }
+      index += 1;
+      result += source.slice(start, index);
+    } else {
+      result += source[index];
+      index += 1;
+    }
+  }
+  return result;
+}
+
+const USE_PATTERN = /^\s*use\s+"([^"\n]*)"/gm;
+
+/** The paths named by the `use` items of `source`, in order. */
+export function findImports(source: string): string[] {
+  return Array.from(blankComments(source).matchAll(USE_PATTERN), (match) => match[1]!);
+}
+
+/**
+ * Reads every module reachable from `entry` through `use` items, not counting
+ * `entry` itself.  Imports the provider does not have are left out, so stage2
+ * reports them with their span, and the stdlib modules the host supplies
+ * still resolve.  `.bpl` libraries are left to {@link loadLibraries}.
+ */
+export async function loadImports(
+  provider: SourceProvider,
+  entry: CompilerModuleSource,
+): Promise<CompilerModuleSource[]> {
+  const modules: CompilerModuleSource[] = [];
+  const seen = new Set([entry.path]);
+  const pending = [entry];
+  for (let module = pending.shift(); module; module = pending.shift()) {
+    for (const specifier of findImports(module.source)) {
+      const path = resolveImportPath(module.path, specifier);
+      if (path === null || seen.has(path) || path.endsWith(LIBRARY_ARCHIVE_EXTENSION)) {
+        continue;
+      }
+      seen.add(path);
+      const source = await provider.readFile(path);
+      if (source !== null) {
+        const loaded = { path, source };
+        modules.push(loaded);
+        pending.push(loaded);
+      }
+    }
+  }
+  return modules;
+}
+
+/**
+ * Reads the `.bpl` libraries that `entry` and `modules` name in `use` items.
+ * Libraries the provider cannot read are left out, like missing modules.
+ */
+export async function loadLibraries(
+  provider: SourceProvider,
+  entry: CompilerModuleSource,
+  modules: ReadonlyArray<CompilerModuleSource>,
+): Promise<CompilerLibrary[]> {
+  const libraries: CompilerLibrary[] = [];
+  for (const module of [entry, ...modules]) {
+    for (const specifier of findImports(module.source)) {
+      const path = resolveImportPath(module.path, specifier);
+      if (path === null || !path.endsWith(LIBRARY_ARCHIVE_EXTENSION) || libraries.some((library) => library.path === path)) {
+        continue;
+      }
+      const archive = (await provider.readBinaryFile?.(path)) ?? null;
+      if (archive !== null) {
+        libraries.push({ path, archive });
+      }
+    }
+  }
+  return libraries;
+}
+
+/** Compiles the module at `entryPath` together with everything it imports from `provider`. */
+export async function compileFromProvider(
+  provider: SourceProvider,
+  entryPath: string = DEFAULT_ENTRY_MODULE_PATH,
+  target: Target | TargetSpec = DEFAULT_TARGET,
+  options: Omit<CompileOptions, "entryPath"> = {},
+): Promise<Compilation> {
+  const source = await provider.readFile(entryPath);
+  if (source === null) {
+    throw new CompileError(formatMessage("E0903", { path: entryPath }));
+  }
+  const modules = await loadImports(provider, { path: entryPath, source });
+  const libraries = await loadLibraries(provider, { path: entryPath, source }, modules);
+  return compile(source, target, {
+    ...options,
+    entryPath,
+    modules: [...modules, ...(options.modules ?? [])],
+    libraries: [...libraries, ...(options.libraries ?? [])],
+  });
+}
@@ -0,0 +1,111 @@
+const encoder = new TextEncoder();
+
+/**
+ * Half-open range of offsets into a module's source text.  `start` is the
+ * first offset covered and `end` the first one past it, so an empty range has
+ * `start === end`.
+ */
+export interface SourceRange {
+  readonly start: number;
+  readonly end: number;
+}
+
+export interface LineColumn {
+  /** One-based line number. */
+  readonly line: number;
+  /** One-based column, counting a tab as one column. */
+  readonly column: number;
+}
+
+export function sourceRange(start: number, end: number = start): SourceRange {
+  if (!Number.isInteger(start) || !Number.isInteger(end) || start < 0 || end < start) {
+    throw new RangeError(`invalid source range ${start}..${end}`);
+  }
+  return { start, end };
+}
+
+/** The smallest range covering both `a` and `b`, including any gap between them. */
+export function mergeRanges(a: SourceRange, b: SourceRange): SourceRange {
+  return { start: Math.min(a.start, b.start), end: Math.max(a.end, b.end) };
+}
+
+/** Whether `offset` lies inside `range`.  An empty range contains nothing. */
+export function rangeContains(range: SourceRange, offset: number): boolean {
+  return offset >= range.start && offset < range.end;
+}
+
+/** Whether every offset of `inner` lies inside `outer`. */
+export function rangeCovers(outer: SourceRange, inner: SourceRange): boolean {
+  return inner.start >= outer.start && inner.end <= outer.end;
+}
+
+/** Whether the ranges share at least one offset, which an empty range never does. */
+export function rangesIntersect(a: SourceRange, b: SourceRange): boolean {
+  return Math.max(a.start, b.start) < Math.min(a.end, b.end);
+}
+
+/**
+ * Converts a UTF-8 byte offset, as the compiler's spans hold, to its line and
+ * column.  Offsets past the end clamp to the end of the source.  `\n`, `\r`,
+ * and a `\r\n` pair each count as one line break, and a column counts
+ * characters, not bytes.
+ */
+export function lineColumnAt(source: string, offset: number): LineColumn {
+  const bytes = encoder.encode(source);
+  const clampedOffset = Math.max(0, Math.min(offset, bytes.length));
+  let line = 1;
+  let column = 1;
+  for (let index = 0; index < clampedOffset; index += 1) {
+    const byte = bytes[index]!;
+    if (byte === 13 || (byte === 10 && bytes[index - 1] !== 13)) {
+      line += 1;
+      column = 1;
+    } else if (byte !== 10 && (byte & 0xc0) !== 0x80) {
+      column += 1;
+    }
+  }
+  return { line, column };
+}
+
+/**
+ * Converts a line and column back to a UTF-8 byte offset, the inverse of
+ * `lineColumnAt`.  Returns -1 when the line does not exist or the column runs
+ * past the end of the line.
+ */
+export function offsetAt(source: string, position: LineColumn): number {
+  if (position.line < 1 || position.column < 1) {
+    return -1;
+  }
+  const bytes = encoder.encode(source);
+  const isBreak = (index: number): boolean => bytes[index] === 10 || bytes[index] === 13;
+  let index = 0;
+  for (let line = 1; line < position.line; line += 1) {
+    while (index < bytes.length && !isBreak(index)) {
+      index += 1;
+    }
+    if (index >= bytes.length) {
+      return -1;
+    }
+    index += bytes[index] === 13 && bytes[index + 1] === 10 ? 2 : 1;
+  }
+  for (let column = 1; column < position.column; column += 1) {
+    if (index >= bytes.length || isBreak(index)) {
+      return -1;
+    }
+    index += 1;
+    while (index < bytes.length && (bytes[index]! & 0xc0) === 0x80) {
+      index += 1;
+    }
+  }
+  return index;
+}
+
+/**
+ * The range of the identifier or number starting at `offset`, or of the one
+ * character there when it starts neither.
+ */
+export function tokenRangeAt(source: string, offset: number): SourceRange {
+  const token = /^[A-Za-z0-9_]+/.exec(source.slice(offset));
+  const length = token ? token[0].length : 1;
+  return { start: offset, end: Math.max(offset, Math.min(offset + length, source.length)) };
+}
@@ -0,0 +1,49 @@
+import type { SourceSpan } from "./index";
+import { readFunctionBodies, readFunctionNames } from "./wasm_reader";
+
+/** One Wasm frame of a trapped call, with its `.bp` source when known. */
+export interface StackFrame {
+  /** Index in the module's function index space, counting imports. */
+  readonly functionIndex: number;
+  /** Byte offset of the frame's instruction, for engines that report one. */
+  readonly offset: number | null;
+  /** The function's name, or `null` for functions without one such as runtime helpers. */
+  readonly name: string | null;
+  readonly span: SourceSpan | null;
+}
+
+// V8 writes `wasm-function[3]:0x5f`, with the byte offset of the
+// instruction.  JavaScriptCore writes `wasm-function[3]@[wasm code]`, or the
+// name in place of the index, and gives no offset.
+const WASM_FRAME_PATTERN = /wasm-function\[([^\]]+)\](?::0x([0-9a-fA-F]+))?/;
+
+/**
+ * Picks the Wasm frames out of an error's stack and maps each to its function
+ * name and `.bp` span.  Without an instruction offset, a frame points at the
+ * function's name.
+ */
+export function symbolicateStack(
+  stack: string,
+  wasm: Uint8Array,
+  locate: (offset: number) => SourceSpan | null,
+): StackFrame[] {
+  const names = readFunctionNames(wasm);
+  const bodies = readFunctionBodies(wasm);
+  const indices = new Map(Array.from(names, ([index, name]): [string, number] => [name, index]));
+  const frames: StackFrame[] = [];
+  for (const line of stack.split("\n")) {
+    const match = WASM_FRAME_PATTERN.exec(line);
+    if (!match) {
+      continue;
+    }
+    const functionIndex = /^[0-9]+$/.test(match[1]!) ? Number(match[1]) : indices.get(match[1]!);
+    if (functionIndex === undefined) {
+      continue;
+    }
+    const offset = match[2] === undefined ? null : parseInt(match[2], 16);
+    const body = bodies.get(functionIndex);
+    const span = offset !== null ? locate(offset) : body === undefined ? null : locate(body);
+    frames.push({ functionIndex, offset, name: names.get(functionIndex) ?? null, span });
+  }
+  return frames;
+}
@@ -0,0 +1,130 @@
+// Host wrappers for the stage2 compiler's partial pipeline exports.  Each
+// runs one phase of a fresh compile, so tests can compare two compilers phase
+// by phase and tools can reuse the lexer or parser on their own.
+
+import { readProgram } from "./frontend";
+import type { Program } from "./frontend";
+import { CompileError, instantiateStage2, readAstFunctions, readCompileErrorRecord } from "./index";
+import type { AstFunction, Stage2Executor, Stage2Layout, Stage2PhaseExport } from "./index";
+
+/** Token kinds in the order of the stage2 `TOKEN_KIND_*` constants, after `end`. */
+export const TOKEN_KINDS = [
+  "identifier",
+  "keyword",
+  "integer",
+  "character",
+  "string",
+  "label",
+  "punctuation",
+  "invalid",
+] as const;
+
+export type TokenKind = (typeof TOKEN_KINDS)[number];
+
+export interface Token {
+  readonly kind: TokenKind;
+  readonly text: string;
+  /** Byte offset of the token's first character. */
+  readonly offset: number;
+  /** The value of an integer or character literal, and 0 otherwise. */
+  readonly value: number;
+}
+
+// Inputs go above the module state, where no phase writes.
+const PHASE_INPUT_PTR = 2_097_152;
+const WORD_SIZE = 4;
+const TOKEN_RECORD_FIELDS = 4;
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+export class Stage2Phases {
+  #stage2: Stage2Executor;
+
+  constructor(stage2: Stage2Executor) {
+    this.#stage2 = stage2;
+  }
+
+  /** Wraps the checked-in `compiler.wasm`, or `wasm` when given. */
+  static async create(wasm?: Uint8Array): Promise<Stage2Phases> {
+    return new Stage2Phases(await instantiateStage2(wasm));
+  }
+
+  // Clears the compiler's memory, copies `source` in, and runs `name`.
+  #run(name: "tokenize" | "parse" | "check", source: string): { status: number; bytes: Uint8Array; outputPtr: number } {
+    const phase: Stage2PhaseExport | undefined = this.#stage2[name];
+    if (!phase) {
+      throw new CompileError(`stage2 compiler does not export '${name}'`);
+    }
+    const memory = this.#stage2.memory;
+    const bytes = encoder.encode(source);
+    const outputPtr = PHASE_INPUT_PTR + (Math.ceil(bytes.length / WORD_SIZE) + 1) * WORD_SIZE;
+    if (outputPtr > memory.buffer.byteLength) {
+      memory.grow(Math.ceil((outputPtr - memory.buffer.byteLength) / 65_536));
+    }
+    new Uint8Array(memory.buffer).fill(0);
+    new Uint8Array(memory.buffer).set(bytes, PHASE_INPUT_PTR);
+    const result = phase(PHASE_INPUT_PTR, bytes.length, outputPtr);
+    return { status: typeof result === "bigint" ? Number(result) : result, bytes, outputPtr };
+  }
+
+  #runToAst<T>(
+    name: "parse" | "check",
+    source: string,
+    read: (memory: WebAssembly.Memory, outputPtr: number, inputLength: number, layout?: Stage2Layout) => T,
+  ): T {
+    const { status, bytes, outputPtr } = this.#run(name, source);
+    const memory = this.#stage2.memory;
+    if (status < 0) {
+      const record = readCompileErrorRecord(memory, outputPtr) ?? undefined;
+      throw new CompileError(record?.message ?? `stage2 ${name} failed`, record, source);
+    }
+    return read(memory, outputPtr, bytes.length, this.#stage2.layout);
+  }
+
+  /**
+   * Splits `source` with the `tokenize` export, which scans with the same
+   * routines the parser uses.
+   */
+  tokenize(source: string): Token[] {
+    const { status: count, bytes, outputPtr } = this.#run("tokenize", source);
+    if (count < 0) {
+      throw new CompileError("stage2 tokenize failed");
+    }
+    const view = new DataView(this.#stage2.memory.buffer);
+    return Array.from({ length: count }, (_, index) => {
+      const [kind, start, length, value] = Array.from({ length: TOKEN_RECORD_FIELDS }, (_, field) =>
+        view.getInt32(outputPtr + (index * TOKEN_RECORD_FIELDS + field) * WORD_SIZE, true),
+      );
+      return {
+        kind: TOKEN_KINDS[kind! - 1]!,
+        text: decoder.decode(bytes.subarray(start, start! + length!)),
+        offset: start!,
+        value: value!,
+      };
+    });
+  }
+
+  /** Parses `source` and returns the functions it declares, in source order. */
+  parse(source: string): AstFunction[] {
+    return this.#runToAst("parse", source, readAstFunctions);
+  }
+
+  /**
+   * Parses and checks `source` without emitting a module.  The functions
+   * include the specializations of functions with const parameters.
+   */
+  check(source: string): AstFunction[] {
+    return this.#runToAst("check", source, readAstFunctions);
+  }
+
+  /** Parses `source` and decodes its whole AST.  See `parseProgram`. */
+  parseProgram(source: string): Program {
+    return this.#runToAst("parse", source, readProgram);
+  }
+
+  /** Parses and checks `source` and decodes the result.  See `lowerToHir`. */
+  lowerToHir(source: string): Program {
+    return this.#runToAst("check", source, readProgram);
+  }
+}
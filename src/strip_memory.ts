@@ -0,0 +1,112 @@
+import { WasmReader, encodeUnsignedLeb } from "./wasm_reader";
+
+export interface StrippedWasm {
+  readonly wasm: Uint8Array;
+  /** Maps an offset in the original module to the same byte in `wasm`. */
+  readonly relocate: (offset: number) => number;
+}
+
+const MEMORY_SECTION_ID = 5;
+const EXPORT_SECTION_ID = 7;
+const CODE_SECTION_ID = 10;
+const DATA_SECTION_ID = 11;
+const DATA_COUNT_SECTION_ID = 12;
+const MEMORY_EXPORT_KIND = 2;
+
+// Loads, stores, `memory.size`, `memory.grow`, and atomics other than the fence.
+function isMemoryInstruction(opcode: number, sub: number): boolean {
+  return (
+    (opcode >= 0x28 && opcode <= 0x40) ||
+    (opcode === 0xfc && sub >= 8 && sub <= 11) ||
+    (opcode === 0xfd && (sub <= 11 || (sub >= 84 && sub <= 93))) ||
+    (opcode === 0xfe && sub !== 3)
+  );
+}
+
+function codeUsesMemory(reader: WasmReader, end: number): boolean {
+  for (let count = reader.readLeb(); count > 0; count -= 1) {
+    const bodySize = reader.readLeb();
+    const bodyEnd = reader.offset + bodySize;
+    for (let runs = reader.readLeb(); runs > 0; runs -= 1) {
+      reader.skipLeb();
+      reader.skipValueType();
+    }
+    while (reader.offset < bodyEnd) {
+      const opcode = reader.readByte();
+      if (isMemoryInstruction(opcode, reader.skipImmediates(opcode))) {
+        return true;
+      }
+    }
+  }
+  return reader.offset !== end;
+}
+
+function section(id: number, payload: ReadonlyArray<number>): number[] {
+  return [id, ...encodeUnsignedLeb(payload.length), ...payload];
+}
+
+/**
+ * Drops the memory section, and the export of that memory, from a module whose
+ * code never touches memory and which has no data segments.  Every other byte
+ * is copied as is, and the export section's sizes are encoded again at their
+ * new lengths.  A module that uses memory is returned unchanged.
+ */
+export function stripUnusedMemory(wasm: Uint8Array): StrippedWasm {
+  const unchanged: StrippedWasm = { wasm, relocate: (offset) => offset };
+  const reader = new WasmReader(wasm, 8);
+  const sections: { id: number; start: number; payload: number; end: number }[] = [];
+  while (reader.offset < wasm.length) {
+    const start = reader.offset;
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    const payload = reader.offset;
+    const end = payload + size;
+    if (id === DATA_SECTION_ID || id === DATA_COUNT_SECTION_ID) {
+      return unchanged;
+    }
+    if (id === CODE_SECTION_ID && codeUsesMemory(reader, end)) {
+      return unchanged;
+    }
+    sections.push({ id, start, payload, end });
+    reader.offset = end;
+  }
+  if (!sections.some((entry) => entry.id === MEMORY_SECTION_ID)) {
+    return unchanged;
+  }
+
+  const out: number[] = Array.from(wasm.subarray(0, 8));
+  // Original offsets past each rewritten section, with how far they moved.
+  const shifts: { from: number; delta: number }[] = [];
+  for (const entry of sections) {
+    if (entry.id === MEMORY_SECTION_ID) {
+      shifts.push({ from: entry.end, delta: entry.start - entry.end });
+      continue;
+    }
+    if (entry.id !== EXPORT_SECTION_ID) {
+      out.push(...wasm.subarray(entry.start, entry.end));
+      continue;
+    }
+    const exports = new WasmReader(wasm, entry.payload);
+    const kept: number[] = [];
+    let keptCount = 0;
+    for (let count = exports.readLeb(); count > 0; count -= 1) {
+      const start = exports.offset;
+      const nameLength = exports.readLeb();
+      exports.offset += nameLength;
+      const kind = exports.readByte();
+      exports.skipLeb();
+      if (kind !== MEMORY_EXPORT_KIND) {
+        kept.push(...wasm.subarray(start, exports.offset));
+        keptCount += 1;
+      }
+    }
+    const rewritten = keptCount > 0 ? section(EXPORT_SECTION_ID, [...encodeUnsignedLeb(keptCount), ...kept]) : [];
+    shifts.push({ from: entry.end, delta: rewritten.length - (entry.end - entry.start) });
+    out.push(...rewritten);
+  }
+  return {
+    wasm: Uint8Array.from(out),
+    relocate: (offset) =>
+      shifts.reduce((moved, shift) => (offset >= shift.from ? moved + shift.delta : moved), offset),
+  };
+}
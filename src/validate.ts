@@ -0,0 +1,85 @@
+import { CompileError, CompileErrorKind } from "./index";
+import type { Compilation } from "./index";
+import { readFunctionBodies, readFunctionNames } from "./wasm_reader";
+
+// Engines report where validation failed in their own words.  V8 writes
+// `Compiling function #3:"main" failed: ... @+120`, JavaScriptCore writes
+// `..., in function at index 3`, and wasmtime writes
+// `function[3]: ... at offset 120`.
+const FUNCTION_PATTERNS: ReadonlyArray<RegExp> = [
+  /function #(\d+)/,
+  /function at index (\d+)/,
+  /function\[(\d+)\]/,
+];
+const OFFSET_PATTERNS: ReadonlyArray<RegExp> = [/@\+(\d+)/, /at offset (\d+)/];
+
+function firstMatch(patterns: ReadonlyArray<RegExp>, text: string): number | null {
+  for (const pattern of patterns) {
+    const match = pattern.exec(text);
+    if (match) {
+      return Number(match[1]);
+    }
+  }
+  return null;
+}
+
+/**
+ * Compiles a compilation's module with the host's engine and turns a
+ * rejection into a `CompileError`.  The error names the function the engine
+ * blamed and points at the source of the failing instruction, or of the
+ * function when the engine gives no offset.
+ */
+export async function validateCompilation(compilation: Compilation): Promise<void> {
+  const wasm = compilation.wasm;
+  let detail: string;
+  try {
+    await WebAssembly.compile(wasm);
+    return;
+  } catch (error) {
+    detail = error instanceof Error ? error.message : String(error);
+  }
+
+  const offset = firstMatch(OFFSET_PATTERNS, detail);
+  const functionIndex = firstMatch(FUNCTION_PATTERNS, detail);
+  throw emitErrorAt(compilation, { offset, functionIndex }, (where) => `emitted wasm fails validation${where}: ${detail}`);
+}
+
+/**
+ * The error for emitted code at `location`, for checks made on a finished
+ * module.  The function is the one given, or else the one containing
+ * `offset`.  `describe` gets ` in function 'name'`, or an empty string when no
+ * function is known, and returns the message.  The span points at the
+ * instruction at `offset`, even outside a known function, or at the function
+ * when there is no offset.
+ */
+export function emitErrorAt(
+  compilation: Compilation,
+  location: { readonly offset: number | null; readonly functionIndex?: number | null },
+  describe: (where: string) => string,
+): CompileError {
+  const wasm = compilation.wasm;
+  const { offset } = location;
+  const bodies = readFunctionBodies(wasm);
+  let functionIndex = location.functionIndex ?? null;
+  if (functionIndex === null && offset !== null) {
+    for (const [index, body] of bodies) {
+      if (body <= offset) {
+        functionIndex = index;
+      }
+    }
+  }
+  const body = functionIndex === null ? undefined : bodies.get(functionIndex);
+  const span = offset !== null ? compilation.locate(offset) : body === undefined ? null : compilation.locate(body);
+  const name =
+    functionIndex === null ? null : (readFunctionNames(wasm).get(functionIndex) ?? `wasm-function[${functionIndex}]`);
+  const message = describe(name ? ` in function '${name}'` : "");
+  if (!span) {
+    return new CompileError(message, { kind: CompileErrorKind.Emit, message });
+  }
+  const source = compilation.modules.find((module) => module.path === span.path)?.source;
+  return new CompileError(
+    `${span.path}:${span.line}:${span.column}: ${message}`,
+    { kind: CompileErrorKind.Emit, message, span },
+    source,
+  );
+}
@@ -0,0 +1,557 @@
+// Visitors over the trees `parseProgram` and `lowerToHir` return.  `Visitor`
+// has a method per node kind that walks the node's children by default, so a
+// pass overrides only the kinds it cares about.  `Transformer` does the same
+// for passes that rewrite the tree: each method returns the node to put in
+// place of its argument, and a node none of whose children changed is kept
+// as is, so an untouched subtree is shared with the input.
+
+import { childExpressions } from "./frontend";
+import type {
+  AddressOfExpression,
+  AnonymousFunctionExpression,
+  ArrayExpression,
+  ArrayGetExpression,
+  ArrayLenExpression,
+  ArrayRepeatExpression,
+  ArraySetExpression,
+  AssignExpression,
+  BinaryExpression,
+  BreakExpression,
+  CallExpression,
+  CastExpression,
+  ConstRefExpression,
+  ContinueExpression,
+  Expression,
+  FieldExpression,
+  FieldLabel,
+  FunctionItem,
+  IfExpression,
+  InlineWasmExpression,
+  LetExpression,
+  LiteralExpression,
+  LoadExpression,
+  LocalExpression,
+  LoopExpression,
+  NotExpression,
+  PanicExpression,
+  ParamExpression,
+  Program,
+  ReturnExpression,
+  SequenceExpression,
+  StackallocExpression,
+  StoreExpression,
+  StructExpression,
+  TupleExpression,
+  TupleGetExpression,
+  TupleSetExpression,
+} from "./frontend";
+
+export class Visitor {
+  visitProgram(program: Program): void {
+    for (const item of program.functions) {
+      this.visitFunction(item);
+    }
+  }
+
+  visitFunction(item: FunctionItem): void {
+    this.visitExpression(item.body);
+  }
+
+  /** Calls the method for `expression`'s kind. */
+  visitExpression(expression: Expression): void {
+    switch (expression.kind) {
+      case "literal":
+        return this.visitLiteral(expression);
+      case "call":
+        return this.visitCall(expression);
+      case "binary":
+        return this.visitBinary(expression);
+      case "not":
+        return this.visitNot(expression);
+      case "param":
+        return this.visitParam(expression);
+      case "local":
+        return this.visitLocal(expression);
+      case "let":
+        return this.visitLet(expression);
+      case "assign":
+        return this.visitAssign(expression);
+      case "sequence":
+        return this.visitSequence(expression);
+      case "if":
+        return this.visitIf(expression);
+      case "loop":
+        return this.visitLoop(expression);
+      case "break":
+        return this.visitBreak(expression);
+      case "continue":
+        return this.visitContinue(expression);
+      case "return":
+        return this.visitReturn(expression);
+      case "load":
+        return this.visitLoad(expression);
+      case "store":
+        return this.visitStore(expression);
+      case "array":
+        return this.visitArray(expression);
+      case "array-repeat":
+        return this.visitArrayRepeat(expression);
+      case "array-get":
+        return this.visitArrayGet(expression);
+      case "array-set":
+        return this.visitArraySet(expression);
+      case "array-len":
+        return this.visitArrayLen(expression);
+      case "tuple":
+        return this.visitTuple(expression);
+      case "tuple-get":
+        return this.visitTupleGet(expression);
+      case "tuple-set":
+        return this.visitTupleSet(expression);
+      case "struct":
+        return this.visitStruct(expression);
+      case "field":
+        return this.visitField(expression);
+      case "cast":
+        return this.visitCast(expression);
+      case "const-ref":
+        return this.visitConstRef(expression);
+      case "inline-wasm":
+        return this.visitInlineWasm(expression);
+      case "anonymous-function":
+        return this.visitAnonymousFunction(expression);
+      case "panic":
+        return this.visitPanic(expression);
+      case "stackalloc":
+        return this.visitStackalloc(expression);
+      case "address-of":
+        return this.visitAddressOf(expression);
+    }
+  }
+
+  /** Visits the children of `expression`, in source order. */
+  walkExpression(expression: Expression): void {
+    for (const child of childExpressions(expression)) {
+      this.visitExpression(child);
+    }
+  }
+
+  visitLiteral(expression: LiteralExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitCall(expression: CallExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitBinary(expression: BinaryExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitNot(expression: NotExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitParam(expression: ParamExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitLocal(expression: LocalExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitLet(expression: LetExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitAssign(expression: AssignExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitSequence(expression: SequenceExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitIf(expression: IfExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitLoop(expression: LoopExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitBreak(expression: BreakExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitContinue(expression: ContinueExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitReturn(expression: ReturnExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitLoad(expression: LoadExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitStore(expression: StoreExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitArray(expression: ArrayExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitArrayRepeat(expression: ArrayRepeatExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitArrayGet(expression: ArrayGetExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitArraySet(expression: ArraySetExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitArrayLen(expression: ArrayLenExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitTuple(expression: TupleExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitTupleGet(expression: TupleGetExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitTupleSet(expression: TupleSetExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitStruct(expression: StructExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitField(expression: FieldExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitCast(expression: CastExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitConstRef(expression: ConstRefExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitInlineWasm(expression: InlineWasmExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitAnonymousFunction(expression: AnonymousFunctionExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitPanic(expression: PanicExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitStackalloc(expression: StackallocExpression): void {
+    this.walkExpression(expression);
+  }
+
+  visitAddressOf(expression: AddressOfExpression): void {
+    this.walkExpression(expression);
+  }
+}
+
+// Returns `node` when every field in `changes` already holds the same value,
+// and a copy with the changes applied otherwise.
+function rebuild<T extends Expression>(node: T, changes: Partial<T>): T {
+  for (const [key, value] of Object.entries(changes)) {
+    const current = (node as Record<string, unknown>)[key];
+    const same =
+      Array.isArray(value) && Array.isArray(current)
+        ? value.length === current.length && value.every((item, index) => item === current[index])
+        : value === current;
+    if (!same) {
+      return { ...node, ...changes };
+    }
+  }
+  return node;
+}
+
+function sameFields(
+  fields: ReadonlyArray<{ readonly label: FieldLabel; readonly value: Expression }>,
+  current: ReadonlyArray<{ readonly label: FieldLabel; readonly value: Expression }>,
+): boolean {
+  return fields.every((field, index) => field.label === current[index]!.label && field.value === current[index]!.value);
+}
+
+export class Transformer {
+  transformProgram(program: Program): Program {
+    const functions = program.functions.map((item) => this.transformFunction(item));
+    return functions.every((item, index) => item === program.functions[index]) ? program : { ...program, functions };
+  }
+
+  transformFunction(item: FunctionItem): FunctionItem {
+    const body = this.transformExpression(item.body);
+    return body === item.body ? item : { ...item, body };
+  }
+
+  /** Calls the method for `expression`'s kind and returns its replacement. */
+  transformExpression(expression: Expression): Expression {
+    switch (expression.kind) {
+      case "literal":
+        return this.transformLiteral(expression);
+      case "call":
+        return this.transformCall(expression);
+      case "binary":
+        return this.transformBinary(expression);
+      case "not":
+        return this.transformNot(expression);
+      case "param":
+        return this.transformParam(expression);
+      case "local":
+        return this.transformLocal(expression);
+      case "let":
+        return this.transformLet(expression);
+      case "assign":
+        return this.transformAssign(expression);
+      case "sequence":
+        return this.transformSequence(expression);
+      case "if":
+        return this.transformIf(expression);
+      case "loop":
+        return this.transformLoop(expression);
+      case "break":
+        return this.transformBreak(expression);
+      case "continue":
+        return this.transformContinue(expression);
+      case "return":
+        return this.transformReturn(expression);
+      case "load":
+        return this.transformLoad(expression);
+      case "store":
+        return this.transformStore(expression);
+      case "array":
+        return this.transformArray(expression);
+      case "array-repeat":
+        return this.transformArrayRepeat(expression);
+      case "array-get":
+        return this.transformArrayGet(expression);
+      case "array-set":
+        return this.transformArraySet(expression);
+      case "array-len":
+        return this.transformArrayLen(expression);
+      case "tuple":
+        return this.transformTuple(expression);
+      case "tuple-get":
+        return this.transformTupleGet(expression);
+      case "tuple-set":
+        return this.transformTupleSet(expression);
+      case "struct":
+        return this.transformStruct(expression);
+      case "field":
+        return this.transformField(expression);
+      case "cast":
+        return this.transformCast(expression);
+      case "const-ref":
+        return this.transformConstRef(expression);
+      case "inline-wasm":
+        return this.transformInlineWasm(expression);
+      case "anonymous-function":
+        return this.transformAnonymousFunction(expression);
+      case "panic":
+        return this.transformPanic(expression);
+      case "stackalloc":
+        return this.transformStackalloc(expression);
+      case "address-of":
+        return this.transformAddressOf(expression);
+    }
+  }
+
+  #optional(expression: Expression | null): Expression | null {
+    return expression ? this.transformExpression(expression) : null;
+  }
+
+  #label(label: FieldLabel): FieldLabel {
+    return typeof label === "string" ? label : this.transformExpression(label);
+  }
+
+  transformLiteral(expression: LiteralExpression): Expression {
+    return expression;
+  }
+
+  transformCall(expression: CallExpression): Expression {
+    return rebuild(expression, { args: expression.args.map((arg) => this.transformExpression(arg)) });
+  }
+
+  transformBinary(expression: BinaryExpression): Expression {
+    return rebuild(expression, {
+      left: this.transformExpression(expression.left),
+      right: this.transformExpression(expression.right),
+    });
+  }
+
+  transformNot(expression: NotExpression): Expression {
+    return rebuild(expression, { value: this.transformExpression(expression.value) });
+  }
+
+  transformParam(expression: ParamExpression): Expression {
+    return expression;
+  }
+
+  transformLocal(expression: LocalExpression): Expression {
+    return expression;
+  }
+
+  transformLet(expression: LetExpression): Expression {
+    return rebuild(expression, {
+      init: this.transformExpression(expression.init),
+      body: this.transformExpression(expression.body),
+    });
+  }
+
+  transformAssign(expression: AssignExpression): Expression {
+    return rebuild(expression, { value: this.transformExpression(expression.value) });
+  }
+
+  transformSequence(expression: SequenceExpression): Expression {
+    return rebuild(expression, {
+      first: this.transformExpression(expression.first),
+      then: this.transformExpression(expression.then),
+    });
+  }
+
+  transformIf(expression: IfExpression): Expression {
+    return rebuild(expression, {
+      condition: this.transformExpression(expression.condition),
+      then: this.transformExpression(expression.then),
+      else: this.#optional(expression.else),
+    });
+  }
+
+  transformLoop(expression: LoopExpression): Expression {
+    return rebuild(expression, { body: this.transformExpression(expression.body) });
+  }
+
+  transformBreak(expression: BreakExpression): Expression {
+    return rebuild(expression, { value: this.#optional(expression.value) });
+  }
+
+  transformContinue(expression: ContinueExpression): Expression {
+    return expression;
+  }
+
+  transformReturn(expression: ReturnExpression): Expression {
+    return rebuild(expression, { value: this.#optional(expression.value) });
+  }
+
+  transformLoad(expression: LoadExpression): Expression {
+    return rebuild(expression, { pointer: this.transformExpression(expression.pointer) });
+  }
+
+  transformStore(expression: StoreExpression): Expression {
+    return rebuild(expression, {
+      pointer: this.transformExpression(expression.pointer),
+      value: this.transformExpression(expression.value),
+    });
+  }
+
+  transformArray(expression: ArrayExpression): Expression {
+    return rebuild(expression, { elements: expression.elements.map((element) => this.transformExpression(element)) });
+  }
+
+  transformArrayRepeat(expression: ArrayRepeatExpression): Expression {
+    return rebuild(expression, {
+      element: this.transformExpression(expression.element),
+      lengthExpression: this.#optional(expression.lengthExpression),
+    });
+  }
+
+  transformArrayGet(expression: ArrayGetExpression): Expression {
+    return rebuild(expression, {
+      array: this.transformExpression(expression.array),
+      element: this.transformExpression(expression.element),
+    });
+  }
+
+  transformArraySet(expression: ArraySetExpression): Expression {
+    return rebuild(expression, {
+      array: this.transformExpression(expression.array),
+      element: this.transformExpression(expression.element),
+      value: this.transformExpression(expression.value),
+    });
+  }
+
+  transformArrayLen(expression: ArrayLenExpression): Expression {
+    return rebuild(expression, { array: this.transformExpression(expression.array) });
+  }
+
+  transformTuple(expression: TupleExpression): Expression {
+    return rebuild(expression, { elements: expression.elements.map((element) => this.transformExpression(element)) });
+  }
+
+  transformTupleGet(expression: TupleGetExpression): Expression {
+    return rebuild(expression, { tuple: this.transformExpression(expression.tuple) });
+  }
+
+  transformTupleSet(expression: TupleSetExpression): Expression {
+    return rebuild(expression, {
+      tuple: this.transformExpression(expression.tuple),
+      value: this.transformExpression(expression.value),
+    });
+  }
+
+  transformStruct(expression: StructExpression): Expression {
+    const structType = this.transformExpression(expression.structType);
+    const fields = expression.fields.map((field) => ({
+      label: this.#label(field.label),
+      value: this.transformExpression(field.value),
+    }));
+    if (structType === expression.structType && sameFields(fields, expression.fields)) {
+      return expression;
+    }
+    return { ...expression, structType, fields };
+  }
+
+  transformField(expression: FieldExpression): Expression {
+    return rebuild(expression, {
+      struct: this.transformExpression(expression.struct),
+      label: this.#label(expression.label),
+    });
+  }
+
+  transformCast(expression: CastExpression): Expression {
+    return rebuild(expression, { value: this.transformExpression(expression.value) });
+  }
+
+  transformConstRef(expression: ConstRefExpression): Expression {
+    return expression;
+  }
+
+  transformInlineWasm(expression: InlineWasmExpression): Expression {
+    return expression;
+  }
+
+  transformAnonymousFunction(expression: AnonymousFunctionExpression): Expression {
+    return rebuild(expression, { body: expression.body.map((item) => this.transformExpression(item)) });
+  }
+
+  transformPanic(expression: PanicExpression): Expression {
+    return expression;
+  }
+
+  transformStackalloc(expression: StackallocExpression): Expression {
+    return rebuild(expression, { size: this.transformExpression(expression.size) });
+  }
+
+  transformAddressOf(expression: AddressOfExpression): Expression {
+    return expression;
+  }
+}
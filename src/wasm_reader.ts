@@ -0,0 +1,299 @@
+// Sequential reader over a wasm binary that knows how to skip each
+// instruction's immediates.
+export class WasmReader {
+  readonly bytes: Uint8Array;
+  offset: number;
+
+  constructor(bytes: Uint8Array, offset = 0) {
+    this.bytes = bytes;
+    this.offset = offset;
+  }
+
+  readByte(): number {
+    if (this.offset >= this.bytes.length) {
+      throw new Error("wasm module is truncated");
+    }
+    return this.bytes[this.offset++]!;
+  }
+
+  readLeb(): number {
+    let result = 0;
+    let shift = 0;
+    let byte: number;
+    do {
+      byte = this.readByte();
+      result += (byte & 0x7f) * 2 ** shift;
+      shift += 7;
+    } while (byte & 0x80);
+    return result;
+  }
+
+  readSignedLeb(): number {
+    let result = 0;
+    let shift = 0;
+    let byte: number;
+    do {
+      byte = this.readByte();
+      result += (byte & 0x7f) * 2 ** shift;
+      shift += 7;
+    } while (byte & 0x80);
+    return byte & 0x40 ? result - 2 ** shift : result;
+  }
+
+  skipLeb(): void {
+    while (this.readByte() & 0x80) {}
+  }
+
+  skipHeapType(): void {
+    this.skipLeb();
+  }
+
+  skipValueType(): void {
+    const byte = this.readByte();
+    if (byte === 0x63 || byte === 0x64) {
+      this.skipHeapType();
+    }
+  }
+
+  skipBlockType(): void {
+    const byte = this.bytes[this.offset];
+    if (byte === 0x63 || byte === 0x64) {
+      this.readByte();
+      this.skipHeapType();
+    } else {
+      this.skipLeb();
+    }
+  }
+
+  skipMemArg(): void {
+    const align = this.readLeb();
+    if (align & 0x40) {
+      this.skipLeb();
+    }
+    this.skipLeb();
+  }
+
+  /**
+   * Skips the immediates of `opcode`, which was just read.  Returns the
+   * sub-opcode of a `0xfb`, `0xfc`, `0xfd`, or `0xfe` instruction, or -1 for
+   * other opcodes.
+   */
+  skipImmediates(opcode: number): number {
+    if (opcode === 0x02 || opcode === 0x03 || opcode === 0x04) {
+      this.skipBlockType();
+    } else if (
+      opcode === 0x0c ||
+      opcode === 0x0d ||
+      opcode === 0x10 ||
+      opcode === 0x12 ||
+      opcode === 0x14 ||
+      opcode === 0x15 ||
+      opcode === 0xd5 ||
+      opcode === 0xd6
+    ) {
+      this.skipLeb();
+    } else if (opcode === 0x0e) {
+      const targets = this.readLeb();
+      for (let target = 0; target <= targets; target += 1) {
+        this.skipLeb();
+      }
+    } else if (opcode === 0x11 || opcode === 0x13) {
+      this.skipLeb();
+      this.skipLeb();
+    } else if (opcode === 0x1c) {
+      const types = this.readLeb();
+      for (let type = 0; type < types; type += 1) {
+        this.skipValueType();
+      }
+    } else if ((opcode >= 0x20 && opcode <= 0x26) || opcode === 0x3f || opcode === 0x40 || opcode === 0xd2) {
+      this.skipLeb();
+    } else if (opcode >= 0x28 && opcode <= 0x3e) {
+      this.skipMemArg();
+    } else if (opcode === 0x41 || opcode === 0x42) {
+      this.skipLeb();
+    } else if (opcode === 0x43) {
+      this.offset += 4;
+    } else if (opcode === 0x44) {
+      this.offset += 8;
+    } else if (opcode === 0xd0) {
+      this.skipHeapType();
+    } else if (opcode === 0xfb) {
+      const sub = this.readLeb();
+      if (sub <= 1 || sub === 6 || sub === 7 || (sub >= 11 && sub <= 14) || sub === 16) {
+        this.skipLeb();
+      } else if (sub <= 5 || (sub >= 8 && sub <= 10) || sub === 17 || sub === 18 || sub === 19) {
+        this.skipLeb();
+        this.skipLeb();
+      } else if (sub >= 20 && sub <= 23) {
+        this.skipHeapType();
+      } else if (sub === 24 || sub === 25) {
+        this.readByte();
+        this.skipLeb();
+        this.skipHeapType();
+        this.skipHeapType();
+      } else if (sub !== 15 && (sub < 26 || sub > 30)) {
+        throw new Error(`unsupported instruction 0xfb ${sub}`);
+      }
+      return sub;
+    } else if (opcode === 0xfc) {
+      const sub = this.readLeb();
+      if (sub === 8 || sub === 10 || sub === 12 || sub === 14) {
+        this.skipLeb();
+        this.skipLeb();
+      } else if (sub === 9 || sub === 11 || sub === 13 || (sub >= 15 && sub <= 17)) {
+        this.skipLeb();
+      } else if (sub > 7) {
+        throw new Error(`unsupported instruction 0xfc ${sub}`);
+      }
+      return sub;
+    } else if (opcode === 0xfd) {
+      const sub = this.readLeb();
+      if (sub <= 11 || sub === 92 || sub === 93) {
+        this.skipMemArg();
+      } else if (sub === 12 || sub === 13) {
+        // `v128.const` and `i8x16.shuffle` carry sixteen bytes.
+        this.offset += 16;
+      } else if (sub >= 21 && sub <= 34) {
+        this.readByte();
+      } else if (sub >= 84 && sub <= 91) {
+        this.skipMemArg();
+        this.readByte();
+      }
+      return sub;
+    } else if (opcode === 0xfe) {
+      const sub = this.readLeb();
+      if (sub === 3) {
+        // `atomic.fence` has a single reserved byte.
+        this.readByte();
+      } else {
+        this.skipMemArg();
+      }
+      return sub;
+    } else if (opcode > 0xc4 && opcode !== 0xd1 && opcode !== 0xd3 && opcode !== 0xd4) {
+      throw new Error(`unsupported instruction 0x${opcode.toString(16)}`);
+    }
+    return -1;
+  }
+}
+
+export function encodeUnsignedLeb(value: number): number[] {
+  const bytes: number[] = [];
+  do {
+    let byte = value % 128;
+    value = Math.floor(value / 128);
+    if (value > 0) {
+      byte |= 0x80;
+    }
+    bytes.push(byte);
+  } while (value > 0);
+  return bytes;
+}
+
+export function encodeSignedLeb(value: number): number[] {
+  const bytes: number[] = [];
+  for (;;) {
+    const byte = ((value % 128) + 128) % 128;
+    value = Math.floor(value / 128);
+    if ((value === 0 && (byte & 0x40) === 0) || (value === -1 && (byte & 0x40) !== 0)) {
+      bytes.push(byte);
+      return bytes;
+    }
+    bytes.push(byte | 0x80);
+  }
+}
+
+/** Section names by section id. */
+export const SECTION_NAMES: ReadonlyArray<string> = [
+  "custom",
+  "type",
+  "import",
+  "function",
+  "table",
+  "memory",
+  "global",
+  "export",
+  "start",
+  "element",
+  "code",
+  "data",
+  "data count",
+  "tag",
+];
+
+/**
+ * Names of a module's functions by function index, from the name section when
+ * there is one and otherwise from the function exports.
+ */
+export function readFunctionNames(wasm: Uint8Array): Map<number, string> {
+  const reader = new WasmReader(wasm, 8);
+  const decoder = new TextDecoder();
+  const readName = (): string => {
+    const length = reader.readLeb();
+    reader.offset += length;
+    return decoder.decode(wasm.subarray(reader.offset - length, reader.offset));
+  };
+  const names = new Map<number, string>();
+  const exportNames = new Map<number, string>();
+  while (reader.offset < wasm.length) {
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    const end = reader.offset + size;
+    if (id === 7) {
+      for (let count = reader.readLeb(); count > 0; count -= 1) {
+        const name = readName();
+        const kind = reader.readByte();
+        const index = reader.readLeb();
+        if (kind === 0 && !exportNames.has(index)) {
+          exportNames.set(index, name);
+        }
+      }
+    } else if (id === 0 && readName() === "name") {
+      while (reader.offset < end) {
+        const subsection = reader.readByte();
+        const subsectionSize = reader.readLeb();
+        const subsectionEnd = reader.offset + subsectionSize;
+        if (subsection === 1) {
+          for (let count = reader.readLeb(); count > 0; count -= 1) {
+            const index = reader.readLeb();
+            names.set(index, readName());
+          }
+        }
+        reader.offset = subsectionEnd;
+      }
+    }
+    reader.offset = end;
+  }
+  for (const [index, name] of exportNames) {
+    if (!names.has(index)) {
+      names.set(index, name);
+    }
+  }
+  return names;
+}
+
+/**
+ * The offset of each defined function's body in the code section, at its size
+ * prefix, by function index.  Bootstrap modules only import functions.
+ */
+export function readFunctionBodies(wasm: Uint8Array): Map<number, number> {
+  const reader = new WasmReader(wasm, 8);
+  const bodies = new Map<number, number>();
+  let importedFunctions = 0;
+  while (reader.offset < wasm.length) {
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    const end = reader.offset + size;
+    if (id === 2) {
+      importedFunctions = reader.readLeb();
+    } else if (id === 10) {
+      const count = reader.readLeb();
+      for (let index = 0; index < count; index += 1) {
+        bodies.set(importedFunctions + index, reader.offset);
+        const bodySize = reader.readLeb();
+        reader.offset += bodySize;
+      }
+    }
+    reader.offset = end;
+  }
+  return bodies;
+}
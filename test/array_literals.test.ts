@@ -193,7 +193,7 @@ test("array literal rejects constant expressions with negative length", async ()
   `);
 
   expect(failure.failure.detail).toBe(
-    "/entry.bp:5:19: array literal length must be non-negative",
+    "/entry.bp:5:17: array literal length must be non-negative",
   );
 });
 
@@ -185,6 +185,36 @@ test("array type length accepts constant expressions", async () => {
   expect(result).toBe(6);
 });
 
+test("array lengths starting with a literal evaluate operators", async () => {
+  const wasm = await compileWithAstCompiler(`
+    const SHIFT: i32 = 2;
+
+    fn take(values: [i32; 1 << SHIFT]) -> i32 {
+        values[3] + len(values)
+    }
+
+    fn main() -> i32 {
+        let values: [i32; 2 * 2] = [5; 8 >> 1];
+        take(values)
+    }
+  `);
+
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(9);
+});
+
+test("negative array type lengths are rejected", async () => {
+  const failure = await expectCompileFailure(`
+    const COUNT: i32 = 2;
+
+    fn main() -> i32 {
+        let values: [i32; COUNT - 3] = [];
+        0
+    }
+  `);
+  expect(failure.failure.detail).toBe("/entry.bp:5:27: array length must be non-negative");
+});
+
 test("array type registration reports capacity diagnostics", async () => {
   const parts: string[] = [];
   for (let length = 1; length <= 257; length += 1) {
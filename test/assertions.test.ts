@@ -0,0 +1,94 @@
+import { expect, test } from "bun:test";
+
+import { Target, compile } from "../src/index";
+import {
+  compileWithAstCompiler,
+  expectExportedFunction,
+  instantiateWasmModuleWithGc,
+  runWasmMainWithGc,
+} from "./helpers";
+
+test("panic records its message and location before trapping", async () => {
+  const source = `fn check(value: i32) -> i32 {
+    if value < 0 {
+        panic("negative input")
+    } else {
+        value * 2
+    }
+}
+
+fn main() -> i32 {
+    check(-1)
+}
+`;
+  const compilation = await compile(source, Target.Wasm, { layoutGlobals: true });
+  const instance = await instantiateWasmModuleWithGc(compilation.toWasm());
+  // The 14-byte message is static data, rounded up to a word.
+  expect(compilation.describeLayout(instance)?.dataEnd).toBe(1040);
+  const check = expectExportedFunction(instance, "check");
+  expect(check(4)).toBe(8);
+  expect(compilation.describePanic(instance)).toBeNull();
+
+  expect(() => check(-1)).toThrow();
+  expect(compilation.describePanic(instance)).toEqual({
+    message: "negative input",
+    path: "/entry.bp",
+    line: 3,
+    column: 9,
+  });
+});
+
+test("assert traps with an optional message", async () => {
+  const source = `fn halve(value: i64) -> i64 {
+    assert(value % 2 as i64 == 0 as i64, "value must be even");
+    value / 2 as i64
+}
+
+fn positive(value: i32) -> i32 {
+    assert(value > 0);
+    value
+}
+
+fn main() -> i32 {
+    0
+}
+`;
+  const compilation = await compile(source, Target.Wasm);
+  const instance = await instantiateWasmModuleWithGc(compilation.toWasm());
+  const halve = expectExportedFunction(instance, "halve");
+  const positive = expectExportedFunction(instance, "positive");
+  expect(halve(10n)).toBe(5);
+  expect(positive(3)).toBe(3);
+
+  expect(() => halve(3n)).toThrow();
+  expect(compilation.describePanic(instance)?.message).toBe(
+    "assertion failed: value must be even",
+  );
+  expect(() => positive(0)).toThrow();
+  expect(compilation.describePanic(instance)).toEqual({
+    message: "assertion failed",
+    path: "/entry.bp",
+    line: 7,
+    column: 5,
+  });
+});
+
+test("test harness reports panic messages from main", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn main() -> i32 {
+        let limit: i32 = 3;
+        if limit > 2 {
+            panic("limit exceeded");
+        };
+        limit
+    }
+  `);
+  let failure: unknown = null;
+  try {
+    await runWasmMainWithGc(wasm);
+  } catch (error) {
+    failure = error;
+  }
+  expect(failure).toBeInstanceOf(Error);
+  expect((failure as Error).message).toBe("panicked: limit exceeded");
+});
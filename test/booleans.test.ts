@@ -1,5 +1,7 @@
 import { expect, test } from "bun:test";
 
+import { lowerToHir, walkProgram } from "../src/frontend";
+
 import {
   compileWithAstCompiler,
   expectCompileFailure,
@@ -117,6 +119,34 @@ test("logical operators short circuit", async () => {
   expect(result).toBe(2);
 });
 
+test("logical operators evaluate operands left to right and lower to if", async () => {
+  const source = `
+    fn main() -> i32 {
+        let mut trace: i32 = 0;
+        let first: bool = { trace = trace * 10 + 1; false } || { trace = trace * 10 + 2; true };
+        let second: bool = { trace = trace * 10 + 3; false } && { trace = trace * 10 + 4; true };
+        let third: bool = ({ trace = trace * 10 + 5; true } || { trace = trace * 10 + 6; true })
+            && { trace = trace * 10 + 7; true };
+        if first && !second && third { trace } else { -1 }
+    }
+  `;
+  expect(await runWasmMainWithGc(await compileWithAstCompiler(source))).toBe(12357);
+
+  const program = await lowerToHir(source);
+  const operators: string[] = [];
+  walkProgram(program, {
+    enterExpression(expression) {
+      if (expression.kind === "binary") {
+        operators.push(expression.operator);
+      }
+    },
+  });
+  expect(operators).not.toContain("&&");
+  expect(operators).not.toContain("||");
+  const body = program.functions[0]!.body;
+  expect(body.kind === "let" && body.body.kind === "let" && body.body.init.kind).toBe("if");
+});
+
 test("boolean locals reject integer initializers", async () => {
   const failure = await expectCompileFailure(`
     fn main() -> i32 {
@@ -0,0 +1,30 @@
+import { expect, test } from "bun:test";
+
+import { diffWasm, formatWasmDiff, verifyBootstrap } from "../src/bootstrap";
+import { compileToWasm } from "../src/index";
+
+import { AST_COMPILER_ENTRY_PATH, readAstCompilerModules } from "./helpers";
+
+test("the checked-in compiler reaches a fixed point", async () => {
+  const modules = await readAstCompilerModules();
+  const entry = modules.find((module) => module.path === AST_COMPILER_ENTRY_PATH);
+  if (!entry) {
+    throw new Error("ast compiler entry module not found");
+  }
+  const extraModules = modules.filter((module) => module.path !== AST_COMPILER_ENTRY_PATH);
+  const report = await verifyBootstrap(entry, extraModules);
+  expect(report.diff).toBeNull();
+  expect(report.stage3).toEqual(report.stage2);
+}, { timeout: 30_000 });
+
+test("summarizes where two modules differ", async () => {
+  const before = await compileToWasm("fn main() -> i32 { 7 }");
+  const after = await compileToWasm("fn main() -> i32 { 7000 }");
+  expect(diffWasm(before, before)).toBeNull();
+  const diff = diffWasm(before, after);
+  expect(diff?.actualSize).toBe(diff!.expectedSize + 1);
+  expect(diff?.sections.map((section) => section.name)).toEqual(["code"]);
+  const summary = formatWasmDiff(diff!, "before", "after");
+  expect(summary).toContain(`before is ${before.length} bytes and after is ${after.length} bytes`);
+  expect(summary).toMatch(/\n  code: \d+ bytes -> \d+ bytes$/);
+});
@@ -0,0 +1,28 @@
+import { expect, test } from "bun:test";
+
+import {
+  BUILD_META_SECTION_NAME,
+  COMPILER_VERSION,
+  appendBuildMeta,
+  compile,
+  formatBuildMeta,
+  readBuildMeta,
+} from "../src/index";
+import { expectExportedFunction, instantiateWasmModuleWithGc } from "./helpers";
+
+test("build metadata round trips through a bootstrap.meta section", async () => {
+  const packageJson = (await Bun.file(new URL("../package.json", import.meta.url)).json()) as { version: string };
+  expect(COMPILER_VERSION).toBe(packageJson.version);
+
+  const compilation = await compile("fn main() -> i32 {\n    7\n}\n");
+  expect(readBuildMeta(compilation.wasm)).toBeNull();
+  const meta = { version: COMPILER_VERSION, compilerHash: "ab".repeat(32), sourceHash: "cd".repeat(32) };
+  const stamped = appendBuildMeta(compilation.wasm, meta);
+  expect(stamped.subarray(0, compilation.wasm.length)).toEqual(compilation.wasm);
+  expect(readBuildMeta(stamped)).toEqual(meta);
+  expect(WebAssembly.Module.customSections(new WebAssembly.Module(stamped), BUILD_META_SECTION_NAME).length).toBe(1);
+  expect(expectExportedFunction(await instantiateWasmModuleWithGc(stamped), "main")()).toBe(7);
+  expect(formatBuildMeta(meta)).toBe(
+    `version: ${COMPILER_VERSION}\ncompiler: sha256:${"ab".repeat(32)}\nsource: sha256:${"cd".repeat(32)}`,
+  );
+});
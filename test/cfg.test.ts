@@ -0,0 +1,67 @@
+import { expect, test } from "bun:test";
+
+import { compile } from "../src/index";
+import { expectExportedFunction, instantiateWasmModuleWithGc } from "./helpers";
+
+const source = `
+#[cfg(debug)]
+fn level() -> i32 {
+    // A brace in a string or comment does not end the item: "}" '}'
+    let text: [u8; 1] = "}";
+    text[0] as i32
+}
+
+#[cfg(not(debug))]
+fn level() -> i32 {
+    1
+}
+
+#[cfg(mode = "fast")]
+const SPEED: i32 = 9;
+
+#[cfg(not(mode = "fast"))]
+const SPEED: i32 = 3;
+
+fn flags() -> i32 {
+    let mut value: i32 = SPEED * 10;
+    if cfg!(debug) {
+        value = value + 1000;
+    }
+    if cfg!(not(trace)) {
+        value = value + 100;
+    }
+    value
+}
+`;
+
+test("cfg keeps or leaves out items by the defines", async () => {
+  const plain = await instantiateWasmModuleWithGc((await compile(source)).wasm);
+  expect(expectExportedFunction(plain, "level")(0)).toBe(1);
+  expect(expectExportedFunction(plain, "flags")(0)).toBe(130);
+
+  const defined = await compile(source, undefined, { defines: { debug: "", mode: "fast" } });
+  const instance = await instantiateWasmModuleWithGc(defined.wasm);
+  expect(expectExportedFunction(instance, "level")(0)).toBe(125);
+  expect(expectExportedFunction(instance, "flags")(0)).toBe(1190);
+
+  await expect(compile("fn main() -> bool { cfg!(1) }")).rejects.toThrow("malformed cfg predicate");
+  await expect(compile(source, undefined, { defines: { "not a name": "" } })).rejects.toThrow(
+    "define name 'not a name' is not an identifier",
+  );
+});
+
+test("a left-out test is not run", async () => {
+  const compilation = await compile(`
+#[test]
+#[cfg(slow)]
+fn exhaustive() -> i32 {
+    0
+}
+
+#[test]
+fn quick() -> i32 {
+    0
+}
+`);
+  expect(compilation.tests).toEqual(["quick"]);
+});
@@ -1,8 +1,24 @@
 import { expect, test } from "bun:test";
 
-import { compileToWasm } from "../src/index";
+import {
+  CompileError,
+  CompileErrorKind,
+  CompileWarningKind,
+  Compiler,
+  CompilerCrash,
+  DEFAULT_STAGE2_LAYOUT,
+  Target,
+  compile,
+  compileToWasm,
+  formatCompileError,
+  formatCompileWarning,
+  instantiateStage2,
+  parseTargetSpec,
+  readInternedName,
+  readStage2Layout,
+} from "../src/index";
 
-import { AST_COMPILER_ENTRY_PATH, readAstCompilerModules } from "./helpers";
+import { AST_COMPILER_ENTRY_PATH, loadAstCompilerWasm, readAstCompilerModules } from "./helpers";
 
 test("compiles the stage1 compiler to wasm", async () => {
   const modules = await readAstCompilerModules();
@@ -21,3 +37,266 @@ test("compiles the stage1 compiler to wasm", async () => {
 test("fails when source is empty", async () => {
   await expect(compileToWasm("")).rejects.toThrow(/source must not be empty/);
 });
+
+test("accepts a target spec in place of a bare target", async () => {
+  const compilation = await compile("fn main() -> i32 { 7 }", { kind: Target.Wasm });
+  expect(compilation.target).toBe(Target.Wasm);
+  expect(compilation.targetSpec).toEqual({ kind: Target.Wasm });
+  expect(parseTargetSpec("wgsl:fragment")).toEqual({ kind: Target.Wgsl, stage: "fragment" });
+  expect(() => parseTargetSpec("wgsl:geometry")).toThrow(/unsupported WGSL shader stage 'geometry'/);
+  expect(() => parseTargetSpec("wasm:simd")).toThrow(/unsupported options for target 'wasm'/);
+  await expect(compile("fn main() -> i32 { 7 }", { kind: Target.Wgsl, stage: "compute" })).rejects.toThrow(
+    /target 'wgsl' is not supported yet/,
+  );
+});
+
+test("reports a structured error record with the failing span", async () => {
+  const source = "fn main() -> i32 {\n    let value: i32 = missing;\n    value\n}\n";
+  const error = await compile(source).then(
+    () => null,
+    (caught: unknown) => caught,
+  );
+  expect(error).toBeInstanceOf(CompileError);
+  const record = (error as CompileError).record;
+  expect(record?.kind).toBe(CompileErrorKind.Semantic);
+  expect(record?.message).not.toMatch(/^\/entry\.bp/);
+  expect(record?.span).toEqual({ path: "/entry.bp", line: 2, column: 22, offset: 40 });
+});
+
+test("names the function and identifier a failure was resolving", async () => {
+  const source = "fn helper() -> i32 {\n    missing_fn(1)\n}\n\nfn main() -> i32 {\n    helper()\n}\n";
+  await expect(compile(source)).rejects.toThrow(/, function=helper, identifier=missing_fn, detail="/);
+  await expect(compile("fn main() -> i32 { MISSING }")).rejects.toThrow(/function=main, identifier=MISSING,/);
+  await expect(compile("fn main() -> i32 { true }")).rejects.toThrow(/function=main, detail="/);
+
+  const stage2 = await instantiateStage2();
+  expect(stage2.layout?.internTableBase).toBeGreaterThan(0);
+  expect(readInternedName(stage2.memory, 0, stage2.layout!)).toBeNull();
+  expect(readInternedName(stage2.memory, 1, DEFAULT_STAGE2_LAYOUT)).toBeNull();
+});
+
+test("reports module errors without a span", async () => {
+  const error = await compile("fn main() -> i32 { 0 }", Target.Wasm, {
+    entryPath: `/${"a".repeat(1_100)}.bp`,
+  }).then(
+    () => null,
+    (caught: unknown) => caught,
+  );
+  expect(error).toBeInstanceOf(CompileError);
+  expect((error as CompileError).kind).toBe(CompileErrorKind.Module);
+  expect((error as CompileError).record?.message).toBe("module path exceeds maximum length");
+  expect((error as CompileError).span).toBeUndefined();
+});
+
+test("renders parse errors at the furthest scanned position with a snippet", async () => {
+  const source = "fn main() -> i32 {\n    let value: i32 = 1 +;\n    value\n}\n";
+  const error = await compile(source).then(
+    () => null,
+    (caught: unknown) => caught,
+  );
+  expect(error).toBeInstanceOf(CompileError);
+  expect((error as CompileError).kind).toBe(CompileErrorKind.Parse);
+  expect((error as CompileError).span).toEqual({
+    path: "/entry.bp",
+    line: 2,
+    column: 25,
+    offset: 43,
+  });
+  expect(formatCompileError(error as CompileError, { entryPath: "src/main.bp" })).toBe(
+    [
+      "error: parsing source failed",
+      " --> src/main.bp:2:25",
+      "  |",
+      "2 |     let value: i32 = 1 +;",
+      "  |                         ^",
+    ].join("\n"),
+  );
+});
+
+test("underlines the offending token and colors diagnostics on request", async () => {
+  const source = "fn main() -> i32 {\n\tlet value: i32 = missing;\n\tvalue\n}\n";
+  const error = (await compile(source).then(
+    () => null,
+    (caught: unknown) => caught,
+  )) as CompileError;
+  expect(formatCompileError(error).split("\n").slice(-2)).toEqual([
+    "2 | \tlet value: i32 = missing;",
+    "  | \t                 ^^^^^^^",
+  ]);
+  const colored = formatCompileError(error, { color: true });
+  expect(colored.startsWith("\x1b[1;31merror\x1b[0m\x1b[1m: ")).toBe(true);
+  expect(colored.replace(/\x1b\[[0-9;]*m/g, "")).toBe(formatCompileError(error));
+});
+
+test("reports unused bindings and unreachable code as warnings", async () => {
+  const source = [
+    "fn helper(value: i32, _ignored: i32) -> i32 {",
+    "    let unused: i32 = 1;",
+    "    let _scratch: i32 = 2;",
+    "    return 3;",
+    "    helper(1, 2)",
+    "}",
+    "",
+    "fn main() -> i32 {",
+    "    helper(0, 0)",
+    "}",
+    "",
+  ].join("\n");
+  const compilation = await compile(source);
+  expect(compilation.warnings.map(({ kind, message, span }) => [kind, message, span.line])).toEqual([
+    [CompileWarningKind.UnusedParameter, "unused parameter `value`", 1],
+    [CompileWarningKind.UnusedLocal, "unused variable `unused`", 2],
+    [CompileWarningKind.UnreachableCode, "unreachable code", 5],
+  ]);
+  expect(formatCompileWarning(compilation.warnings[1]!, { entryPath: "src/main.bp" })).toBe(
+    [
+      "warning: unused variable `unused`",
+      " --> src/main.bp:2:9",
+      "  |",
+      "2 |     let unused: i32 = 1;",
+      "  |         ^^^^^^",
+    ].join("\n"),
+  );
+});
+
+test("compiles the stage1 compiler without warnings", async () => {
+  const modules = await readAstCompilerModules();
+  const entry = modules.find((module) => module.path === AST_COMPILER_ENTRY_PATH);
+  if (!entry) {
+    throw new Error("ast compiler entry module not found");
+  }
+  const compilation = await compile(entry.source, Target.Wasm, {
+    entryPath: AST_COMPILER_ENTRY_PATH,
+    modules: modules.filter((module) => module.path !== AST_COMPILER_ENTRY_PATH),
+  });
+  expect(compilation.warnings).toEqual([]);
+});
+
+test("warns about attributes the compiler does not know", async () => {
+  const compilation = await compile("#[inline(always)]\nfn main() -> i32 {\n    1\n}\n");
+  expect(compilation.warnings.map(({ kind, message, span }) => [kind, message, span.column])).toEqual([
+    [CompileWarningKind.UnknownAttribute, "unknown attribute `inline`", 3],
+  ]);
+});
+
+test("exports `#[export]` constants as globals, as the compiler does its layout", async () => {
+  const source = `#[export]
+const SCALE: i32 = 3 * 7;
+
+#[export]
+const ENABLED: bool = true;
+
+fn main() -> i32 {
+    SCALE
+}
+`;
+  const compilation = await compile(source);
+  expect(compilation.warnings).toEqual([]);
+  const { instance } = await WebAssembly.instantiate(compilation.wasm, {});
+  expect((instance.exports.SCALE as WebAssembly.Global).value).toBe(21);
+  // Only `i32` constants have a global.
+  expect(instance.exports.ENABLED).toBeUndefined();
+
+  const stage2 = await instantiateStage2();
+  const { instance: compiler } = await WebAssembly.instantiate(await loadAstCompilerWasm(), {});
+  expect(stage2.layout).toEqual(readStage2Layout(compiler.exports));
+  expect((compiler.exports.AST_MAX_FUNCTIONS as WebAssembly.Global).value).toBe(stage2.layout?.astMaxFunctions);
+  expect(readStage2Layout({})).toEqual(DEFAULT_STAGE2_LAYOUT);
+});
+
+test("warns about branches that constant conditions rule out", async () => {
+  const source = [
+    "const DEBUG: bool = false;",
+    "const LIMIT: i32 = 4;",
+    "",
+    "fn pick(const N: i32) -> i32 {",
+    "    if N > 2 { 1 } else { 0 }",
+    "}",
+    "",
+    "fn main() -> i32 {",
+    "    let mut total: i32 = pick(3);",
+    "    if DEBUG { total = 0; };",
+    "    if !DEBUG && LIMIT > 3 { total = total + 2; } else { total = 5; };",
+    "    while LIMIT < 0 { total = total + 1; };",
+    "    while true { break; };",
+    "    total",
+    "}",
+    "",
+  ].join("\n");
+  const compilation = await compile(source);
+  expect(compilation.warnings.map(({ kind, span }) => [kind, span.line, span.column])).toEqual([
+    [CompileWarningKind.ConditionAlwaysFalse, 10, 8],
+    [CompileWarningKind.ConditionAlwaysTrue, 11, 8],
+    [CompileWarningKind.ConditionAlwaysFalse, 12, 11],
+  ]);
+  expect(compilation.warnings[0]?.message).toBe(
+    "condition is always `false`, so this branch never runs",
+  );
+});
+
+test("reuses one compiler instance across compilations", async () => {
+  const first = "fn main() -> i32 {\n    let unused: i32 = 1;\n    40 + 2\n}\n";
+  const second = "fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n\nfn main() -> i32 {\n    add(1, 2)\n}\n";
+  let instances = 0;
+  const compiler = await Compiler.create(() => {
+    instances += 1;
+    return instantiateStage2();
+  });
+  const reused = await compiler.compile(first);
+  await expect(compiler.compile("fn main() -> i32 { missing }")).rejects.toThrow(CompileError);
+  const afterError = await compiler.compile(second);
+  const again = await compiler.compile(first);
+  expect(instances).toBe(1);
+
+  const fresh = [await (await Compiler.create()).compile(first), await (await Compiler.create()).compile(second)];
+  expect(reused.wasm).toEqual(fresh[0]!.wasm);
+  expect(afterError.wasm).toEqual(fresh[1]!.wasm);
+  expect(again.wasm).toEqual(fresh[0]!.wasm);
+  expect(again.warnings).toEqual(fresh[0]!.warnings);
+  expect(afterError.warnings).toEqual([]);
+
+  const concurrent = await Promise.all([compile(first), compile(second), compile(first)]);
+  expect(concurrent.map(({ wasm }) => wasm)).toEqual([fresh[0]!.wasm, fresh[1]!.wasm, fresh[0]!.wasm]);
+});
+
+test("runs the stage2 compiler through a supplied executor", async () => {
+  const calls: string[] = [];
+  let crash = false;
+  const compiler = await Compiler.create(async () => {
+    const stage2 = await instantiateStage2();
+    calls.push("instantiate");
+    return {
+      memory: stage2.memory,
+      loadModuleFromSource: (pathPtr, contentPtr) => {
+        calls.push("load");
+        return stage2.loadModuleFromSource(pathPtr, contentPtr);
+      },
+      compileFromPath: (pathPtr) => {
+        calls.push("compile");
+        if (crash) {
+          crash = false;
+          throw new WebAssembly.RuntimeError("unreachable");
+        }
+        return stage2.compileFromPath(pathPtr);
+      },
+      parseFromPath: (pathPtr) => {
+        calls.push("parse");
+        return stage2.parseFromPath!(pathPtr);
+      },
+      setCompileFlags: (flags) => stage2.setCompileFlags(flags),
+    };
+  });
+  const source = "fn main() -> i32 {\n    7\n}\n";
+  const compilation = await compiler.compile(source);
+  expect(compilation.wasm).toEqual((await compile(source)).wasm);
+  expect(calls).toEqual(["instantiate", "load", "load", "load", "load", "load", "parse", "compile"]);
+
+  await expect(compiler.compile("fn main() -> i32 {")).rejects.toThrow(CompileError);
+  await compiler.compile(source);
+  expect(calls.filter((call) => call === "instantiate")).toHaveLength(1);
+
+  crash = true;
+  await expect(compiler.compile(source)).rejects.toThrow(CompilerCrash);
+  expect((await compiler.compile(source)).wasm).toEqual(compilation.wasm);
+  expect(calls.filter((call) => call === "instantiate")).toHaveLength(2);
+});
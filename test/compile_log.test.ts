@@ -0,0 +1,19 @@
+import { expect, test } from "bun:test";
+
+import { compile } from "../src/index";
+
+test("the log follows each phase and the stage2 memory it uses", async () => {
+  const messages: string[] = [];
+  await compile("fn main() -> i32 { 7 }", undefined, {
+    log: (level, message) => messages.push(`${level} ${message}`),
+  });
+  const matching = (pattern: RegExp): string[] => messages.filter((message) => pattern.test(message));
+  expect(matching(/^2 load \/entry\.bp: path at 1024, 22 content bytes at 4096, status 0, storage top \d+$/)).toHaveLength(
+    1,
+  );
+  expect(matching(/^1 compile: status \d+ in [0-9.]+ms$/)).toHaveLength(1);
+  expect(matching(/^2 output at \d+, \d+ of \d+ reserved bytes; AST at \d+ for a 22-byte entry module$/)).toHaveLength(
+    1,
+  );
+  expect(messages.at(-1)).toMatch(/^1 produced \d+ bytes with 0 warnings$/);
+});
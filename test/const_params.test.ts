@@ -112,7 +112,7 @@ test("const parameter templates specialize if expressions", async () => {
     }
   `);
     const result = await runWasmMainWithGc(wasm);
-    expect(result).toBe(15);
+    expect(result).toBe(20);
 });
 
 test("const parameter templates specialize if statement", async () => {
@@ -253,8 +253,12 @@ test("const argument metadata exhaustion reports diagnostic", async () => {
     `}`,
   ];
   const failure = await expectCompileFailure(sourceLines.join("\n"));
+  // Each call's const arguments take room in the AST's call data, so the
+  // failing line follows AST_CALL_DATA_CAPACITY.  It moved from 154 to 352
+  // when the call data and constants together grew from 131072 to 196608
+  // words, after the compiler's own sources outgrew the old size.
   expect(failure.failure.detail).toBe(
-    "/entry.bp:154:5: const argument metadata capacity exceeded",
+    "/entry.bp:352:5: const argument metadata capacity exceeded",
   );
 });
 
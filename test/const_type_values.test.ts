@@ -146,3 +146,18 @@ test("cyclic const type aliases report metadata diagnostics", async () => {
     "/entry.bp:2:11: const initializer type metadata resolution failed for 'First'",
   );
 });
+
+test("type-valued const specializations stay distinct under casts and operators", async () => {
+    const wasm = await compileWithAstCompiler(`
+    fn forward(const T: type, value: T) -> T {
+        value
+    }
+
+    fn main() -> i32 {
+        let wide = forward(i64, 30 as i64) as i32;
+        wide + forward(i32, 12)
+    }
+  `);
+    const result = await runWasmMainWithGc(wasm);
+    expect(result).toBe(42);
+});
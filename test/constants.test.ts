@@ -1,5 +1,6 @@
 import { expect, test } from "bun:test";
 
+import { readConstantTable } from "../src/index";
 import { compileWithAstCompiler, expectCompileFailure, runWasmMainWithGc } from "./helpers";
 
 test("constant main returns literal value", async () => {
@@ -59,6 +60,21 @@ test("non literal constant initializers are evaluated", async () => {
   expect(result).toBe(4);
 });
 
+test("constant initializers evaluate shifts and comparisons", async () => {
+  const wasm = await compileWithAstCompiler(`
+    const WIDTH: i32 = 4;
+    const MASK: i32 = (1 << WIDTH) - 1;
+    const FLOOR: i32 = -64 >> WIDTH;
+    const MATCHES: bool = MASK == 15 && FLOOR < 0 && MASK >= WIDTH * 3;
+
+    fn main() -> i32 {
+        if MATCHES { MASK * 2 + FLOOR } else { 0 }
+    }
+  `);
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(26);
+});
+
 test("non-const function calls in constant initializers are rejected", async () => {
   const failure = await expectCompileFailure(`
     const VALUE: i32 = helper();
@@ -263,6 +279,63 @@ test("const functions can use loops", async () => {
   expect(result).toBe(10);
 });
 
+test("const functions can use do while loops", async () => {
+  const wasm = await compileWithAstCompiler(`
+    const fn odd_sum(limit: i32) -> i32 {
+        let mut acc: i32 = 0;
+        let mut i: i32 = 0;
+        do {
+            i = i + 1;
+            if i % 2 == 0 {
+                continue;
+            };
+            acc = acc + i;
+        } while i < limit;
+        acc
+    }
+
+    const VALUE: i32 = odd_sum(6);
+
+    fn main() -> i32 {
+        VALUE
+    }
+  `);
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(9);
+});
+
+test("const functions can use labeled breaks and continues", async () => {
+  const wasm = await compileWithAstCompiler(`
+    const fn pair_code(target: i32) -> i32 {
+        let mut total: i32 = 0;
+        let mut a: i32 = 0;
+        'outer: while a < 9 {
+            a = a + 1;
+            let mut b: i32 = 0;
+            loop {
+                b = b + 1;
+                if b > a {
+                    continue 'outer;
+                };
+                total = total + 1;
+                if a * b == target {
+                    break 'outer;
+                };
+            };
+        };
+        total * 100 + a
+    }
+
+    const VALUE: i32 = pair_code(12);
+
+    fn main() -> i32 {
+        VALUE
+    }
+  `);
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(904);
+});
+
 test("const functions specialize simple const parameters during interpretation", async () => {
   const wasm = await compileWithAstCompiler(`
     const fn add_count(const COUNT: i32, value: i32) -> i32 {
@@ -466,3 +539,28 @@ test("function names cannot conflict with constants", async () => {
   );
 });
 
+test("constant table section lists scalar constants", async () => {
+  const source = `
+    const BASE: i32 = 40;
+    const LIMIT: u32 = 4294967295;
+    const ENABLED: bool = true;
+    const OFFSET: i32 = -40;
+    const WIDE: i64 = 5000000000;
+    const Word: type = i32;
+
+    fn main() -> i32 {
+        BASE + 2
+    }
+  `;
+  const wasm = await compileWithAstCompiler(source, { constantTable: true });
+  expect(readConstantTable(wasm)).toEqual([
+    { name: "BASE", type: "i32", value: 40 },
+    { name: "LIMIT", type: "u32", value: 4294967295 },
+    { name: "ENABLED", type: "bool", value: 1 },
+    { name: "OFFSET", type: "i32", value: -40 },
+  ]);
+  expect(await runWasmMainWithGc(wasm)).toBe(42);
+
+  const plainWasm = await compileWithAstCompiler(source);
+  expect(readConstantTable(plainWasm)).toEqual([]);
+});
@@ -1,6 +1,8 @@
 import { expect, test } from "bun:test";
 
-import { compileWithAstCompiler, expectCompileFailure, runWasmMainWithGc } from "./helpers";
+import { CompileWarningKind, Target, compile } from "../src/index";
+
+import { compileWithAstCompiler, disassembleWasm, expectCompileFailure, runWasmMainWithGc } from "./helpers";
 
 test("loops and break execute", async () => {
   const wasm = await compileWithAstCompiler(`
@@ -168,6 +170,32 @@ test("else if chains execute", async () => {
   expect(result).toBe(2099);
 });
 
+test("if expressions produce 64-bit and heap values in any position", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn widen(value: i64) -> i64 {
+        value * (2 as i64)
+    }
+
+    fn second(pair: (i32, i32)) -> i32 {
+        pair.1
+    }
+
+    fn pick(flag: bool) -> [i32; 2] {
+        if flag { [1, 2] } else { [3, 4] }
+    }
+
+    fn main() -> i32 {
+        let flag: bool = true;
+        let wide: i64 = widen(if flag { 20 as i64 } else { 0 as i64 });
+        let pair: (i32, i32) = if flag { (1, 2) } else { (3, 4) };
+        let total: u64 = (if flag { 100 as u64 } else { 0 as u64 }) + (5 as u64);
+        wide as i32 + second(if flag { pair } else { (0, 0) }) + pick(false)[1] + total as i32
+    }
+  `);
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(40 + 2 + 4 + 105);
+});
+
 test("if branches with mismatched types report precise diagnostics", async () => {
   const failure = await expectCompileFailure(`
     fn mismatched(flag: bool) -> i32 {
@@ -195,7 +223,54 @@ test("if conditions require boolean values", async () => {
         }
     }
   `);
-  expect(failure.failure.detail).toBe("/entry.bp:3:12: if condition type mismatch");
+  expect(failure.failure.detail).toBe(
+    "/entry.bp:3:12: if condition is an integer, not `bool`; compare it with `!= 0`",
+  );
+
+  const loopFailure = await expectCompileFailure(`
+    fn main() -> i32 {
+        let mut count: i32 = 3;
+        while count {
+            count = count - 1;
+        };
+        count
+    }
+  `);
+  expect(loopFailure.failure.detail).toContain("compare it with `!= 0`");
+
+  const tupleFailure = await expectCompileFailure(`
+    fn main() -> i32 {
+        if (1, 2) {
+            1
+        } else {
+            0
+        }
+    }
+  `);
+  expect(tupleFailure.failure.detail).toBe("/entry.bp:3:12: if condition type mismatch");
+});
+
+test("integerConditions accepts integer conditions as != 0 with a warning", async () => {
+  const source = [
+    "fn main() -> i32 {",
+    "    let mut count: i32 = 3;",
+    "    let mut steps: i32 = 0;",
+    "    while count {",
+    "        count = count - 1;",
+    "        steps = steps + 1;",
+    "    };",
+    "    let wide: i64 = 0 as i64;",
+    "    if wide { 100 } else { steps }",
+    "}",
+    "",
+  ].join("\n");
+  const compilation = await compile(source, Target.Wasm, { integerConditions: true });
+  expect(await runWasmMainWithGc(compilation.wasm)).toBe(3);
+  expect(compilation.warnings.map(({ kind, span }) => [kind, span.line, span.column])).toEqual([
+    [CompileWarningKind.IntegerCondition, 4, 11],
+    [CompileWarningKind.IntegerCondition, 9, 8],
+  ]);
+  expect(compilation.warnings[0]?.message).toBe("integer condition is treated as `!= 0`; compare it explicitly");
 });
 
 test(
@@ -258,7 +333,7 @@ test("loop expressions used as values must break with a value", async () => {
     }
   `);
   expect(failure.failure.detail).toBe(
-    "/entry.bp:5:9: loop expressions used as values must break with a value",
+    "/entry.bp:4:13: loop expressions used as values must break with a value",
   );
 });
 
@@ -334,11 +409,9 @@ test("loop and break support boolean conditions", async () => {
                 total = total + count;
                 count = count + 1;
                 remaining = remaining - 1;
-                0
             } else {
                 break;
-                0
-            };
+            }
         }
         total
     }
@@ -457,18 +530,14 @@ test("nested loops can break with values", async () => {
                     if inner > 0 {
                         total = total + outer;
                         inner = inner - 1;
-                        0
                     } else {
                         break;
-                        0
-                    };
+                    }
                 }
                 outer = outer - 1;
-                0
             } else {
                 break total;
-                0
-            };
+            }
         }
     }
 
@@ -498,6 +567,102 @@ test("diverging if tail statements are allowed", async () => {
   expect(result).toBe(10);
 });
 
+test("blocks ending in return, break, continue, or panic fit any type", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn branch(flag: bool) -> i64 {
+        if flag { return 10 as i64 } else { panic("no") }
+    }
+
+    fn first_over(limit: i32) -> i32 {
+        let mut value: i32 = 0;
+        loop {
+            value = value + 1;
+            let even: i32 = if value % 2 == 0 { value } else { continue };
+            if even > limit {
+                break even
+            }
+        }
+    }
+
+    fn halve(value: i32) -> (i32, bool) {
+        let half: i32 = if value % 2 == 0 { value / 2 } else { return (value, false) };
+        (half, true)
+    }
+
+    fn main() -> i32 {
+        let (half, even) = halve(8);
+        (branch(true) as i32) + first_over(5) + if even { half } else { 0 }
+    }
+  `);
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(10 + 6 + 4);
+});
+
+test("return, break, and continue work as operands", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn widen(value: i64) -> i64 {
+        value
+    }
+
+    fn add(a: i32, b: i32) -> i32 {
+        a + b
+    }
+
+    fn early(v: i32) -> i32 {
+        let doubled: i32 = v * 2 + if v > 2 { return 100 } else { 0 };
+        widen(if doubled > 2 { return doubled } else { 7 as i64 }) as i32
+    }
+
+    fn skip_two(limit: i32) -> i32 {
+        let mut i: i32 = 0;
+        let mut total: i32 = 0;
+        loop {
+            i = i + 1;
+            total = add(total, if i == 2 { continue } else { i });
+            total = total + if i >= limit { break } else { 0 };
+        }
+        total
+    }
+
+    fn main() -> i32 {
+        let tail: i32 = return early(1) + early(2) + early(3) + skip_two(4);
+        tail
+    }
+  `);
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(7 + 4 + 100 + 8);
+});
+
+test("nothing is emitted after a diverging statement", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn early(flag: bool) -> i64 {
+        if flag {
+            return 7 as i64;
+            early(false);
+        } else {
+            8 as i64
+        }
+    }
+
+    fn spin(limit: i32) -> i32 {
+        let mut count: i32 = 0;
+        loop {
+            count = count + 1;
+            if count < limit { continue } else { return count }
+        }
+    }
+
+    fn main() -> i32 {
+        (early(true) as i32) * 10 + spin(3)
+    }
+  `);
+  const lines = disassembleWasm(wasm).split("\n").map((line) => line.trim());
+  const early = lines.slice(lines.indexOf("func early"), lines.indexOf("func spin"));
+  expect(early[early.indexOf("return") + 1]).toBe("else");
+  expect(early.filter((line) => line === "drop")).toEqual([]);
+  expect(await runWasmMainWithGc(wasm)).toBe(70 + 3);
+});
+
 test("break outside loop is rejected", async () => {
   const failure = await expectCompileFailure(`
     fn break_outside_loop() -> i32 {
@@ -590,6 +755,170 @@ test("while loops reject break values", async () => {
   );
 });
 
+test("do while loops run the body before checking the condition", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn main() -> i32 {
+        let mut runs: i32 = 0;
+        do {
+            runs = runs + 1;
+        } while false;
+        let mut value: i32 = 0;
+        do {
+            value = value + 2;
+        } while value < 7;
+        runs * 100 + value
+    }
+  `);
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(108);
+});
+
+test("do while continue re-checks the condition", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn main() -> i32 {
+        let mut total: i32 = 0;
+        let mut value: i32 = 0;
+        do {
+            value = value + 1;
+            if value == 3 {
+                continue;
+            };
+            total = total + value;
+        } while value < 6;
+        total
+    }
+  `);
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(18);
+});
+
+test("do while loops reject break values", async () => {
+  const failure = await expectCompileFailure(`
+    fn attempt() {
+        do {
+            break 1;
+        } while true;
+    }
+  `);
+  expect(failure.failure.detail).toBe(
+    "/entry.bp:4:13: while loops cannot break with values",
+  );
+});
+
+test("do loops require a while condition", async () => {
+  const failure = await expectCompileFailure(`
+    fn attempt() {
+        do {
+            break;
+        };
+    }
+  `);
+  expect(failure.failure.detail).toBe(
+    "/entry.bp:5:10: expected \`while\` after \`do\` loop body",
+  );
+});
+
+test("labeled breaks leave outer loops with values", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn find(target: i32) -> i32 {
+        let mut row: i32 = 0;
+        let found: i32 = 'rows: loop {
+            let mut col: i32 = 0;
+            loop {
+                if row * col == target {
+                    break 'rows row * 10 + col;
+                };
+                if col == 9 {
+                    break;
+                };
+                col = col + 1;
+            };
+            row = row + 1;
+            if row == 10 {
+                break -1;
+            };
+        };
+        found
+    }
+
+    fn main() -> i32 {
+        find(12) * 1000 + find(49)
+    }
+  `);
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(26077);
+});
+
+test("labeled continue resumes an outer while loop", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn main() -> i32 {
+        let mut total: i32 = 0;
+        let mut i: i32 = 0;
+        'outer: while i < 4 {
+            i = i + 1;
+            let mut j: i32 = 0;
+            do {
+                j = j + 1;
+                if j > i {
+                    continue 'outer;
+                };
+                total = total + j;
+            } while true;
+        };
+        total
+    }
+  `);
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(20);
+});
+
+test("breaks and continues can target any enclosing loop", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn main() -> i32 {
+        let mut hits: i32 = 0;
+        let mut a: i32 = 0;
+        'a: loop {
+            a = a + 1;
+            if a > 3 {
+                break;
+            };
+            let mut b: i32 = 0;
+            'b: loop {
+                b = b + 1;
+                if b > 3 {
+                    continue 'a;
+                };
+                let mut c: i32 = 0;
+                loop {
+                    c = c + 1;
+                    if c > b {
+                        continue 'b;
+                    };
+                    if a * 100 + b * 10 + c == 233 {
+                        break 'a;
+                    };
+                    hits = hits + 1;
+                };
+            };
+        };
+        hits * 10 + a
+    }
+  `);
+  const result = await runWasmMainWithGc(wasm);
+  expect(result).toBe(112);
+});
+
+test("unknown loop labels are rejected", async () => {
+  const failure = await expectCompileFailure(`
+    fn attempt() {
+        'outer: loop {
+            break 'inner;
+        };
+    }
+  `);
+  expect(failure.failure.detail).toBe("/entry.bp:4:19: unknown loop label");
+});
+
 test("nested if expressions execute", async () => {
   const wasm = await compileWithAstCompiler(`
     fn pick(a: bool, b: bool) -> i32 {
@@ -0,0 +1,126 @@
+import { expect, test } from "bun:test";
+
+import { compile, readImplicitModule } from "../src/index";
+import { expectExportedFunction, instantiateWasmModuleWithGc } from "./helpers";
+
+test("programs call the core library without a use", async () => {
+  const source = `
+fn round_trip(value: i32) -> i32 {
+    let len: i32 = format_i32(value, 64);
+    parse_i32(64, len) + len * 1000000
+}
+
+fn shifted() -> i32 {
+    mem_fill(128, 0, 8);
+    mem_fill(128, 1, 1);
+    mem_fill(129, 2, 1);
+    mem_fill(130, 3, 1);
+    mem_copy(129, 128, 3);
+    bytes_index_of(128, 8, 3) * 10 + cstr_len(128)
+}
+
+fn abs(x: i32) -> i32 {
+    x * 3
+}
+
+fn divisor() -> i32 {
+    gcd(4, 6) + clamp(pow(2, 5), 0, 20)
+}
+`;
+  const compilation = await compile(source);
+  const instance = await instantiateWasmModuleWithGc(compilation.wasm);
+  expect(expectExportedFunction(instance, "round_trip")(-2147483648)).toBe(11000000 - 2147483648);
+  expect(expectExportedFunction(instance, "round_trip")(0)).toBe(1000000);
+  expect(expectExportedFunction(instance, "shifted")(0)).toBe(34);
+  // The program's `abs` replaces the core one, which `gcd` calls too.
+  expect(expectExportedFunction(instance, "divisor")(0)).toBe(6 + 20);
+  const exports = WebAssembly.Module.exports(new WebAssembly.Module(compilation.wasm)).map((entry) => entry.name);
+  expect(exports).toContain("bytes_index_of");
+  expect(exports).not.toContain("mem_equal");
+  expect(exports).not.toContain("bytes_starts_with");
+
+  const plain = await compile("fn main() -> i32 { 1 }");
+  const freestanding = await compile("fn main() -> i32 { 1 }", undefined, { noStd: true });
+  expect(plain.wasm).toEqual(freestanding.wasm);
+  await expect(compile("fn main() -> i32 { max(1, 2) }", undefined, { noStd: true })).rejects.toThrow(
+    "call references undefined function",
+  );
+});
+
+test("the prelude stays available without the core library", async () => {
+  const source = `
+fn grows() -> i32 {
+    grow_to(65536) * 10 + grow_to(1048576 * 17)
+}
+
+fn label(value: i32) -> i32 {
+    let len: i32 = int_to_str(value, 4096);
+    copy_bytes(4097, 4096, len);
+    len * 100 + cstr_len(4096)
+}
+
+fn cstr_len(ptr: i32) -> i32 {
+    let mut len: i32 = 0;
+    while load_byte(ptr + len) != 0 {
+        len = len + 1;
+    };
+    len
+}
+
+fn load_byte(ptr: i32) -> i32 {
+    inline_wasm([0x20, 0x00, 0x2d, 0x00, 0x00])
+}
+`;
+  const compilation = await compile(source, undefined, { noStd: true });
+  const instance = await instantiateWasmModuleWithGc(compilation.wasm);
+  // Memory is already at its 16 MiB maximum.
+  expect(expectExportedFunction(instance, "grows")()).toBe(-1);
+  expect(expectExportedFunction(instance, "label")(-305)).toBe(405);
+  expect(expectExportedFunction(instance, "label")(-2147483648)).toBe(1112);
+});
+
+test("indented and const user functions replace core ones", async () => {
+  const source = `
+    fn abs(x: i32) -> i32 {
+        x * 3
+    }
+
+const fn min(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+fn main() -> i32 {
+    abs(-2) * 100 + min(1, 2) * 10 + gcd(4, 6)
+}
+`;
+  const instance = await instantiateWasmModuleWithGc((await compile(source)).wasm);
+  // `gcd` takes the program's `abs` too, so it sees 12 and 18.
+  expect(expectExportedFunction(instance, "main")()).toBe(-600 + 30 + 6);
+
+  const unclosed = { path: "/stdlib/broken.bp", source: "fn one() -> i32 {\n    1\n\nfn two() -> i32 {\n    2\n}\n" };
+  await expect(readImplicitModule(unclosed)).rejects.toThrow("/stdlib/broken.bp:4:1: parsing source failed");
+});
+
+test("only calls the parser resolves to no function pull in core ones", async () => {
+  const source = `
+use "./math.bp";
+
+fn twice(const min: fn(i32) -> i32, x: i32) -> i32 {
+    min(min(x))
+}
+
+fn main() -> i32 {
+    let text: [u8; 8] = "gcd(4,6)";
+    let magnitude: i32 = (0 - 5).abs();
+    magnitude + clamp(text[0] as i32) + sign(-3)
+}
+`;
+  const modules = [{ path: "/math.bp", source: "fn clamp(value: i32) -> i32 {\n    value / 10\n}\n" }];
+  const compilation = await compile(source, undefined, { modules });
+  const instance = await instantiateWasmModuleWithGc(compilation.wasm);
+  expect(expectExportedFunction(instance, "main")()).toBe(5 + 10 - 1);
+  // A call through a parameter, a method, and a string do not name core
+  // functions, and the imported `clamp` replaces the core one.
+  const exports = WebAssembly.Module.exports(new WebAssembly.Module(compilation.wasm)).map((entry) => entry.name);
+  expect(exports).toEqual(["memory", "sign", "clamp", "main"]);
+});
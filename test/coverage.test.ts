@@ -0,0 +1,63 @@
+import { expect, test } from "bun:test";
+
+import { COVERAGE_TABLE_EXPORT, CompileError, Target, compile, formatLcov } from "../src/index";
+
+const SOURCE = `fn square(x: i32) -> i32 {
+    x * x
+}
+
+fn sum_squares(n: i32) -> i32 {
+    let mut total: i32 = 0;
+    let mut i: i32 = 1;
+    while i <= n {
+        total = total + square(i);
+        i = i + 1;
+    };
+    total
+}
+
+fn clamp(x: i32) -> i32 {
+    if x > 100 {
+        return 100;
+    };
+    x
+}
+
+fn main() -> i32 {
+    clamp(sum_squares(3))
+}
+`;
+
+test("coverage counts executed blocks and reports them per line as lcov", async () => {
+  const compilation = await compile(SOURCE, Target.Wasm, { coverage: true });
+  const { instance } = await WebAssembly.instantiate(compilation.wasm, {});
+  expect(typeof (instance.exports[COVERAGE_TABLE_EXPORT] as WebAssembly.Global).value).toBe("number");
+  expect((instance.exports.main as () => number)()).toBe(14);
+
+  const report = compilation.readCoverage(instance)!;
+  const calls = new Map(report.functions.map((coverage) => [coverage.name, coverage.calls]));
+  expect([calls.get("main"), calls.get("sum_squares"), calls.get("square"), calls.get("clamp")]).toEqual([
+    1, 1, 3, 1,
+  ]);
+  expect(report.functions.find((coverage) => coverage.name === "square")?.span?.line).toBe(1);
+  const lines = new Map(
+    report.lines.filter((line) => line.path === "/entry.bp").map((line) => [line.line, line.count]),
+  );
+  expect([lines.get(2), lines.get(9), lines.get(16), lines.get(17)]).toEqual([3, 3, 1, 0]);
+
+  // A later instance's counts add to an earlier report.
+  const again = (await WebAssembly.instantiate(compilation.wasm, {})).instance;
+  (again.exports.main as () => number)();
+  const total = compilation.readCoverage(again, report)!;
+  expect(total.functions.find((coverage) => coverage.name === "square")?.calls).toBe(6);
+
+  const lcov = formatLcov(report, { entryPath: "main.bp" });
+  expect(lcov).toContain("SF:main.bp\nFN:1,square\n");
+  expect(lcov).toContain("FNDA:3,square\n");
+  expect(lcov).toContain("DA:17,0\n");
+  expect(lcov.endsWith("end_of_record\n")).toBe(true);
+
+  const plain = await compile(SOURCE);
+  expect(plain.readCoverage((await WebAssembly.instantiate(plain.wasm, {})).instance)).toBeNull();
+  await expect(compile(SOURCE, Target.Wgsl, { coverage: true })).rejects.toThrow(CompileError);
+});
@@ -0,0 +1,69 @@
+import { expect, test } from "bun:test";
+import { createHash } from "node:crypto";
+
+import { Compiler, Target, compileToWasm } from "../src/index";
+import type { CompileOptions, CompilerModuleSource } from "../src/index";
+
+const MODULES: CompilerModuleSource[] = [
+  {
+    path: "/lib/math.bp",
+    source: `
+      const SCALE: i32 = 3;
+
+      fn scale(value: i32) -> i32 {
+          value * SCALE
+      }
+    `,
+  },
+  {
+    path: "/lib/text.bp",
+    source: `
+      fn greeting() -> [u8; 5] {
+          "hello"
+      }
+
+      fn first_letter() -> i32 {
+          greeting()[0] as i32
+      }
+    `,
+  },
+];
+
+const SOURCE = `
+  use "/lib/math.bp";
+  use "/lib/text.bp";
+
+  fn fib(n: i32) -> i32 {
+      if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+  }
+
+  fn main() -> i32 {
+      let mut total: i32 = 0;
+      let mut index: i32 = 0;
+      loop {
+          if index >= 10 {
+              break;
+          };
+          total = total + scale(fib(index));
+          index = index + 1;
+      };
+      total + first_letter()
+  }
+`;
+
+function sha256(wasm: Uint8Array): string {
+  return createHash("sha256").update(wasm).digest("hex");
+}
+
+test("compiling the same program gives the same bytes every time", async () => {
+  const options: CompileOptions = { modules: MODULES, constantTable: true };
+  const expected = sha256(await compileToWasm(SOURCE, options));
+  for (let run = 0; run < 100; run += 1) {
+    expect(sha256(await compileToWasm(SOURCE, options))).toBe(expected);
+  }
+
+  const fresh = await Compiler.create();
+  expect(sha256((await fresh.compile(SOURCE, Target.Wasm, options)).wasm)).toBe(expected);
+  const reordered = { ...options, modules: [...MODULES].reverse() };
+  expect(sha256(await compileToWasm(SOURCE, reordered))).toBe(expected);
+}, { timeout: 60_000 });
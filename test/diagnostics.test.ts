@@ -0,0 +1,43 @@
+import { expect, test } from "bun:test";
+import { readdir } from "node:fs/promises";
+
+import { CompileError, compile, formatCompileError, formatCompileWarning } from "../src/index";
+
+// Each `test/diagnostics/<name>.bp` is compiled and its rendered diagnostics
+// are compared with `<name>.stderr`. Run with `BOOTSTRAP_UPDATE_GOLDEN=1` to
+// rewrite the expected files, then review the diff.
+const CORPUS_URL = new URL("./diagnostics/", import.meta.url);
+const UPDATE_GOLDEN = process.env.BOOTSTRAP_UPDATE_GOLDEN === "1";
+
+async function renderDiagnostics(name: string, source: string): Promise<string> {
+  const options = { entryPath: `${name}.bp` };
+  try {
+    const compilation = await compile(source);
+    return compilation.warnings.map((warning) => formatCompileWarning(warning, options)).join("\n\n");
+  } catch (error) {
+    if (!(error instanceof CompileError)) {
+      throw error;
+    }
+    return formatCompileError(error, options);
+  }
+}
+
+const names = (await readdir(CORPUS_URL))
+  .filter((file) => file.endsWith(".bp"))
+  .map((file) => file.slice(0, -".bp".length))
+  .sort();
+
+for (const name of names) {
+  test(`renders diagnostics for ${name}`, async () => {
+    const source = await Bun.file(new URL(`${name}.bp`, CORPUS_URL)).text();
+    const rendered = `${await renderDiagnostics(name, source)}\n`;
+    const golden = Bun.file(new URL(`${name}.stderr`, CORPUS_URL));
+    if (UPDATE_GOLDEN) {
+      await Bun.write(golden, rendered);
+    }
+    if (!(await golden.exists())) {
+      throw new Error(`${name}.stderr is missing; run with BOOTSTRAP_UPDATE_GOLDEN=1 to create it`);
+    }
+    expect(rendered).toBe(await golden.text());
+  });
+}
@@ -0,0 +1,98 @@
+import { expect, test } from "bun:test";
+
+import { compile, evaluate, runTests } from "../src/index";
+
+test("evaluates programs into plain results", async () => {
+  const ok = await evaluate(
+    "fn main() -> i64 {\n    let unused: i32 = 1;\n    let base: i64 = 100000 as i64;\n    base * base\n}\n",
+  );
+  expect(ok.status).toBe("ok");
+  expect(ok.value).toBe("10000000000");
+  expect(ok.warnings).toHaveLength(1);
+  expect(ok.warnings[0]).toMatch(/^warning: unused variable `unused`/);
+  expect(JSON.parse(JSON.stringify(ok))).toEqual(ok);
+
+  const failed = await evaluate("fn main() -> i32 {\n    missing\n}\n", {
+    format: { entryPath: "main.bp" },
+  });
+  expect(failed.status).toBe("compile-error");
+  expect(failed.error).toContain(" --> main.bp:2:5");
+  expect(failed.value).toBeUndefined();
+
+  const panicked = await evaluate('fn main() -> i32 {\n    panic("boom")\n}\n');
+  expect(panicked.status).toBe("runtime-error");
+  expect(panicked.error).toBe("panicked at /entry.bp:2:5: boom");
+  expect(panicked.panic).toEqual({ message: "boom", path: "/entry.bp", line: 2, column: 5 });
+});
+
+test("runs #[test] functions each in a fresh instance", async () => {
+  const source = [
+    'use "/stdlib/memory.bp";',
+    "",
+    "const LIMIT: i32 = 4;",
+    "",
+    "#[test]",
+    "fn counts_up() {",
+    "    let mut total: i32 = 0;",
+    "    let mut i: i32 = 0;",
+    "    while i < LIMIT {",
+    "        total = total + i;",
+    "        i = i + 1;",
+    "    };",
+    "    assert(total == 6);",
+    "}",
+    "",
+    "#[test] #[ignored(reason)]",
+    "fn fails() {",
+    "    assert(LIMIT == 5);",
+    "}",
+    "",
+    "#[test]",
+    "fn writes_memory() {",
+    "    assert(load_i32(64) == 0);",
+    "    store_i32(64, 1);",
+    "}",
+    "",
+    "#[test]",
+    "fn writes_memory_again() {",
+    "    assert(load_i32(64) == 0);",
+    "    store_i32(64, 1);",
+    "}",
+    "",
+    "#[test]",
+    "fn takes_argument(x: i32) {",
+    "    assert(x == 0);",
+    "}",
+  ].join("\n");
+  const compilation = await compile(source);
+  expect(compilation.tests).toEqual([
+    "counts_up",
+    "fails",
+    "writes_memory",
+    "writes_memory_again",
+    "takes_argument",
+  ]);
+  expect(compilation.attributes[2]).toEqual({
+    name: "ignored",
+    arguments: "reason",
+    span: { path: "/entry.bp", line: 16, column: 11, offset: source.indexOf("ignored") },
+    item: "fails",
+  });
+  expect(compilation.warnings.map((warning) => warning.message)).toEqual([
+    "unknown attribute `ignored`",
+  ]);
+
+  const results = await runTests(compilation);
+  expect(results.map(({ name, passed }) => [name, passed])).toEqual([
+    ["counts_up", true],
+    ["fails", false],
+    ["writes_memory", true],
+    ["writes_memory_again", true],
+    ["takes_argument", false],
+  ]);
+  expect(results[1]?.error).toBe("panicked at /entry.bp:18:5: assertion failed");
+  expect(results[4]?.error).toContain("takes parameters");
+
+  const filtered = await runTests(compilation, { filter: "again" });
+  expect(filtered).toEqual([{ name: "writes_memory_again", passed: true }]);
+});
@@ -0,0 +1,51 @@
+import { expect, test } from "bun:test";
+
+import { compile, readSizeReport, stripUnusedMemory } from "../src/index";
+import { runWasmFunctionWithFuel, runWasmMainWithGc } from "./helpers";
+
+const HELLO_URL = new URL("../examples/hello.bp", import.meta.url);
+const MANDELBROT_URL = new URL("../examples/mandelbrot.bp", import.meta.url);
+
+test("mandelbrot renders the set into linear memory", async () => {
+  const wasm = (await compile(await Bun.file(MANDELBROT_URL).text())).wasm;
+  const { instance } = await WebAssembly.instantiate(wasm, {});
+  const exports = instance.exports as Record<string, unknown>;
+  const render = exports.render as (width: number, height: number, iterations: number) => number;
+  const memory = exports.memory as WebAssembly.Memory;
+
+  const [width, height] = [35, 25];
+  const pixels = new Uint8Array(memory.buffer, render(width, height, 64), width * height * 3);
+  const pixel = (x: number, y: number): number[] =>
+    Array.from(pixels.subarray((y * width + x) * 3, (y * width + x + 1) * 3));
+  // The origin is in the set and the top left corner escapes at once.
+  expect(pixel(25, 12)).toEqual([0, 0, 0]);
+  expect(pixel(0, 0)).toEqual([3, 12, 252]);
+
+  // Rendering is a benchmark fixture too, so a codegen regression in the
+  // inner loop shows up as a higher instruction count.
+  const { result, instructions } = await runWasmFunctionWithFuel(wasm, "main", []);
+  expect(result).toBe(430841);
+  expect(instructions).toBeLessThanOrEqual(1_200_000);
+});
+
+test("optimized examples drop memory that no code uses", async () => {
+  const hello = await Bun.file(HELLO_URL).text();
+  const plain = (await compile(hello)).wasm;
+  const optimized = (await compile(hello, undefined, { optimize: true })).wasm;
+  // The memory section and the `memory` export are 8 and 9 bytes.
+  expect(stripUnusedMemory(plain).wasm.length).toBe(plain.length - 17);
+  expect(optimized.length).toBeLessThan(plain.length - 17);
+  expect(readSizeReport(optimized).sections.map((section) => section.name)).toEqual([
+    "type",
+    "function",
+    "export",
+    "code",
+  ]);
+  const { instance } = await WebAssembly.instantiate(optimized, {});
+  expect(Object.keys(instance.exports)).toEqual(["add", "main"]);
+  expect(await runWasmMainWithGc(optimized)).toBe(42);
+
+  const mandelbrot = (await compile(await Bun.file(MANDELBROT_URL).text(), undefined, { optimize: true })).wasm;
+  expect(stripUnusedMemory(mandelbrot).wasm).toBe(mandelbrot);
+  expect(readSizeReport(mandelbrot).sections.map((section) => section.name)).toContain("memory");
+});
@@ -0,0 +1,48 @@
+import { expect, test } from "bun:test";
+
+import { compileExpression } from "../src/expression";
+
+test("expressions compile to standalone evaluators", async () => {
+  const scaled = await compileExpression("base * factor + 1", [
+    { name: "base", type: "i32" },
+    { name: "factor", type: "i32" },
+  ]);
+  expect(scaled.returns).toBe("i32");
+  const evaluate = await scaled.evaluator();
+  expect(evaluate(6, 7)).toBe(43);
+  expect(evaluate(-3, 5)).toBe(-14);
+
+  const rule = await compileExpression(
+    "let limit: i64 = fifth(budget);\n    spent < limit && enabled",
+    [
+      { name: "spent", type: "i64" },
+      { name: "budget", type: "i64" },
+      { name: "enabled", type: "bool" },
+    ],
+    { prelude: "fn fifth(value: i64) -> i64 {\n    value / 5 as i64\n}" },
+  );
+  expect(rule.returns).toBe("bool");
+  const allowed = await rule.evaluator();
+  expect(allowed(999, 5000, true)).toBe(true);
+  expect(allowed(1001n, 5000n, true)).toBe(false);
+  expect(allowed(1, 5000, false)).toBe(false);
+
+  const wide = await (await compileExpression("x * 2 as u32", [{ name: "x", type: "u32" }])).evaluator();
+  expect(wide(3_000_000_000)).toBe(1_705_032_704);
+});
+
+test("expression errors surface as compile errors and panics", async () => {
+  await expect(compileExpression("missing + 1")).rejects.toThrow("identifier not found");
+  await expect(compileExpression("1", [{ name: "1x", type: "i32" }])).rejects.toThrow(
+    "expression parameter name '1x' is not an identifier",
+  );
+  await expect(compileExpression("true", [], { returns: "i32" })).rejects.toThrow(
+    "return expression type does not match function return type",
+  );
+
+  const checked = await compileExpression("assert(x > 0);\n    x", [{ name: "x", type: "i32" }]);
+  const evaluate = await checked.evaluator();
+  expect(evaluate(4)).toBe(4);
+  expect(() => evaluate(0)).toThrow("panicked at");
+  expect(() => evaluate()).toThrow("expression takes 1 arguments but got 0");
+});
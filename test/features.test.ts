@@ -0,0 +1,115 @@
+import { expect, test } from "bun:test";
+
+import { CompileError, CompileErrorKind, MVP_WASM_FEATURES, Target, compile, findDisabledFeatureUse } from "../src/index";
+import { disassembleWasm, expectExportedFunction, instantiateWasmModuleWithGc } from "./helpers";
+
+test("sign extension uses extend8_s unless sign-ext is disabled", async () => {
+  const source = `fn narrow(x: i32) -> i32 {
+    (x as i8) as i32 + (x as i16) as i32
+}
+
+fn main() -> i32 {
+    narrow(200)
+}
+`;
+  const current = await compile(source);
+  const mvp = await compile(source, Target.Wasm, { features: MVP_WASM_FEATURES });
+  const current_text = disassembleWasm(current.wasm);
+  expect(current_text).toContain("i32.extend8_s");
+  expect(current_text).toContain("i32.extend16_s");
+  expect(disassembleWasm(mvp.wasm)).not.toContain("extend8_s");
+  expect(mvp.wasm.length).toBeGreaterThan(current.wasm.length);
+  expect(findDisabledFeatureUse(mvp.wasm, MVP_WASM_FEATURES)).toBeNull();
+  // Staging a source much larger than a page leaves the features alone.
+  const padded = await compile(`${"// padding\n".repeat(1000)}${source}`, Target.Wasm, {
+    features: MVP_WASM_FEATURES,
+  });
+  expect(disassembleWasm(padded.wasm)).not.toContain("extend8_s");
+  for (const { wasm } of [current, mvp]) {
+    const narrow = expectExportedFunction(await instantiateWasmModuleWithGc(wasm), "narrow");
+    expect(narrow(200)).toBe(-56 + 200);
+    expect(narrow(40000)).toBe(64 - 25536);
+  }
+});
+
+test("code that needs a disabled feature is rejected in its function", async () => {
+  const source = `fn copy(dest: i32, src: i32, len: i32) {
+    inline_wasm([0x20, 0x00, 0x20, 0x01, 0x20, 0x02, 0xfc, 0x0a, 0x00, 0x00, 0x41, 0x00])
+}
+
+fn main() -> i32 {
+    copy(64, 32, 4);
+    0
+}
+`;
+  await compile(source, Target.Wasm, { features: { signExt: false } });
+  const error = await compile(source, Target.Wasm, { features: { bulkMemory: false } }).catch(
+    (caught: unknown) => caught,
+  );
+  expect(error).toBeInstanceOf(CompileError);
+  const failure = error as CompileError;
+  expect(failure.kind).toBe(CompileErrorKind.Emit);
+  expect(failure.record?.message).toBe("emitted wasm needs the disabled bulk-memory feature in function 'copy'");
+  expect(failure.span?.line).toBe(1);
+
+  const arrays = `fn main() -> i32 {
+    let values: [i32; 2] = [1, 2];
+    values[0]
+}
+`;
+  await expect(compile(arrays, Target.Wasm, { features: { referenceTypes: false } })).rejects.toThrow(
+    "needs the disabled reference-types feature",
+  );
+});
+
+test("simd operators scan bytes and need the simd feature", async () => {
+  const source = `use "/stdlib/memory.bp";
+use "/stdlib/simd.bp";
+
+fn find_byte(ptr: i32, needle: i32) -> i32 {
+    ctz(i8x16_bitmask(i8x16_eq(v128_load(ptr), i8x16_splat(needle))))
+}
+
+fn lanes() -> i32 {
+    let sum: v128 = i32x4_add(i32x4(1, 2, 3, 4), i32x4_mul(i32x4_splat(10), i32x4(1, 2, 3, 4)));
+    i32x4_extract_lane(sum, 0) + i32x4_extract_lane(sum, 3) * 100 + i32x4_extract_lane(sum, 6) * 10000
+}
+
+fn main() -> i32 {
+    store_i32(256, 0x64636261);
+    store_i32(260, 0x20202020);
+    find_byte(256, 0x20) + find_byte(256, 0x7a) * 100
+}
+`;
+  const compilation = await compile(source);
+  expect(disassembleWasm(compilation.wasm)).toContain("v128");
+  const instance = await instantiateWasmModuleWithGc(compilation.wasm);
+  expect(expectExportedFunction(instance, "main")()).toBe(4 + 32 * 100);
+  expect(expectExportedFunction(instance, "lanes")()).toBe(11 + 44 * 100 + 33 * 10000);
+
+  await expect(compile(source, Target.Wasm, { features: { simd: false } })).rejects.toThrow(
+    "needs the disabled simd feature",
+  );
+});
+
+test("shared memory and atomics need the threads feature", async () => {
+  const source = `use "/stdlib/atomics.bp";
+
+fn main() -> i32 {
+    atomic_store(1024, 5);
+    atomic_rmw_add(1024, 3) + atomic_load(1024) * 100
+}
+`;
+  const shared = await compile(source, Target.Wasm, { sharedMemory: true });
+  expect(findDisabledFeatureUse(shared.wasm, { threads: false })?.feature).toBe("threads");
+  const plain = await compile(source);
+  expect(shared.wasm.length).toBe(plain.wasm.length);
+  expect(disassembleWasm(plain.wasm)).toContain("0xfe 30 02 00");
+
+  await expect(compile(source, Target.Wasm, { features: { threads: false } })).rejects.toThrow(
+    "needs the disabled threads feature in function 'atomic_load'",
+  );
+  await expect(
+    compile(`fn main() -> i32 {\n    0\n}\n`, Target.Wasm, { sharedMemory: true, features: { threads: false } }),
+  ).rejects.toThrow("needs the disabled threads feature");
+});
@@ -0,0 +1,71 @@
+import { expect, test } from "bun:test";
+
+import { BUILTIN_TYPES, childExpressions, lowerToHir, parseProgram, walkProgram } from "../src/frontend";
+import type { Expression, Program } from "../src/frontend";
+import { CompileError } from "../src/index";
+
+const SOURCE = `const LIMIT: i32 = 10;
+
+fn scale(const factor: i32, x: i32) -> i32 {
+    x * factor
+}
+
+fn main() -> i32 {
+    let mut total: i32 = 0;
+    let pair: (i32, bool) = (3, true);
+    while total < LIMIT {
+        total = total + scale(2, pair.0);
+    };
+    if !pair.1 {
+        panic("unreachable");
+    };
+    total
+}
+`;
+
+function collect(program: Program, name: string): Expression[] {
+  const found: Expression[] = [];
+  walkProgram(program, {
+    enterExpression(expression, item) {
+      if (item.name === name) {
+        found.push(expression);
+      }
+    },
+  });
+  return found;
+}
+
+test("parseProgram returns the typed tree the parser built", async () => {
+  const program = await parseProgram(SOURCE);
+  expect(program.functions.map((item) => item.name)).toEqual(["scale", "main"]);
+  const [scale] = program.functions;
+  expect(scale!.params).toEqual([
+    { name: "factor", type: 0, isConst: true },
+    { name: "x", type: 0, isConst: false },
+  ]);
+  expect(scale!.body.kind === "binary" && scale!.body.operator).toBe("*");
+  expect(childExpressions(scale!.body).map((child) => child.kind === "param" && child.param)).toEqual([1, 0]);
+
+  const kinds = new Set(collect(program, "main").map((expression) => expression.kind));
+  for (const kind of ["let", "loop", "break", "assign", "call", "tuple", "tuple-get", "not", "panic"]) {
+    expect(kinds.has(kind as Expression["kind"])).toBe(true);
+  }
+  const call = collect(program, "main").find((expression) => expression.kind === "call");
+  expect(call?.kind === "call" && [call.name, call.args.length, call.callee]).toEqual(["scale", 2, -1]);
+  const panic = collect(program, "main").find((expression) => expression.kind === "panic");
+  expect(panic?.kind === "panic" && panic.message).toBe("unreachable");
+});
+
+test("lowerToHir resolves calls, assigns types, and adds specializations", async () => {
+  const program = await lowerToHir(SOURCE);
+  expect(program.functions.length).toBe(3);
+  const call = collect(program, "main").find((expression) => expression.kind === "call");
+  expect(call?.kind === "call" && program.functions[call.callee]?.params.length).toBe(1);
+  const condition = collect(program, "main").find(
+    (expression) => expression.kind === "binary" && expression.operator === "<",
+  );
+  expect(BUILTIN_TYPES[condition!.type]).toBe("bool");
+
+  await expect(lowerToHir("fn main() -> i32 { true }")).rejects.toThrow(CompileError);
+  expect((await parseProgram("fn main() -> i32 { true }")).functions.length).toBe(1);
+});
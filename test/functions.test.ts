@@ -13,6 +13,7 @@ import {
   readFunctionCount,
   readFunctionEntry,
   readModuleStorageTop,
+  readAttributeRecords,
   AST_COMPILER_ENTRY_PATH,
 } from "./helpers";
 
@@ -113,12 +114,12 @@ test("duplicate function parameter names report diagnostics", async () => {
 });
 
 test("parser reports diagnostic when function limit is exceeded", async () => {
-  const functionCount = 1_025;
+  const functionCount = 2_049;
   const source = Array.from({ length: functionCount }, (_, index) => {
     return `fn f${index}() -> i32 {\n    ${index}\n}`;
   }).join("\n\n");
   const failure = await expectCompileFailure(source);
-  expect(failure.failure.detail).toBe("/entry.bp:4097:1: function limit exceeded");
+  expect(failure.failure.detail).toBe("/entry.bp:8193:1: function limit exceeded");
 });
 
 test("functions may omit return types", async () => {
@@ -294,6 +295,56 @@ test("unit function results cannot initialize locals", async () => {
   );
 });
 
+test("strict mode rejects ignored call results", async () => {
+  const failure = await expectCompileFailure(
+    `
+    fn status() -> i32 {
+        1
+    }
+
+    fn main() -> i32 {
+        status();
+        0
+    }
+  `,
+    { strict: true },
+  );
+  expect(failure.failure.detail).toBe(
+    "/entry.bp:7:9: unused result of value-returning call; wrap it in `discard(...)`",
+  );
+});
+
+test("strict mode accepts discarded and unit call results", async () => {
+  const source = `
+    fn status(value: i32) -> i32 {
+        value
+    }
+
+    fn helper() {
+    }
+
+    fn main() -> i32 {
+        helper();
+        discard(status(3));
+        let ignored: i32 = discard(status(4));
+        status(5)
+    }
+  `;
+  const strictWasm = await compileWithAstCompiler(source, { strict: true });
+  expect(await runWasmMainWithGc(strictWasm)).toBe(5);
+  const wasm = await compileWithAstCompiler(`
+    fn status() -> i32 {
+        1
+    }
+
+    fn main() -> i32 {
+        status();
+        2
+    }
+  `);
+  expect(await runWasmMainWithGc(wasm)).toBe(2);
+});
+
 test(
   "block expression results must respect declared return types",
   async () => {
@@ -405,6 +456,39 @@ test("doc attributes are recorded in function entries", async () => {
   expect(result).toBe(42);
 });
 
+test("attributes before functions and constants are recorded with spans", async () => {
+  const compiler = await instantiateAstCompiler();
+  const source = [
+    "#[inline]",
+    "fn add(a: i32) -> i32 {",
+    "    a + 1",
+    "}",
+    "",
+    "#[repr(i32, \")\")] #[align(debug)]",
+    "const BASE: i32 = 41;",
+    "",
+    "#[doc = \"Entry point\"]",
+    "fn main() -> i32 {",
+    "    add(BASE)",
+    "}",
+  ].join("\n");
+  const wasm = compiler.compileAt(COMPILER_INPUT_PTR, DEFAULT_OUTPUT_STRIDE, source);
+  const records = readAttributeRecords(compiler.memory, source);
+  expect(records.map(({ name, args, itemStart }) => [name, args, itemStart])).toEqual([
+    ["inline", null, source.indexOf("fn add")],
+    ["repr", "i32, \")\"", source.indexOf("const BASE")],
+    ["align", "debug", source.indexOf("const BASE")],
+  ]);
+  expect(records[0]?.nameStart).toBe(2);
+  expect(await runWasmMainWithGc(wasm)).toBe(42);
+});
+
+test("malformed attributes fail to parse", async () => {
+  await expectCompileFailure("#[]\nfn main() -> i32 { 1 }");
+  await expectCompileFailure("#[cfg(debug]\nfn main() -> i32 { 1 }");
+  await expectCompileFailure("fn main() -> i32 { 1 }\n#[inline]");
+});
+
 test("ast compiler source can be compiled once", async () => {
   const compiler = await instantiateAstCompiler();
   const modules = await readAstCompilerModules();
@@ -0,0 +1,43 @@
+import { expect, test } from "bun:test";
+
+import { fuzz, fuzzCompile } from "../src/fuzz";
+import { CompileError, MAX_NESTING_DEPTH, compile } from "../src/index";
+
+test("deep nesting is rejected instead of crashing the compiler", async () => {
+  const nested = (depth: number): string =>
+    `fn main() -> i32 {\n    ${"(".repeat(depth)}1${")".repeat(depth)}\n}\n`;
+  expect(await fuzzCompile(nested(200))).toBe("accepted");
+  for (const depth of [300, 100_000]) {
+    expect(await fuzzCompile(nested(depth))).toBe("rejected");
+  }
+  const blocks = `fn main() -> i32 {\n    ${"{ ".repeat(2_000)}1${" }".repeat(2_000)}\n}\n`;
+  const error = await compile(blocks).catch((caught: unknown) => caught);
+  expect(error).toBeInstanceOf(CompileError);
+  expect((error as CompileError).record?.message).toBe("expression too deeply nested");
+});
+
+test("the nesting limit is configurable", async () => {
+  const source = `fn main() -> i32 {\n    if true {\n        ((((1))))\n    } else {\n        0\n    }\n}\n`;
+  expect((await compile(source, undefined, { maxNestingDepth: 6 })).wasm.length).toBeGreaterThan(0);
+  const error = await compile(source, undefined, { maxNestingDepth: 5 }).catch((caught: unknown) => caught);
+  expect(error).toBeInstanceOf(CompileError);
+  expect((error as CompileError).record?.message).toBe("expression too deeply nested");
+  const span = (error as CompileError).span;
+  expect([span?.line, span?.column]).toEqual([3, 13]);
+
+  for (const maxNestingDepth of [0, MAX_NESTING_DEPTH + 1]) {
+    await expect(compile(source, undefined, { maxNestingDepth })).rejects.toThrow(
+      `maxNestingDepth must be an integer from 1 to ${MAX_NESTING_DEPTH}`,
+    );
+  }
+});
+
+test("arbitrary bytes never crash the compiler", async () => {
+  const bytes = new Uint8Array(256).map((_, index) => (index * 167 + 13) & 0xff);
+  expect(await fuzzCompile(bytes)).toBe("rejected");
+
+  const seed = await Bun.file(new URL("../test/snapshots/control_flow.bp", import.meta.url)).text();
+  const report = await fuzz([seed], { iterations: 60, seed: 2822 });
+  expect(report.crashes.map((crash) => crash.source)).toEqual([]);
+  expect(report.accepted + report.rejected).toBe(60);
+});
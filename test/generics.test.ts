@@ -0,0 +1,74 @@
+import { expect, test } from "bun:test";
+
+import {
+    compileWithAstCompiler,
+    expectCompileFailure,
+    runWasmMainWithGc,
+} from "./helpers";
+
+test("generic functions infer primitive type parameters at each call", async () => {
+    const wasm = await compileWithAstCompiler(`
+    fn id<T>(value: T) -> T {
+        value
+    }
+
+    fn select<T>(flag: bool, on_true: T, on_false: T) -> T {
+        if flag { on_true } else { on_false }
+    }
+
+    fn main() -> i32 {
+        let wide: i64 = id(40 as i64);
+        let narrow: u8 = id(2 as u8);
+        let picked = select(false, 0, 100);
+        let bonus = if id(true) { 1 } else { 0 };
+        (wide as i32) + (narrow as i32) + picked + bonus
+    }
+  `);
+    const result = await runWasmMainWithGc(wasm);
+    expect(result).toBe(143);
+});
+
+test("generic functions accept multiple type parameters", async () => {
+    const wasm = await compileWithAstCompiler(`
+    fn second<A, B>(first: A, second: B) -> B {
+        second
+    }
+
+    fn main() -> i32 {
+        second(true, 7) + (second(1, 5 as i64) as i32)
+    }
+  `);
+    const result = await runWasmMainWithGc(wasm);
+    expect(result).toBe(12);
+});
+
+test("generic functions reject non-primitive type arguments", async () => {
+    const failure = await expectCompileFailure(`
+    fn id<T>(value: T) -> T {
+        value
+    }
+
+    fn main() -> i32 {
+        let values: [i32; 2] = [1, 2];
+        id(values)[0]
+    }
+  `);
+    expect(failure.failure.detail).toBe(
+        "/entry.bp:8:9: generic parameters only accept primitive types",
+    );
+});
+
+test("generic parameters must appear in a runtime parameter type", async () => {
+    const failure = await expectCompileFailure(`
+    fn make<T>(value: i32) -> i32 {
+        value
+    }
+
+    fn main() -> i32 {
+        make(1)
+    }
+  `);
+    expect(failure.failure.detail).toBe(
+        "/entry.bp:7:9: generic parameter cannot be inferred from call arguments",
+    );
+});
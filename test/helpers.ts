@@ -10,12 +10,25 @@ import {
   FUNCTIONS_COUNT_PTR_OFFSET,
   INSTR_OFFSET_PTR_OFFSET,
   STAGE1_MAX_FUNCTIONS,
+  DEFAULT_STAGE2_LAYOUT,
   describeCompilationFailure,
+  describeRuntimePanic,
   FAILURE_DETAIL_CAPACITY,
+  PRELUDE_MODULE_PATH,
+  readImplicitModule,
+  readStage2Layout,
+  selectImplicitModules,
 } from "../src/index";
-import type { CompilerModuleSource, CompileFailureDetails } from "../src/index";
+import type { CompilerModuleSource, CompileFailureDetails, ImplicitModule, Stage2Layout } from "../src/index";
+
+import { readCallNames } from "../src/frontend";
+import { instrumentModule, readModuleShape } from "../src/instrument";
+import { Stage2Phases } from "../src/stage2";
+import type { Token } from "../src/stage2";
+import { WasmReader, encodeSignedLeb, encodeUnsignedLeb } from "../src/wasm_reader";
 
 export type { CompilerModuleSource, CompileFailureDetails } from "../src/index";
+export type { Token, TokenKind } from "../src/stage2";
 
 export { COMPILER_INPUT_PTR, describeCompilationFailure, FAILURE_DETAIL_CAPACITY } from "../src/index";
 
@@ -42,15 +55,10 @@ const SCRATCH_TYPE_METADATA_DEBUG_SUBJECT_OFFSET = 4_036;
 const SCRATCH_TYPE_METADATA_DEBUG_EXTRA_OFFSET = 4_040;
 
 const WORD_SIZE = 4;
-const SCRATCH_INSTR_CAPACITY = 131_072;
-const SCRATCH_FN_BASE_OFFSET = 921_600;
-const SCRATCH_TYPES_CAPACITY = 2_048;
-const SCRATCH_TYPES_BASE_OFFSET = SCRATCH_FN_BASE_OFFSET - SCRATCH_TYPES_CAPACITY * TYPE_ENTRY_SIZE;
-const SCRATCH_TYPES_COUNT_OFFSET = SCRATCH_TYPES_BASE_OFFSET - WORD_SIZE;
-
-const AST_MAX_FUNCTIONS = 1_024;
-const AST_FUNCTION_ENTRY_SIZE = 68;
-const AST_NAMES_CAPACITY = 131_072;
+const ATTRIBUTE_COUNT_OFFSET = 7_548;
+const ATTRIBUTE_RECORDS_OFFSET = 7_552;
+const ATTRIBUTE_RECORD_SIZE = 24;
+
 const AST_CONSTANTS_CAPACITY = 1_024;
 const AST_CONSTANT_ENTRY_SIZE = 28;
 const AST_CONSTANT_ENTRY_NAME_OFFSET = 0;
@@ -73,12 +81,10 @@ const AST_ARRAY_HEAP_INDEX_SECTION_SIZE = AST_ARRAY_TYPES_CAPACITY * WORD_SIZE;
 const AST_TUPLE_HEAP_INDEX_SECTION_SIZE = AST_TUPLE_TYPES_CAPACITY * WORD_SIZE;
 const AST_STRUCT_HEAP_INDEX_SECTION_SIZE = AST_STRUCT_TYPES_CAPACITY * WORD_SIZE;
 const AST_EXPR_ENTRY_SIZE = 16;
-const AST_EXPR_CAPACITY = 131_072;
+const AST_EXPR_CAPACITY = 196_608;
 
 const AST_CONSTANTS_SECTION_SIZE =
   WORD_SIZE + AST_CONSTANTS_CAPACITY * AST_CONSTANT_ENTRY_SIZE;
-const AST_CONSTANTS_SECTION_WORDS = AST_CONSTANTS_SECTION_SIZE >> 2;
-const AST_CALL_DATA_CAPACITY = 131072 - AST_CONSTANTS_SECTION_WORDS;
 const AST_ARRAY_TYPES_SECTION_SIZE =
   WORD_SIZE + AST_ARRAY_TYPES_CAPACITY * AST_ARRAY_TYPE_ENTRY_SIZE;
 const AST_TUPLE_TYPES_SECTION_SIZE =
@@ -89,6 +95,7 @@ const AST_STRUCT_TYPES_SECTION_SIZE =
 const AST_FUNCTION_TYPES_SECTION_SIZE =
   WORD_SIZE + AST_FUNCTION_TYPES_CAPACITY * AST_FUNCTION_TYPE_ENTRY_SIZE;
 const memoryIntrinsicsSourceUrl = new URL("../stdlib/memory.bp", import.meta.url);
+const preludeSourceUrl = new URL("../stdlib/prelude.bp", import.meta.url);
 
 const encoder = new TextEncoder();
 const decoder = new TextDecoder();
@@ -114,7 +121,24 @@ export interface ScratchTypeEntry {
 export interface CompileWithAstCompilerOptions {
   readonly entryPath?: string;
   readonly modules?: ReadonlyArray<CompilerModuleSource>;
-}
+  readonly checkedDivision?: boolean;
+  readonly strict?: boolean;
+  readonly constantTable?: boolean;
+  readonly layoutGlobals?: boolean;
+  readonly memoryCanaries?: boolean;
+  readonly guardStores?: boolean;
+  readonly addressSanitizer?: boolean;
+  readonly memoryPages?: number;
+}
+
+const COMPILE_FLAG_CHECKED_DIVISION = 1;
+const COMPILE_FLAG_STRICT_DISCARD = 2;
+const COMPILE_FLAG_CONSTANT_TABLE = 4;
+const COMPILE_FLAG_LAYOUT_GLOBALS = 8;
+const COMPILE_FLAG_MEMORY_CANARIES = 16;
+const COMPILE_FLAG_GUARDED_STORES = 32;
+const COMPILE_FLAG_ADDRESS_SANITIZER = 64;
+const COMPILE_FLAGS_MEMORY_PAGES_SHIFT = 16;
 
 function ensureModuleMemoryCapacity(memory: WebAssembly.Memory, required: number) {
   if (required <= memory.buffer.byteLength) {
@@ -155,77 +179,79 @@ export function readModuleStorageTop(memory: WebAssembly.Memory): number {
   }
 }
 
-function astOutputReserve(inputLen: number): number {
-  const optionA = inputLen + SCRATCH_INSTR_CAPACITY;
-  const optionB = SCRATCH_FN_BASE_OFFSET + 16_384;
-  return optionA > optionB ? optionA : optionB;
+// Layouts read from the compiler instances the harness created, by memory, so
+// readers given only a memory find the regions of the compiler that owns it.
+const stage2Layouts = new WeakMap<WebAssembly.Memory, Stage2Layout>();
+
+function layoutOf(memory: WebAssembly.Memory): Stage2Layout {
+  return stage2Layouts.get(memory) ?? DEFAULT_STAGE2_LAYOUT;
 }
 
-function astBase(outPtr: number, inputLen: number): number {
-  return outPtr + astOutputReserve(inputLen);
+function astBase(outPtr: number, inputLen: number, layout: Stage2Layout): number {
+  return outPtr + Math.max(inputLen + layout.scratchInstrCapacity, layout.astOutputReserveMin);
 }
 
-function astConstantsCountPtr(astBasePtr: number): number {
-  const functionsSection = WORD_SIZE + AST_MAX_FUNCTIONS * AST_FUNCTION_ENTRY_SIZE;
-  const namesSection = WORD_SIZE + AST_NAMES_CAPACITY;
-  const callDataSection = WORD_SIZE + AST_CALL_DATA_CAPACITY * WORD_SIZE;
+function astConstantsCountPtr(astBasePtr: number, layout: Stage2Layout): number {
+  const functionsSection = WORD_SIZE + layout.astMaxFunctions * layout.astFunctionEntrySize;
+  const namesSection = WORD_SIZE + layout.astNamesCapacity;
+  const callDataSection = WORD_SIZE + layout.astCallDataCapacity * WORD_SIZE;
   return astBasePtr + functionsSection + namesSection + callDataSection;
 }
 
-function astArrayTypesCountPtr(astBasePtr: number): number {
-  return astConstantsCountPtr(astBasePtr) + AST_CONSTANTS_SECTION_SIZE;
+function astArrayTypesCountPtr(astBasePtr: number, layout: Stage2Layout): number {
+  return astConstantsCountPtr(astBasePtr, layout) + AST_CONSTANTS_SECTION_SIZE;
 }
 
-function astTupleTypesCountPtr(astBasePtr: number): number {
-  return astArrayTypesCountPtr(astBasePtr) + AST_ARRAY_TYPES_SECTION_SIZE;
+function astTupleTypesCountPtr(astBasePtr: number, layout: Stage2Layout): number {
+  return astArrayTypesCountPtr(astBasePtr, layout) + AST_ARRAY_TYPES_SECTION_SIZE;
 }
 
-function astArrayHeapIndicesBase(astBasePtr: number): number {
-  return astTupleTypesCountPtr(astBasePtr) + AST_TUPLE_TYPES_SECTION_SIZE;
+function astArrayHeapIndicesBase(astBasePtr: number, layout: Stage2Layout): number {
+  return astTupleTypesCountPtr(astBasePtr, layout) + AST_TUPLE_TYPES_SECTION_SIZE;
 }
 
-function astTupleHeapIndicesBase(astBasePtr: number): number {
-  return astArrayHeapIndicesBase(astBasePtr) + AST_ARRAY_HEAP_INDEX_SECTION_SIZE;
+function astTupleHeapIndicesBase(astBasePtr: number, layout: Stage2Layout): number {
+  return astArrayHeapIndicesBase(astBasePtr, layout) + AST_ARRAY_HEAP_INDEX_SECTION_SIZE;
 }
 
-function astStructTypesCountPtr(astBasePtr: number): number {
-  return astTupleHeapIndicesBase(astBasePtr) + AST_TUPLE_HEAP_INDEX_SECTION_SIZE;
+function astStructTypesCountPtr(astBasePtr: number, layout: Stage2Layout): number {
+  return astTupleHeapIndicesBase(astBasePtr, layout) + AST_TUPLE_HEAP_INDEX_SECTION_SIZE;
 }
 
-function astStructHeapIndicesBase(astBasePtr: number): number {
-  return astStructTypesCountPtr(astBasePtr) + AST_STRUCT_TYPES_SECTION_SIZE;
+function astStructHeapIndicesBase(astBasePtr: number, layout: Stage2Layout): number {
+  return astStructTypesCountPtr(astBasePtr, layout) + AST_STRUCT_TYPES_SECTION_SIZE;
 }
 
-function astFunctionTypesCountPtr(astBasePtr: number): number {
-  return astStructHeapIndicesBase(astBasePtr) + AST_STRUCT_HEAP_INDEX_SECTION_SIZE;
+function astFunctionTypesCountPtr(astBasePtr: number, layout: Stage2Layout): number {
+  return astStructHeapIndicesBase(astBasePtr, layout) + AST_STRUCT_HEAP_INDEX_SECTION_SIZE;
 }
 
-function astExtraBase(astBasePtr: number): number {
-  return astFunctionTypesCountPtr(astBasePtr) + AST_FUNCTION_TYPES_SECTION_SIZE;
+function astExtraBase(astBasePtr: number, layout: Stage2Layout): number {
+  return astFunctionTypesCountPtr(astBasePtr, layout) + AST_FUNCTION_TYPES_SECTION_SIZE;
 }
 
-function astExprCountPtr(astBasePtr: number): number {
-  return astExtraBase(astBasePtr);
+function astExprCountPtr(astBasePtr: number, layout: Stage2Layout): number {
+  return astExtraBase(astBasePtr, layout);
 }
 
-function astExprTypesBase(astBasePtr: number): number {
-  return astExtraBase(astBasePtr) + WORD_SIZE + AST_EXPR_CAPACITY * AST_EXPR_ENTRY_SIZE;
+function astExprTypesBase(astBasePtr: number, layout: Stage2Layout): number {
+  return astExtraBase(astBasePtr, layout) + WORD_SIZE + AST_EXPR_CAPACITY * AST_EXPR_ENTRY_SIZE;
 }
 
-function astNamesLenPtr(astBasePtr: number): number {
-  return astBasePtr + WORD_SIZE + AST_MAX_FUNCTIONS * AST_FUNCTION_ENTRY_SIZE;
+function astNamesLenPtr(astBasePtr: number, layout: Stage2Layout): number {
+  return astBasePtr + WORD_SIZE + layout.astMaxFunctions * layout.astFunctionEntrySize;
 }
 
-function astNamesBase(astBasePtr: number): number {
-  return astNamesLenPtr(astBasePtr) + WORD_SIZE;
+function astNamesBase(astBasePtr: number, layout: Stage2Layout): number {
+  return astNamesLenPtr(astBasePtr, layout) + WORD_SIZE;
 }
 
-function astCallDataLenPtr(astBasePtr: number): number {
-  return astNamesBase(astBasePtr) + AST_NAMES_CAPACITY;
+function astCallDataLenPtr(astBasePtr: number, layout: Stage2Layout): number {
+  return astNamesBase(astBasePtr, layout) + layout.astNamesCapacity;
 }
 
-function astCallDataBase(astBasePtr: number): number {
-  return astCallDataLenPtr(astBasePtr) + WORD_SIZE;
+function astCallDataBase(astBasePtr: number, layout: Stage2Layout): number {
+  return astCallDataLenPtr(astBasePtr, layout) + WORD_SIZE;
 }
 
 export function readExpressionCount(
@@ -233,14 +259,15 @@ export function readExpressionCount(
   outPtr: number,
   inputLen: number,
 ): number {
-  const astBasePtr = astBase(outPtr, inputLen);
-  const exprCountPtr = astExprCountPtr(astBasePtr);
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
+  const exprCountPtr = astExprCountPtr(astBasePtr, layout);
   const view = new DataView(memory.buffer);
   return safeReadI32(view, exprCountPtr);
 }
 
 export function getAstBasePointer(outPtr: number, inputLen: number): number {
-  return astBase(outPtr, inputLen);
+  return astBase(outPtr, inputLen, DEFAULT_STAGE2_LAYOUT);
 }
 
 export function readCallDataInfo(
@@ -248,10 +275,11 @@ export function readCallDataInfo(
   outPtr: number,
   inputLen: number,
 ): { readonly base: number; readonly words: number } {
-  const astBasePtr = astBase(outPtr, inputLen);
-  const lenPtr = astCallDataLenPtr(astBasePtr);
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
+  const lenPtr = astCallDataLenPtr(astBasePtr, layout);
   const usedWords = safeReadI32(new DataView(memory.buffer), lenPtr);
-  const base = astCallDataBase(astBasePtr);
+  const base = astCallDataBase(astBasePtr, layout);
   return { base, words: usedWords };
 }
 
@@ -268,8 +296,9 @@ export function readExpressionEntry(
   inputLen: number,
   index: number,
 ): ExpressionEntry {
-  const astBasePtr = astBase(outPtr, inputLen);
-  const entryPtr = astExtraBase(astBasePtr) + WORD_SIZE + index * AST_EXPR_ENTRY_SIZE;
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
+  const entryPtr = astExtraBase(astBasePtr, layout) + WORD_SIZE + index * AST_EXPR_ENTRY_SIZE;
   const view = new DataView(memory.buffer, entryPtr, AST_EXPR_ENTRY_SIZE);
   return {
     kind: view.getInt32(0, true),
@@ -289,9 +318,10 @@ export function readExpressionType(
   if (index < 0 || index >= exprCount) {
     return -1;
   }
-  const astBasePtr = astBase(outPtr, inputLen);
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
   const view = new DataView(memory.buffer);
-  return safeReadI32(view, astExprTypesBase(astBasePtr) + index * WORD_SIZE);
+  return safeReadI32(view, astExprTypesBase(astBasePtr, layout) + index * WORD_SIZE);
 }
 
 export interface AstConstantEntryInfo {
@@ -309,9 +339,10 @@ export function readAstConstantCount(
   outPtr: number,
   inputLen: number,
 ): number {
-  const astBasePtr = astBase(outPtr, inputLen);
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
   const view = new DataView(memory.buffer);
-  return safeReadI32(view, astConstantsCountPtr(astBasePtr));
+  return safeReadI32(view, astConstantsCountPtr(astBasePtr, layout));
 }
 
 export function readAstConstantEntry(
@@ -320,9 +351,10 @@ export function readAstConstantEntry(
   inputLen: number,
   index: number,
 ): AstConstantEntryInfo {
-  const astBasePtr = astBase(outPtr, inputLen);
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
   const entryPtr =
-    astConstantsCountPtr(astBasePtr) + WORD_SIZE + index * AST_CONSTANT_ENTRY_SIZE;
+    astConstantsCountPtr(astBasePtr, layout) + WORD_SIZE + index * AST_CONSTANT_ENTRY_SIZE;
   const view = new DataView(memory.buffer);
   const nameStart = safeReadI32(view, entryPtr + AST_CONSTANT_ENTRY_NAME_OFFSET);
   const nameLength = safeReadI32(view, entryPtr + AST_CONSTANT_ENTRY_NAME_LEN_OFFSET);
@@ -345,9 +377,10 @@ export function readAstArrayTypeCount(
   outPtr: number,
   inputLen: number,
 ): number {
-  const astBasePtr = astBase(outPtr, inputLen);
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
   const view = new DataView(memory.buffer);
-  return safeReadI32(view, astArrayTypesCountPtr(astBasePtr));
+  return safeReadI32(view, astArrayTypesCountPtr(astBasePtr, layout));
 }
 
 export function readAstArrayTypeEntry(
@@ -356,9 +389,10 @@ export function readAstArrayTypeEntry(
   inputLen: number,
   index: number,
 ): AstArrayTypeEntryInfo {
-  const astBasePtr = astBase(outPtr, inputLen);
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
   const entryPtr =
-    astArrayTypesCountPtr(astBasePtr) + WORD_SIZE + index * AST_ARRAY_TYPE_ENTRY_SIZE;
+    astArrayTypesCountPtr(astBasePtr, layout) + WORD_SIZE + index * AST_ARRAY_TYPE_ENTRY_SIZE;
   const view = new DataView(memory.buffer, entryPtr, AST_ARRAY_TYPE_ENTRY_SIZE);
   return {
     elementType: view.getInt32(0, true),
@@ -379,9 +413,10 @@ export function readAstTupleTypeCount(
   outPtr: number,
   inputLen: number,
 ): number {
-  const astBasePtr = astBase(outPtr, inputLen);
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
   const view = new DataView(memory.buffer);
-  return safeReadI32(view, astTupleTypesCountPtr(astBasePtr));
+  return safeReadI32(view, astTupleTypesCountPtr(astBasePtr, layout));
 }
 
 export function readAstTupleTypeEntry(
@@ -390,9 +425,10 @@ export function readAstTupleTypeEntry(
   inputLen: number,
   index: number,
 ): AstTupleTypeEntryInfo {
-  const astBasePtr = astBase(outPtr, inputLen);
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
   const entryPtr =
-    astTupleTypesCountPtr(astBasePtr) + WORD_SIZE + index * AST_TUPLE_TYPE_ENTRY_SIZE;
+    astTupleTypesCountPtr(astBasePtr, layout) + WORD_SIZE + index * AST_TUPLE_TYPE_ENTRY_SIZE;
   const view = new DataView(memory.buffer, entryPtr, AST_TUPLE_TYPE_ENTRY_SIZE);
   const elementCount = view.getInt32(0, true);
   const elementsPtr = view.getInt32(4, true);
@@ -407,6 +443,40 @@ export function readAstTupleTypeEntry(
   return { elementCount, elementsPtr, cachedTypeId, elements };
 }
 
+export interface AttributeRecord {
+  readonly moduleIndex: number;
+  readonly name: string;
+  readonly nameStart: number;
+  /** Text between the parentheses, or null for a bare `#[name]`. */
+  readonly args: string | null;
+  readonly itemStart: number;
+}
+
+/** Reads the attribute table filled while parsing `source` as the only user module. */
+export function readAttributeRecords(
+  memory: WebAssembly.Memory,
+  source: string,
+): AttributeRecord[] {
+  const view = new DataView(memory.buffer);
+  const count = safeReadI32(view, ATTRIBUTE_COUNT_OFFSET);
+  const records: AttributeRecord[] = [];
+  for (let index = 0; index < count; index += 1) {
+    const recordPtr = ATTRIBUTE_RECORDS_OFFSET + index * ATTRIBUTE_RECORD_SIZE;
+    const [moduleIndex, nameStart, nameLength, argsStart, argsLength, itemStart] = Array.from(
+      { length: ATTRIBUTE_RECORD_SIZE / WORD_SIZE },
+      (_, field) => safeReadI32(view, recordPtr + field * WORD_SIZE),
+    );
+    records.push({
+      moduleIndex,
+      name: source.slice(nameStart, nameStart + nameLength),
+      nameStart,
+      args: argsStart > 0 ? source.slice(argsStart, argsStart + argsLength) : null,
+      itemStart,
+    });
+  }
+  return records;
+}
+
 export interface FunctionEntryInfo {
   readonly namePtr: number;
   readonly nameLength: number;
@@ -432,7 +502,8 @@ export function readFunctionCount(
   outPtr: number,
   inputLen: number,
 ): number {
-  const astBasePtr = astBase(outPtr, inputLen);
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
   const view = new DataView(memory.buffer);
   return safeReadI32(view, astBasePtr);
 }
@@ -443,8 +514,9 @@ export function readFunctionEntry(
   inputLen: number,
   index: number,
 ): FunctionEntryInfo {
-  const astBasePtr = astBase(outPtr, inputLen);
-  const entryPtr = astBasePtr + WORD_SIZE + index * AST_FUNCTION_ENTRY_SIZE;
+  const layout = layoutOf(memory);
+  const astBasePtr = astBase(outPtr, inputLen, layout);
+  const entryPtr = astBasePtr + WORD_SIZE + index * layout.astFunctionEntrySize;
   const view = new DataView(memory.buffer);
   const namePtr = safeReadI32(view, entryPtr);
   const nameLength = safeReadI32(view, entryPtr + WORD_SIZE);
@@ -627,6 +699,8 @@ interface CompilerExports {
   readonly compile?: (inputPtr: number, inputLen: number, outputPtr: number) => number | bigint;
   readonly loadModuleFromSource?: (pathPtr: number, contentPtr: number) => number | bigint;
   readonly compileFromPath?: (pathPtr: number) => number | bigint;
+  readonly parseFromPath?: (pathPtr: number) => number | bigint;
+  readonly setCompileFlags?: (flags: number) => number | bigint;
 }
 
 export class CompilerInstance {
@@ -634,20 +708,29 @@ export class CompilerInstance {
   #compile: (inputPtr: number, inputLen: number, outputPtr: number) => number | bigint;
   #loadModuleFromSource: ((pathPtr: number, contentPtr: number) => number | bigint) | null;
   #compileFromPath: ((pathPtr: number) => number | bigint) | null;
+  #parseFromPath: ((pathPtr: number) => number | bigint) | null;
+  #setCompileFlags: ((flags: number) => number | bigint) | null;
   #memoryIntrinsicsSource: string | null;
+  #prelude: ImplicitModule | null;
 
   private constructor(
     memory: WebAssembly.Memory,
     compile: (inputPtr: number, inputLen: number, outputPtr: number) => number | bigint,
     loadModuleFromSource: ((pathPtr: number, contentPtr: number) => number | bigint) | undefined,
     compileFromPath: ((pathPtr: number) => number | bigint) | undefined,
+    parseFromPath: ((pathPtr: number) => number | bigint) | undefined,
+    setCompileFlags: ((flags: number) => number | bigint) | undefined,
     memoryIntrinsicsSource: string | null,
+    prelude: ImplicitModule | null,
   ) {
     this.#memory = memory;
     this.#compile = compile;
     this.#loadModuleFromSource = loadModuleFromSource ?? null;
     this.#compileFromPath = compileFromPath ?? null;
+    this.#parseFromPath = parseFromPath ?? null;
+    this.#setCompileFlags = setCompileFlags ?? null;
     this.#memoryIntrinsicsSource = memoryIntrinsicsSource;
+    this.#prelude = prelude;
   }
 
   static async create(wasm: Uint8Array): Promise<CompilerInstance> {
@@ -662,13 +745,20 @@ export class CompilerInstance {
     const supportsModules =
       typeof exports.loadModuleFromSource === "function" && typeof exports.compileFromPath === "function";
     const memoryIntrinsicsSource = supportsModules ? await loadMemoryIntrinsicsSource() : null;
+    const prelude = supportsModules
+      ? await readImplicitModule({ path: PRELUDE_MODULE_PATH, source: await loadPreludeSource() })
+      : null;
+    stage2Layouts.set(exports.memory, readStage2Layout(instance.exports));
 
     return new CompilerInstance(
       exports.memory,
       exports.compile,
       typeof exports.loadModuleFromSource === "function" ? exports.loadModuleFromSource : undefined,
       typeof exports.compileFromPath === "function" ? exports.compileFromPath : undefined,
+      typeof exports.parseFromPath === "function" ? exports.parseFromPath : undefined,
+      typeof exports.setCompileFlags === "function" ? exports.setCompileFlags : undefined,
       memoryIntrinsicsSource,
+      prelude,
     );
   }
 
@@ -676,6 +766,13 @@ export class CompilerInstance {
     return this.#memory;
   }
 
+  setCompileFlags(flags: number): void {
+    if (!this.#setCompileFlags) {
+      throw new Error("stage1 compiler missing compile flag export");
+    }
+    this.#setCompileFlags(flags);
+  }
+
   compileAt(inputPtr: number, outputPtr: number, source: string): Uint8Array {
     if (this.#loadModuleFromSource && this.#compileFromPath) {
       return this.#compileUsingModules(DEFAULT_ENTRY_MODULE_PATH, source, []);
@@ -768,15 +865,15 @@ export class CompilerInstance {
 
   readScratchTypesCount(outputPtr: number): number {
     const view = new DataView(this.#memory.buffer);
-    return safeReadI32(view, outputPtr + SCRATCH_TYPES_COUNT_OFFSET);
+    return safeReadI32(view, outputPtr + layoutOf(this.#memory).scratchTypesBaseOffset - WORD_SIZE);
   }
 
   readScratchTypeEntry(outputPtr: number, index: number): ScratchTypeEntry {
-    if (index < 0 || index >= SCRATCH_TYPES_CAPACITY) {
+    const layout = layoutOf(this.#memory);
+    if (index < 0 || index >= layout.scratchTypesCapacity) {
       return { typeId: -1, namePtr: -1, nameLength: -1, extra: -1 };
     }
-    const entryPtr =
-      outputPtr + SCRATCH_TYPES_BASE_OFFSET + index * TYPE_ENTRY_SIZE;
+    const entryPtr = outputPtr + layout.scratchTypesBaseOffset + index * TYPE_ENTRY_SIZE;
     const view = new DataView(this.#memory.buffer);
     return {
       typeId: safeReadI32(view, entryPtr + TYPE_ENTRY_TYPE_ID_OFFSET),
@@ -809,8 +906,8 @@ export class CompilerInstance {
     if (!this.#loadModuleFromSource || !this.#compileFromPath) {
       throw new Error("stage1 compiler missing module loading exports");
     }
-    if (!this.#memoryIntrinsicsSource) {
-      throw new Error("memory intrinsics source not loaded");
+    if (!this.#memoryIntrinsicsSource || !this.#prelude) {
+      throw new Error("stdlib sources not loaded");
     }
 
     const modules: CompilerModuleSource[] = [
@@ -853,6 +950,32 @@ export class CompilerInstance {
 
     loadModuleSource(entryPath, source);
 
+    // Stage1 runs get the prelude but not the core library, like the
+    // compiler's own build.
+    let prelude: CompilerModuleSource[] = [this.#prelude];
+    if (this.#parseFromPath) {
+      let parsed: number;
+      try {
+        parsed = coerceToI32(this.#parseFromPath(MODULE_PATH_PTR));
+      } catch (cause) {
+        throw this.#failure(readModuleStorageTop(this.#memory), -1, -1, cause);
+      }
+      if (parsed <= 0) {
+        throw this.#failure(readModuleStorageTop(this.#memory), parsed, -1);
+      }
+      const calls = readCallNames(
+        this.#memory,
+        readModuleStorageTop(this.#memory),
+        encoder.encode(normalizeNewlines(source)).length,
+        layoutOf(this.#memory),
+      );
+      prelude = selectImplicitModules([this.#prelude], calls);
+    }
+    for (const module of prelude) {
+      loadModuleSource(module.path, module.source);
+    }
+    writeModuleString(this.#memory, MODULE_PATH_PTR, entryPath);
+
     let producedLength: number;
     try {
       const result = compileFromPath(MODULE_PATH_PTR);
@@ -880,11 +1003,18 @@ export class CompilerInstance {
     producedLength: number,
     inputLength: number,
   ): CompileFailureDetails {
-    return describeCompilationFailure(this.#memory, outputPtr, producedLength, inputLength);
+    return describeCompilationFailure(
+      this.#memory,
+      outputPtr,
+      producedLength,
+      inputLength,
+      layoutOf(this.#memory),
+    );
   }
 }
 
 let memoryIntrinsicsSourcePromise: Promise<string> | null = null;
+let preludeSourcePromise: Promise<string> | null = null;
 let astCompilerModuleSourcesPromise: Promise<CompilerModuleSource[]> | null = null;
 let astCompilerSourcePromise: Promise<string> | null = null;
 let astCompilerWasmPromise: Promise<Uint8Array> | null = null;
@@ -896,6 +1026,13 @@ async function loadMemoryIntrinsicsSource(): Promise<string> {
   return memoryIntrinsicsSourcePromise;
 }
 
+async function loadPreludeSource(): Promise<string> {
+  if (!preludeSourcePromise) {
+    preludeSourcePromise = Bun.file(preludeSourceUrl).text();
+  }
+  return preludeSourcePromise;
+}
+
 async function loadAstCompilerModuleSources(): Promise<CompilerModuleSource[]> {
   if (!astCompilerModuleSourcesPromise) {
     astCompilerModuleSourcesPromise = (async () => {
@@ -946,6 +1083,7 @@ export async function loadAstCompilerWasm(): Promise<Uint8Array> {
       const wasm = await compileToWasm(entry.source, {
         entryPath: AST_COMPILER_ENTRY_PATH,
         modules: extraModules,
+        strict: true,
       });
       return wasm;
     })();
@@ -967,12 +1105,45 @@ export async function instantiateAstCompiler(): Promise<CompilerInstance> {
   return CompilerInstance.create(wasm);
 }
 
+/** Splits `source` with the `tokenize` export of the freshly built stage2 compiler. */
+export async function tokenizeWithAstCompiler(source: string): Promise<Token[]> {
+  return (await Stage2Phases.create(await loadAstCompilerWasm())).tokenize(source);
+}
+
 export async function tryCompileWithAstCompiler(
   source: string,
   options: CompileWithAstCompilerOptions = {},
 ): Promise<Uint8Array> {
   const wasm = await loadAstCompilerWasm();
   const compiler = await CompilerInstance.create(wasm);
+  let compileFlags = 0;
+  if (options.checkedDivision) {
+    compileFlags |= COMPILE_FLAG_CHECKED_DIVISION;
+  }
+  if (options.strict) {
+    compileFlags |= COMPILE_FLAG_STRICT_DISCARD;
+  }
+  if (options.constantTable) {
+    compileFlags |= COMPILE_FLAG_CONSTANT_TABLE;
+  }
+  if (options.layoutGlobals) {
+    compileFlags |= COMPILE_FLAG_LAYOUT_GLOBALS;
+  }
+  if (options.memoryCanaries) {
+    compileFlags |= COMPILE_FLAG_MEMORY_CANARIES;
+  }
+  if (options.guardStores) {
+    compileFlags |= COMPILE_FLAG_GUARDED_STORES;
+  }
+  if (options.addressSanitizer) {
+    compileFlags |= COMPILE_FLAG_ADDRESS_SANITIZER;
+  }
+  if (options.memoryPages !== undefined) {
+    compileFlags |= options.memoryPages << COMPILE_FLAGS_MEMORY_PAGES_SHIFT;
+  }
+  if (compileFlags !== 0) {
+    compiler.setCompileFlags(compileFlags);
+  }
   const modules = options.modules ?? [];
   if (modules.length > 0) {
     const entryPath = options.entryPath ?? "/tests/main.bp";
@@ -1052,10 +1223,34 @@ export function expectExportedMemory(
   return value;
 }
 
-export async function runWasmMainWithGc(wasm: Uint8Array): Promise<number> {
+export interface RunWasmMainOptions {
+  /** Fail unless the module's memory is exactly this many 64 KiB pages. */
+  readonly memoryPages?: number;
+}
+
+export async function runWasmMainWithGc(
+  wasm: Uint8Array,
+  options: RunWasmMainOptions = {},
+): Promise<number> {
   const instance = await instantiateWasmModuleWithGc(wasm);
+  if (options.memoryPages !== undefined) {
+    const memory = expectExportedMemory(instance);
+    const pages = memory.buffer.byteLength / 65_536;
+    if (pages !== options.memoryPages) {
+      throw new Error(`expected ${options.memoryPages} memory pages but module has ${pages}`);
+    }
+  }
   const main = expectExportedFunction(instance, "main");
-  return main();
+  try {
+    return main();
+  } catch (error) {
+    // Surface `panic`/`assert` messages and other recorded panics in test failures.
+    const panic = describeRuntimePanic(instance, []);
+    if (panic) {
+      throw new Error(`panicked: ${panic.message}`, { cause: error });
+    }
+    throw error;
+  }
 }
 
 export interface TypeMetadataDebugInfo {
@@ -1086,3 +1281,491 @@ function safeReadI32(view: DataView, offset: number): number {
     return -1;
   }
 }
+
+export interface FuelRunOptions {
+  /** Trap once more than this many instructions have run. */
+  readonly maxInstructions?: number;
+  /** Also count each function's own instructions; see `readFunctionFuel`. */
+  readonly perFunction?: boolean;
+}
+
+export interface FuelRunResult {
+  readonly result: number;
+  /** Executed instructions, not counting the structural `else` and `end`. */
+  readonly instructions: number;
+}
+
+const FUEL_GLOBAL_EXPORT = "__fuel";
+const FUNCTION_FUEL_EXPORT_PREFIX = "__fuel.";
+
+/**
+ * Calls `name` in a copy of `wasm` instrumented to count executed
+ * instructions.  The count only depends on the code and the arguments, so
+ * tests can bound it to catch code generation regressions.
+ */
+export async function runWasmFunctionWithFuel(
+  wasm: Uint8Array,
+  name: string,
+  args: ReadonlyArray<number | bigint> = [],
+  options: FuelRunOptions = {},
+): Promise<FuelRunResult> {
+  const instance = await instantiateWasmModuleWithGc(instrumentWasmWithFuel(wasm, options));
+  const fuel = (instance.exports as Record<string, unknown>)[FUEL_GLOBAL_EXPORT] as WebAssembly.Global;
+  const func = expectExportedFunction(instance, name);
+  let result: number;
+  try {
+    result = func(...args);
+  } catch (error) {
+    const limit = options.maxInstructions;
+    if (limit !== undefined && Number(fuel.value) > limit) {
+      throw new Error(`'${name}' ran out of fuel after ${limit} instructions`, { cause: error });
+    }
+    throw error;
+  }
+  return { result, instructions: Number(fuel.value) };
+}
+
+/**
+ * Reads the per-function counts of an instance of a module instrumented with
+ * `perFunction`, keyed by export name or `#<index>`.  Each count covers only
+ * the function's own instructions, not those of its callees.
+ */
+export function readFunctionFuel(instance: WebAssembly.Instance): Map<string, number> {
+  const counts = new Map<string, number>();
+  for (const [name, value] of Object.entries(instance.exports)) {
+    if (name.startsWith(FUNCTION_FUEL_EXPORT_PREFIX) && value instanceof WebAssembly.Global) {
+      counts.set(name.slice(FUNCTION_FUEL_EXPORT_PREFIX.length), Number(value.value));
+    }
+  }
+  return counts;
+}
+
+/**
+ * Adds an exported mutable i64 global `__fuel` and, at the start of every
+ * straight-line run of code, adds the run's instruction count to it.  Runs are
+ * those of `instrumentModule`, which the profiler and coverage counters share.
+ * With `perFunction`, each defined function also gets a counter exported as
+ * `__fuel.<name>`.
+ */
+export function instrumentWasmWithFuel(wasm: Uint8Array, options: FuelRunOptions = {}): Uint8Array {
+  const shape = readModuleShape(wasm);
+  const functionNames = new Map<number, string>();
+  const exportSection = shape.sections.find((section) => section.id === 7);
+  if (exportSection) {
+    const reader = new WasmReader(wasm, exportSection.start);
+    const count = reader.readLeb();
+    for (let entry = 0; entry < count; entry += 1) {
+      const length = reader.readLeb();
+      const name = new TextDecoder().decode(wasm.subarray(reader.offset, reader.offset + length));
+      reader.offset += length;
+      const kind = reader.readByte();
+      const index = reader.readLeb();
+      if (kind === 0 && !functionNames.has(index)) {
+        functionNames.set(index, name);
+      }
+    }
+  }
+  const fuelGlobal = shape.globalCount;
+  // Function `i`'s own counter follows the total, at `fuelGlobal + 1 + i`.
+  const functionCounters = options.perFunction ? shape.definedFunctions : 0;
+
+  const fuelIncrement = (body: number, count: number): number[] => {
+    if (count === 0) {
+      return [];
+    }
+    const bytes = [0x23, ...encodeUnsignedLeb(fuelGlobal), 0x42, ...encodeSignedLeb(count), 0x7c];
+    bytes.push(0x24, ...encodeUnsignedLeb(fuelGlobal));
+    if (functionCounters > 0) {
+      const counter = encodeUnsignedLeb(fuelGlobal + 1 + body);
+      bytes.push(0x23, ...counter, 0x42, ...encodeSignedLeb(count), 0x7c, 0x24, ...counter);
+    }
+    if (options.maxInstructions !== undefined) {
+      bytes.push(0x23, ...encodeUnsignedLeb(fuelGlobal), 0x42, ...encodeSignedLeb(options.maxInstructions));
+      bytes.push(0x56, 0x04, 0x40, 0x00, 0x0b);
+    }
+    return bytes;
+  };
+
+  const hooks = {
+    enter: (): number[] => [],
+    run: (body: number, _start: number, _stop: number, length: number): number[] => fuelIncrement(body, length),
+    exit: (): number[] => [],
+  };
+  return instrumentModule(wasm, hooks, () => ({
+    globals: Array.from({ length: functionCounters + 1 }, () => [0x7e, 0x01, 0x42, 0x00, 0x0b]),
+    exports: [
+      { name: FUEL_GLOBAL_EXPORT, global: 0 },
+      ...Array.from({ length: functionCounters }, (_, counter) => {
+        const index = shape.importedFunctions + counter;
+        return { name: `${FUNCTION_FUEL_EXPORT_PREFIX}${functionNames.get(index) ?? `#${index}`}`, global: 1 + counter };
+      }),
+    ],
+  })).wasm;
+}
+
+/**
+ * Renumbers each function's non-parameter locals so two builds that differ
+ * only in local numbering produce the same bytes.  Locals are ordered by the
+ * first `local.get`, `local.set`, or `local.tee` that names them, with unused
+ * ones last, and then grouped by type in order of each type's first local, so
+ * every type gets one declaration run.
+ */
+export function normalizeWasmLocals(wasm: Uint8Array): Uint8Array {
+  const reader = new WasmReader(wasm, 8);
+  const paramCounts: number[] = [];
+  const functionTypes: number[] = [];
+  const sections: { id: number; start: number; end: number }[] = [];
+  const skipStorageType = (): void => {
+    const byte = wasm[reader.offset];
+    if (byte === 0x77 || byte === 0x78) {
+      reader.readByte();
+    } else {
+      reader.skipValueType();
+    }
+  };
+  const readSubtype = (form: number): void => {
+    if (form === 0x4f || form === 0x50) {
+      const supertypes = reader.readLeb();
+      for (let index = 0; index < supertypes; index += 1) {
+        reader.skipLeb();
+      }
+      form = reader.readByte();
+    }
+    if (form === 0x60) {
+      const params = reader.readLeb();
+      for (let index = 0; index < params; index += 1) {
+        reader.skipValueType();
+      }
+      const results = reader.readLeb();
+      for (let index = 0; index < results; index += 1) {
+        reader.skipValueType();
+      }
+      paramCounts.push(params);
+      return;
+    }
+    if (form === 0x5f) {
+      const fields = reader.readLeb();
+      for (let index = 0; index < fields; index += 1) {
+        skipStorageType();
+        reader.readByte();
+      }
+    } else if (form === 0x5e) {
+      skipStorageType();
+      reader.readByte();
+    } else {
+      throw new Error(`unsupported type form 0x${form.toString(16)}`);
+    }
+    paramCounts.push(-1);
+  };
+
+  while (reader.offset < wasm.length) {
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    sections.push({ id, start: reader.offset, end: reader.offset + size });
+    if (id === 1) {
+      const count = reader.readLeb();
+      for (let entry = 0; entry < count; entry += 1) {
+        const form = reader.readByte();
+        if (form === 0x4e) {
+          const members = reader.readLeb();
+          for (let member = 0; member < members; member += 1) {
+            readSubtype(reader.readByte());
+          }
+        } else {
+          readSubtype(form);
+        }
+      }
+    } else if (id === 2) {
+      const count = reader.readLeb();
+      for (let entry = 0; entry < count; entry += 1) {
+        reader.offset += reader.readLeb();
+        reader.offset += reader.readLeb();
+        if (reader.readByte() !== 0) {
+          throw new Error("only function imports are supported");
+        }
+        reader.skipLeb();
+      }
+    } else if (id === 3) {
+      const count = reader.readLeb();
+      for (let entry = 0; entry < count; entry += 1) {
+        functionTypes.push(reader.readLeb());
+      }
+    }
+    reader.offset = sections[sections.length - 1]!.end;
+  }
+
+  const normalizeBody = (paramCount: number, end: number): number[] => {
+    const localTypes: Uint8Array[] = [];
+    const groups = reader.readLeb();
+    for (let group = 0; group < groups; group += 1) {
+      const count = reader.readLeb();
+      const typeStart = reader.offset;
+      reader.skipValueType();
+      const type = wasm.subarray(typeStart, reader.offset);
+      for (let local = 0; local < count; local += 1) {
+        localTypes.push(type);
+      }
+    }
+
+    const codeStart = reader.offset;
+    const references: { start: number; end: number; local: number }[] = [];
+    const firstUse: number[] = [];
+    const seen = new Set<number>();
+    while (reader.offset < end) {
+      const opcode = reader.readByte();
+      if (opcode >= 0x20 && opcode <= 0x22) {
+        const start = reader.offset;
+        const local = reader.readLeb();
+        references.push({ start, end: reader.offset, local });
+        if (local >= paramCount && !seen.has(local)) {
+          seen.add(local);
+          firstUse.push(local);
+        }
+      } else {
+        reader.skipImmediates(opcode);
+      }
+    }
+    for (let local = paramCount; local < paramCount + localTypes.length; local += 1) {
+      if (!seen.has(local)) {
+        firstUse.push(local);
+      }
+    }
+
+    const typeKey = (local: number): string => localTypes[local - paramCount]!.join(",");
+    const typeOrder: string[] = [];
+    for (const local of firstUse) {
+      if (!typeOrder.includes(typeKey(local))) {
+        typeOrder.push(typeKey(local));
+      }
+    }
+    const ordered = typeOrder.flatMap((key) => firstUse.filter((local) => typeKey(local) === key));
+    const renumbered = new Map<number, number>();
+    ordered.forEach((local, index) => renumbered.set(local, paramCount + index));
+
+    const out: number[] = [...encodeUnsignedLeb(typeOrder.length)];
+    for (const key of typeOrder) {
+      const locals = ordered.filter((local) => typeKey(local) === key);
+      out.push(...encodeUnsignedLeb(locals.length), ...localTypes[locals[0]! - paramCount]!);
+    }
+    let copied = codeStart;
+    for (const reference of references) {
+      out.push(...wasm.subarray(copied, reference.start));
+      out.push(...encodeUnsignedLeb(renumbered.get(reference.local) ?? reference.local));
+      copied = reference.end;
+    }
+    out.push(...wasm.subarray(copied, end));
+    return out;
+  };
+
+  const out: number[] = [...wasm.subarray(0, 8)];
+  for (const section of sections) {
+    let payload: ArrayLike<number> = wasm.subarray(section.start, section.end);
+    if (section.id === 10) {
+      reader.offset = section.start;
+      const count = reader.readLeb();
+      const bodies: number[] = [...encodeUnsignedLeb(count)];
+      for (let body = 0; body < count; body += 1) {
+        const size = reader.readLeb();
+        const end = reader.offset + size;
+        const paramCount = paramCounts[functionTypes[body]!];
+        if (paramCount === undefined || paramCount < 0) {
+          throw new Error(`function ${body} does not have a function type`);
+        }
+        const normalized = normalizeBody(paramCount, end);
+        bodies.push(...encodeUnsignedLeb(normalized.length), ...normalized);
+        reader.offset = end;
+      }
+      payload = bodies;
+    }
+    out.push(section.id, ...encodeUnsignedLeb(payload.length));
+    for (let index = 0; index < payload.length; index += 1) {
+      out.push(payload[index]!);
+    }
+  }
+  return new Uint8Array(out);
+}
+
+const OPCODE_NAMES = new Map<number, string>([
+  [0x00, "unreachable"], [0x01, "nop"], [0x02, "block"], [0x03, "loop"], [0x04, "if"],
+  [0x05, "else"], [0x0b, "end"], [0x0c, "br"], [0x0d, "br_if"], [0x0e, "br_table"],
+  [0x0f, "return"], [0x10, "call"], [0x11, "call_indirect"], [0x12, "return_call"],
+  [0x13, "return_call_indirect"], [0x14, "call_ref"], [0x15, "return_call_ref"], [0x1a, "drop"],
+  [0x1b, "select"], [0x1c, "select"], [0x20, "local.get"], [0x21, "local.set"],
+  [0x22, "local.tee"], [0x23, "global.get"], [0x24, "global.set"], [0x25, "table.get"],
+  [0x26, "table.set"], [0x28, "i32.load"], [0x29, "i64.load"], [0x2a, "f32.load"],
+  [0x2b, "f64.load"], [0x2c, "i32.load8_s"], [0x2d, "i32.load8_u"], [0x2e, "i32.load16_s"],
+  [0x2f, "i32.load16_u"], [0x30, "i64.load8_s"], [0x31, "i64.load8_u"], [0x32, "i64.load16_s"],
+  [0x33, "i64.load16_u"], [0x34, "i64.load32_s"], [0x35, "i64.load32_u"], [0x36, "i32.store"],
+  [0x37, "i64.store"], [0x38, "f32.store"], [0x39, "f64.store"], [0x3a, "i32.store8"],
+  [0x3b, "i32.store16"], [0x3c, "i64.store8"], [0x3d, "i64.store16"], [0x3e, "i64.store32"],
+  [0x3f, "memory.size"], [0x40, "memory.grow"], [0x41, "i32.const"], [0x42, "i64.const"],
+  [0x43, "f32.const"], [0x44, "f64.const"], [0x45, "i32.eqz"], [0x46, "i32.eq"], [0x47, "i32.ne"],
+  [0x48, "i32.lt_s"], [0x49, "i32.lt_u"], [0x4a, "i32.gt_s"], [0x4b, "i32.gt_u"],
+  [0x4c, "i32.le_s"], [0x4d, "i32.le_u"], [0x4e, "i32.ge_s"], [0x4f, "i32.ge_u"],
+  [0x50, "i64.eqz"], [0x51, "i64.eq"], [0x52, "i64.ne"], [0x53, "i64.lt_s"], [0x54, "i64.lt_u"],
+  [0x55, "i64.gt_s"], [0x56, "i64.gt_u"], [0x57, "i64.le_s"], [0x58, "i64.le_u"],
+  [0x59, "i64.ge_s"], [0x5a, "i64.ge_u"], [0x5b, "f32.eq"], [0x5c, "f32.ne"], [0x5d, "f32.lt"],
+  [0x5e, "f32.gt"], [0x5f, "f32.le"], [0x60, "f32.ge"], [0x61, "f64.eq"], [0x62, "f64.ne"],
+  [0x63, "f64.lt"], [0x64, "f64.gt"], [0x65, "f64.le"], [0x66, "f64.ge"], [0x67, "i32.clz"],
+  [0x68, "i32.ctz"], [0x69, "i32.popcnt"], [0x6a, "i32.add"], [0x6b, "i32.sub"], [0x6c, "i32.mul"],
+  [0x6d, "i32.div_s"], [0x6e, "i32.div_u"], [0x6f, "i32.rem_s"], [0x70, "i32.rem_u"],
+  [0x71, "i32.and"], [0x72, "i32.or"], [0x73, "i32.xor"], [0x74, "i32.shl"], [0x75, "i32.shr_s"],
+  [0x76, "i32.shr_u"], [0x77, "i32.rotl"], [0x78, "i32.rotr"], [0x79, "i64.clz"],
+  [0x7a, "i64.ctz"], [0x7b, "i64.popcnt"], [0x7c, "i64.add"], [0x7d, "i64.sub"], [0x7e, "i64.mul"],
+  [0x7f, "i64.div_s"], [0x80, "i64.div_u"], [0x81, "i64.rem_s"], [0x82, "i64.rem_u"],
+  [0x83, "i64.and"], [0x84, "i64.or"], [0x85, "i64.xor"], [0x86, "i64.shl"], [0x87, "i64.shr_s"],
+  [0x88, "i64.shr_u"], [0x89, "i64.rotl"], [0x8a, "i64.rotr"], [0x8b, "f32.abs"],
+  [0x8c, "f32.neg"], [0x8d, "f32.ceil"], [0x8e, "f32.floor"], [0x8f, "f32.trunc"],
+  [0x90, "f32.nearest"], [0x91, "f32.sqrt"], [0x92, "f32.add"], [0x93, "f32.sub"],
+  [0x94, "f32.mul"], [0x95, "f32.div"], [0x96, "f32.min"], [0x97, "f32.max"],
+  [0x98, "f32.copysign"], [0x99, "f64.abs"], [0x9a, "f64.neg"], [0x9b, "f64.ceil"],
+  [0x9c, "f64.floor"], [0x9d, "f64.trunc"], [0x9e, "f64.nearest"], [0x9f, "f64.sqrt"],
+  [0xa0, "f64.add"], [0xa1, "f64.sub"], [0xa2, "f64.mul"], [0xa3, "f64.div"], [0xa4, "f64.min"],
+  [0xa5, "f64.max"], [0xa6, "f64.copysign"], [0xa7, "i32.wrap_i64"], [0xa8, "i32.trunc_f32_s"],
+  [0xa9, "i32.trunc_f32_u"], [0xaa, "i32.trunc_f64_s"], [0xab, "i32.trunc_f64_u"],
+  [0xac, "i64.extend_i32_s"], [0xad, "i64.extend_i32_u"], [0xae, "i64.trunc_f32_s"],
+  [0xaf, "i64.trunc_f32_u"], [0xb0, "i64.trunc_f64_s"], [0xb1, "i64.trunc_f64_u"],
+  [0xb2, "f32.convert_i32_s"], [0xb3, "f32.convert_i32_u"], [0xb4, "f32.convert_i64_s"],
+  [0xb5, "f32.convert_i64_u"], [0xb6, "f32.demote_f64"], [0xb7, "f64.convert_i32_s"],
+  [0xb8, "f64.convert_i32_u"], [0xb9, "f64.convert_i64_s"], [0xba, "f64.convert_i64_u"],
+  [0xbb, "f64.promote_f32"], [0xbc, "i32.reinterpret_f32"], [0xbd, "i64.reinterpret_f64"],
+  [0xbe, "f32.reinterpret_i32"], [0xbf, "f64.reinterpret_i64"], [0xc0, "i32.extend8_s"],
+  [0xc1, "i32.extend16_s"], [0xc2, "i64.extend8_s"], [0xc3, "i64.extend16_s"],
+  [0xc4, "i64.extend32_s"], [0xd0, "ref.null"], [0xd1, "ref.is_null"], [0xd2, "ref.func"],
+  [0xd3, "ref.eq"], [0xd4, "ref.as_non_null"], [0xd5, "br_on_null"], [0xd6, "br_on_non_null"],
+]);
+
+const VALUE_TYPE_NAMES = new Map<number, string>([
+  [0x7f, "i32"], [0x7e, "i64"], [0x7d, "f32"], [0x7c, "f64"], [0x7b, "v128"], [0x70, "funcref"], [0x6f, "externref"],
+]);
+
+function hexBytes(bytes: Uint8Array): string {
+  return Array.from(bytes, (byte) => byte.toString(16).padStart(2, "0")).join(" ");
+}
+
+/**
+ * Renders `wasm` as text for golden-file tests.  Each function body is listed
+ * one instruction per line, indented by block depth, with LEB immediates of
+ * constants, locals, globals, calls, and branches decoded.  Other sections are
+ * listed as hex, 16 bytes per line.
+ */
+export function disassembleWasm(wasm: Uint8Array): string {
+  const reader = new WasmReader(wasm, 8);
+  const lines: string[] = [];
+  const functionNames = new Map<number, string>();
+  let importedFunctions = 0;
+  const sections: { id: number; start: number; end: number }[] = [];
+  while (reader.offset < wasm.length) {
+    const id = reader.readByte();
+    const size = reader.readLeb();
+    sections.push({ id, start: reader.offset, end: reader.offset + size });
+    if (id === 2) {
+      const count = reader.readLeb();
+      for (let entry = 0; entry < count; entry += 1) {
+        reader.offset += reader.readLeb();
+        reader.offset += reader.readLeb();
+        if (reader.readByte() === 0) {
+          importedFunctions += 1;
+        }
+        reader.skipLeb();
+      }
+    } else if (id === 7) {
+      const count = reader.readLeb();
+      for (let entry = 0; entry < count; entry += 1) {
+        const length = reader.readLeb();
+        const name = new TextDecoder().decode(wasm.subarray(reader.offset, reader.offset + length));
+        reader.offset += length;
+        const kind = reader.readByte();
+        const index = reader.readLeb();
+        if (kind === 0 && !functionNames.has(index)) {
+          functionNames.set(index, name);
+        }
+      }
+    }
+    reader.offset = sections[sections.length - 1]!.end;
+  }
+
+  const readSignedLeb = (): bigint => {
+    let result = 0n;
+    let shift = 0n;
+    let byte: number;
+    do {
+      byte = reader.readByte();
+      result |= BigInt(byte & 0x7f) << shift;
+      shift += 7n;
+    } while (byte & 0x80);
+    return byte & 0x40 ? result - (1n << shift) : result;
+  };
+  const disassembleBody = (end: number): void => {
+    const groups = reader.readLeb();
+    const locals: string[] = [];
+    for (let group = 0; group < groups; group += 1) {
+      const count = reader.readLeb();
+      const typeStart = reader.offset;
+      reader.skipValueType();
+      const type = wasm.subarray(typeStart, reader.offset);
+      locals.push(`${count} ${VALUE_TYPE_NAMES.get(type[0]!) ?? hexBytes(type)}`);
+    }
+    if (locals.length > 0) {
+      lines.push(`  (locals ${locals.join(", ")})`);
+    }
+    let depth = 1;
+    while (reader.offset < end) {
+      const start = reader.offset;
+      const opcode = reader.readByte();
+      let text: string;
+      if (opcode >= 0x02 && opcode <= 0x04) {
+        const typeStart = reader.offset;
+        reader.skipBlockType();
+        const type = wasm.subarray(typeStart, reader.offset);
+        const typeName = type[0] === 0x40 ? "" : ` ${VALUE_TYPE_NAMES.get(type[0]!) ?? hexBytes(type)}`;
+        text = `${OPCODE_NAMES.get(opcode)}${typeName}`;
+      } else if (opcode === 0x41 || opcode === 0x42) {
+        text = `${OPCODE_NAMES.get(opcode)} ${readSignedLeb()}`;
+      } else if ((opcode >= 0x0c && opcode <= 0x0d) || opcode === 0x10 || (opcode >= 0x20 && opcode <= 0x24)) {
+        text = `${OPCODE_NAMES.get(opcode)} ${reader.readLeb()}`;
+      } else {
+        const immediatesStart = reader.offset;
+        const sub = reader.skipImmediates(opcode);
+        const name = OPCODE_NAMES.get(opcode) ?? `0x${opcode.toString(16)}${sub >= 0 ? ` ${sub}` : ""}`;
+        const immediates = wasm.subarray(immediatesStart, reader.offset);
+        const shown = sub >= 0 ? immediates.subarray(encodeUnsignedLeb(sub).length) : immediates;
+        text = shown.length > 0 ? `${name} ${hexBytes(shown)}` : name;
+      }
+      if (opcode === 0x0b || opcode === 0x05) {
+        depth -= 1;
+      }
+      // The body's final `end` closes the function itself.
+      if (!(opcode === 0x0b && depth === 0 && reader.offset === end)) {
+        lines.push(`${"  ".repeat(depth)}${text}`);
+      }
+      if (opcode === 0x02 || opcode === 0x03 || opcode === 0x04 || opcode === 0x05) {
+        depth += 1;
+      }
+      if (reader.offset <= start) {
+        throw new Error("instruction did not advance");
+      }
+    }
+  };
+
+  for (const section of sections) {
+    reader.offset = section.start;
+    if (section.id === 10) {
+      const count = reader.readLeb();
+      for (let body = 0; body < count; body += 1) {
+        const size = reader.readLeb();
+        const end = reader.offset + size;
+        const index = importedFunctions + body;
+        lines.push(`func ${functionNames.get(index) ?? `#${index}`}`);
+        disassembleBody(end);
+        reader.offset = end;
+      }
+      continue;
+    }
+    lines.push(`section ${section.id}`);
+    for (let offset = section.start; offset < section.end; offset += 16) {
+      lines.push(`  ${hexBytes(wasm.subarray(offset, Math.min(offset + 16, section.end)))}`);
+    }
+  }
+  return `${lines.join("\n")}\n`;
+}
@@ -1,5 +1,6 @@
 import { expect, test } from "bun:test";
 
+import { CompileError, CompileErrorKind, Target, compile } from "../src/index";
 import {
   compileWithAstCompiler,
   expectCompileFailure,
@@ -51,3 +52,23 @@ test("inline_wasm enforces u8 range", async () => {
     "/entry.bp:3:9: inline_wasm argument must be an array literal of u8 values",
   );
 });
+
+test("validateOutput rejects invalid inline_wasm and names the function", async () => {
+  const source = `fn widen() -> i32 {
+    inline_wasm([0x42, 0x01])
+}
+
+fn main() -> i32 {
+    widen()
+}
+`;
+  const unchecked = await compile(source);
+  expect(WebAssembly.validate(unchecked.wasm)).toBe(false);
+
+  const error = await compile(source, Target.Wasm, { validateOutput: true }).catch((caught: unknown) => caught);
+  expect(error).toBeInstanceOf(CompileError);
+  const failure = error as CompileError;
+  expect(failure.kind).toBe(CompileErrorKind.Emit);
+  expect(failure.record?.message).toMatch(/^emitted wasm fails validation in function 'widen': /);
+  expect(failure.span?.line).toBe(1);
+});
@@ -0,0 +1,55 @@
+import { expect, test } from "bun:test";
+
+import { COMPILER_VERSION, appendBuildMeta, compile, formatWasmInspection, inspectWasm } from "../src/index";
+
+test("inspection lists exports, memory, functions, and sections", async () => {
+  const { wasm } = await compile("fn main() -> i32 {\n    7\n}\n", undefined, { sharedMemory: true });
+  const meta = { version: COMPILER_VERSION, compilerHash: "ab".repeat(32), sourceHash: "cd".repeat(32) };
+  const inspection = inspectWasm(appendBuildMeta(wasm, meta));
+  expect(inspection.imports).toEqual([]);
+  expect(inspection.exports).toEqual([
+    { name: "memory", kind: "memory", index: 0 },
+    { name: "main", kind: "func", index: 0 },
+  ]);
+  expect(inspection.memory).toEqual({ minimum: 256, maximum: 256, shared: true });
+  expect([inspection.importedFunctions, inspection.definedFunctions]).toEqual([0, 1]);
+  expect(inspection.sections.map((section) => section.name)).toEqual([
+    "type",
+    "function",
+    "memory",
+    "export",
+    "code",
+    "custom:bootstrap.meta",
+  ]);
+  expect(inspection.meta).toEqual(meta);
+
+  const lines = formatWasmInspection(inspection).split("\n");
+  expect(lines.slice(0, 6)).toEqual([
+    "imports: 0",
+    "exports: 2",
+    "  memory: memory 0",
+    "  main: func 0",
+    "memory: 256 pages, max 256 pages, shared",
+    "functions: 0 imported, 1 defined",
+  ]);
+  expect(lines.at(-3)).toBe(`version: ${COMPILER_VERSION}`);
+});
+
+test("inspection reads imports of modules from other toolchains", () => {
+  const name = (text: string): number[] => [text.length, ...new TextEncoder().encode(text)];
+  const imports = [2, ...name("env"), ...name("log"), 0x00, 0, ...name("env"), ...name("mem"), 0x02, 0x00, 1];
+  const wasm = new Uint8Array([
+    ...[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00],
+    ...[1, 4, 1, 0x60, 0, 0],
+    ...[2, imports.length, ...imports],
+  ]);
+  const inspection = inspectWasm(wasm);
+  expect(inspection.imports).toEqual([
+    { module: "env", name: "log", kind: "func" },
+    { module: "env", name: "mem", kind: "memory" },
+  ]);
+  expect(inspection.memory).toEqual({ minimum: 1, maximum: null, shared: false });
+  expect(inspection.importedFunctions).toBe(1);
+  expect(inspection.meta).toBeNull();
+  expect(formatWasmInspection(inspection)).toContain("memory: 1 pages, no maximum\n");
+});
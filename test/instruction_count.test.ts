@@ -0,0 +1,197 @@
+import { expect, test } from "bun:test";
+
+import {
+  compileWithAstCompiler,
+  disassembleWasm,
+  expectExportedFunction,
+  instantiateWasmModuleWithGc,
+  instrumentWasmWithFuel,
+  readFunctionFuel,
+  runWasmFunctionWithFuel,
+} from "./helpers";
+
+const LOOP_SUM_SOURCE = `
+fn loop_sum(n: i32) -> i32 {
+    let mut total: i32 = 0;
+    let mut i: i32 = 0;
+    while i < n {
+        total = total + i;
+        i = i + 1;
+    };
+    total
+}
+
+fn main() -> i32 {
+    loop_sum(10)
+}
+`;
+
+test("counts executed instructions deterministically", async () => {
+  const wasm = await compileWithAstCompiler(LOOP_SUM_SOURCE);
+  const ten = await runWasmFunctionWithFuel(wasm, "loop_sum", [10]);
+  const hundred = await runWasmFunctionWithFuel(wasm, "loop_sum", [100]);
+  expect(ten.result).toBe(45);
+  expect(hundred.result).toBe(4950);
+  expect(await runWasmFunctionWithFuel(wasm, "loop_sum", [10])).toEqual(ten);
+
+  // Each iteration costs the same, so the count grows linearly with `n`.
+  const perIteration = (hundred.instructions - ten.instructions) / 90;
+  expect(Number.isInteger(perIteration)).toBe(true);
+  expect(perIteration).toBeLessThanOrEqual(17);
+  expect(ten.instructions).toBeLessThanOrEqual(200);
+});
+
+test("traps when a run exceeds its fuel", async () => {
+  const wasm = await compileWithAstCompiler(LOOP_SUM_SOURCE);
+  await expect(
+    runWasmFunctionWithFuel(wasm, "loop_sum", [1_000], { maxInstructions: 500 }),
+  ).rejects.toThrow("'loop_sum' ran out of fuel after 500 instructions");
+  const { result } = await runWasmFunctionWithFuel(wasm, "main", [], { maxInstructions: 500 });
+  expect(result).toBe(45);
+});
+
+test("splits the count between functions", async () => {
+  const wasm = await compileWithAstCompiler(LOOP_SUM_SOURCE);
+  const instance = await instantiateWasmModuleWithGc(
+    instrumentWasmWithFuel(wasm, { perFunction: true }),
+  );
+  expect(expectExportedFunction(instance, "main")()).toBe(45);
+  const counts = readFunctionFuel(instance);
+  const loopSum = await runWasmFunctionWithFuel(wasm, "loop_sum", [10]);
+  const main = await runWasmFunctionWithFuel(wasm, "main");
+  expect(counts.get("loop_sum")).toBe(loopSum.instructions);
+  expect(counts.get("main")).toBe(main.instructions - loopSum.instructions);
+});
+
+test("lowers statement guards to br_if", async () => {
+  const wasm = await compileWithAstCompiler(`
+fn first_multiple(limit: i32, step: i32) -> i32 {
+    let mut i: i32 = 1;
+    let mut found: i32 = -1;
+    loop {
+        if i > limit {
+            break;
+        };
+        i = i + 1;
+        if i % step != 0 {
+            continue;
+        };
+        if found < 0 {
+            found = i;
+        };
+    };
+    found
+}
+
+fn main() -> i32 {
+    first_multiple(20, 7)
+}
+`);
+  const operators = disassembleWasm(wasm)
+    .split("\n")
+    .map((line) => line.trim().split(" ")[0]);
+  expect(operators.filter((operator) => operator === "br_if")).toHaveLength(2);
+  expect(operators.filter((operator) => operator === "if")).toHaveLength(1);
+  expect(operators.filter((operator) => operator === "else")).toHaveLength(0);
+
+  const { result, instructions } = await runWasmFunctionWithFuel(wasm, "first_multiple", [20, 7]);
+  expect(result).toBe(7);
+  expect(instructions).toBeLessThanOrEqual(379);
+});
+
+test("emits statement else-if chains as nested void ifs", async () => {
+  const wasm = await compileWithAstCompiler(`
+fn bucket(x: i32) -> i32 {
+    let mut y: i32 = 0;
+    if x < 0 {
+        y = 1;
+    } else if x == 0 {
+        y = 2;
+    } else if x < 10 {
+        y = 3;
+    };
+    y
+}
+
+fn main() -> i32 {
+    bucket(0) * 10 + bucket(5)
+}
+`);
+  const text = disassembleWasm(wasm);
+  const operators = text
+    .slice(text.indexOf("func bucket"), text.indexOf("func main"))
+    .split("\n")
+    .slice(2)
+    .map((line) => line.trim().split(" ")[0])
+    .filter((operator) => operator !== "");
+  expect(operators.filter((operator) => operator === "if")).toHaveLength(3);
+  expect(operators.filter((operator) => operator === "else")).toHaveLength(2);
+  expect(operators.filter((operator) => operator === "drop")).toHaveLength(0);
+  expect(operators).toHaveLength(26);
+
+  const { result } = await runWasmFunctionWithFuel(wasm, "main");
+  expect(result).toBe(23);
+});
+
+test("lowers dense literal else-if chains to br_table", async () => {
+  const wasm = await compileWithAstCompiler(`
+fn dense(d: i32) -> i32 {
+    if d == 3 {
+        40
+    } else if d == 4 {
+        30
+    } else if d == 5 {
+        30
+    } else if d == 7 {
+        50
+    } else {
+        d
+    }
+}
+
+fn sparse(d: i32) -> i32 {
+    if d == 1 {
+        1
+    } else if d == 10 {
+        2
+    } else if d == 100 {
+        3
+    } else if d == 1000 {
+        4
+    } else {
+        0
+    }
+}
+
+fn stop_at(limit: i32) -> i32 {
+    let mut i: i32 = 0;
+    loop {
+        i = i + 1;
+        if i == 1 {
+            continue;
+        } else if i == 2 {
+            i = i + 1;
+        } else if i == 3 {
+            i = i + 2;
+        } else if i == limit {
+            break i
+        }
+    }
+}
+
+fn main() -> i32 {
+    dense(4) + sparse(100) + stop_at(8)
+}
+`);
+  const text = disassembleWasm(wasm);
+  const body = (name: string): string => text.slice(text.indexOf(`func ${name}`)).split("\nfunc ")[0]!;
+  expect(body("dense")).toContain("br_table 05 00 01 02 04 03 04");
+  expect(body("dense")).not.toContain("if");
+  expect(body("sparse")).not.toContain("br_table");
+  expect(body("stop_at")).not.toContain("br_table");
+
+  const instance = await instantiateWasmModuleWithGc(wasm);
+  const dense = expectExportedFunction(instance, "dense");
+  expect([2, 3, 4, 5, 6, 7, 8, -1].map((d) => dense(d))).toEqual([2, 40, 30, 30, 6, 50, 8, -1]);
+  expect(expectExportedFunction(instance, "main")()).toBe(30 + 3 + 8);
+});
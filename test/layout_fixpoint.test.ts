@@ -0,0 +1,97 @@
+import { expect, test } from "bun:test";
+
+import { verifyBootstrap } from "../src/bootstrap";
+import { seededRandom } from "../src/fuzz";
+import { CompileError, Compiler, DEFAULT_STAGE2_LAYOUT, instantiateStage2 } from "../src/index";
+import type { CompilerModuleSource, Stage2Layout } from "../src/index";
+
+import { AST_COMPILER_ENTRY_PATH, readAstCompilerModules } from "./helpers";
+
+// Each layout costs two self-compiles.  Raise the count for a longer soak, and
+// pick the seed to reproduce a failure.
+const LAYOUT_RUNS = Number(process.env.BOOTSTRAP_LAYOUT_RUNS ?? 2);
+const LAYOUT_SEED = Number(process.env.BOOTSTRAP_LAYOUT_SEED ?? 2830);
+
+// The scratch function table starts where the default type table ends.
+const SCRATCH_FN_BASE_OFFSET =
+  DEFAULT_STAGE2_LAYOUT.scratchTypesBaseOffset + DEFAULT_STAGE2_LAYOUT.scratchTypesCapacity * 16;
+
+// The call data capacity is a word count less the constants section's words.
+const CONSTANTS_SECTION_WORDS = 196_608 - DEFAULT_STAGE2_LAYOUT.astCallDataCapacity;
+
+// Constants rewritten in the compiler's source, with how far each may move.
+// Capacities only grow, so the compiler still fits its own program.
+interface LayoutChoice {
+  readonly constants: Readonly<Record<string, string>>;
+  readonly expected: Partial<Stage2Layout>;
+}
+
+function randomLayout(random: () => number): LayoutChoice {
+  const between = (low: number, high: number, step: number): number =>
+    low + Math.floor(random() * ((high - low) / step + 1)) * step;
+  const scratchInstrCapacity = between(65_536, 524_288, 4_096);
+  const scratchTypesCapacity = between(2_048, 4_096, 256);
+  const astMaxFunctions = between(2_048, 4_096, 64);
+  const reserveSlack = between(16_384, 262_144, 4_096);
+  const astNamesCapacity = between(262_144, 393_216, 4_096);
+  const callDataWords = between(196_608, 229_376, 1_024);
+  return {
+    constants: {
+      SCRATCH_INSTR_CAPACITY: String(scratchInstrCapacity),
+      SCRATCH_TYPES_CAPACITY: String(scratchTypesCapacity),
+      AST_MAX_FUNCTIONS: String(astMaxFunctions),
+      AST_NAMES_CAPACITY: String(astNamesCapacity),
+      AST_CALL_DATA_CAPACITY: `${callDataWords} - AST_CONSTANTS_SECTION_WORDS`,
+      AST_OUTPUT_RESERVE_MIN: `SCRATCH_FN_BASE_OFFSET + ${reserveSlack}`,
+    },
+    expected: {
+      scratchInstrCapacity,
+      scratchTypesCapacity,
+      astMaxFunctions,
+      astNamesCapacity,
+      astCallDataCapacity: callDataWords - CONSTANTS_SECTION_WORDS,
+      astOutputReserveMin: SCRATCH_FN_BASE_OFFSET + reserveSlack,
+      scratchTypesBaseOffset: SCRATCH_FN_BASE_OFFSET - scratchTypesCapacity * 16,
+    },
+  };
+}
+
+function applyLayout(modules: ReadonlyArray<CompilerModuleSource>, layout: LayoutChoice): CompilerModuleSource[] {
+  const remaining = new Set(Object.keys(layout.constants));
+  const rewritten = modules.map((module) => ({
+    path: module.path,
+    source: module.source.replace(/^const ([A-Z0-9_]+): i32 = [^;]+;/gm, (line, name: string) => {
+      const value = layout.constants[name];
+      if (value === undefined) {
+        return line;
+      }
+      remaining.delete(name);
+      return `const ${name}: i32 = ${value};`;
+    }),
+  }));
+  expect([...remaining]).toEqual([]);
+  return rewritten;
+}
+
+test("self-compilation reaches a fixed point under randomized memory layouts", async () => {
+  const modules = await readAstCompilerModules();
+  const random = seededRandom(LAYOUT_SEED);
+  for (let run = 0; run < LAYOUT_RUNS; run += 1) {
+    const layout = randomLayout(random);
+    const rewritten = applyLayout(modules, layout);
+    const entry = rewritten.find((module) => module.path === AST_COMPILER_ENTRY_PATH)!;
+    const report = await verifyBootstrap(entry, rewritten.filter((module) => module !== entry));
+    expect({ layout: layout.constants, identical: report.diff === null }).toEqual({
+      layout: layout.constants,
+      identical: true,
+    });
+
+    // The host takes the layout from the rebuilt compiler's globals, and still
+    // decodes its output and its failures.
+    const stage2 = await instantiateStage2(report.stage2);
+    expect({ ...stage2.layout, ...layout.expected }).toEqual({ ...stage2.layout! });
+    const compiler = await Compiler.create(() => instantiateStage2(report.stage2));
+    expect((await compiler.compile("fn main() -> i32 { 7 }")).wasm.length).toBeGreaterThan(0);
+    await expect(compiler.compile("fn main() -> i32 { true }")).rejects.toThrow(CompileError);
+  }
+}, { timeout: 60_000 * Math.max(1, LAYOUT_RUNS) });
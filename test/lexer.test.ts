@@ -0,0 +1,65 @@
+import { expect, test } from "bun:test";
+import { readdir } from "node:fs/promises";
+
+import { lineColumnAt } from "../src/index";
+import { tokenizeWithAstCompiler } from "./helpers";
+import type { Token } from "./helpers";
+
+// Each `test/lexer/<name>.bp` is split into tokens by the stage2 compiler's
+// `tokenize` export and compared with `<name>.tokens`, one token per line.
+// Run with `BOOTSTRAP_UPDATE_GOLDEN=1` to rewrite the expected files, then
+// review the diff.
+const CORPUS_URL = new URL("./lexer/", import.meta.url);
+const UPDATE_GOLDEN = process.env.BOOTSTRAP_UPDATE_GOLDEN === "1";
+
+function renderTokens(source: string, tokens: ReadonlyArray<Token>): string {
+  return tokens
+    .map((token) => {
+      const { line, column } = lineColumnAt(source, token.offset);
+      const value = token.kind === "integer" || token.kind === "character" ? ` = ${token.value}` : "";
+      return `${line}:${column} ${token.kind} ${token.text}${value}\n`;
+    })
+    .join("");
+}
+
+// Whatever lies between tokens must be whitespace or comments, so no source
+// text is skipped.
+function expectOnlyTriviaBetween(source: string, tokens: ReadonlyArray<Token>): void {
+  let cursor = 0;
+  for (const token of tokens) {
+    expect(token.offset).toBeGreaterThanOrEqual(cursor);
+    const gap = source.slice(cursor, token.offset).replace(/\/\/[^\n]*/g, "");
+    expect(gap.replace(/\/\*[\s\S]*\*\//g, "").trim()).toBe("");
+    cursor = token.offset + token.text.length;
+  }
+}
+
+const names = (await readdir(CORPUS_URL))
+  .filter((file) => file.endsWith(".bp"))
+  .map((file) => file.slice(0, -".bp".length))
+  .sort();
+
+for (const name of names) {
+  test(`tokenizes ${name}`, async () => {
+    const source = await Bun.file(new URL(`${name}.bp`, CORPUS_URL)).text();
+    const tokens = await tokenizeWithAstCompiler(source);
+    expectOnlyTriviaBetween(source, tokens);
+    const rendered = renderTokens(source, tokens);
+    const golden = Bun.file(new URL(`${name}.tokens`, CORPUS_URL));
+    if (UPDATE_GOLDEN) {
+      await Bun.write(golden, rendered);
+    }
+    if (!(await golden.exists())) {
+      throw new Error(`${name}.tokens is missing; run with BOOTSTRAP_UPDATE_GOLDEN=1 to create it`);
+    }
+    expect(rendered).toBe(await golden.text());
+  });
+}
+
+test("an invalid byte ends the token stream", async () => {
+  const tokens = await tokenizeWithAstCompiler("a $ b");
+  expect(tokens.map((token) => [token.kind, token.text])).toEqual([
+    ["identifier", "a"],
+    ["invalid", "$"],
+  ]);
+});
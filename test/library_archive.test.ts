@@ -0,0 +1,68 @@
+import { expect, test } from "bun:test";
+
+import {
+  compile,
+  compileLibrary,
+  libraryInterfaceSource,
+  readLibraryArchive,
+  readLibraryInterface,
+  writeLibraryArchive,
+} from "../src/index";
+import { expectExportedFunction, instantiateWasmModuleWithGc } from "./helpers";
+
+const LIBRARY = `// Numeric helpers.
+fn square(x: i32) -> i32 {
+    x * x
+}
+
+fn clamp_sum(pair: (i32, i32), limit: i32) -> i32 {
+    let sum: i32 = pair.0 + pair.1;
+    if sum > limit { limit } else { sum }
+}
+
+fn identity<T>(value: T) -> T {
+    value
+}
+
+fn scale(const factor: i32, x: i32) -> i32 {
+    factor * x
+}
+`;
+
+test("library archives carry an interface and compiled bodies", async () => {
+  expect(readLibraryInterface(LIBRARY)).toEqual([
+    { name: "square", params: [{ name: "x", type: "i32" }], returnType: "i32" },
+    {
+      name: "clamp_sum",
+      params: [
+        { name: "pair", type: "(i32, i32)" },
+        { name: "limit", type: "i32" },
+      ],
+      returnType: "i32",
+    },
+  ]);
+
+  const archive = await compileLibrary(LIBRARY);
+  const decoded = readLibraryArchive(archive);
+  expect(writeLibraryArchive(decoded)).toEqual(archive);
+  expect(libraryInterfaceSource(decoded).split("\n").slice(0, 3)).toEqual([
+    "fn square(x: i32) -> i32 {",
+    "    inline_wasm([0x00])",
+    "}",
+  ]);
+  expect(() => readLibraryArchive(decoded.wasm, "/lib/numeric.bpl")).toThrow(
+    "'/lib/numeric.bpl' is not a library archive",
+  );
+
+  const program = `use "/lib/numeric.bpl";
+
+fn main() -> i32 {
+    square(6) + clamp_sum((3, 4), 5)
+}
+`;
+  const compilation = await compile(program, undefined, {
+    libraries: [{ path: "/lib/numeric.bpl", archive }],
+  });
+  const instance = await instantiateWasmModuleWithGc(compilation.wasm);
+  expect(expectExportedFunction(instance, "main")()).toBe(41);
+});
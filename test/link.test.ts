@@ -0,0 +1,52 @@
+import { expect, test } from "bun:test";
+
+import { compile, inspectWasm, link } from "../src/index";
+import { expectExportedFunction, instantiateWasmModuleWithGc } from "./helpers";
+
+const name = (text: string): number[] => [text.length, ...new TextEncoder().encode(text)];
+
+// Exports `cube(x)` as `x * square(x)`, with `square` imported from `env`.
+function cubeModule(): Uint8Array {
+  const imports = [1, ...name("env"), ...name("square"), 0x00, 0];
+  const exports = [1, ...name("cube"), 0x00, 1];
+  const body = [0, 0x20, 0, 0x10, 0, 0x20, 0, 0x6c, 0x0b];
+  return new Uint8Array([
+    ...[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00],
+    ...[1, 6, 1, 0x60, 1, 0x7f, 1, 0x7f],
+    ...[2, imports.length, ...imports],
+    ...[3, 2, 1, 0],
+    ...[7, exports.length, ...exports],
+    ...[10, body.length + 2, 1, body.length, ...body],
+  ]);
+}
+
+test("linking merges modules and resolves imports by export name", async () => {
+  const square = await compile("fn square(x: i32) -> i32 {\n    x * x\n}\n");
+  const pair = await compile(`fn twice(x: i32) -> i32 {
+    let pair: (i32, i32) = (x, x);
+    pair.0 + pair.1
+}
+
+fn main() -> i32 {
+    twice(21)
+}
+`);
+  const linked = link([square.wasm, pair.wasm, cubeModule()]);
+  const inspection = inspectWasm(linked);
+  expect(inspection.imports).toEqual([]);
+  expect(inspection.exports.map((entry) => entry.name)).toEqual(["memory", "square", "twice", "main", "cube"]);
+  expect(inspection.definedFunctions).toBe(4);
+
+  const instance = await instantiateWasmModuleWithGc(linked);
+  expect(expectExportedFunction(instance, "square")(5)).toBe(25);
+  expect(expectExportedFunction(instance, "main")()).toBe(42);
+  expect(expectExportedFunction(instance, "cube")(3)).toBe(27);
+
+  // Without a module exporting `square`, the import is left for the host.
+  expect(inspectWasm(link([pair.wasm, cubeModule()])).imports).toEqual([
+    { module: "env", name: "square", kind: "func" },
+  ]);
+  expect(() => link([square.wasm, square.wasm], { labels: ["a.wasm", "b.wasm"] })).toThrow(
+    "export 'square' is defined by both a.wasm and b.wasm",
+  );
+});
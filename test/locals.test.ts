@@ -2,7 +2,9 @@ import { expect, test } from "bun:test";
 
 import {
   compileWithAstCompiler,
+  disassembleWasm,
   expectCompileFailure,
+  normalizeWasmLocals,
   runWasmMainWithGc,
 } from "./helpers";
 
@@ -90,3 +92,54 @@ test(
     expect(failure.failure.detail).toBe("/entry.bp:3:28: local initializer type mismatch");
   },
 );
+
+test("normalizing locals groups them by type without changing behavior", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn main() -> i32 {
+        let small: i32 = 3;
+        let wide: i64 = 40 as i64;
+        let other: i32 = small + 1;
+        let wider: i64 = wide + other as i64;
+        wider as i32 - small
+    }
+  `);
+  const normalized = normalizeWasmLocals(wasm);
+  // Four declaration runs (i32, i64, i32, i64) become two, saving two bytes each.
+  expect(normalized.length).toBe(wasm.length - 4);
+  expect(normalizeWasmLocals(normalized)).toEqual(normalized);
+  expect(await runWasmMainWithGc(normalized)).toBe(await runWasmMainWithGc(wasm));
+  expect(await runWasmMainWithGc(normalized)).toBe(41);
+});
+
+test("lets with disjoint scopes share wasm locals", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn sum(n: i32) -> i32 {
+        let mut total: i32 = 0;
+        let mut i: i32 = 0;
+        while i < n {
+            let square: i32 = i * i;
+            total = total + square;
+            i = i + 1;
+        };
+        let mut j: i32 = 0;
+        while j < n {
+            let half: i32 = j / 2;
+            let wide: i64 = half as i64;
+            total = total + wide as i32;
+            j = j + 1;
+        };
+        total
+    }
+
+    fn main() -> i32 {
+        sum(6)
+    }
+  `);
+  const lines = disassembleWasm(wasm).split("\n");
+  const body = lines.slice(lines.indexOf("func sum") + 1);
+  // Six lets need four i32 slots and one i64 slot, as `j` reuses the slot of
+  // `square`.  `half` is stored with a tee that also feeds `wide`.
+  expect(body[0]).toBe("  (locals 4 i32, 1 i64)");
+  expect(body.filter((line) => line.trim() === "local.tee 4")).toHaveLength(1);
+  expect(await runWasmMainWithGc(wasm)).toBe(61);
+});
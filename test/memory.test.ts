@@ -1,10 +1,18 @@
 import { expect, test } from "bun:test";
 
+import {
+  DEFAULT_MEMORY_PAGES,
+  Target,
+  compile,
+  compileToWasm,
+  describeMemoryLayout,
+} from "../src/index";
 import {
   compileWithAstCompiler,
   expectExportedFunction,
   expectExportedMemory,
   instantiateWasmModuleWithGc,
+  runWasmMainWithGc,
 } from "./helpers";
 
 const MEMORY_INTRINSICS_PATH = "/stdlib/memory.bp";
@@ -37,6 +45,293 @@ test("exports multi-page memory", async () => {
   expect(sliceLen(0, 42)).toBe(42);
 });
 
+test("memory pages size the emitted memory", async () => {
+  const source = `
+use "/stdlib/memory.bp";
+
+fn main() -> i32 {
+    store_i32(65532, 7);
+    load_i32(65532)
+}
+`;
+  const small = await compileMemoryProgram(source, "/tests/pages.bp");
+  expect(await runWasmMainWithGc(small, { memoryPages: DEFAULT_MEMORY_PAGES })).toBe(7);
+  const single = await compileWithAstCompiler(source, {
+    entryPath: "/tests/pages.bp",
+    modules: [{ path: MEMORY_INTRINSICS_PATH, source: await memoryIntrinsicsSourcePromise }],
+    memoryPages: 1,
+  });
+  expect(await runWasmMainWithGc(single, { memoryPages: 1 })).toBe(7);
+  await expect(runWasmMainWithGc(single, { memoryPages: 2 })).rejects.toThrow(
+    /expected 2 memory pages but module has 1/,
+  );
+  await expect(compileToWasm(source, { memoryPages: 0 })).rejects.toThrow(/memoryPages/);
+});
+
+test("layout globals report data end and heap base", async () => {
+  const source = `
+    fn divide(a: i32, b: i32) -> i32 {
+        a / b
+    }
+
+    fn main() -> i32 {
+        divide(8, 2)
+    }
+  `;
+  const instance = await instantiateWasmModuleWithGc(
+    await compileWithAstCompiler(source, { layoutGlobals: true, checkedDivision: true }),
+  );
+  const memory = expectExportedMemory(instance);
+  expect(describeMemoryLayout(instance)).toEqual({
+    memoryBytes: memory.buffer.byteLength,
+    dataEnd: 1024,
+    heapBase: 1024,
+  });
+  const divide = expectExportedFunction(instance, "divide");
+  expect(() => divide(1, 0)).toThrow();
+  const panicCode = (instance.exports as Record<string, unknown>).__panic_code as WebAssembly.Global;
+  expect(panicCode.value).toBe(1);
+
+  const plain = await instantiateWasmModuleWithGc(await compileWithAstCompiler(source));
+  expect((plain.exports as Record<string, unknown>).__heap_base).toBeUndefined();
+  expect(describeMemoryLayout(plain)).toBeNull();
+});
+
+test("guarded stores trap below static data with the faulting address", async () => {
+  const source = `
+use "/stdlib/memory.bp";
+
+fn write_word(ptr: i32, value: i32) -> i32 {
+    store_i32(ptr, value);
+    load_i32(ptr)
+}
+
+fn main() -> i32 {
+    0
+}
+`;
+  const compilation = await compile(source, Target.Wasm, { guardStores: true });
+  const instance = await instantiateWasmModuleWithGc(compilation.toWasm());
+  const writeWord = expectExportedFunction(instance, "write_word");
+  expect(writeWord(2048, 77)).toBe(77);
+  expect(compilation.describePanic(instance)).toBeNull();
+
+  expect(() => writeWord(16, 1)).toThrow();
+  expect(compilation.describePanic(instance)).toEqual({
+    message: "store outside writable memory",
+    path: "/entry.bp",
+    line: 0,
+    column: 0,
+    address: 16,
+  });
+});
+
+test("memory canaries bracket static data and detect corruption", async () => {
+  const source = `
+use "/stdlib/memory.bp";
+
+fn poke(ptr: i32, value: i32) -> i32 {
+    store_u8(ptr, value);
+    load_u8(ptr)
+}
+
+fn main() -> i32 {
+    0
+}
+`;
+  const compilation = await compile(source, Target.Wasm, {
+    memoryCanaries: true,
+    guardStores: true,
+    layoutGlobals: true,
+  });
+  const instance = await instantiateWasmModuleWithGc(compilation.toWasm());
+  const memory = expectExportedMemory(instance);
+  const view = new DataView(memory.buffer);
+  expect(view.getUint32(1020, true)).toBe(0x5afec0de);
+  expect(view.getUint32(1024, true)).toBe(0x5afec0de);
+  expect(compilation.describeLayout(instance)).toEqual({
+    memoryBytes: memory.buffer.byteLength,
+    dataEnd: 1024,
+    heapBase: 1040,
+  });
+  expect(compilation.checkCanaries(instance)).toBeNull();
+
+  const poke = expectExportedFunction(instance, "poke");
+  expect(poke(1028, 9)).toBe(9);
+  expect(() => poke(1027, 9)).toThrow();
+  expect(compilation.describePanic(instance)?.address).toBe(1027);
+
+  view.setUint8(1021, 0);
+  expect(compilation.checkCanaries(instance)).toEqual({
+    message: "memory canary corrupted",
+    path: "/entry.bp",
+    line: 0,
+    column: 0,
+    address: 1020,
+  });
+});
+
+test("address sanitizer reports out-of-bounds and freed accesses", async () => {
+  const source = `
+use "/stdlib/memory.bp";
+use "/stdlib/alloc.bp";
+
+fn fill(len: i32, index: i32) -> i32 {
+    let buffer: i32 = alloc(len);
+    store_u8(buffer + index, 7);
+    load_u8(buffer + index)
+}
+
+fn read_after_free() -> i32 {
+    let buffer: i32 = alloc(8);
+    store_i32(buffer, 5);
+    free(buffer);
+    load_i32(buffer)
+}
+
+fn main() -> i32 {
+    fill(5, 4)
+}
+`;
+  const compilation = await compile(source, Target.Wasm, { addressSanitizer: true });
+  const instance = await instantiateWasmModuleWithGc(compilation.toWasm());
+  const fill = expectExportedFunction(instance, "fill");
+  expect(fill(5, 4)).toBe(7);
+  expect(() => fill(5, 5)).toThrow();
+  const overflow = compilation.describePanic(instance);
+  expect(overflow?.message).toBe("invalid memory access (outside any live allocation)");
+  expect((overflow?.address ?? 0) % 8).toBe(5);
+
+  const readAfterFree = expectExportedFunction(instance, "read_after_free");
+  expect(() => readAfterFree()).toThrow();
+  expect(compilation.describePanic(instance)?.message).toBe(
+    "invalid memory access (use after free)",
+  );
+
+  const plain = await compile(source, Target.Wasm);
+  const plainInstance = await instantiateWasmModuleWithGc(plain.toWasm());
+  expect(expectExportedFunction(plainInstance, "fill")(5, 5)).toBe(7);
+
+  // The shadow region moves with a smaller memory.
+  const small = await compile(source, Target.Wasm, { addressSanitizer: true, memoryPages: 4 });
+  const smallInstance = await instantiateWasmModuleWithGc(small.toWasm());
+  expect(expectExportedFunction(smallInstance, "fill")(5, 4)).toBe(7);
+  expect(() => expectExportedFunction(smallInstance, "read_after_free")()).toThrow();
+  expect(small.describePanic(smallInstance)?.message).toBe("invalid memory access (use after free)");
+});
+
+test("stackalloc reserves frame scratch space released on every exit", async () => {
+  const source = `
+use "/stdlib/memory.bp";
+
+fn sum_squares(count: i32) -> i32 {
+    let buffer: i32 = stackalloc(count * 4);
+    let mut idx: i32 = 0;
+    while idx < count {
+        store_i32(buffer + idx * 4, idx * idx);
+        idx = idx + 1;
+    };
+    let mut total: i32 = 0;
+    idx = 0;
+    while idx < count {
+        total = total + load_i32(buffer + idx * 4);
+        idx = idx + 1;
+    };
+    total
+}
+
+fn scratch_address(early: bool) -> i32 {
+    let buffer: i32 = stackalloc(12);
+    if early {
+        return buffer;
+    };
+    sum_squares(3);
+    buffer
+}
+
+fn main() -> i32 {
+    sum_squares(4)
+}
+`;
+  const instance = await instantiateWasmModuleWithGc((await compile(source)).toWasm());
+  const stackPointer = instance.exports.__stack_pointer as WebAssembly.Global;
+  expect(stackPointer.value).toBe(65_536);
+  expect(expectExportedFunction(instance, "main")()).toBe(14);
+  expect(stackPointer.value).toBe(65_536);
+  const scratchAddress = expectExportedFunction(instance, "scratch_address");
+  expect(scratchAddress(1)).toBe(65_520);
+  expect(scratchAddress(0)).toBe(65_520);
+  expect(stackPointer.value).toBe(65_536);
+
+  await expect(
+    compile(`
+const fn scratch() -> i32 {
+    stackalloc(4)
+}
+
+fn main() -> i32 {
+    scratch()
+}
+`),
+  ).rejects.toThrow(/stackalloc is only allowed in runtime functions/);
+});
+
+test("address-taken locals live in shadow stack slots across recursion", async () => {
+  const source = `
+use "/stdlib/memory.bp";
+
+fn bump(ptr: i32) {
+    store_i32(ptr, load_i32(ptr) + 1);
+}
+
+fn depth_sum(n: i32) -> i32 {
+    let mut acc: i32 = n;
+    bump(&acc);
+    if n > 0 {
+        let inner: i32 = depth_sum(n - 1);
+        let mut copy: i32 = n;
+        bump(&copy);
+        return acc + inner + copy;
+    };
+    acc
+}
+
+fn inner_address() -> i32 {
+    let y: i32 = 0;
+    &y
+}
+
+fn frame_distance() -> i32 {
+    let x: i32 = 0;
+    let outer: i32 = &x;
+    outer - inner_address()
+}
+
+fn main() -> i32 {
+    depth_sum(3)
+}
+`;
+  const instance = await instantiateWasmModuleWithGc((await compile(source)).toWasm());
+  const stackPointer = instance.exports.__stack_pointer as WebAssembly.Global;
+  expect(expectExportedFunction(instance, "main")()).toBe(19);
+  expect(stackPointer.value).toBe(65_536);
+  expect(expectExportedFunction(instance, "depth_sum")(3)).toBe(19);
+  expect(expectExportedFunction(instance, "frame_distance")()).toBe(8);
+  expect(stackPointer.value).toBe(65_536);
+
+  await expect(
+    compile(`
+fn twice(n: i32) -> i32 {
+    &n
+}
+
+fn main() -> i32 {
+    twice(1)
+}
+`),
+  ).rejects.toThrow(/address-of operand must be a local variable/);
+});
+
 test("reads last byte from input slice", async () => {
   const wasm = await compileMemoryProgram(
     `
@@ -0,0 +1,62 @@
+import { expect, test } from "bun:test";
+
+import {
+  CompileError,
+  MESSAGES,
+  compile,
+  formatCompileError,
+  formatCompileWarning,
+  formatMessage,
+  localizeMessage,
+  matchMessage,
+} from "../src/index";
+import type { MessageCode } from "../src/index";
+
+import { readAstCompilerModules } from "./helpers";
+
+test("every diagnostic the stage2 compiler writes is in the catalog", async () => {
+  const missing: string[] = [];
+  for (const module of await readAstCompilerModules()) {
+    for (const [, literal] of module.source.matchAll(/"([a-z][^"\n]* [^"\n]*)"/g)) {
+      const message = literal!.replace(/(\\0)+$/, "");
+      if (!matchMessage(message)) {
+        missing.push(`${module.path}: ${message}`);
+      }
+    }
+  }
+  expect(missing).toEqual([]);
+});
+
+test("renders errors and warnings in the requested locale", async () => {
+  const failure = await compile("fn main() -> i32 {\n    break;\n    0\n}\n").catch((error: unknown) => error);
+  expect(failure).toBeInstanceOf(CompileError);
+  const error = failure as CompileError;
+  expect(error.record?.code).toBe("E0111");
+  expect(formatCompileError(error).split("\n")[0]).toBe("error: break statements must be inside loop");
+  expect(formatCompileError(error, { locale: "es" }).split("\n")[0]).toBe(
+    "error: las sentencias break deben estar dentro de un bucle",
+  );
+
+  const [warning] = (await compile("fn main() -> i32 {\n    let unused: i32 = 1;\n    0\n}\n")).warnings;
+  expect(warning!.code).toBe("W0001");
+  expect(formatCompileWarning(warning!, { locale: "es" }).split("\n")[0]).toBe(
+    "warning: variable sin usar `unused`",
+  );
+
+  const empty = await compile("").catch((caught: unknown) => caught as CompileError);
+  expect(formatCompileError(empty, { locale: "es" })).toBe("error: el código fuente no puede estar vacío");
+});
+
+test("interpolates parameters and falls back to English", () => {
+  expect(formatMessage("E0337", { field: "x" })).toBe("duplicate struct field x");
+  expect(matchMessage("duplicate struct field x")).toEqual({ code: "E0337", params: { field: "x" } });
+  expect(formatMessage("E0337", { field: "x" }, "xx")).toBe("duplicate struct field x");
+  expect(localizeMessage("not a catalog message", "es")).toBe("not a catalog message");
+
+  const placeholders = (text: string): string[] => [...text.matchAll(/\{[A-Za-z]+\}/g)].map(([name]) => name).sort();
+  for (const code of Object.keys(MESSAGES) as MessageCode[]) {
+    const template = MESSAGES[code];
+    const translated = formatMessage(code, {}, "es");
+    expect(placeholders(translated)).toEqual(placeholders(template));
+  }
+});
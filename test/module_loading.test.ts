@@ -15,7 +15,7 @@ const encoder = new TextEncoder();
 
 const MODULE_STATE_BASE = 1_048_576;
 const MODULE_STORAGE_TOP_OFFSET = 4;
-const MODULE_TABLE_OFFSET = 8;
+const MODULE_TABLE_OFFSET = 20;
 const MODULE_CONTENT_PTR_OFFSET = 8;
 const MODULE_CONTENT_LEN_OFFSET = 12;
 const MODULE_ENTRY_SIZE = 20;
@@ -194,7 +194,7 @@ fn main() -> i32 {
   expect(status).toBeLessThan(0);
 
   const failure = readCompileFailure(compiler, status);
-  expect(failure.detail).toBe("/fixtures/invalid-use.bp:1:1: parsing source failed");
+  expect(failure.detail).toBe("/fixtures/invalid-use.bp:1:5: parsing source failed");
 });
 
 test("loadModuleFromSource reports module table capacity reached", async () => {
@@ -1,7 +1,10 @@
 import { expect, test } from "bun:test";
 
+import { compile, Target } from "../src/index";
+
 import {
   compileWithAstCompiler,
+  disassembleWasm,
   expectCompileFailure,
   expectExportedFunction,
   instantiateWasmModuleWithGc,
@@ -285,3 +288,289 @@ test("mixed addition and subtraction executes", async () => {
   const result = await runWasmMainWithGc(wasm);
   expect(result).toBe(10);
 });
+
+test("checked division reports the divide by zero site", async () => {
+  const source = `fn divide(a: i32, b: i32) -> i32 {
+    a / b
+}
+
+fn main() -> i32 {
+    divide(7, 0)
+}
+`;
+  const compilation = await compile(source, Target.Wasm, { checkedDivision: true });
+  const instance = await instantiateWasmModuleWithGc(compilation.toWasm());
+  const divide = expectExportedFunction(instance, "divide");
+  expect(divide(7, 2)).toBe(3);
+  expect(compilation.describePanic(instance)).toBeNull();
+
+  const main = expectExportedFunction(instance, "main");
+  expect(() => main()).toThrow();
+  expect(compilation.describePanic(instance)).toEqual({
+    message: "attempt to divide by zero",
+    path: "/entry.bp",
+    line: 2,
+    column: 7,
+  });
+});
+
+test("checked division detects signed overflow and zero remainders", async () => {
+  const wasm = await compileWithAstCompiler(
+    `
+    fn divide(a: i64, b: i64) -> i64 {
+        a / b
+    }
+
+    fn remainder(a: i32, b: i32) -> i32 {
+        a % b
+    }
+
+    fn main() -> i32 {
+        0
+    }
+  `,
+    { checkedDivision: true },
+  );
+  const instance = await instantiateWasmModuleWithGc(wasm);
+  const exports = instance.exports as Record<string, unknown>;
+  const panicCode = exports.__panic_code as WebAssembly.Global;
+  const divide = expectExportedFunction(instance, "divide");
+  const remainder = expectExportedFunction(instance, "remainder");
+
+  expect(divide(-9n, 2n)).toBe(-4);
+  expect(remainder(-9, 2)).toBe(-1);
+  expect(panicCode.value).toBe(0);
+
+  expect(() => divide(-(1n << 63n), -1n)).toThrow();
+  expect(panicCode.value).toBe(3);
+  expect(() => remainder(5, 0)).toThrow();
+  expect(panicCode.value).toBe(2);
+});
+
+test("checked division is disabled by default", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn main() -> i32 {
+        let a: u32 = 17 as u32;
+        let b: i32 = -17;
+        (a / 5 as u32) as i32 + (a % 5 as u32) as i32 * 10 + b / 5 * 100 + (b % 5) * 1000
+    }
+  `);
+  const instance = await instantiateWasmModuleWithGc(wasm);
+  expect((instance.exports as Record<string, unknown>).__panic_code).toBeUndefined();
+  const main = expectExportedFunction(instance, "main");
+  expect(main()).toBe(3 + 20 - 300 - 2000);
+});
+
+test("integer methods lower to wasm numeric instructions", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn clamp_span(x: i32, y: i32) -> i32 {
+        x.abs() * 10000 + x.min(y) * 100 + (x * 2).max(y + 1)
+    }
+
+    fn wide(x: i64, y: i64) -> i64 {
+        x.abs() + x.min(y) + y.max(x)
+    }
+
+    fn narrow(x: i8) -> i32 {
+        x.abs() as i32 * 100 + x.count_ones()
+    }
+
+    fn smallest(x: u32, y: u32) -> u32 {
+        x.min(y)
+    }
+
+    fn main() -> i32 {
+        (-1).count_ones() + (-1 as i64).count_ones() + 12.count_ones()
+    }
+  `);
+  const instance = await instantiateWasmModuleWithGc(wasm);
+  const clampSpan = expectExportedFunction(instance, "clamp_span");
+  const wide = expectExportedFunction(instance, "wide");
+  const narrow = expectExportedFunction(instance, "narrow");
+  const smallest = expectExportedFunction(instance, "smallest");
+  const main = expectExportedFunction(instance, "main");
+
+  expect(clampSpan(-7, 3)).toBe(70000 - 700 + 4);
+  expect(clampSpan(0, 0)).toBe(1);
+  expect(wide(-5n, 2n)).toBe(5 - 5 + 2);
+  expect(wide(9n, -4n)).toBe(9 - 4 + 9);
+  expect(narrow(-3)).toBe(300 + 7);
+  expect(narrow(-128)).toBe(-12800 + 1);
+  expect(smallest(-1, 5)).toBe(5);
+  expect(main()).toBe(32 + 64 + 2);
+  const text = disassembleWasm(wasm);
+  expect(text).toContain("i32.popcnt");
+  expect(text).toContain("i64.popcnt");
+  expect(text).toContain("i32.lt_u");
+  expect(text).toContain("select");
+});
+
+test("integer methods check their receiver and arguments", async () => {
+  const unknown = await expectCompileFailure(`
+    fn main() -> i32 {
+        3.sqrt()
+    }
+  `);
+  expect(unknown.failure.detail).toBe("/entry.bp:3:11: unknown method");
+
+  const unsigned = await expectCompileFailure(`
+    fn main() -> i32 {
+        (3 as u32).abs() as i32
+    }
+  `);
+  expect(unsigned.failure.detail).toBe("/entry.bp:3:20: `abs` needs a signed integer receiver");
+
+  const mismatch = await expectCompileFailure(`
+    fn main() -> i32 {
+        3.min(4 as i64)
+    }
+  `);
+  expect(mismatch.failure.detail).toBe("/entry.bp:3:11: call argument type mismatch");
+});
+
+test("bit intrinsics cover zero and all-ones operands", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn counts(x: i32) -> i32 {
+        clz(x) * 10000 + ctz(x) * 100 + popcnt(x)
+    }
+
+    fn wide_counts(x: i64) -> i32 {
+        (clz(x) * 10000 as i64 + ctz(x) * 100 as i64 + popcnt(x)) as i32
+    }
+
+    fn rotate(x: i32, n: i32) -> i32 {
+        rotl(x, n) | rotr(x, n)
+    }
+
+    fn wide_rotate(x: u64, n: u64) -> u64 {
+        rotr(rotl(x, n), 1 as u64)
+    }
+
+    fn main() -> i32 {
+        rotl(1, 33) + rotr(1, 1)
+    }
+  `);
+  const instance = await instantiateWasmModuleWithGc(wasm);
+  const counts = expectExportedFunction(instance, "counts");
+  const wideCounts = expectExportedFunction(instance, "wide_counts");
+  const rotate = expectExportedFunction(instance, "rotate");
+  const wideRotate = expectExportedFunction(instance, "wide_rotate");
+  const main = expectExportedFunction(instance, "main");
+
+  expect(counts(0)).toBe(320000 + 3200);
+  expect(counts(-1)).toBe(32);
+  expect(counts(40)).toBe(260000 + 300 + 2);
+  expect(wideCounts(0n)).toBe(640000 + 6400);
+  expect(wideCounts(-1n)).toBe(64);
+  expect(wideCounts(1n << 40n)).toBe(230000 + 4000 + 1);
+  expect(rotate(-1, 7)).toBe(-1);
+  expect(rotate(0, 7)).toBe(0);
+  expect(rotate(1, 1)).toBe(2 | -0x80000000);
+  expect(BigInt.asUintN(64, BigInt(wideRotate(-1n, 13n)))).toBe((1n << 64n) - 1n);
+  expect(BigInt.asUintN(64, BigInt(wideRotate(1n, 0n)))).toBe(1n << 63n);
+  expect(main()).toBe(2 - 0x80000000);
+  const text = disassembleWasm(wasm);
+  for (const op of ["clz", "ctz", "popcnt", "rotl", "rotr"]) {
+    expect(text).toContain(`i32.${op}`);
+    expect(text).toContain(`i64.${op}`);
+  }
+});
+
+test("bit intrinsics need a 32- or 64-bit operand", async () => {
+  const narrow = await expectCompileFailure(`
+    fn main() -> i32 {
+        clz(3 as u8) as i32
+    }
+  `);
+  expect(narrow.failure.detail).toBe("/entry.bp:3:9: bit intrinsics need a 32- or 64-bit integer operand");
+
+  const mismatch = await expectCompileFailure(`
+    fn main() -> i32 {
+        rotl(3, 1 as i64)
+    }
+  `);
+  expect(mismatch.failure.detail).toBe("/entry.bp:3:9: call argument type mismatch");
+
+  const count = await expectCompileFailure(`
+    fn main() -> i32 {
+        popcnt(3, 4)
+    }
+  `);
+  expect(count.failure.detail).toBe("/entry.bp:3:9: call argument count mismatch");
+});
+
+test("checked arithmetic panics on overflow and saturating arithmetic clamps", async () => {
+  const source = `fn add_i8(a: i8, b: i8) -> i8 {
+    saturating_add(a, b)
+}
+
+fn mul_u32(a: u32, b: u32) -> u32 {
+    saturating_mul(a, b)
+}
+
+fn mul_i64(a: i64, b: i64) -> i64 {
+    saturating_mul(a, b)
+}
+
+fn checked(a: i32, b: i32) -> i32 {
+    checked_mul(checked_add(a, b), 2)
+}
+
+fn wide_add(a: u64, b: u64) -> u64 {
+    checked_add(a, b)
+}
+
+fn main() -> i32 {
+    let mut calls: i32 = 0;
+    let total: i32 = saturating_add({ calls = calls + 1; calls }, 2147483647);
+    if total == 2147483647 { 10 + calls } else { calls }
+}
+`;
+  const compilation = await compile(source, Target.Wasm);
+  const instance = await instantiateWasmModuleWithGc(compilation.toWasm());
+  const addI8 = expectExportedFunction(instance, "add_i8");
+  const mulU32 = expectExportedFunction(instance, "mul_u32");
+  const mulI64 = expectExportedFunction(instance, "mul_i64");
+  const checked = expectExportedFunction(instance, "checked");
+  const wideAdd = expectExportedFunction(instance, "wide_add");
+  const main = expectExportedFunction(instance, "main");
+
+  expect(addI8(100, 27)).toBe(127);
+  expect(addI8(100, 28)).toBe(127);
+  expect(addI8(-100, -29)).toBe(-128);
+  expect(addI8(-1, 0)).toBe(-1);
+  expect(mulU32(65536, 65535) >>> 0).toBe(0xffff0000);
+  expect(mulU32(65536, 65536) >>> 0).toBe(0xffffffff);
+  expect(mulU32(-1, 0)).toBe(0);
+  expect(mulI64(-1n, -(1n << 63n))).toBe(Number((1n << 63n) - 1n));
+  expect(mulI64(-(1n << 62n), 2n)).toBe(Number(-(1n << 63n)));
+  expect(mulI64(3037000500n, -3037000500n)).toBe(Number(-(1n << 63n)));
+  expect(mulI64(-1n, 5n)).toBe(-5);
+  expect(main()).toBe(11);
+
+  expect(checked(20, 1)).toBe(42);
+  expect(() => checked(2147483647, 1)).toThrow();
+  expect(compilation.describePanic(instance)).toEqual({
+    message: "attempt to add with overflow",
+    path: "/entry.bp",
+    line: 14,
+    column: 17,
+  });
+  expect(() => checked(1 << 30, 0)).toThrow();
+  expect(compilation.describePanic(instance)?.message).toBe("attempt to multiply with overflow");
+  expect(wideAdd(-1n, 0n)).toBe(-1);
+  expect(() => wideAdd(-1n, 1n)).toThrow();
+});
+
+test("integer intrinsics are rejected in const functions", async () => {
+  const failure = await expectCompileFailure(`
+    const fn doubled(value: i32) -> i32 {
+        checked_add(value, value)
+    }
+
+    fn main() -> i32 {
+        doubled(2)
+    }
+  `);
+  expect(failure.failure.detail).toBe("/entry.bp:3:9: integer intrinsics are not available in const functions");
+});
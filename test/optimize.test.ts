@@ -0,0 +1,166 @@
+import { expect, test } from "bun:test";
+
+import { commonSubexpressionElimination, compile, stripUnusedMemory } from "../src/index";
+
+import { disassembleWasm, expectExportedFunction, instantiateWasmModuleWithGc } from "./helpers";
+
+function operatorCount(wasm: Uint8Array, name: string): number {
+  return disassembleWasm(wasm)
+    .split("\n")
+    .filter((line) => line.trim().split(" ")[0] === name).length;
+}
+
+async function call(wasm: Uint8Array, name: string, args: ReadonlyArray<number>): Promise<unknown> {
+  const instance = await instantiateWasmModuleWithGc(wasm);
+  return expectExportedFunction(instance, name)(...args);
+}
+
+const REPEATED_SOURCE = `fn area(w: i32, h: i32) -> i32 {
+    let border: i32 = w * h - (w - 2) * (h - 2);
+    if w * h > 100 {
+        border + w * h
+    } else {
+        w * h - border
+    }
+}
+
+fn counted(n: i32) -> i32 {
+    let step: i32 = n * 3;
+    let mut total: i32 = 0;
+    let mut i: i32 = 0;
+    while i < n {
+        total = total + n * 3 + step * step;
+        i = i + 1;
+    };
+    total + step * step
+}
+
+fn main() -> i32 {
+    area(4, 5) + counted(2)
+}
+`;
+
+test("common subexpression elimination computes repeated pure expressions once", async () => {
+  const plain = await compile(REPEATED_SOURCE);
+  const optimized = await compile(REPEATED_SOURCE, undefined, { optimize: true });
+  expect(operatorCount(plain.wasm, "i32.mul")).toBe(9);
+  expect(operatorCount(optimized.wasm, "i32.mul")).toBe(4);
+  for (const [name, args] of [
+    ["area", [4, 5]],
+    ["area", [20, 30]],
+    ["counted", [2]],
+    ["counted", [0]],
+  ] as const) {
+    expect(await call(optimized.wasm, name, args)).toBe(await call(plain.wasm, name, args));
+  }
+
+  const alone = await compile(REPEATED_SOURCE, undefined, { extraPasses: [commonSubexpressionElimination] });
+  expect(stripUnusedMemory(alone.wasm).wasm).toEqual(optimized.wasm);
+});
+
+test("common subexpression elimination leaves assigned variables and trapping operators alone", async () => {
+  const source = `fn f(x: i32, y: i32) -> i32 {
+    let mut a: i32 = x + 1;
+    let first: i32 = a * 2;
+    a = a + 5;
+    first + a * 2 + x / y + x / y
+}
+
+fn main() -> i32 {
+    f(3, 1)
+}
+`;
+  const plain = await compile(source);
+  const optimized = await compile(source, undefined, { optimize: true });
+  expect(optimized.wasm).toEqual(stripUnusedMemory(plain.wasm).wasm);
+  expect(await call(optimized.wasm, "main", [])).toBe(32);
+});
+
+function functionBody(wasm: Uint8Array, name: string): string[] {
+  const lines = disassembleWasm(wasm).split("\n");
+  const start = lines.indexOf(`func ${name}`);
+  const end = lines.findIndex((line, index) => index > start && !line.startsWith(" "));
+  return lines.slice(start + 1, end < 0 ? undefined : end).map((line) => line.trim());
+}
+
+test("constant propagation folds immutable lets with constant initializers", async () => {
+  const source = `fn scaled() -> i32 {
+    let x: i32 = 4;
+    x * 2
+}
+
+fn nested(n: i32) -> i32 {
+    let base: i32 = 3;
+    let square: i32 = {
+        let next: i32 = base + 1;
+        next * next
+    };
+    let mut total: i32 = square - base;
+    total = total + n;
+    let limit: u32 = 0 as u32 - 1 as u32;
+    if limit > 7 as u32 { total } else { 0 }
+}
+
+fn trapping() -> i32 {
+    let zero: i32 = 0;
+    10 / zero
+}
+
+fn main() -> i32 {
+    scaled() + nested(5)
+}
+`;
+  const optimized = await compile(source, undefined, { optimize: true });
+  expect(functionBody(optimized.wasm, "scaled")).toEqual(["i32.const 8"]);
+  expect(functionBody(optimized.wasm, "nested")[0]).toBe("(locals 1 i32)");
+  expect(functionBody(optimized.wasm, "trapping")).toEqual(["i32.const 10", "i32.const 0", "i32.div_s"]);
+  expect(await call(optimized.wasm, "nested", [5])).toBe(18);
+  expect(await call(optimized.wasm, "main", [])).toBe(26);
+});
+
+test("branch folding replaces ifs with constant conditions by the taken branch", async () => {
+  const source = `const DEBUG: bool = false;
+
+fn pick(n: i32) -> i32 {
+    let limit: i32 = 10;
+    let mut total: i32 = n;
+    if DEBUG {
+        let extra: i32 = n * 7;
+        total = total + extra;
+    };
+    while false {
+        total = 0;
+    };
+    if limit > 5 { total + limit } else { total - 1 }
+}
+
+fn still_branches(n: i32) -> i32 {
+    if n > 5 { 1 } else { 2 }
+}
+
+fn leaves_loop(n: i32) -> i32 {
+    let mut i: i32 = 0;
+    loop {
+        i = i + 1;
+        if true {
+            if i >= n {
+                break;
+            };
+        };
+    };
+    i
+}
+
+fn main() -> i32 {
+    if true { pick(3) } else { still_branches(1) }
+}
+`;
+  const plain = await compile(source);
+  const optimized = await compile(source, undefined, { optimize: true });
+  expect(operatorCount(plain.wasm, "if")).toBe(6);
+  expect(operatorCount(optimized.wasm, "if")).toBe(2);
+  expect(functionBody(optimized.wasm, "pick")[0]).toBe("(locals 1 i32)");
+  expect(await call(optimized.wasm, "pick", [4])).toBe(14);
+  expect(await call(optimized.wasm, "leaves_loop", [4])).toBe(4);
+  expect(await call(optimized.wasm, "main", [])).toBe(await call(plain.wasm, "main", []));
+});
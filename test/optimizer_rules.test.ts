@@ -0,0 +1,72 @@
+import { expect, test } from "bun:test";
+
+import { compileWithAstCompiler, expectExportedFunction, instantiateWasmModuleWithGc } from "./helpers";
+
+// `optimizer_rules.json` lists the rewrites an optimizer may make, as
+// `pattern -> rewrite` over an operand `x` of type `T`. No bootstrap pass
+// applies them yet, so each rule is checked against both evaluators it
+// would have to agree with: the const interpreter, which folds the rule for
+// every sample, and the emitted code, which runs it on the same sample.
+interface OptimizerRule {
+  readonly name: string;
+  readonly pattern: string;
+  readonly rewrite: string;
+  readonly types: ReadonlyArray<string>;
+  readonly result?: string;
+  readonly note?: string;
+}
+
+interface OptimizerRuleTable {
+  readonly samples: Readonly<Record<string, ReadonlyArray<string>>>;
+  readonly rules: ReadonlyArray<OptimizerRule>;
+}
+
+const RULES_URL = new URL("./optimizer_rules.json", import.meta.url);
+const table = JSON.parse(await Bun.file(RULES_URL).text()) as OptimizerRuleTable;
+
+function sampleSource(type: string, sample: string): string {
+  return type === "i32" || type === "bool" ? `(${sample})` : `(${sample} as ${type})`;
+}
+
+function instantiate(expression: string, type: string, operand: string): string {
+  return expression.replace(/\bT\b/g, type).replace(/\bx\b/g, operand);
+}
+
+function ruleProgram(rule: OptimizerRule, type: string, samples: ReadonlyArray<string>): string {
+  const result = rule.result ?? type;
+  const lines = [
+    `fn pattern(x: ${type}) -> ${result} {\n    ${instantiate(rule.pattern, type, "x")}\n}`,
+    `fn rewrite(x: ${type}) -> ${result} {\n    ${instantiate(rule.rewrite, type, "x")}\n}`,
+  ];
+  for (const [index, sample] of samples.entries()) {
+    const operand = sampleSource(type, sample);
+    for (const side of ["pattern", "rewrite"] as const) {
+      const name = `FOLDED_${side.toUpperCase()}_${index}`;
+      lines.push(`const ${name}: ${result} = ${instantiate(rule[side], type, operand)};`);
+      lines.push(`fn folded_${side}_${index}() -> ${result} {\n    ${name}\n}`);
+      lines.push(`fn run_${side}_${index}() -> ${result} {\n    ${side}(${operand})\n}`);
+    }
+  }
+  lines.push("fn main() -> i32 {\n    0\n}");
+  return `${lines.join("\n\n")}\n`;
+}
+
+for (const rule of table.rules) {
+  for (const type of rule.types) {
+    test(`optimizer rule ${rule.name} holds for ${type}`, async () => {
+      const samples = table.samples[type];
+      expect(samples).toBeDefined();
+      const wasm = await compileWithAstCompiler(ruleProgram(rule, type, samples));
+      const instance = await instantiateWasmModuleWithGc(wasm);
+      for (const [index, sample] of samples.entries()) {
+        const expected = expectExportedFunction(instance, `run_pattern_${index}`)();
+        const values = [
+          expectExportedFunction(instance, `run_rewrite_${index}`)(),
+          expectExportedFunction(instance, `folded_pattern_${index}`)(),
+          expectExportedFunction(instance, `folded_rewrite_${index}`)(),
+        ];
+        expect({ sample, values }).toEqual({ sample, values: [expected, expected, expected] });
+      }
+    });
+  }
+}
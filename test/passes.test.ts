@@ -0,0 +1,81 @@
+import { expect, test } from "bun:test";
+
+import { CompileError, compile } from "../src/index";
+import type { HirPass } from "../src/index";
+import type { BinaryExpression, Expression, FunctionItem } from "../src/frontend";
+import { Transformer } from "../src/visit";
+
+import { runWasmMainWithGc } from "./helpers";
+
+const SOURCE = `fn scale(x: i32) -> i32 {
+    x * (2 + 3)
+}
+
+fn main() -> i32 {
+    let base: i32 = 1 + 2;
+    scale(base) + 4
+}
+`;
+
+class FoldAddition extends Transformer {
+  folded = 0;
+
+  override transformBinary(expression: BinaryExpression): Expression {
+    const next = super.transformBinary(expression);
+    if (next.kind !== "binary" || next.operator !== "+") {
+      return next;
+    }
+    if (next.left.kind !== "literal" || next.right.kind !== "literal") {
+      return next;
+    }
+    this.folded += 1;
+    const { index, type, offset } = next;
+    return { index, type, offset, kind: "literal", value: next.left.value + next.right.value };
+  }
+}
+
+test("extra passes rewrite the checked program before it is emitted", async () => {
+  const fold = new FoldAddition();
+  const pass: HirPass = { name: "fold-addition", run: (program) => fold.transformProgram(program) };
+  const plain = await compile(SOURCE);
+  const compilation = await compile(SOURCE, undefined, { extraPasses: [pass] });
+  expect(fold.folded).toBe(2);
+  expect(compilation.wasm.length).toBeLessThan(plain.wasm.length);
+  expect(await runWasmMainWithGc(compilation.wasm)).toBe(19);
+
+  const answer: HirPass = {
+    name: "answer",
+    run: (program) => ({
+      functions: program.functions.map((item): FunctionItem =>
+        item.name === "main"
+          ? { ...item, body: { index: -1, type: item.returnType, offset: null, kind: "literal", value: 42 } }
+          : item,
+      ),
+    }),
+  };
+  const replaced = await compile(SOURCE, undefined, { extraPasses: [answer] });
+  expect(await runWasmMainWithGc(replaced.wasm)).toBe(42);
+});
+
+test("a failing pass is reported by name", async () => {
+  const broken: HirPass = {
+    name: "broken",
+    run() {
+      throw new Error("not implemented");
+    },
+  };
+  const failure = await compile(SOURCE, undefined, { extraPasses: [broken] }).catch((error: unknown) => error);
+  expect(failure).toBeInstanceOf(CompileError);
+  expect((failure as CompileError).message).toContain("pass 'broken' failed: not implemented");
+
+  const skipsLocal: HirPass = {
+    name: "skips-local",
+    run: (program) => ({
+      functions: program.functions.map((item): FunctionItem =>
+        item.body.kind === "let" ? { ...item, body: { ...item.body, local: item.body.local + 1 } } : item,
+      ),
+    }),
+  };
+  const gap = await compile(SOURCE, undefined, { extraPasses: [skipsLocal] }).catch((error: unknown) => error);
+  expect((gap as CompileError).message).toContain("extra passes must number the locals of 'main'");
+});
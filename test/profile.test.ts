@@ -0,0 +1,69 @@
+import { expect, test } from "bun:test";
+
+import { CompileError, PROFILE_TABLE_EXPORT, Target, compile } from "../src/index";
+import { runWasmFunctionWithFuel } from "./helpers";
+
+const SOURCE = `fn square(x: i32) -> i32 {
+    x * x
+}
+
+fn sum_squares(n: i32) -> i32 {
+    let mut total: i32 = 0;
+    let mut i: i32 = 1;
+    while i <= n {
+        total = total + square(i);
+        i = i + 1;
+    };
+    total
+}
+
+fn ratio(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        return a / b;
+    };
+    a / b
+}
+
+fn main() -> i32 {
+    sum_squares(10)
+}
+`;
+
+test("profile counts each function's calls, returns, and own instructions", async () => {
+  const plain = await compile(SOURCE);
+  const compilation = await compile(SOURCE, Target.Wasm, { profile: true });
+  const { instance } = await WebAssembly.instantiate(compilation.wasm, {});
+  expect(typeof (instance.exports[PROFILE_TABLE_EXPORT] as WebAssembly.Global).value).toBe("number");
+  expect((instance.exports.main as () => number)()).toBe(385);
+
+  const profiles = new Map(compilation.readProfile(instance)!.map((profile) => [profile.name, profile]));
+  expect([profiles.get("main")?.calls, profiles.get("sum_squares")?.calls, profiles.get("square")?.calls]).toEqual([
+    1, 1, 10,
+  ]);
+  expect(profiles.get("ratio")?.calls).toBe(0);
+  for (const profile of profiles.values()) {
+    expect(profile.returns).toBe(profile.calls);
+  }
+  // Self instructions add up to the same total as the harness's fuel.
+  const total = Array.from(profiles.values()).reduce((sum, profile) => sum + profile.selfInstructions, 0);
+  expect(total).toBe((await runWasmFunctionWithFuel(plain.wasm, "main")).instructions);
+  expect(profiles.get("square")?.selfInstructions).toBe(10 * 3);
+
+  // A trap leaves the calls it unwound without returns.
+  const ratio = instance.exports.ratio as (a: number, b: number) => number;
+  expect(() => ratio(1, 0)).toThrow();
+  const trapped = compilation.readProfile(instance)!.find((profile) => profile.name === "ratio")!;
+  expect([trapped.calls, trapped.returns]).toEqual([1, 0]);
+
+  // Code locations move with the instructions they describe.  Function starts
+  // point at the body's size, which grows.
+  const opcodes = (from: typeof plain): (number | undefined)[] =>
+    from.codeLocations.map((location) =>
+      location.span && !SOURCE.startsWith("fn ", location.span.offset - 3) ? from.wasm[location.offset] : undefined,
+    );
+  expect(compilation.codeLocations.length).toBe(plain.codeLocations.length);
+  expect(opcodes(compilation)).toEqual(opcodes(plain));
+
+  expect(plain.readProfile((await WebAssembly.instantiate(plain.wasm, {})).instance)).toBeNull();
+  await expect(compile(SOURCE, Target.Wgsl, { profile: true })).rejects.toThrow(CompileError);
+});
@@ -0,0 +1,50 @@
+import { expect, test } from "bun:test";
+
+import { ReplSession } from "../src/repl";
+
+test("repl keeps definitions in scope for later expressions", async () => {
+  const session = new ReplSession();
+  expect(await session.submit("fn square(x: i32) -> i32 {\n    x * x\n}")).toEqual({
+    kind: "defined",
+    warnings: [],
+  });
+  expect(await session.submit("const LIMIT: i32 = 3;")).toEqual({ kind: "defined", warnings: [] });
+
+  expect(await session.submit("square(12) + 1")).toEqual({
+    kind: "value",
+    value: "145",
+    type: "i32",
+    warnings: [],
+  });
+  expect(await session.submit("let base: i64 = LIMIT as i64 * 100000 as i64;\nbase * base")).toEqual({
+    kind: "value",
+    value: "90000000000",
+    type: "i64",
+    warnings: [],
+  });
+  expect(await session.submit("square(3) == 9")).toEqual({
+    kind: "value",
+    value: "true",
+    type: "bool",
+    warnings: [],
+  });
+  expect(session.items).toHaveLength(2);
+});
+
+test("repl rejects broken definitions without losing earlier ones", async () => {
+  const session = new ReplSession();
+  await session.submit("fn one() -> i32 {\n    1\n}");
+  const broken = await session.submit("fn two() -> i32 {\n    missing\n}");
+  expect(broken.kind).toBe("error");
+  if (broken.kind === "error") {
+    expect(broken.message).toMatch(/^error: /);
+    expect(broken.message).toContain("--> <repl>:");
+  }
+  expect(session.items).toHaveLength(1);
+
+  const panicked = await session.submit('panic("boom")');
+  expect(panicked.kind).toBe("error");
+
+  session.reset();
+  expect((await session.submit("one()")).kind).toBe("error");
+});
@@ -0,0 +1,48 @@
+import { expect, test } from "bun:test";
+import { mkdtemp, rm } from "node:fs/promises";
+import { tmpdir } from "node:os";
+import { join } from "node:path";
+
+import { CompileError, Target, compile } from "../src/index";
+import type { CompileOptions } from "../src/index";
+import { readReplayCase, replay, replayCaseFromFailure, writeReplayCase } from "../src/replay";
+
+async function failedCompile(source: string, options: CompileOptions): Promise<CompileError> {
+  try {
+    await compile(source, Target.Wasm, options);
+  } catch (error) {
+    if (error instanceof CompileError) {
+      return error;
+    }
+    throw error;
+  }
+  throw new Error("expected the compile to fail");
+}
+
+test("recorded failures replay against the current compiler", async () => {
+  const directory = await mkdtemp(join(tmpdir(), "bootstrap-replay-"));
+  try {
+    const source = 'use "/lib/math.bp";\n\nfn main() -> i32 {\n    helper(2)\n}\n';
+    const options = {
+      strict: true,
+      modules: [{ path: "/lib/math.bp", source: "fn twice(x: i32) -> i32 {\n    x * 2\n}\n" }],
+    };
+    const error = await failedCompile(source, options);
+    const recorded = replayCaseFromFailure("src/main.bp", source, Target.Wasm, options, error);
+    const caseDirectory = await writeReplayCase(directory, recorded);
+    expect(caseDirectory).toMatch(/main-[0-9a-f]{12}$/);
+    expect(await writeReplayCase(directory, recorded)).toBe(caseDirectory);
+
+    const loaded = await readReplayCase(caseDirectory);
+    expect(loaded).toEqual(recorded);
+    expect((await replay(loaded)).status).toBe("reproduced");
+
+    const fixedSource = source.replace("helper", "twice");
+    expect((await replay({ ...loaded, source: fixedSource })).status).toBe("fixed");
+    const changed = await replay({ ...loaded, source: source.replace("helper(2)", "helper(2") });
+    expect(changed.status).toBe("changed");
+    expect(changed.error).toBeInstanceOf(CompileError);
+  } finally {
+    await rm(directory, { recursive: true, force: true });
+  }
+});
@@ -0,0 +1,42 @@
+import { expect, test } from "bun:test";
+
+import { compile, formatSizeReport, readSizeReport } from "../src/index";
+
+const SOURCE = `fn square(x: i32) -> i32 {
+    x * x
+}
+
+fn sum_squares(n: i32) -> i32 {
+    let mut total: i32 = 0;
+    let mut i: i32 = 1;
+    while i <= n {
+        total = total + square(i);
+        i = i + 1;
+    };
+    total
+}
+
+fn main() -> i32 {
+    sum_squares(10)
+}
+`;
+
+test("size reports measure every section and function body", async () => {
+  const { wasm } = await compile(SOURCE);
+  const report = readSizeReport(wasm);
+  expect(report.total).toBe(wasm.length);
+  expect(report.sections.reduce((sum, section) => sum + section.size, 8)).toBe(wasm.length);
+  expect(report.functions.map((entry) => entry.name)).toEqual(["square", "sum_squares", "main"]);
+
+  // The code section is its 2-byte header, the function count, and the bodies.
+  const code = report.sections.find((section) => section.name === "code")!;
+  expect(code.size).toBe(2 + 1 + report.functions.reduce((sum, entry) => sum + entry.size, 0));
+  expect(report.functions.find((entry) => entry.name === "square")!.size).toBe(8);
+
+  const lines = formatSizeReport(report, 1).split("\n");
+  expect(lines[0]).toBe(`size: ${wasm.length} bytes, 3 functions`);
+  const share = ((code.size / wasm.length) * 100).toFixed(1);
+  expect(lines).toContain(`${String(code.size).padStart(10)}${share.padStart(7)}%  code`);
+  expect(lines.at(-2)).toMatch(/ sum_squares$/);
+  expect(lines.at(-1)).toMatch(/^\.\.\. 2 more, \d+ bytes$/);
+});
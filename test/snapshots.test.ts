@@ -0,0 +1,38 @@
+import { expect, test } from "bun:test";
+import { readdir } from "node:fs/promises";
+
+import { compile } from "../src/index";
+import { disassembleWasm } from "./helpers";
+
+// Each `examples/<name>.bp` and `test/snapshots/<name>.bp` is compiled and its
+// disassembly is compared with `test/snapshots/<name>.wasm.txt`, so codegen
+// changes show up as reviewable diffs. Run with `BOOTSTRAP_UPDATE_GOLDEN=1` to
+// rewrite the expected files, then review the diff.
+const SNAPSHOTS_URL = new URL("./snapshots/", import.meta.url);
+const EXAMPLES_URL = new URL("../examples/", import.meta.url);
+const UPDATE_GOLDEN = process.env.BOOTSTRAP_UPDATE_GOLDEN === "1";
+
+async function programsIn(directory: URL): Promise<{ name: string; url: URL }[]> {
+  return (await readdir(directory))
+    .filter((file) => file.endsWith(".bp"))
+    .map((file) => ({ name: file.slice(0, -".bp".length), url: new URL(file, directory) }));
+}
+
+const programs = [...(await programsIn(EXAMPLES_URL)), ...(await programsIn(SNAPSHOTS_URL))].sort(
+  (a, b) => a.name.localeCompare(b.name),
+);
+
+for (const { name, url } of programs) {
+  test(`emits the expected code for ${name}`, async () => {
+    const compilation = await compile(await Bun.file(url).text());
+    const rendered = disassembleWasm(compilation.wasm);
+    const golden = Bun.file(new URL(`${name}.wasm.txt`, SNAPSHOTS_URL));
+    if (UPDATE_GOLDEN) {
+      await Bun.write(golden, rendered);
+    }
+    if (!(await golden.exists())) {
+      throw new Error(`${name}.wasm.txt is missing; run with BOOTSTRAP_UPDATE_GOLDEN=1 to create it`);
+    }
+    expect(rendered).toBe(await golden.text());
+  });
+}
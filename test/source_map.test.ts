@@ -0,0 +1,60 @@
+import { expect, test } from "bun:test";
+
+import { Target, appendSourceMappingUrl, compile, encodeVlq } from "../src/index";
+
+const LIB = {
+  path: "/lib/math.bp",
+  source: `fn ratio(a: i32, b: i32) -> i32 {
+    a / b
+}
+`,
+};
+
+const ENTRY = `use "./lib/math.bp";
+
+fn main() -> i32 {
+    let x: i32 = 10;
+    ratio(x, 2)
+}
+`;
+
+test("code locations map instructions back to the lines that wrote them", async () => {
+  const compilation = await compile(ENTRY, Target.Wasm, { modules: [LIB] });
+  const wasm = compilation.wasm;
+  const locations = compilation.codeLocations;
+  const offsets = locations.map((location) => location.offset);
+  expect(offsets).toEqual([...offsets].sort((a, b) => a - b));
+
+  const divisionIndex = locations.findIndex(
+    (location) => location.span?.path === LIB.path && location.span.offset === LIB.source.indexOf("/"),
+  );
+  expect(divisionIndex).toBeGreaterThanOrEqual(0);
+  const division = locations[divisionIndex]!;
+  expect(division.span).toEqual({ path: LIB.path, line: 2, column: 7, offset: LIB.source.indexOf("/") });
+  const divisionCode = wasm.slice(division.offset, locations[divisionIndex + 1]!.offset);
+  // i32.div_s
+  expect(divisionCode.includes(0x6d)).toBe(true);
+  expect(compilation.locate(division.offset + divisionCode.indexOf(0x6d))?.line).toBe(2);
+
+  const call = locations.find((location) => location.span?.path === "/entry.bp" && location.span.line === 5);
+  expect(call?.span?.column).toBe(5);
+  expect(compilation.locate(0)).toBeNull();
+});
+
+test("sourceMap writes version 3 mappings with the sources inlined", async () => {
+  const compilation = await compile(ENTRY, Target.Wasm, { modules: [LIB] });
+  const map = compilation.sourceMap({ file: "main.wasm", entryPath: "main.bp" });
+  expect(map.version).toBe(3);
+  expect(map.file).toBe("main.wasm");
+  expect([...map.sources].sort()).toEqual(["/lib/math.bp", "main.bp"]);
+  expect(map.sourcesContent[map.sources.indexOf("main.bp")]).toBe(ENTRY);
+  expect(map.mappings).toMatch(/^[A-Za-z0-9+/]+(,[A-Za-z0-9+/]+)*$/);
+  expect(map.mappings.split(",").length).toBe(compilation.codeLocations.length);
+
+  expect([0, 1, -1, 15, 16, -16, 1000].map(encodeVlq)).toEqual(["A", "C", "D", "e", "gB", "hB", "w+B"]);
+
+  const linked = appendSourceMappingUrl(compilation.wasm, "main.wasm.map");
+  const sections = WebAssembly.Module.customSections(new WebAssembly.Module(linked), "sourceMappingURL");
+  expect(sections.length).toBe(1);
+  expect(new Uint8Array(sections[0]!)).toEqual(new Uint8Array([13, ...new TextEncoder().encode("main.wasm.map")]));
+});
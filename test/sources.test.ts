@@ -0,0 +1,98 @@
+import { expect, test } from "bun:test";
+import { mkdtemp, mkdir, rm, writeFile } from "node:fs/promises";
+import { tmpdir } from "node:os";
+import { join } from "node:path";
+
+import { FileSystemSourceProvider } from "../src/fs_source_provider";
+import { runCompilation } from "../src/index";
+import {
+  MemorySourceProvider,
+  compileFromProvider,
+  findImports,
+  loadImports,
+  resolveImportPath,
+} from "../src/sources";
+
+const PROJECT = [
+  {
+    path: "/app/main.bp",
+    source: `
+      use "./util/math.bp";
+      // use "./missing.bp";
+      /* use "./also_missing.bp"; /* nested */ */
+
+      fn main() -> i32 {
+          triple(add_one(6))
+      }
+    `,
+  },
+  {
+    path: "/app/util/math.bp",
+    source: `
+      use "../shared.bp";
+
+      fn triple(value: i32) -> i32 {
+          value * FACTOR
+      }
+    `,
+  },
+  {
+    path: "/app/shared.bp",
+    source: `
+      const FACTOR: i32 = 3;
+
+      fn add_one(value: i32) -> i32 {
+          value + 1
+      }
+    `,
+  },
+];
+
+test("resolves use paths the way stage2 does", () => {
+  expect(resolveImportPath("/app/util/math.bp", "../shared.bp")).toBe("/app/shared.bp");
+  expect(resolveImportPath("/app/main.bp", "./util/./math.bp")).toBe("/app/util/math.bp");
+  expect(resolveImportPath("/app/main.bp", "/stdlib/memory.bp")).toBe("/stdlib/memory.bp");
+  expect(resolveImportPath("/main.bp", "../outside.bp")).toBeNull();
+  expect(resolveImportPath("/app/main.bp", "util//math.bp")).toBeNull();
+  expect(findImports(PROJECT[0]!.source)).toEqual(["./util/math.bp"]);
+});
+
+test("compiles a multi-file project from memory", async () => {
+  const provider = new MemorySourceProvider(PROJECT);
+  expect(await provider.listDirectory("/app")).toEqual(["main.bp", "shared.bp", "util"]);
+  const imports = await loadImports(provider, PROJECT[0]!);
+  expect(imports.map((module) => module.path)).toEqual(["/app/util/math.bp", "/app/shared.bp"]);
+  const cycle = new MemorySourceProvider([{ path: "/b.bp", source: 'use "/a.bp";' }]);
+  expect(await loadImports(cycle, { path: "/a.bp", source: 'use "./b.bp";' })).toEqual([
+    { path: "/b.bp", source: 'use "/a.bp";' },
+  ]);
+
+  const compilation = await compileFromProvider(provider, "/app/main.bp");
+  expect((await runCompilation(compilation)).value).toBe("21");
+
+  provider.writeFile("/app/shared.bp", PROJECT[2]!.source.replace("= 3", "= 4"));
+  const edited = await compileFromProvider(provider, "/app/main.bp");
+  expect((await runCompilation(edited)).value).toBe("28");
+  await expect(compileFromProvider(provider, "/app/absent.bp")).rejects.toThrow(
+    /entry module '\/app\/absent.bp' not found/,
+  );
+});
+
+test("reads modules from a directory on disk", async () => {
+  const root = await mkdtemp(join(tmpdir(), "bootstrap-sources-"));
+  try {
+    for (const module of PROJECT) {
+      await mkdir(join(root, module.path, ".."), { recursive: true });
+      await writeFile(join(root, module.path), module.source);
+    }
+    const provider = new FileSystemSourceProvider(root);
+    expect(await provider.readFile("/app/shared.bp")).toBe(PROJECT[2]!.source);
+    expect(await provider.readFile("/app/missing.bp")).toBeNull();
+    expect(await provider.readFile("/../escape.bp")).toBeNull();
+    expect(await provider.listDirectory("/app/util")).toEqual(["math.bp"]);
+    const compilation = await compileFromProvider(provider, "/app/main.bp");
+    expect((await runCompilation(compilation)).value).toBe("21");
+  } finally {
+    await rm(root, { recursive: true, force: true });
+  }
+});
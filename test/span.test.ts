@@ -0,0 +1,93 @@
+import { expect, test } from "bun:test";
+
+import {
+  lineColumnAt,
+  mergeRanges,
+  offsetAt,
+  rangeContains,
+  rangeCovers,
+  rangesIntersect,
+  sourceRange,
+  tokenRangeAt,
+} from "../src/index";
+import type { SourceRange } from "../src/index";
+
+// A fixed-seed generator keeps the property tests reproducible.
+function createRandom(seed: number): (limit: number) => number {
+  let state = seed;
+  return (limit: number): number => {
+    state = (Math.imul(state, 1_103_515_245) + 12_345) >>> 0;
+    return (state >>> 8) % limit;
+  };
+}
+
+const random = createRandom(2814);
+
+function randomRange(): SourceRange {
+  const start = random(40);
+  return sourceRange(start, start + random(10));
+}
+
+test("merged ranges cover both inputs and nothing beyond them", () => {
+  for (let trial = 0; trial < 500; trial += 1) {
+    const a = randomRange();
+    const b = randomRange();
+    const merged = mergeRanges(a, b);
+    expect(mergeRanges(b, a)).toEqual(merged);
+    expect(rangeCovers(merged, a) && rangeCovers(merged, b)).toBe(true);
+    expect(merged.start === a.start || merged.start === b.start).toBe(true);
+    expect(merged.end === a.end || merged.end === b.end).toBe(true);
+    expect(mergeRanges(merged, a)).toEqual(merged);
+  }
+});
+
+test("intersection matches a shared contained offset", () => {
+  for (let trial = 0; trial < 500; trial += 1) {
+    const a = randomRange();
+    const b = randomRange();
+    let shared = false;
+    for (let offset = 0; offset < 50; offset += 1) {
+      shared ||= rangeContains(a, offset) && rangeContains(b, offset);
+    }
+    expect(rangesIntersect(a, b)).toBe(shared);
+    expect(rangesIntersect(b, a)).toBe(shared);
+  }
+  expect(rangeContains(sourceRange(3), 3)).toBe(false);
+  expect(() => sourceRange(4, 2)).toThrow(RangeError);
+});
+
+test("offsets and line/column positions round-trip", () => {
+  const alphabet = ["a", "1", "_", " ", "\t", "\n", "\r\n", "\r", "é", "→"];
+  for (let trial = 0; trial < 200; trial += 1) {
+    let source = "";
+    for (let length = random(30); length > 0; length -= 1) {
+      source += alphabet[random(alphabet.length)];
+    }
+    const bytes = new TextEncoder().encode(source);
+    for (let offset = 0; offset <= bytes.length; offset += 1) {
+      if ((bytes[offset]! & 0xc0) === 0x80 || (bytes[offset] === 10 && bytes[offset - 1] === 13)) {
+        // Offsets inside a character, or at the `\n` of a `\r\n` pair, share
+        // a position with an earlier one.
+        continue;
+      }
+      expect(offsetAt(source, lineColumnAt(source, offset))).toBe(offset);
+    }
+  }
+  expect(lineColumnAt("fn a\r\n  b", 8)).toEqual({ line: 2, column: 3 });
+  expect(lineColumnAt("fn a\r  b", 7)).toEqual({ line: 2, column: 3 });
+  expect(offsetAt("one\ntwo", { line: 3, column: 1 })).toBe(-1);
+  expect(offsetAt("one\ntwo", { line: 1, column: 5 })).toBe(-1);
+});
+
+test("columns count characters of a UTF-8 source", () => {
+  // `x` is at byte 5, after the two bytes of `é`, and is the fifth character.
+  expect(lineColumnAt("é = x", 5)).toEqual({ line: 1, column: 5 });
+  expect(offsetAt("é = x", { line: 1, column: 5 })).toBe(5);
+  expect(lineColumnAt("a\n→ é b", 9)).toEqual({ line: 2, column: 5 });
+});
+
+test("finds the token under an offset", () => {
+  expect(tokenRangeAt("let value = 12;", 4)).toEqual({ start: 4, end: 9 });
+  expect(tokenRangeAt("let value = 12;", 10)).toEqual({ start: 10, end: 11 });
+  expect(tokenRangeAt("x", 1)).toEqual({ start: 1, end: 1 });
+});
@@ -0,0 +1,64 @@
+import { expect, test } from "bun:test";
+
+import { Target, compile, formatStackTrace, readFunctionAbi } from "../src/index";
+
+const LIB = {
+  path: "/lib/math.bp",
+  source: `fn ratio(a: i32, b: i32) -> i32 {
+    a / b
+}
+`,
+};
+
+const ENTRY = `use "./lib/math.bp";
+
+fn halve(x: i32) -> i32 {
+    ratio(x, 0)
+}
+
+fn main() -> i32 {
+    halve(10)
+}
+`;
+
+// Stack lines in the shapes V8 and JavaScriptCore give to Wasm frames.
+function trapError(lines: ReadonlyArray<string>): Error {
+  const error = new WebAssembly.RuntimeError("divide by zero");
+  error.stack = ["RuntimeError: divide by zero", ...lines].join("\n");
+  return error;
+}
+
+test("symbolicate maps a trap's wasm frames to function names and lines", async () => {
+  const compilation = await compile(ENTRY, Target.Wasm, { modules: [LIB] });
+  const functions = readFunctionAbi(compilation.wasm).map((abi) => abi.name);
+  const index = (name: string): number => functions.indexOf(name);
+  const offsetOf = (path: string, line: number): number =>
+    compilation.codeLocations.find((location) => location.span?.path === path && location.span.line === line)!
+      .offset;
+
+  const v8 = compilation.symbolicate(
+    trapError([
+      `    at ratio (wasm://wasm/3f1a2b:wasm-function[${index("ratio")}]:0x${offsetOf(LIB.path, 2).toString(16)})`,
+      `    at halve (wasm://wasm/3f1a2b:wasm-function[${index("halve")}]:0x${offsetOf("/entry.bp", 4).toString(16)})`,
+      "    at main.test.ts:12:5",
+    ]),
+  );
+  expect(v8.map((frame) => [frame.name, frame.span?.path, frame.span?.line])).toEqual([
+    ["ratio", LIB.path, 2],
+    ["halve", "/entry.bp", 4],
+  ]);
+
+  // Without offsets, each frame points at its function's name.
+  const jsc = compilation.symbolicate(
+    trapError([`<?>.wasm-function[${index("halve")}]@[wasm code]`, "<?>.wasm-function[main]@[wasm code]"]),
+  );
+  expect(jsc.map((frame) => [frame.name, frame.offset, frame.span?.line])).toEqual([
+    ["halve", null, 3],
+    ["main", null, 7],
+  ]);
+
+  expect(formatStackTrace(v8, { entryPath: "main.bp" })).toBe(
+    "    at ratio (/lib/math.bp:2:7)\n    at halve (main.bp:4:5)",
+  );
+  expect(compilation.symbolicate(new Error("not a trap"))).toEqual([]);
+});
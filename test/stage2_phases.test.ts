@@ -0,0 +1,37 @@
+import { expect, test } from "bun:test";
+
+import { CompileError } from "../src/index";
+import { Stage2Phases } from "../src/stage2";
+import { loadAstCompilerWasm } from "./helpers";
+
+const SOURCE = `fn scale(const factor: i32, x: i32) -> i32 {
+    x * factor
+}
+
+fn main() -> i32 {
+    scale(3, 4) + scale(5, 6)
+}
+`;
+
+test("the checked-in and freshly built compilers agree phase by phase", async () => {
+  const checkedIn = await Stage2Phases.create();
+  const built = await Stage2Phases.create(await loadAstCompilerWasm());
+  for (const phases of [checkedIn, built]) {
+    expect(phases.tokenize(SOURCE)).toEqual(built.tokenize(SOURCE));
+    expect(phases.parse(SOURCE)).toEqual(built.parse(SOURCE));
+    expect(phases.check(SOURCE)).toEqual(built.check(SOURCE));
+  }
+
+  const parsed = built.parse(SOURCE);
+  expect(parsed.map((fn) => [fn.name, fn.paramCount])).toEqual([
+    ["scale", 2],
+    ["main", 0],
+  ]);
+  // Checking adds a specialization for each distinct const argument.
+  expect(built.check(SOURCE).length).toBe(parsed.length + 2);
+
+  const mistyped = "fn main() -> i32 {\n    true\n}\n";
+  expect(built.parse(mistyped).map((fn) => fn.name)).toEqual(["main"]);
+  expect(() => built.check(mistyped)).toThrow(CompileError);
+  expect(() => built.parse("fn main( -> i32 {}")).toThrow(CompileError);
+});
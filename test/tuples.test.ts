@@ -1,8 +1,11 @@
 import { expect, test } from "bun:test";
 
+import { AbiValueKind, Target, compile, readFunctionAbi } from "../src/index";
 import {
   compileWithAstCompiler,
+  disassembleWasm,
   expectCompileFailure,
+  instantiateWasmModuleWithGc,
   runWasmMainWithGc,
 } from "./helpers";
 
@@ -23,8 +26,13 @@ function containsSequence(haystack: Uint8Array, needle: readonly number[]): bool
 
 test("tuple literal emits struct.new", async () => {
   const wasm = await compileWithAstCompiler(`
-    fn build() -> (i32, i32) {
-        (1, 2)
+    fn first(pair: (i32, i32)) -> i32 {
+        pair.0
+    }
+
+    fn build() -> i32 {
+        let pair: (i32, i32) = (1, 2);
+        first(pair)
     }
 
     fn main() -> i32 {
@@ -36,6 +44,24 @@ test("tuple literal emits struct.new", async () => {
   expect(containsSequence(wasm, pattern)).toBe(true);
 });
 
+test("tuple locals read only by field live in one wasm local per field", async () => {
+  const source = `fn divmod(a: i32, b: i32) -> (i32, i32) {
+    (a / b, a % b)
+}
+
+fn main() -> i32 {
+    let mut point: (i32, bool) = (3, true);
+    point.0 = point.0 + 4;
+    let nested: ((i32, i32), i32) = ((1, 2), 5);
+    let parts: (i32, i32) = divmod(17, 5);
+    if point.1 { point.0 * 100 + nested.0.1 * 10 + nested.1 + parts.0 * parts.1 } else { 0 }
+}
+`;
+  const { wasm } = await compile(source);
+  expect(disassembleWasm(wasm).split("func main")[1]).not.toContain("0xfb");
+  expect(await runWasmMainWithGc(wasm)).toBe(700 + 20 + 5 + 6);
+});
+
 test("tuple literal can be passed to function arguments", async () => {
   const wasm = await compileWithAstCompiler(`
     fn take(arg: (i32, bool)) -> i32 {
@@ -184,3 +210,104 @@ test("nested tuple and arrays can be deeply nested", async () => {
   expect(result).toBe(3);
 });
 
+
+test("aggregates cross function boundaries as GC references or results", async () => {
+  const wasm = await compileWithAstCompiler(`
+    fn swap(pair: (i32, bool)) -> (bool, i32) {
+        (pair.1, pair.0)
+    }
+
+    fn first(values: [i64; 3]) -> i64 {
+        values[0]
+    }
+
+    fn touch(pair: (i32, i32)) {
+        pair.0;
+    }
+
+    fn main() -> i32 {
+        0
+    }
+  `);
+
+  const abi = new Map(readFunctionAbi(wasm).map((entry) => [entry.name, entry]));
+  expect(abi.get("swap")).toEqual({
+    name: "swap",
+    params: [AbiValueKind.StructRef],
+    results: [AbiValueKind.I32, AbiValueKind.I32],
+  });
+  expect(abi.get("first")?.params).toEqual([AbiValueKind.ArrayRef]);
+  expect(abi.get("first")?.results).toEqual([AbiValueKind.I64]);
+  expect(abi.get("touch")?.results).toEqual([AbiValueKind.I32]);
+  expect(abi.get("main")?.results).toEqual([AbiValueKind.I32]);
+});
+
+test("functions return tuples as multiple values that let patterns take apart", async () => {
+  const source = `fn divmod(a: i32, b: i32) -> (i32, i32) {
+    (a / b, a % b)
+}
+
+fn widen(x: i32) -> (i32, i64) {
+    if x < 0 {
+        return (0, x as i64);
+    }
+    let pair: (i32, i64) = (x, (x + 3) as i64);
+    pair
+}
+
+fn main() -> i32 {
+    let (q, r) = divmod(17, 5);
+    let (mut d, _) = later(3);
+    d = d + 1;
+    let (n, wide) = widen(4);
+    q * 1000 + r * 100 + d * 10 + n + (wide as i32) * 10000
+}
+
+fn later(x: i32) -> (i32, i32) {
+    divmod(x * 2, 1)
+}
+`;
+  const values = await compile(source);
+  const abi = new Map(readFunctionAbi(values.wasm).map((entry) => [entry.name, entry]));
+  expect(abi.get("divmod")?.results).toEqual([AbiValueKind.I32, AbiValueKind.I32]);
+  expect(abi.get("widen")?.results).toEqual([AbiValueKind.I32, AbiValueKind.I64]);
+  expect(disassembleWasm(values.wasm).split("func main")[1]).not.toContain("0xfb 0");
+  const { divmod } = (await instantiateWasmModuleWithGc(values.wasm)).exports as {
+    divmod: (a: number, b: number) => number[];
+  };
+  expect(divmod(17, 5)).toEqual([3, 2]);
+
+  const references = await compile(source, Target.Wasm, { features: { multiValue: false } });
+  expect(readFunctionAbi(references.wasm).find((entry) => entry.name === "divmod")?.results).toEqual([
+    AbiValueKind.StructRef,
+  ]);
+  for (const { wasm } of [values, references]) {
+    expect(await runWasmMainWithGc(wasm)).toBe(70000 + 3000 + 200 + 70 + 4);
+  }
+});
+
+test("let tuple patterns must match the tuple", async () => {
+  const notTuple = await expectCompileFailure(`
+    fn main() -> i32 {
+        let (a, b) = 5;
+        a
+    }
+  `);
+  expect(notTuple.failure.detail).toBe("/entry.bp:3:22: tuple pattern requires tuple value");
+
+  const wrongCount = await expectCompileFailure(`
+    fn main() -> i32 {
+        let (a, b, c) = (1, 2);
+        a
+    }
+  `);
+  expect(wrongCount.failure.detail).toBe("/entry.bp:3:13: tuple pattern does not match tuple element count");
+
+  const duplicate = await expectCompileFailure(`
+    fn main() -> i32 {
+        let (a, a) = (1, 2);
+        a
+    }
+  `);
+  expect(duplicate.failure.detail).toBe("/entry.bp:3:17: duplicate local declaration");
+});
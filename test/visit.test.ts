@@ -0,0 +1,90 @@
+import { expect, test } from "bun:test";
+
+import { parseProgram, walkProgram } from "../src/frontend";
+import type { BinaryExpression, CallExpression, Expression } from "../src/frontend";
+import { Transformer, Visitor } from "../src/visit";
+
+const SOURCE = `fn double(x: i32) -> i32 {
+    x * 2
+}
+
+fn main() -> i32 {
+    let mut values: [i32; 3] = [1 + 2, double(4), 0];
+    let pair: (i32, bool) = (values[0], !false);
+    values[2] = double(pair.0) + 3 * 4;
+    if pair.1 {
+        return values[2];
+    };
+    loop {
+        break;
+    };
+    values.len() as i32
+}
+`;
+
+test("a default visitor reaches every node and calls the method for its kind", async () => {
+  const program = await parseProgram(SOURCE);
+  let walked = 0;
+  walkProgram(program, {
+    enterExpression() {
+      walked += 1;
+    },
+  });
+
+  class Counter extends Visitor {
+    nodes = 0;
+    calls: string[] = [];
+
+    override visitExpression(expression: Expression): void {
+      this.nodes += 1;
+      super.visitExpression(expression);
+    }
+
+    override visitCall(expression: CallExpression): void {
+      this.calls.push(expression.name);
+      super.visitCall(expression);
+    }
+  }
+  const counter = new Counter();
+  counter.visitProgram(program);
+  expect(counter.nodes).toBe(walked);
+  expect(counter.calls).toEqual(["double", "double"]);
+});
+
+test("a transformer replaces nodes and shares the subtrees it leaves alone", async () => {
+  const program = await parseProgram(SOURCE);
+
+  class FoldArithmetic extends Transformer {
+    override transformBinary(expression: BinaryExpression): Expression {
+      const folded = super.transformBinary(expression);
+      if (folded.kind !== "binary" || folded.left.kind !== "literal" || folded.right.kind !== "literal") {
+        return folded;
+      }
+      const apply = { "+": (a: number, b: number) => a + b, "*": Math.imul }[folded.operator as "+" | "*"];
+      if (!apply) {
+        return folded;
+      }
+      const { index, type, offset } = folded;
+      return { index, type, offset, kind: "literal", value: apply(folded.left.value, folded.right.value) };
+    }
+  }
+  const folded = new FoldArithmetic().transformProgram(program);
+  expect(folded).not.toBe(program);
+  expect(folded.functions[0]).toBe(program.functions[0]);
+
+  const literals = (source: typeof program): number[] => {
+    const values: number[] = [];
+    walkProgram(source, {
+      enterExpression(expression, item) {
+        if (item.name === "main" && expression.kind === "literal" && expression.value > 2) {
+          values.push(expression.value);
+        }
+      },
+    });
+    return values;
+  };
+  expect(literals(program)).toEqual([4, 3, 4]);
+  expect(literals(folded)).toEqual([3, 4, 12]);
+
+  expect(new Transformer().transformProgram(program)).toBe(program);
+});